@@ -0,0 +1,17 @@
+#![feature(test)]
+
+extern crate test;
+extern crate braid;
+
+#[macro_use]
+mod common;
+
+pub use braid::MemoryDatastore;
+pub use braid::tests;
+pub use test::Bencher;
+
+fn datastore() -> MemoryDatastore {
+    MemoryDatastore::new(false)
+}
+
+bench_transaction_impl!(datastore());
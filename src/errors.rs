@@ -9,6 +9,10 @@ pub enum Error {
     EdgeNotFound,
     MetadataNotFound,
     Unauthorized,
+    UniqueConstraintViolated,
+    VertexAlreadyExists,
+    Conflict,
+    InvalidMetadataOperation,
     OutOfRange(String),
     Unexpected(String),
 }
@@ -23,6 +27,10 @@ impl Error {
             "Edge does not exist" => Error::EdgeNotFound,
             "Metadata does not exist" => Error::MetadataNotFound,
             "Unauthorized" => Error::Unauthorized,
+            "Unique constraint violated" => Error::UniqueConstraintViolated,
+            "Vertex already exists" => Error::VertexAlreadyExists,
+            "Conflict" => Error::Conflict,
+            "Invalid metadata operation" => Error::InvalidMetadataOperation,
             _ => {
                 if message.starts_with("Value out of range: ") {
                     Error::OutOfRange(message[20..message.len()].to_string())
@@ -42,6 +50,10 @@ impl StdError for Error {
             Error::EdgeNotFound => "Edge does not exist",
             Error::MetadataNotFound => "Metadata does not exist",
             Error::Unauthorized => "Unauthorized",
+            Error::UniqueConstraintViolated => "Unique constraint violated",
+            Error::VertexAlreadyExists => "Vertex already exists",
+            Error::Conflict => "Conflict",
+            Error::InvalidMetadataOperation => "Invalid metadata operation",
             Error::OutOfRange(_) => "Value out of range",
             Error::Unexpected(_) => "Unexpected error",
         }
@@ -14,7 +14,9 @@ extern crate serde_json;
 extern crate libc;
 extern crate rand;
 extern crate regex;
+extern crate byteorder;
 #[macro_use] extern crate serde_derive;
+#[macro_use] extern crate lazy_static;
 
 #[cfg(feature="postgres-datastore")]
 extern crate postgres;
@@ -25,21 +27,17 @@ extern crate r2d2_postgres;
 #[cfg(feature="postgres-datastore")]
 extern crate num_cpus;
 #[cfg(feature="rocksdb-datastore")]
-#[macro_use]
-extern crate lazy_static;
-#[cfg(feature="rocksdb-datastore")]
 extern crate rocksdb;
 #[cfg(feature="rocksdb-datastore")]
 extern crate librocksdb_sys;
 #[cfg(feature="rocksdb-datastore")]
 extern crate bincode;
-#[cfg(feature="rocksdb-datastore")]
-extern crate byteorder;
 
 #[macro_use]
 pub mod tests;
 mod errors;
 mod models;
+mod paths;
 mod traits;
 pub mod util;
 
@@ -55,4 +53,9 @@ pub use pg::{PostgresDatastore, PostgresTransaction};
 #[cfg(feature="rocksdb-datastore")]
 mod rdb;
 #[cfg(feature="rocksdb-datastore")]
-pub use rdb::{RocksdbDatastore, RocksdbTransaction};
+pub use rdb::{ConsistencyReport, RocksdbDatastore, RocksdbTransaction};
+
+#[cfg(feature="memory-datastore")]
+mod memory;
+#[cfg(feature="memory-datastore")]
+pub use memory::{MemoryDatastore, MemoryTransaction};
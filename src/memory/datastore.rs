@@ -0,0 +1,1545 @@
+use super::super::{Datastore, Transaction, VertexQuery, EdgeQuery, EdgeOrder, QueryTypeConverter};
+use models;
+use uuid::Uuid;
+use errors::Error;
+use util::{generate_random_secret, get_salted_hash, parent_uuid, child_uuid, edge_as_of, expiry_datetime, check_datetime};
+use serde_json::Value as JsonValue;
+use chrono::{DateTime, Duration, UTC};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
+use super::models::{AccountValue, EdgeValue, VertexValue};
+
+/// Builds an edge key with the outbound and inbound IDs swapped. These are
+/// used to look up the inbound edges of a vertex.
+fn reverse_edge_key(key: &models::EdgeKey) -> models::EdgeKey {
+    models::EdgeKey::new(key.inbound_id, key.t.clone(), key.outbound_id)
+}
+
+/// Gets the keys of all of the metadata owned by a given ID.
+fn owned_metadata_keys(metadata: &BTreeMap<(Uuid, String), JsonValue>, owner_id: Uuid) -> Vec<(Uuid, String)> {
+    metadata.range((owner_id, String::new())..)
+        .map(|(key, _)| key)
+        .take_while(|&&(id, _)| id == owner_id)
+        .cloned()
+        .collect()
+}
+
+/// Gets the names and values of all of the metadata owned by a given owner,
+/// sorted by name.
+fn owned_metadata<K: Ord + Clone>(metadata: &BTreeMap<(K, String), JsonValue>, owner: &K) -> Vec<(String, JsonValue)> {
+    metadata.range((owner.clone(), String::new())..)
+        .take_while(|&(&(ref k, _), _)| k == owner)
+        .map(|(&(_, ref name), value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// Gets the keys of everything that has expired as of a given datetime,
+/// along with when they expired.
+fn expired_keys<K: Ord + Clone>(expiries: &BTreeMap<K, DateTime<UTC>>, now: DateTime<UTC>) -> Vec<(K, DateTime<UTC>)> {
+    expiries.iter()
+        .filter(|&(_, expiry)| *expiry <= now)
+        .map(|(key, expiry)| (key.clone(), *expiry))
+        .collect()
+}
+
+/// Checks whether an item has expired, given the expiries of its kind.
+fn has_expired<K: Ord>(expiries: &BTreeMap<K, DateTime<UTC>>, key: &K) -> bool {
+    expiries.get(key).map_or(false, |expiry| *expiry <= UTC::now())
+}
+
+/// The contents of an in-memory datastore. This is shared between the
+/// datastore and all of its transactions.
+#[derive(Debug, Default)]
+struct InternalMemoryDatastore {
+    accounts: BTreeMap<Uuid, AccountValue>,
+    vertices: BTreeMap<Uuid, VertexValue>,
+    edges: BTreeMap<models::EdgeKey, EdgeValue>,
+    reversed_edges: BTreeSet<models::EdgeKey>,
+    global_metadata: BTreeMap<String, JsonValue>,
+    global_metadata_versions: BTreeMap<String, u64>,
+    account_metadata: BTreeMap<(Uuid, String), JsonValue>,
+    account_metadata_versions: BTreeMap<(Uuid, String), u64>,
+    vertex_metadata: BTreeMap<(Uuid, String), JsonValue>,
+    vertex_metadata_versions: BTreeMap<(Uuid, String), u64>,
+    edge_metadata: BTreeMap<(models::EdgeKey, String), JsonValue>,
+    edge_metadata_versions: BTreeMap<(models::EdgeKey, String), u64>,
+    vertex_metadata_indexes: BTreeSet<models::VertexMetadataIndex>,
+    unique_constraints: BTreeSet<models::UniqueConstraint>,
+    edge_history_types: BTreeSet<models::Type>,
+    edge_history: BTreeMap<models::EdgeKey, BTreeMap<DateTime<UTC>, Option<(models::Weight, u64)>>>,
+    vertex_expiries: BTreeMap<Uuid, DateTime<UTC>>,
+    edge_expiries: BTreeMap<models::EdgeKey, DateTime<UTC>>,
+    vertex_metadata_expiries: BTreeMap<(Uuid, String), DateTime<UTC>>,
+    edge_metadata_expiries: BTreeMap<(models::EdgeKey, String), DateTime<UTC>>,
+}
+
+impl InternalMemoryDatastore {
+    /// Gets a vertex, unless it has expired. Expired items stay around until
+    /// they're swept up by `delete_expired`, so reads have to skip them.
+    fn get_vertex(&self, id: &Uuid) -> Option<&VertexValue> {
+        if has_expired(&self.vertex_expiries, id) {
+            None
+        } else {
+            self.vertices.get(id)
+        }
+    }
+
+    /// Gets an edge, unless it or either of its vertices has expired.
+    fn get_edge(&self, key: &models::EdgeKey) -> Option<&EdgeValue> {
+        if has_expired(&self.edge_expiries, key) || self.get_vertex(&key.outbound_id).is_none() || self.get_vertex(&key.inbound_id).is_none() {
+            None
+        } else {
+            self.edges.get(key)
+        }
+    }
+
+    /// Gets a vertex metadata value, unless it or its vertex has expired.
+    fn get_vertex_metadata_value(&self, key: &(Uuid, String)) -> Option<&JsonValue> {
+        if has_expired(&self.vertex_metadata_expiries, key) || self.get_vertex(&key.0).is_none() {
+            None
+        } else {
+            self.vertex_metadata.get(key)
+        }
+    }
+
+    /// Gets an edge metadata value, unless it or its edge has expired.
+    fn get_edge_metadata_value(&self, key: &(models::EdgeKey, String)) -> Option<&JsonValue> {
+        if has_expired(&self.edge_metadata_expiries, key) || self.get_edge(&key.0).is_none() {
+            None
+        } else {
+            self.edge_metadata.get(key)
+        }
+    }
+
+    /// Gets the versions of an edge, as they'll be once anything that has
+    /// expired is deleted.
+    fn get_edge_versions(&self, key: &models::EdgeKey) -> Vec<models::EdgeVersion> {
+        // The history of an edge goes along with either of its vertices
+        if self.get_vertex(&key.outbound_id).is_none() || self.get_vertex(&key.inbound_id).is_none() {
+            return Vec::new();
+        }
+
+        let mut versions = match self.edge_history.get(key) {
+            Some(versions) => versions.clone(),
+            None => return Vec::new()
+        };
+
+        if has_expired(&self.edge_expiries, key) {
+            versions.insert(self.edge_expiries[key], None);
+        }
+
+        versions.into_iter().map(|(datetime, value)| {
+            models::EdgeVersion::new(value.map(|(weight, _)| weight), value.map(|(_, version)| version), datetime)
+        }).collect()
+    }
+
+    fn get_vertex_values_by_query(&self, q: VertexQuery) -> Vec<(Uuid, VertexValue)> {
+        match q {
+            VertexQuery::All(start_id, limit) => {
+                let low = match start_id {
+                    Some(start_id) => Bound::Excluded(start_id),
+                    None => Bound::Unbounded
+                };
+
+                self.vertices
+                    .range((low, Bound::Unbounded))
+                    .filter(|&(id, _)| self.get_vertex(id).is_some())
+                    .take(limit as usize)
+                    .map(|(id, value)| (*id, value.clone()))
+                    .collect()
+            },
+            VertexQuery::AllOfType(t, start_id, limit) => {
+                let low = match start_id {
+                    Some(start_id) => Bound::Excluded(start_id),
+                    None => Bound::Unbounded
+                };
+
+                self.vertices
+                    .range((low, Bound::Unbounded))
+                    .filter(|&(id, value)| value.t == t && self.get_vertex(id).is_some())
+                    .take(limit as usize)
+                    .map(|(id, value)| (*id, value.clone()))
+                    .collect()
+            },
+            VertexQuery::Vertex(id) => {
+                match self.get_vertex(&id) {
+                    Some(value) => vec![(id, value.clone())],
+                    None => vec![]
+                }
+            },
+            VertexQuery::Vertices(ids) => {
+                ids.into_iter()
+                    .filter_map(|id| self.get_vertex(&id).map(|value| (id, value.clone())))
+                    .collect()
+            },
+            VertexQuery::Pipe(edge_query, converter, limit) => {
+                let edge_values = self.get_edge_values_by_query(*edge_query);
+
+                let ids: BTreeSet<Uuid> = edge_values.into_iter().map(|(key, _)| {
+                    match converter {
+                        QueryTypeConverter::Outbound => key.outbound_id,
+                        QueryTypeConverter::Inbound => key.inbound_id
+                    }
+                }).collect();
+
+                ids.into_iter()
+                    .filter_map(|id| self.get_vertex(&id).map(|value| (id, value.clone())))
+                    .take(limit as usize)
+                    .collect()
+            },
+            VertexQuery::Traverse(vertex_query, converter, t, min_depth, max_depth, limit) => {
+                let mut visited: BTreeSet<Uuid> = BTreeSet::new();
+                let mut found: BTreeSet<Uuid> = BTreeSet::new();
+                let mut frontier: Vec<Uuid> = Vec::new();
+
+                for (id, _) in self.get_vertex_values_by_query(*vertex_query) {
+                    if visited.insert(id) {
+                        frontier.push(id);
+                    }
+                }
+
+                let mut depth = 0;
+
+                loop {
+                    if depth >= min_depth {
+                        found.extend(frontier.iter().cloned());
+                    }
+
+                    if depth == max_depth || frontier.is_empty() {
+                        break;
+                    }
+
+                    let mut next_frontier: Vec<Uuid> = Vec::new();
+
+                    for id in frontier {
+                        for key in self.get_edge_keys_for_vertex(id, &converter, &t) {
+                            if self.get_edge(&key).is_none() {
+                                continue;
+                            }
+
+                            let next_id = match converter {
+                                QueryTypeConverter::Outbound => key.inbound_id,
+                                QueryTypeConverter::Inbound => key.outbound_id
+                            };
+
+                            if visited.insert(next_id) {
+                                next_frontier.push(next_id);
+                            }
+                        }
+                    }
+
+                    frontier = next_frontier;
+                    depth += 1;
+                }
+
+                found.into_iter()
+                    .filter_map(|id| self.get_vertex(&id).map(|value| (id, value.clone())))
+                    .take(limit as usize)
+                    .collect()
+            },
+            VertexQuery::Union(left_query, right_query) => {
+                let mut values: BTreeMap<Uuid, VertexValue> = self.get_vertex_values_by_query(*left_query).into_iter().collect();
+                values.extend(self.get_vertex_values_by_query(*right_query));
+                values.into_iter().collect()
+            },
+            VertexQuery::Intersection(left_query, right_query) => {
+                let values: BTreeMap<Uuid, VertexValue> = self.get_vertex_values_by_query(*left_query).into_iter().collect();
+                let right_ids: BTreeSet<Uuid> = self.get_vertex_values_by_query(*right_query).into_iter().map(|(id, _)| id).collect();
+                values.into_iter().filter(|&(id, _)| right_ids.contains(&id)).collect()
+            },
+            VertexQuery::Difference(left_query, right_query) => {
+                let values: BTreeMap<Uuid, VertexValue> = self.get_vertex_values_by_query(*left_query).into_iter().collect();
+                let right_ids: BTreeSet<Uuid> = self.get_vertex_values_by_query(*right_query).into_iter().map(|(id, _)| id).collect();
+                values.into_iter().filter(|&(id, _)| !right_ids.contains(&id)).collect()
+            },
+            VertexQuery::Filter(vertex_query, name, predicate) => {
+                self.get_vertex_values_by_query(*vertex_query)
+                    .into_iter()
+                    .filter(|&(id, _)| {
+                        self.get_vertex_metadata_value(&(id, name.clone())).map_or(false, |value| predicate.matches(value))
+                    })
+                    .collect()
+            },
+            VertexQuery::Lookup(t, name, predicate, start_id, limit) => {
+                // Indexes only speed up lookups, so they aren't maintained
+                // here; every lookup scans the vertices instead
+                let low = match start_id {
+                    Some(start_id) => Bound::Excluded(start_id),
+                    None => Bound::Unbounded
+                };
+
+                self.vertices
+                    .range((low, Bound::Unbounded))
+                    .filter(|&(id, value)| {
+                        value.t == t && self.get_vertex_metadata_value(&(*id, name.clone())).map_or(false, |value| predicate.matches(value))
+                    })
+                    .take(limit as usize)
+                    .map(|(id, value)| (*id, value.clone()))
+                    .collect()
+            },
+            VertexQuery::HasMetadata(name, start_id, limit) => {
+                let low = match start_id {
+                    Some(start_id) => Bound::Excluded(start_id),
+                    None => Bound::Unbounded
+                };
+
+                self.vertices
+                    .range((low, Bound::Unbounded))
+                    .filter(|&(id, _)| self.get_vertex_metadata_value(&(*id, name.clone())).is_some())
+                    .take(limit as usize)
+                    .map(|(id, value)| (*id, value.clone()))
+                    .collect()
+            }
+        }
+    }
+
+    fn get_edge_values_by_query(&self, q: EdgeQuery) -> Vec<(models::EdgeKey, EdgeValue)> {
+        match q {
+            EdgeQuery::Edge(key) => {
+                match self.get_edge(&key) {
+                    Some(value) => vec![(key, value.clone())],
+                    None => vec![]
+                }
+            },
+            EdgeQuery::Edges(keys) => {
+                keys.into_iter()
+                    .filter_map(|key| self.get_edge(&key).map(|value| (key.clone(), value.clone())))
+                    .collect()
+            },
+            EdgeQuery::Pipe(vertex_query, converter, t, high, low, limit) => {
+                self.get_edge_values_in_datetime_range(*vertex_query, converter, t, high, low, limit, |value| value.update_datetime)
+            },
+            EdgeQuery::CreatedPipe(vertex_query, converter, t, high, low, limit) => {
+                self.get_edge_values_in_datetime_range(*vertex_query, converter, t, high, low, limit, |value| value.created_datetime)
+            },
+            EdgeQuery::SortedPipe(vertex_query, converter, t, high, low, order, limit) => {
+                let vertex_values = self.get_vertex_values_by_query(*vertex_query);
+                let mut edge_values = Vec::new();
+
+                for (id, _) in vertex_values {
+                    for key in self.get_edge_keys_for_vertex(id, &converter, &t) {
+                        let value = match self.get_edge(&key) {
+                            Some(value) => value.clone(),
+                            None => continue
+                        };
+
+                        if let Some(high) = high {
+                            if value.weight > high {
+                                continue;
+                            }
+                        }
+
+                        if let Some(low) = low {
+                            if value.weight < low {
+                                continue;
+                            }
+                        }
+
+                        edge_values.push((key, value));
+                    }
+                }
+
+                edge_values.sort_by(|a, b| {
+                    let ordering = match order {
+                        EdgeOrder::NewestFirst => b.1.update_datetime.cmp(&a.1.update_datetime),
+                        EdgeOrder::OldestFirst => a.1.update_datetime.cmp(&b.1.update_datetime),
+                        EdgeOrder::StrongestFirst => b.1.weight.cmp(&a.1.weight),
+                        EdgeOrder::WeakestFirst => a.1.weight.cmp(&b.1.weight)
+                    };
+
+                    ordering.then_with(|| a.0.cmp(&b.0))
+                });
+
+                edge_values.truncate(limit as usize);
+                edge_values
+            },
+            EdgeQuery::PagedPipe(vertex_query, converter, t, cursor, limit) => {
+                let mut ids: Vec<Uuid> = self.get_vertex_values_by_query(*vertex_query).into_iter().map(|(id, _)| id).collect();
+                ids.sort();
+                ids.dedup();
+
+                // Edges are ordered by the vertex they were piped from, then
+                // type, then newest first, and then the other vertex
+                let page_order = |key: &models::EdgeKey, update_datetime: DateTime<UTC>, other_key: &models::EdgeKey, other_update_datetime: DateTime<UTC>| {
+                    let (first_id, second_id, other_first_id, other_second_id) = match converter {
+                        QueryTypeConverter::Outbound => (key.outbound_id, key.inbound_id, other_key.outbound_id, other_key.inbound_id),
+                        QueryTypeConverter::Inbound => (key.inbound_id, key.outbound_id, other_key.inbound_id, other_key.outbound_id)
+                    };
+
+                    first_id.cmp(&other_first_id)
+                        .then_with(|| key.t.cmp(&other_key.t))
+                        .then_with(|| other_update_datetime.cmp(&update_datetime))
+                        .then_with(|| second_id.cmp(&other_second_id))
+                };
+
+                let mut edge_values = Vec::new();
+
+                for id in ids {
+                    for key in self.get_edge_keys_for_vertex(id, &converter, &t) {
+                        let value = match self.get_edge(&key) {
+                            Some(value) => value.clone(),
+                            None => continue
+                        };
+
+                        if let Some(ref cursor) = cursor {
+                            if page_order(&key, value.update_datetime, cursor.key(), cursor.update_datetime()) != Ordering::Greater {
+                                continue;
+                            }
+                        }
+
+                        edge_values.push((key, value));
+                    }
+                }
+
+                edge_values.sort_by(|a, b| page_order(&a.0, a.1.update_datetime, &b.0, b.1.update_datetime));
+                edge_values.truncate(limit as usize);
+                edge_values
+            },
+            EdgeQuery::Union(left_query, right_query) => {
+                let mut values: BTreeMap<models::EdgeKey, EdgeValue> = self.get_edge_values_by_query(*left_query).into_iter().collect();
+                values.extend(self.get_edge_values_by_query(*right_query));
+                values.into_iter().collect()
+            },
+            EdgeQuery::Intersection(left_query, right_query) => {
+                let values: BTreeMap<models::EdgeKey, EdgeValue> = self.get_edge_values_by_query(*left_query).into_iter().collect();
+                let right_keys: BTreeSet<models::EdgeKey> = self.get_edge_values_by_query(*right_query).into_iter().map(|(key, _)| key).collect();
+                values.into_iter().filter(|&(ref key, _)| right_keys.contains(key)).collect()
+            },
+            EdgeQuery::Difference(left_query, right_query) => {
+                let values: BTreeMap<models::EdgeKey, EdgeValue> = self.get_edge_values_by_query(*left_query).into_iter().collect();
+                let right_keys: BTreeSet<models::EdgeKey> = self.get_edge_values_by_query(*right_query).into_iter().map(|(key, _)| key).collect();
+                values.into_iter().filter(|&(ref key, _)| !right_keys.contains(key)).collect()
+            },
+            EdgeQuery::Filter(edge_query, name, predicate) => {
+                self.get_edge_values_by_query(*edge_query)
+                    .into_iter()
+                    .filter(|&(ref key, _)| {
+                        self.get_edge_metadata_value(&(key.clone(), name.clone())).map_or(false, |value| predicate.matches(value))
+                    })
+                    .collect()
+            },
+            EdgeQuery::HasMetadata(name, start_key, limit) => {
+                let low = match start_key {
+                    Some(start_key) => Bound::Excluded(start_key),
+                    None => Bound::Unbounded
+                };
+
+                self.edges
+                    .range((low, Bound::Unbounded))
+                    .filter(|&(key, _)| self.get_edge_metadata_value(&(key.clone(), name.clone())).is_some())
+                    .take(limit as usize)
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect()
+            }
+        }
+    }
+
+    fn get_edge_keys_for_vertex(&self, id: Uuid, converter: &QueryTypeConverter, t: &Option<models::Type>) -> Vec<models::EdgeKey> {
+        // Start iterating at the lowest possible key for the vertex (and
+        // type, if one was specified), and stop once we're out of the prefix
+        let low_t = t.clone().unwrap_or_else(|| models::Type("".to_string()));
+        let low_key = models::EdgeKey::new(id, low_t, Uuid::default());
+
+        let in_range = |key: &models::EdgeKey| -> bool {
+            key.outbound_id == id && t.as_ref().map_or(true, |t| &key.t == t)
+        };
+
+        match *converter {
+            QueryTypeConverter::Outbound => {
+                self.edges
+                    .range(low_key..)
+                    .map(|(key, _)| key)
+                    .take_while(|&key| in_range(key))
+                    .cloned()
+                    .collect()
+            },
+            QueryTypeConverter::Inbound => {
+                self.reversed_edges
+                    .range(low_key..)
+                    .take_while(|&key| in_range(key))
+                    .map(reverse_edge_key)
+                    .collect()
+            }
+        }
+    }
+
+    /// Gets the edges of a vertex query whose datetime, as picked out by
+    /// `datetime`, is between `low` and `high` inclusive, latest first.
+    fn get_edge_values_in_datetime_range<F>(&self,
+                                            vertex_query: VertexQuery,
+                                            converter: QueryTypeConverter,
+                                            t: Option<models::Type>,
+                                            high: Option<DateTime<UTC>>,
+                                            low: Option<DateTime<UTC>>,
+                                            limit: u32,
+                                            datetime: F)
+                                            -> Vec<(models::EdgeKey, EdgeValue)>
+        where F: Fn(&EdgeValue) -> DateTime<UTC>
+    {
+        let vertex_values = self.get_vertex_values_by_query(vertex_query);
+        let mut edge_values = Vec::new();
+
+        for (id, _) in vertex_values {
+            for key in self.get_edge_keys_for_vertex(id, &converter, &t) {
+                let value = match self.get_edge(&key) {
+                    Some(value) => value.clone(),
+                    None => continue
+                };
+
+                if let Some(high) = high {
+                    if datetime(&value) > high {
+                        continue;
+                    }
+                }
+
+                if let Some(low) = low {
+                    if datetime(&value) < low {
+                        continue;
+                    }
+                }
+
+                edge_values.push((key, value));
+            }
+        }
+
+        // Match the other datastores by returning the latest edges first
+        edge_values.sort_by(|a, b| datetime(&b.1).cmp(&datetime(&a.1)));
+        edge_values.truncate(limit as usize);
+        edge_values
+    }
+
+    /// Creates or updates an edge, returning its new version. Updated edges
+    /// keep their original created datetime, but stop expiring.
+    fn set_edge(&mut self, key: models::EdgeKey, update_datetime: DateTime<UTC>, weight: models::Weight) -> u64 {
+        // An expired edge that hasn't been deleted yet is replaced, rather
+        // than updated
+        if has_expired(&self.edge_expiries, &key) {
+            let expiry = self.edge_expiries[&key];
+            self.expire_edge(&key, expiry);
+        }
+
+        let (created_datetime, version) = match self.edges.get(&key) {
+            Some(value) => (value.created_datetime, value.version + 1),
+            None => (update_datetime, 1)
+        };
+
+        self.edge_expiries.remove(&key);
+        self.reversed_edges.insert(reverse_edge_key(&key));
+        self.set_edge_version(&key, update_datetime, Some((weight, version)));
+        self.edges.insert(key, EdgeValue::new(created_datetime, update_datetime, weight, version));
+        version
+    }
+
+    /// Sets a global metadata value, returning its new version.
+    fn set_global_metadata_value(&mut self, name: String, value: JsonValue) -> u64 {
+        let version = self.global_metadata_versions.get(&name).map_or(1, |version| version + 1);
+        self.global_metadata_versions.insert(name.clone(), version);
+        self.global_metadata.insert(name, value);
+        version
+    }
+
+    /// Deletes a global metadata value, returning whether it was set.
+    fn delete_global_metadata_value(&mut self, name: &str) -> bool {
+        self.global_metadata_versions.remove(name);
+        self.global_metadata.remove(name).is_some()
+    }
+
+    /// Sets an account metadata value, returning its new version.
+    fn set_account_metadata_value(&mut self, owner_id: Uuid, name: String, value: JsonValue) -> u64 {
+        let version = self.account_metadata_versions.get(&(owner_id, name.clone())).map_or(1, |version| version + 1);
+        self.account_metadata_versions.insert((owner_id, name.clone()), version);
+        self.account_metadata.insert((owner_id, name), value);
+        version
+    }
+
+    /// Deletes an account metadata value, returning whether it was set.
+    fn delete_account_metadata_value(&mut self, key: &(Uuid, String)) -> bool {
+        self.account_metadata_versions.remove(key);
+        self.account_metadata.remove(key).is_some()
+    }
+
+    /// Sets a vertex metadata value, returning its new version.
+    fn set_vertex_metadata_value(&mut self, id: Uuid, name: String, value: JsonValue) -> u64 {
+        let version = self.vertex_metadata_versions.get(&(id, name.clone())).map_or(1, |version| version + 1);
+        self.vertex_metadata_versions.insert((id, name.clone()), version);
+        self.vertex_metadata_expiries.remove(&(id, name.clone()));
+        self.vertex_metadata.insert((id, name), value);
+        version
+    }
+
+    fn delete_vertex_metadata_value(&mut self, key: &(Uuid, String)) {
+        self.vertex_metadata.remove(key);
+        self.vertex_metadata_versions.remove(key);
+        self.vertex_metadata_expiries.remove(key);
+    }
+
+    /// Sets an edge metadata value, returning its new version.
+    fn set_edge_metadata_value(&mut self, key: models::EdgeKey, name: String, value: JsonValue) -> u64 {
+        let version = self.edge_metadata_versions.get(&(key.clone(), name.clone())).map_or(1, |version| version + 1);
+        self.edge_metadata_versions.insert((key.clone(), name.clone()), version);
+        self.edge_metadata_expiries.remove(&(key.clone(), name.clone()));
+        self.edge_metadata.insert((key, name), value);
+        version
+    }
+
+    fn delete_edge_metadata_value(&mut self, key: &(models::EdgeKey, String)) {
+        self.edge_metadata.remove(key);
+        self.edge_metadata_versions.remove(key);
+        self.edge_metadata_expiries.remove(key);
+    }
+
+    /// Keeps a version of an edge, if history is enabled for its type. A
+    /// version without a weight and version number marks the edge as
+    /// deleted.
+    fn set_edge_version(&mut self, key: &models::EdgeKey, datetime: DateTime<UTC>, value: Option<(models::Weight, u64)>) {
+        if self.edge_history_types.contains(&key.t) {
+            self.edge_history.entry(key.clone()).or_insert_with(BTreeMap::new).insert(datetime, value);
+        }
+    }
+
+    /// Deletes the history of all of the edges matching a predicate.
+    fn delete_edge_history<F>(&mut self, predicate: F)
+        where F: Fn(&models::EdgeKey) -> bool
+    {
+        let keys: Vec<models::EdgeKey> = self.edge_history.keys().filter(|key| predicate(key)).cloned().collect();
+
+        for key in keys {
+            self.edge_history.remove(&key);
+        }
+    }
+
+    /// Checks whether setting metadata on some vertices, given as tuples of
+    /// the vertex ID, its type and the new value, would give more than one
+    /// vertex the same value for a unique constraint.
+    fn violates_unique_constraints(&self, values: &[(Uuid, &models::Type, &JsonValue)], name: &str) -> bool {
+        for constraint in self.unique_constraints.iter().filter(|constraint| constraint.name == name) {
+            let constrained_values: Vec<(Uuid, &JsonValue)> = values.iter()
+                .filter(|&&(_, t, _)| *t == constraint.t)
+                .map(|&(id, _, value)| (id, value))
+                .collect();
+
+            let ids: BTreeSet<Uuid> = constrained_values.iter().map(|&(id, _)| id).collect();
+
+            for (i, &(_, value)) in constrained_values.iter().enumerate() {
+                let predicate = models::MetadataPredicate::Equal(value.clone());
+
+                if constrained_values[..i].iter().any(|&(_, other_value)| predicate.matches(other_value)) {
+                    return true;
+                }
+
+                let conflicts = self.vertex_metadata.iter().any(|(key, metadata_value)| {
+                    key.1 == name && !ids.contains(&key.0) && predicate.matches(metadata_value) &&
+                        self.get_vertex_metadata_value(key).is_some() &&
+                        self.get_vertex(&key.0).map_or(false, |vertex_value| vertex_value.t == constraint.t)
+                });
+
+                if conflicts {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn delete_account(&mut self, id: Uuid) {
+        self.accounts.remove(&id);
+
+        for key in owned_metadata_keys(&self.account_metadata, id) {
+            self.delete_account_metadata_value(&key);
+        }
+
+        let vertex_ids: Vec<Uuid> = self.vertices
+            .iter()
+            .filter(|&(_, value)| value.owner_id == id)
+            .map(|(vertex_id, _)| *vertex_id)
+            .collect();
+
+        for vertex_id in vertex_ids {
+            self.delete_vertex(vertex_id);
+        }
+    }
+
+    fn delete_vertex(&mut self, id: Uuid) {
+        self.vertices.remove(&id);
+        self.vertex_expiries.remove(&id);
+
+        for key in owned_metadata_keys(&self.vertex_metadata, id) {
+            self.delete_vertex_metadata_value(&key);
+        }
+
+        for key in self.get_edge_keys_for_vertex(id, &QueryTypeConverter::Outbound, &None) {
+            self.delete_edge(&key);
+        }
+
+        for key in self.get_edge_keys_for_vertex(id, &QueryTypeConverter::Inbound, &None) {
+            self.delete_edge(&key);
+        }
+
+        self.delete_edge_history(|key| key.outbound_id == id || key.inbound_id == id);
+    }
+
+    fn delete_edge(&mut self, key: &models::EdgeKey) {
+        self.edges.remove(key);
+        self.reversed_edges.remove(&reverse_edge_key(key));
+        self.edge_expiries.remove(key);
+
+        let metadata_keys: Vec<(models::EdgeKey, String)> = self.edge_metadata
+            .range((key.clone(), String::new())..)
+            .map(|(metadata_key, _)| metadata_key)
+            .take_while(|&&(ref owner_key, _)| owner_key == key)
+            .cloned()
+            .collect();
+
+        for metadata_key in metadata_keys {
+            self.delete_edge_metadata_value(&metadata_key);
+        }
+    }
+
+    /// Deletes an expired edge, giving it a version without a weight at the
+    /// datetime it expired, if its type has history enabled.
+    fn expire_edge(&mut self, key: &models::EdgeKey, expiry: DateTime<UTC>) {
+        self.delete_edge(key);
+        self.set_edge_version(key, expiry, None);
+    }
+
+    /// Deletes everything that has expired as of a given datetime. Expired
+    /// edges get a version without a weight at the datetime they expired,
+    /// if their type has history enabled.
+    fn delete_expired(&mut self, now: DateTime<UTC>) {
+        for (id, _) in expired_keys(&self.vertex_expiries, now) {
+            self.delete_vertex(id);
+        }
+
+        for (key, expiry) in expired_keys(&self.edge_expiries, now) {
+            self.expire_edge(&key, expiry);
+        }
+
+        for (key, _) in expired_keys(&self.vertex_metadata_expiries, now) {
+            self.delete_vertex_metadata_value(&key);
+        }
+
+        for (key, _) in expired_keys(&self.edge_metadata_expiries, now) {
+            self.delete_edge_metadata_value(&key);
+        }
+    }
+}
+
+/// A datastore that is backed by memory.
+///
+/// Nothing is persisted, so this is mostly useful for tests and prototyping.
+/// Clones of the datastore share the same underlying data.
+#[derive(Clone, Debug)]
+pub struct MemoryDatastore {
+    /// The datastore contents.
+    datastore: Arc<RwLock<InternalMemoryDatastore>>,
+    /// Whether to use secure UUIDs.
+    secure_uuids: bool
+}
+
+impl MemoryDatastore {
+    /// Creates a new, empty in-memory datastore.
+    ///
+    /// # Arguments
+    /// * `secure_uuids` - If true, UUIDv4 will be used, which will result in
+    ///   difficult to guess UUIDs at the detriment of a more index-optimized
+    ///   (and thus faster) variant.
+    pub fn new(secure_uuids: bool) -> MemoryDatastore {
+        MemoryDatastore {
+            datastore: Arc::new(RwLock::new(InternalMemoryDatastore::default())),
+            secure_uuids: secure_uuids
+        }
+    }
+}
+
+impl Datastore<MemoryTransaction> for MemoryDatastore {
+    fn has_account(&self, account_id: Uuid) -> Result<bool, Error> {
+        let datastore = self.datastore.read().unwrap();
+        Ok(datastore.accounts.contains_key(&account_id))
+    }
+
+    fn create_account(&self) -> Result<(Uuid, String), Error> {
+        let id = parent_uuid();
+        let salt = generate_random_secret();
+        let secret = generate_random_secret();
+        let hash = get_salted_hash(&salt[..], None, &secret[..]);
+        let mut datastore = self.datastore.write().unwrap();
+        datastore.accounts.insert(id, AccountValue::new(salt, hash));
+        Ok((id, secret))
+    }
+
+    fn delete_account(&self, account_id: Uuid) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        if !datastore.accounts.contains_key(&account_id) {
+            return Err(Error::AccountNotFound);
+        }
+
+        datastore.delete_account(account_id);
+        Ok(())
+    }
+
+    fn auth(&self, account_id: Uuid, secret: String) -> Result<bool, Error> {
+        let datastore = self.datastore.read().unwrap();
+
+        match datastore.accounts.get(&account_id) {
+            Some(value) => {
+                let expected_hash = get_salted_hash(&value.salt[..], None, &secret[..]);
+                Ok(expected_hash == value.hash)
+            }
+            _ => {
+                // Calculate the hash anyways to prevent a timing attack
+                get_salted_hash("", None, &secret[..]);
+                Ok(false)
+            }
+        }
+    }
+
+    fn transaction(&self, account_id: Uuid) -> Result<MemoryTransaction, Error> {
+        MemoryTransaction::new(self.datastore.clone(), account_id, self.secure_uuids)
+    }
+
+    fn create_vertex_metadata_index(&self, index: models::VertexMetadataIndex) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+        datastore.vertex_metadata_indexes.insert(index);
+        Ok(())
+    }
+
+    fn delete_vertex_metadata_index(&self, index: models::VertexMetadataIndex) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+        datastore.vertex_metadata_indexes.remove(&index);
+        Ok(())
+    }
+
+    fn get_vertex_metadata_indexes(&self) -> Result<Vec<models::VertexMetadataIndex>, Error> {
+        let datastore = self.datastore.read().unwrap();
+        Ok(datastore.vertex_metadata_indexes.iter().cloned().collect())
+    }
+
+    fn create_unique_constraint(&self, constraint: models::UniqueConstraint) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        let values: Vec<JsonValue> = datastore.vertex_metadata.iter()
+            .filter(|&(key, _)| {
+                key.1 == constraint.name && datastore.get_vertex_metadata_value(key).is_some() &&
+                    datastore.get_vertex(&key.0).map_or(false, |value| value.t == constraint.t)
+            })
+            .map(|(_, value)| value.clone())
+            .collect();
+
+        for (i, value) in values.iter().enumerate() {
+            let predicate = models::MetadataPredicate::Equal(value.clone());
+
+            if values[i + 1..].iter().any(|other_value| predicate.matches(other_value)) {
+                return Err(Error::UniqueConstraintViolated);
+            }
+        }
+
+        datastore.unique_constraints.insert(constraint);
+        Ok(())
+    }
+
+    fn delete_unique_constraint(&self, constraint: models::UniqueConstraint) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+        datastore.unique_constraints.remove(&constraint);
+        Ok(())
+    }
+
+    fn get_unique_constraints(&self) -> Result<Vec<models::UniqueConstraint>, Error> {
+        let datastore = self.datastore.read().unwrap();
+        Ok(datastore.unique_constraints.iter().cloned().collect())
+    }
+
+    fn enable_edge_history(&self, t: models::Type) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        if !datastore.edge_history_types.insert(t.clone()) {
+            return Ok(());
+        }
+
+        let edge_values: Vec<(models::EdgeKey, EdgeValue)> = datastore.edges.iter()
+            .filter(|&(key, _)| key.t == t)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        for (key, value) in edge_values {
+            datastore.set_edge_version(&key, value.update_datetime, Some((value.weight, value.version)));
+        }
+
+        Ok(())
+    }
+
+    fn disable_edge_history(&self, t: models::Type) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+        datastore.edge_history_types.remove(&t);
+        datastore.delete_edge_history(|key| key.t == t);
+        Ok(())
+    }
+
+    fn get_edge_history_types(&self) -> Result<Vec<models::Type>, Error> {
+        let datastore = self.datastore.read().unwrap();
+        Ok(datastore.edge_history_types.iter().cloned().collect())
+    }
+
+    fn delete_expired(&self) -> Result<(), Error> {
+        self.datastore.write().unwrap().delete_expired(UTC::now());
+        Ok(())
+    }
+}
+
+/// A transaction that is backed by memory.
+#[derive(Debug)]
+pub struct MemoryTransaction {
+    /// The datastore contents.
+    datastore: Arc<RwLock<InternalMemoryDatastore>>,
+    /// The ID of the account that's triggering this transaction.
+    account_id: Uuid,
+    /// Whether to use secure UUIDs.
+    secure_uuids: bool
+}
+
+impl MemoryTransaction {
+    fn new(datastore: Arc<RwLock<InternalMemoryDatastore>>, account_id: Uuid, secure_uuids: bool) -> Result<Self, Error> {
+        Ok(MemoryTransaction {
+            datastore: datastore,
+            account_id: account_id,
+            secure_uuids: secure_uuids
+        })
+    }
+
+    fn check_write_permissions(&self, datastore: &InternalMemoryDatastore, id: Uuid, not_found_err: Error) -> Result<(), Error> {
+        match datastore.get_vertex(&id) {
+            None => Err(not_found_err),
+            Some(value) => {
+                if value.owner_id != self.account_id {
+                    Err(Error::Unauthorized)
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Sets a vertex metadata value for each vertex matching a query, making
+    /// it expire at a given datetime, or never if it's `None`.
+    fn set_vertex_metadata_until(&self, q: VertexQuery, name: String, value: JsonValue, expiry: Option<DateTime<UTC>>) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+        let vertex_values = datastore.get_vertex_values_by_query(q);
+
+        {
+            let values: Vec<(Uuid, &models::Type, &JsonValue)> = vertex_values.iter()
+                .map(|&(id, ref vertex_value)| (id, &vertex_value.t, &value))
+                .collect();
+
+            if datastore.violates_unique_constraints(&values[..], &name[..]) {
+                return Err(Error::UniqueConstraintViolated);
+            }
+        }
+
+        for (id, _) in vertex_values {
+            datastore.set_vertex_metadata_value(id, name.clone(), value.clone());
+
+            if let Some(expiry) = expiry {
+                datastore.vertex_metadata_expiries.insert((id, name.clone()), expiry);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets an edge metadata value for each edge matching a query, making it
+    /// expire at a given datetime, or never if it's `None`.
+    fn set_edge_metadata_until(&self, q: EdgeQuery, name: String, value: JsonValue, expiry: Option<DateTime<UTC>>) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        for (key, _) in datastore.get_edge_values_by_query(q) {
+            datastore.set_edge_metadata_value(key.clone(), name.clone(), value.clone());
+
+            if let Some(expiry) = expiry {
+                datastore.edge_metadata_expiries.insert((key, name.clone()), expiry);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Transaction for MemoryTransaction {
+    fn create_vertex(&self, t: models::Type) -> Result<Uuid, Error> {
+        let id = if self.secure_uuids {
+            parent_uuid()
+        } else {
+            child_uuid(self.account_id)
+        };
+
+        let mut datastore = self.datastore.write().unwrap();
+        datastore.vertices.insert(id, VertexValue::new(self.account_id, t));
+        Ok(id)
+    }
+
+    fn create_vertex_with_id(&self, id: Uuid, t: models::Type) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        if datastore.get_vertex(&id).is_some() {
+            return Err(Error::VertexAlreadyExists);
+        }
+
+        // Replace any expired vertex with the ID that hasn't been deleted yet
+        if datastore.vertices.contains_key(&id) {
+            datastore.delete_vertex(id);
+        }
+
+        datastore.vertices.insert(id, VertexValue::new(self.account_id, t));
+        Ok(())
+    }
+
+    fn create_vertices(&self, types: Vec<models::Type>) -> Result<Vec<Uuid>, Error> {
+        let mut datastore = self.datastore.write().unwrap();
+        let mut ids: Vec<Uuid> = Vec::with_capacity(types.len());
+
+        for t in types {
+            let id = if self.secure_uuids {
+                parent_uuid()
+            } else {
+                child_uuid(self.account_id)
+            };
+
+            datastore.vertices.insert(id, VertexValue::new(self.account_id, t));
+            ids.push(id);
+        }
+
+        Ok(ids)
+    }
+
+    fn create_vertex_with_ttl(&self, t: models::Type, ttl: Duration) -> Result<Uuid, Error> {
+        let expiry = expiry_datetime(ttl)?;
+        let id = self.create_vertex(t)?;
+        self.datastore.write().unwrap().vertex_expiries.insert(id, expiry);
+        Ok(id)
+    }
+
+    fn get_vertices(&self, q: VertexQuery) -> Result<Vec<models::Vertex>, Error> {
+        let datastore = self.datastore.read().unwrap();
+
+        let vertices = datastore.get_vertex_values_by_query(q)
+            .into_iter()
+            .map(|(id, value)| models::Vertex::new(id, value.t))
+            .collect();
+
+        Ok(vertices)
+    }
+
+    fn delete_vertices(&self, q: VertexQuery) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        for (id, value) in datastore.get_vertex_values_by_query(q) {
+            if value.owner_id == self.account_id {
+                datastore.delete_vertex(id);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn create_edge(&self, key: models::EdgeKey, weight: models::Weight) -> Result<(), Error> {
+        self.create_edge_at(key, weight, UTC::now())
+    }
+
+    fn create_edge_at(&self, key: models::EdgeKey, weight: models::Weight, update_datetime: DateTime<UTC>) -> Result<(), Error> {
+        let update_datetime = check_datetime(update_datetime, "update_datetime")?;
+        let mut datastore = self.datastore.write().unwrap();
+
+        // Verify that the vertices exist and that we own the vertex with the outbound ID
+        self.check_write_permissions(&datastore, key.outbound_id, Error::VertexNotFound)?;
+        if datastore.get_vertex(&key.inbound_id).is_none() {
+            return Err(Error::VertexNotFound);
+        }
+
+        datastore.set_edge(key, update_datetime, weight);
+        Ok(())
+    }
+
+    fn create_edge_with_ttl(&self, key: models::EdgeKey, weight: models::Weight, ttl: Duration) -> Result<(), Error> {
+        let expiry = expiry_datetime(ttl)?;
+        let mut datastore = self.datastore.write().unwrap();
+
+        self.check_write_permissions(&datastore, key.outbound_id, Error::VertexNotFound)?;
+        if datastore.get_vertex(&key.inbound_id).is_none() {
+            return Err(Error::VertexNotFound);
+        }
+
+        datastore.set_edge(key.clone(), UTC::now(), weight);
+        datastore.edge_expiries.insert(key, expiry);
+        Ok(())
+    }
+
+    fn create_edge_if_version(&self, key: models::EdgeKey, weight: models::Weight, expected_version: Option<u64>) -> Result<u64, Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        self.check_write_permissions(&datastore, key.outbound_id, Error::VertexNotFound)?;
+        if datastore.get_vertex(&key.inbound_id).is_none() {
+            return Err(Error::VertexNotFound);
+        }
+
+        if datastore.get_edge(&key).map(|value| value.version) != expected_version {
+            return Err(Error::Conflict);
+        }
+
+        Ok(datastore.set_edge(key, UTC::now(), weight))
+    }
+
+    fn create_edges(&self, items: Vec<(models::EdgeKey, models::Weight)>) -> Result<Vec<Result<(), Error>>, Error> {
+        let mut datastore = self.datastore.write().unwrap();
+        let new_update_datetime = UTC::now();
+        let mut results: Vec<Result<(), Error>> = Vec::with_capacity(items.len());
+
+        for (key, weight) in items {
+            if let Err(err) = self.check_write_permissions(&datastore, key.outbound_id, Error::VertexNotFound) {
+                results.push(Err(err));
+            } else if datastore.get_vertex(&key.inbound_id).is_none() {
+                results.push(Err(Error::VertexNotFound));
+            } else {
+                datastore.set_edge(key, new_update_datetime, weight);
+                results.push(Ok(()));
+            }
+        }
+
+        Ok(results)
+    }
+
+    fn get_edges(&self, q: EdgeQuery) -> Result<Vec<models::Edge>, Error> {
+        let datastore = self.datastore.read().unwrap();
+
+        let edges = datastore.get_edge_values_by_query(q)
+            .into_iter()
+            .map(|(key, value)| models::Edge::new_with_version(key, value.weight, value.created_datetime, value.update_datetime, value.version))
+            .collect();
+
+        Ok(edges)
+    }
+
+    fn get_edge_history(&self, key: models::EdgeKey) -> Result<Vec<models::EdgeVersion>, Error> {
+        let datastore = self.datastore.read().unwrap();
+
+        Ok(datastore.get_edge_versions(&key))
+    }
+
+    fn get_edges_as_of(&self,
+                       q: VertexQuery,
+                       converter: QueryTypeConverter,
+                       t: models::Type,
+                       datetime: DateTime<UTC>,
+                       limit: u32)
+                       -> Result<Vec<models::Edge>, Error> {
+        let datastore = self.datastore.read().unwrap();
+        let mut edges = Vec::new();
+
+        for (id, _) in datastore.get_vertex_values_by_query(q) {
+            let history = datastore.edge_history.iter().filter(|&(key, _)| {
+                key.t == t && match converter {
+                    QueryTypeConverter::Outbound => key.outbound_id == id,
+                    QueryTypeConverter::Inbound => key.inbound_id == id
+                }
+            });
+
+            for key in history.map(|(key, _)| key) {
+                let versions = datastore.get_edge_versions(key);
+
+                if let Some(edge) = edge_as_of(key.clone(), &versions, datetime) {
+                    edges.push(edge);
+                }
+            }
+        }
+
+        edges.sort_by(|a, b| b.updated_datetime.cmp(&a.updated_datetime));
+        edges.truncate(limit as usize);
+        Ok(edges)
+    }
+
+    fn delete_edges(&self, q: EdgeQuery) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+        let deleted_datetime = UTC::now();
+
+        for (key, _) in datastore.get_edge_values_by_query(q) {
+            let is_owned = match datastore.vertices.get(&key.outbound_id) {
+                Some(value) => value.owner_id == self.account_id,
+                None => false
+            };
+
+            if is_owned {
+                datastore.delete_edge(&key);
+                datastore.set_edge_version(&key, deleted_datetime, None);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_edge_count(&self, q: EdgeQuery) -> Result<u64, Error> {
+        let datastore = self.datastore.read().unwrap();
+        Ok(datastore.get_edge_values_by_query(q).len() as u64)
+    }
+
+    fn get_global_metadata(&self, name: String) -> Result<JsonValue, Error> {
+        let datastore = self.datastore.read().unwrap();
+        datastore.global_metadata.get(&name).cloned().ok_or_else(|| Error::MetadataNotFound)
+    }
+
+    fn get_all_global_metadata(&self, prefix: String, start_name: Option<String>, limit: u32) -> Result<Vec<(String, JsonValue)>, Error> {
+        let datastore = self.datastore.read().unwrap();
+
+        let low = match start_name {
+            Some(start_name) if start_name >= prefix => Bound::Excluded(start_name),
+            _ => Bound::Included(prefix.clone())
+        };
+
+        let metadata = datastore.global_metadata.range((low, Bound::Unbounded))
+            .take_while(|&(name, _)| name.starts_with(&prefix[..]))
+            .take(limit as usize)
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+
+        Ok(metadata)
+    }
+
+    fn set_global_metadata(&self, name: String, value: JsonValue) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+        datastore.set_global_metadata_value(name, value);
+        Ok(())
+    }
+
+    fn update_global_metadata(&self, name: String, operation: models::MetadataOperation) -> Result<JsonValue, Error> {
+        let mut datastore = self.datastore.write().unwrap();
+        let value = operation.apply(datastore.global_metadata.get(&name))?;
+        datastore.set_global_metadata_value(name, value.clone());
+        Ok(value)
+    }
+
+    fn delete_global_metadata(&self, name: String) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        if datastore.delete_global_metadata_value(&name[..]) {
+            Ok(())
+        } else {
+            Err(Error::MetadataNotFound)
+        }
+    }
+
+    fn get_versioned_global_metadata(&self, name: String) -> Result<models::VersionedMetadata, Error> {
+        let datastore = self.datastore.read().unwrap();
+
+        match datastore.global_metadata.get(&name) {
+            Some(value) => Ok(models::VersionedMetadata::new(value.clone(), datastore.global_metadata_versions[&name])),
+            None => Err(Error::MetadataNotFound)
+        }
+    }
+
+    fn set_global_metadata_if_version(&self, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        if datastore.global_metadata_versions.get(&name).cloned() != expected_version {
+            return Err(Error::Conflict);
+        }
+
+        Ok(datastore.set_global_metadata_value(name, value))
+    }
+
+    fn get_account_metadata(&self, owner_id: Uuid, name: String) -> Result<JsonValue, Error> {
+        let datastore = self.datastore.read().unwrap();
+
+        if !datastore.accounts.contains_key(&owner_id) {
+            return Err(Error::AccountNotFound);
+        }
+
+        datastore.account_metadata.get(&(owner_id, name)).cloned().ok_or_else(|| Error::MetadataNotFound)
+    }
+
+    fn get_all_account_metadata(&self, owner_id: Uuid) -> Result<Vec<(String, JsonValue)>, Error> {
+        let datastore = self.datastore.read().unwrap();
+
+        if !datastore.accounts.contains_key(&owner_id) {
+            return Err(Error::AccountNotFound);
+        }
+
+        Ok(owned_metadata(&datastore.account_metadata, &owner_id))
+    }
+
+    fn set_account_metadata(&self, owner_id: Uuid, name: String, value: JsonValue) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        if !datastore.accounts.contains_key(&owner_id) {
+            return Err(Error::AccountNotFound);
+        }
+
+        datastore.set_account_metadata_value(owner_id, name, value);
+        Ok(())
+    }
+
+    fn update_account_metadata(&self, owner_id: Uuid, name: String, operation: models::MetadataOperation) -> Result<JsonValue, Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        if !datastore.accounts.contains_key(&owner_id) {
+            return Err(Error::AccountNotFound);
+        }
+
+        let value = operation.apply(datastore.account_metadata.get(&(owner_id, name.clone())))?;
+        datastore.set_account_metadata_value(owner_id, name, value.clone());
+        Ok(value)
+    }
+
+    fn delete_account_metadata(&self, owner_id: Uuid, name: String) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        if datastore.delete_account_metadata_value(&(owner_id, name)) {
+            Ok(())
+        } else {
+            Err(Error::MetadataNotFound)
+        }
+    }
+
+    fn get_versioned_account_metadata(&self, owner_id: Uuid, name: String) -> Result<models::VersionedMetadata, Error> {
+        let datastore = self.datastore.read().unwrap();
+
+        if !datastore.accounts.contains_key(&owner_id) {
+            return Err(Error::AccountNotFound);
+        }
+
+        let key = (owner_id, name);
+
+        match datastore.account_metadata.get(&key) {
+            Some(value) => Ok(models::VersionedMetadata::new(value.clone(), datastore.account_metadata_versions[&key])),
+            None => Err(Error::MetadataNotFound)
+        }
+    }
+
+    fn set_account_metadata_if_version(&self, owner_id: Uuid, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        if !datastore.accounts.contains_key(&owner_id) {
+            return Err(Error::AccountNotFound);
+        }
+
+        if datastore.account_metadata_versions.get(&(owner_id, name.clone())).cloned() != expected_version {
+            return Err(Error::Conflict);
+        }
+
+        Ok(datastore.set_account_metadata_value(owner_id, name, value))
+    }
+
+    fn get_vertex_metadata(&self, q: VertexQuery, name: String) -> Result<HashMap<Uuid, JsonValue>, Error> {
+        let datastore = self.datastore.read().unwrap();
+        let mut metadata: HashMap<Uuid, JsonValue> = HashMap::new();
+
+        for (id, _) in datastore.get_vertex_values_by_query(q) {
+            if let Some(value) = datastore.get_vertex_metadata_value(&(id, name.clone())) {
+                metadata.insert(id, value.clone());
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    fn get_all_vertex_metadata(&self, id: Uuid) -> Result<Vec<(String, JsonValue)>, Error> {
+        let datastore = self.datastore.read().unwrap();
+
+        if datastore.get_vertex(&id).is_none() {
+            return Err(Error::VertexNotFound);
+        }
+
+        let metadata = owned_metadata(&datastore.vertex_metadata, &id)
+            .into_iter()
+            .filter(|&(ref name, _)| datastore.get_vertex_metadata_value(&(id, name.clone())).is_some())
+            .collect();
+
+        Ok(metadata)
+    }
+
+    fn set_vertex_metadata(&self, q: VertexQuery, name: String, value: JsonValue) -> Result<(), Error> {
+        self.set_vertex_metadata_until(q, name, value, None)
+    }
+
+    fn set_vertex_metadata_with_ttl(&self, q: VertexQuery, name: String, value: JsonValue, ttl: Duration) -> Result<(), Error> {
+        self.set_vertex_metadata_until(q, name, value, Some(expiry_datetime(ttl)?))
+    }
+
+    fn update_vertex_metadata(&self, q: VertexQuery, name: String, operation: models::MetadataOperation) -> Result<HashMap<Uuid, JsonValue>, Error> {
+        let mut datastore = self.datastore.write().unwrap();
+        let vertex_values = datastore.get_vertex_values_by_query(q);
+        let mut values: Vec<JsonValue> = Vec::with_capacity(vertex_values.len());
+
+        for &(id, _) in &vertex_values {
+            values.push(operation.apply(datastore.get_vertex_metadata_value(&(id, name.clone())))?);
+        }
+
+        {
+            let values: Vec<(Uuid, &models::Type, &JsonValue)> = vertex_values.iter()
+                .zip(values.iter())
+                .map(|(&(id, ref vertex_value), value)| (id, &vertex_value.t, value))
+                .collect();
+
+            if datastore.violates_unique_constraints(&values[..], &name[..]) {
+                return Err(Error::UniqueConstraintViolated);
+            }
+        }
+
+        let mut metadata: HashMap<Uuid, JsonValue> = HashMap::new();
+
+        for ((id, _), value) in vertex_values.into_iter().zip(values.into_iter()) {
+            datastore.set_vertex_metadata_value(id, name.clone(), value.clone());
+            metadata.insert(id, value);
+        }
+
+        Ok(metadata)
+    }
+
+    fn delete_vertex_metadata(&self, q: VertexQuery, name: String) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        for (id, _) in datastore.get_vertex_values_by_query(q) {
+            datastore.delete_vertex_metadata_value(&(id, name.clone()));
+        }
+
+        Ok(())
+    }
+
+    fn get_versioned_vertex_metadata(&self, q: VertexQuery, name: String) -> Result<HashMap<Uuid, models::VersionedMetadata>, Error> {
+        let datastore = self.datastore.read().unwrap();
+        let mut metadata: HashMap<Uuid, models::VersionedMetadata> = HashMap::new();
+
+        for (id, _) in datastore.get_vertex_values_by_query(q) {
+            let key = (id, name.clone());
+
+            if let Some(value) = datastore.get_vertex_metadata_value(&key) {
+                metadata.insert(id, models::VersionedMetadata::new(value.clone(), datastore.vertex_metadata_versions[&key]));
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    fn set_vertex_metadata_if_version(&self, id: Uuid, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error> {
+        let mut datastore = self.datastore.write().unwrap();
+        let vertex_values = datastore.get_vertex_values_by_query(VertexQuery::Vertex(id));
+
+        if vertex_values.is_empty() {
+            return Err(Error::VertexNotFound);
+        }
+
+        let key = (id, name.clone());
+
+        if datastore.get_vertex_metadata_value(&key).map(|_| datastore.vertex_metadata_versions[&key]) != expected_version {
+            return Err(Error::Conflict);
+        }
+
+        if datastore.violates_unique_constraints(&[(id, &vertex_values[0].1.t, &value)], &name[..]) {
+            return Err(Error::UniqueConstraintViolated);
+        }
+
+        Ok(datastore.set_vertex_metadata_value(id, name, value))
+    }
+
+    fn get_edge_metadata(&self, q: EdgeQuery, name: String) -> Result<HashMap<models::EdgeKey, JsonValue>, Error> {
+        let datastore = self.datastore.read().unwrap();
+        let mut metadata: HashMap<models::EdgeKey, JsonValue> = HashMap::new();
+
+        for (key, _) in datastore.get_edge_values_by_query(q) {
+            if let Some(value) = datastore.get_edge_metadata_value(&(key.clone(), name.clone())) {
+                metadata.insert(key, value.clone());
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    fn get_all_edge_metadata(&self, key: models::EdgeKey) -> Result<Vec<(String, JsonValue)>, Error> {
+        let datastore = self.datastore.read().unwrap();
+
+        if datastore.get_edge(&key).is_none() {
+            return Err(Error::EdgeNotFound);
+        }
+
+        let metadata = owned_metadata(&datastore.edge_metadata, &key)
+            .into_iter()
+            .filter(|&(ref name, _)| datastore.get_edge_metadata_value(&(key.clone(), name.clone())).is_some())
+            .collect();
+
+        Ok(metadata)
+    }
+
+    fn set_edge_metadata(&self, q: EdgeQuery, name: String, value: JsonValue) -> Result<(), Error> {
+        self.set_edge_metadata_until(q, name, value, None)
+    }
+
+    fn set_edge_metadata_with_ttl(&self, q: EdgeQuery, name: String, value: JsonValue, ttl: Duration) -> Result<(), Error> {
+        self.set_edge_metadata_until(q, name, value, Some(expiry_datetime(ttl)?))
+    }
+
+    fn update_edge_metadata(&self, q: EdgeQuery, name: String, operation: models::MetadataOperation) -> Result<HashMap<models::EdgeKey, JsonValue>, Error> {
+        let mut datastore = self.datastore.write().unwrap();
+        let mut metadata: HashMap<models::EdgeKey, JsonValue> = HashMap::new();
+
+        for (key, _) in datastore.get_edge_values_by_query(q) {
+            let value = operation.apply(datastore.get_edge_metadata_value(&(key.clone(), name.clone())))?;
+            metadata.insert(key, value);
+        }
+
+        for (key, value) in &metadata {
+            datastore.set_edge_metadata_value(key.clone(), name.clone(), value.clone());
+        }
+
+        Ok(metadata)
+    }
+
+    fn delete_edge_metadata(&self, q: EdgeQuery, name: String) -> Result<(), Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        for (key, _) in datastore.get_edge_values_by_query(q) {
+            datastore.delete_edge_metadata_value(&(key, name.clone()));
+        }
+
+        Ok(())
+    }
+
+    fn get_versioned_edge_metadata(&self, q: EdgeQuery, name: String) -> Result<HashMap<models::EdgeKey, models::VersionedMetadata>, Error> {
+        let datastore = self.datastore.read().unwrap();
+        let mut metadata: HashMap<models::EdgeKey, models::VersionedMetadata> = HashMap::new();
+
+        for (key, _) in datastore.get_edge_values_by_query(q) {
+            let metadata_key = (key.clone(), name.clone());
+
+            if let Some(value) = datastore.get_edge_metadata_value(&metadata_key) {
+                metadata.insert(key, models::VersionedMetadata::new(value.clone(), datastore.edge_metadata_versions[&metadata_key]));
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    fn set_edge_metadata_if_version(&self, key: models::EdgeKey, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error> {
+        let mut datastore = self.datastore.write().unwrap();
+
+        if datastore.get_edge(&key).is_none() {
+            return Err(Error::EdgeNotFound);
+        }
+
+        let metadata_key = (key.clone(), name.clone());
+
+        if datastore.get_edge_metadata_value(&metadata_key).map(|_| datastore.edge_metadata_versions[&metadata_key]) != expected_version {
+            return Err(Error::Conflict);
+        }
+
+        Ok(datastore.set_edge_metadata_value(key, name, value))
+    }
+
+    fn commit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn rollback(self) -> Result<(), Error> {
+        Err(Error::Unexpected(
+            "Transactions cannot be rolled back in the memory datastore implementation"
+        .to_string()))
+    }
+}
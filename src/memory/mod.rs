@@ -0,0 +1,5 @@
+mod datastore;
+mod models;
+mod tests;
+
+pub use self::datastore::{MemoryDatastore, MemoryTransaction};
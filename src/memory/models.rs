@@ -0,0 +1,52 @@
+use uuid::Uuid;
+use models;
+use chrono::{DateTime, UTC};
+
+#[derive(Clone, Debug)]
+pub struct AccountValue {
+    pub salt: String,
+    pub hash: String,
+}
+
+impl AccountValue {
+    pub fn new(salt: String, hash: String) -> Self {
+        AccountValue {
+            salt: salt,
+            hash: hash,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VertexValue {
+    pub owner_id: Uuid,
+    pub t: models::Type,
+}
+
+impl VertexValue {
+    pub fn new(owner_id: Uuid, t: models::Type) -> Self {
+        VertexValue {
+            owner_id: owner_id,
+            t: t,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EdgeValue {
+    pub created_datetime: DateTime<UTC>,
+    pub update_datetime: DateTime<UTC>,
+    pub weight: models::Weight,
+    pub version: u64,
+}
+
+impl EdgeValue {
+    pub fn new(created_datetime: DateTime<UTC>, update_datetime: DateTime<UTC>, weight: models::Weight, version: u64) -> Self {
+        EdgeValue {
+            created_datetime: created_datetime,
+            update_datetime: update_datetime,
+            weight: weight,
+            version: version,
+        }
+    }
+}
@@ -0,0 +1,12 @@
+#![cfg(test)]
+
+pub use super::datastore::MemoryDatastore;
+pub use super::super::tests;
+
+fn datastore() -> MemoryDatastore {
+    MemoryDatastore::new(false)
+}
+
+test_account_impl!(datastore());
+test_transaction_impl!(datastore());
+test_metadata_impl!(datastore());
@@ -1,8 +1,15 @@
 use regex::Regex;
-use errors::ValidationError;
+use errors::{Error, ValidationError};
 use core::str::FromStr;
 use uuid::Uuid;
 use chrono::{UTC, DateTime};
+use std::hash::{Hash, Hasher};
+use std::cmp::Ordering;
+use serde_json::Value as JsonValue;
+use serde_json::{Map, Number};
+use std::i64;
+use serde::{Deserialize, Deserializer};
+use serde::de::Error as DeError;
 
 lazy_static! {
     static ref TYPE_VALIDATOR: Regex = Regex::new("^[a-zA-Z0-9-_]+$").unwrap();
@@ -12,6 +19,9 @@ lazy_static! {
 ///
 /// Vertices are how you would represent nouns in the datastore. An example
 /// might be a user, or a movie. All vertices have a unique ID and a type.
+/// Neither can change once the vertex is created, so unlike edges and
+/// metadata, vertices have no version; `Transaction::create_vertex_with_id`
+/// already fails if another writer created the vertex first.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Vertex {
     /// The id of the vertex.
@@ -43,7 +53,7 @@ impl PartialEq for Vertex {
 impl Eq for Vertex {}
 
 /// Represents a uniquely identifiable key to an edge.
-#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct EdgeKey {
     /// The id of the outbound vertex.
     pub outbound_id: Uuid,
@@ -87,7 +97,14 @@ pub struct Edge {
     pub weight: Weight,
 
     /// When the edge was created.
-    pub created_datetime: DateTime<UTC>
+    pub created_datetime: DateTime<UTC>,
+
+    /// When the edge was last created or updated.
+    pub updated_datetime: DateTime<UTC>,
+
+    /// The version of the edge, which starts at 1 when the edge is created
+    /// and goes up by 1 every time it's updated.
+    pub version: u64
 }
 
 impl Edge {
@@ -101,26 +118,77 @@ impl Edge {
         Self::new(key, weight, UTC::now())
     }
 
-    /// Creates a new edge with a specified datetime.
+    /// Creates a new edge with a specified datetime. The edge is at its
+    /// first version, and hasn't been updated since it was created.
     ///
     /// # Arguments
     /// * `key` - The key to the edge.
     /// * `weight` - The weight of the edge.
     /// * `created_datetime` - When the edge was created.
     pub fn new(key: EdgeKey, weight: Weight, created_datetime: DateTime<UTC>) -> Edge {
+        Self::new_with_version(key, weight, created_datetime, created_datetime, 1)
+    }
+
+    /// Creates a new edge with specified datetimes and version.
+    ///
+    /// # Arguments
+    /// * `key` - The key to the edge.
+    /// * `weight` - The weight of the edge.
+    /// * `created_datetime` - When the edge was created.
+    /// * `updated_datetime` - When the edge was last updated.
+    /// * `version` - The version of the edge.
+    pub fn new_with_version(key: EdgeKey, weight: Weight, created_datetime: DateTime<UTC>, updated_datetime: DateTime<UTC>, version: u64) -> Edge {
         Edge {
             key: key,
             weight: weight,
-            created_datetime: created_datetime
+            created_datetime: created_datetime,
+            updated_datetime: updated_datetime,
+            version: version
+        }
+    }
+}
+
+/// A version of an edge, as kept by datastores with history enabled for the
+/// edge's type.
+///
+/// A version records the weight an edge was set to at a given datetime,
+/// along with the edge's version number. Deleting an edge also records a
+/// version, without a weight or a version number.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct EdgeVersion {
+    /// The weight of the edge, or `None` if the edge was deleted.
+    pub weight: Option<Weight>,
+
+    /// The version number of the edge, or `None` if the edge was deleted.
+    pub version: Option<u64>,
+
+    /// When the edge was set to this version.
+    pub datetime: DateTime<UTC>
+}
+
+impl EdgeVersion {
+    /// Creates a new edge version.
+    ///
+    /// # Arguments
+    /// * `weight` - The weight of the edge, or `None` if it was deleted.
+    /// * `version` - The version number of the edge, or `None` if it was
+    ///   deleted.
+    /// * `datetime` - When the edge was set to this version.
+    pub fn new(weight: Option<Weight>, version: Option<u64>, datetime: DateTime<UTC>) -> EdgeVersion {
+        EdgeVersion {
+            weight: weight,
+            version: version,
+            datetime: datetime
         }
     }
 }
 
 /// An edge weight.
 ///
-/// Edge weights must be between -1.0 and 1.0.
-#[derive(Clone, Debug, Serialize, Deserialize, Copy)]
-pub struct Weight(pub f32);
+/// Edge weights must be between -1.0 and 1.0. They can only be built with
+/// `Weight::new`, so they're never NaN.
+#[derive(Clone, Debug, Serialize, Copy)]
+pub struct Weight(f32);
 
 impl Weight {
     /// Constructs a new edge weight.
@@ -130,21 +198,69 @@ impl Weight {
     /// * `weight` - The weight, between -1.0 and 1.0.
     ///
     /// # Errors
-    /// Returns a `ValidationError` if the weight is below -1.0 or above 1.0.
+    /// Returns a `ValidationError` if the weight is below -1.0, above 1.0,
+    /// or NaN.
     pub fn new(w: f32) -> Result<Self, ValidationError> {
-        if w < -1.0 || w > 1.0 {
+        if !(w >= -1.0 && w <= 1.0) {
             Err(ValidationError::new("Weight out of range".to_string()))
+        } else if w == 0.0 {
+            // Normalize -0.0, so that equal weights are stored identically
+            Ok(Weight(0.0))
         } else {
             Ok(Weight(w))
         }
     }
+
+    /// Gets the weight as a float.
+    pub fn value(&self) -> f32 {
+        self.0
+    }
+}
+
+// Weights are deserialized through `Weight::new`, so that they can't be
+// NaN or out of range either way.
+impl<'de> Deserialize<'de> for Weight {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Weight, D::Error> {
+        let w = f32::deserialize(deserializer)?;
+        Weight::new(w).map_err(DeError::custom)
+    }
+}
+
+// Weights are compared as floats. Since they can't be NaN, that's a total
+// order, so that they can be used in queries and sorted. Zeroes are hashed
+// alike to match.
+impl PartialEq for Weight {
+    fn eq(&self, other: &Weight) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Weight {}
+
+impl PartialOrd for Weight {
+    fn partial_cmp(&self, other: &Weight) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Weight {
+    fn cmp(&self, other: &Weight) -> Ordering {
+        self.0.partial_cmp(&other.0).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl Hash for Weight {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let w = if self.0 == 0.0 { 0.0f32 } else { self.0 };
+        w.to_bits().hash(state);
+    }
 }
 
 /// An edge or vertex type.
 ///
 /// Types must be less than 256 characters long, and can only contain letters,
 /// numbers, dashes and underscores.
-#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize, Hash, Ord, PartialOrd)]
 pub struct Type(pub String);
 
 impl Type {
@@ -200,22 +316,134 @@ pub enum QueryTypeConverter {
 pub enum VertexQuery {
     #[serde(rename="all")]
     All(Option<Uuid>, u32),
+    #[serde(rename="all_of_type")]
+    AllOfType(Type, Option<Uuid>, u32),
     #[serde(rename="vertex")]
     Vertex(Uuid),
     #[serde(rename="vertices")]
     Vertices(Vec<Uuid>),
     #[serde(rename="pipe")]
-    Pipe(Box<EdgeQuery>, QueryTypeConverter, u32)
+    Pipe(Box<EdgeQuery>, QueryTypeConverter, u32),
+    #[serde(rename="traverse")]
+    Traverse(Box<VertexQuery>, QueryTypeConverter, Option<Type>, u32, u32, u32),
+    #[serde(rename="union")]
+    Union(Box<VertexQuery>, Box<VertexQuery>),
+    #[serde(rename="intersection")]
+    Intersection(Box<VertexQuery>, Box<VertexQuery>),
+    #[serde(rename="difference")]
+    Difference(Box<VertexQuery>, Box<VertexQuery>),
+    #[serde(rename="filter")]
+    Filter(Box<VertexQuery>, String, MetadataPredicate),
+    #[serde(rename="lookup")]
+    Lookup(Type, String, MetadataPredicate, Option<Uuid>, u32),
+    /// Gets the vertices that have metadata with a given name, ordered by
+    /// ID, starting after an optional vertex ID.
+    #[serde(rename="has_metadata")]
+    HasMetadata(String, Option<Uuid>, u32)
 }
 
 impl VertexQuery {
+    /// Gets the outbound edges of this query's vertices that were last
+    /// updated between `low` and `high` inclusive, most recently updated
+    /// first.
     pub fn outbound_edges(self, t: Option<Type>, high: Option<DateTime<UTC>>, low: Option<DateTime<UTC>>, limit: u32) -> EdgeQuery {
         EdgeQuery::Pipe(Box::new(self), QueryTypeConverter::Outbound, t, high, low, limit)
     }
 
+    /// Gets the inbound edges of this query's vertices that were last
+    /// updated between `low` and `high` inclusive, most recently updated
+    /// first.
     pub fn inbound_edges(self, t: Option<Type>, high: Option<DateTime<UTC>>, low: Option<DateTime<UTC>>, limit: u32) -> EdgeQuery {
         EdgeQuery::Pipe(Box::new(self), QueryTypeConverter::Inbound, t, high, low, limit)
     }
+
+    /// Gets the outbound edges of this query's vertices that were created
+    /// between `low` and `high` inclusive, most recently created first.
+    pub fn created_outbound_edges(self, t: Option<Type>, high: Option<DateTime<UTC>>, low: Option<DateTime<UTC>>, limit: u32) -> EdgeQuery {
+        EdgeQuery::CreatedPipe(Box::new(self), QueryTypeConverter::Outbound, t, high, low, limit)
+    }
+
+    /// Gets the inbound edges of this query's vertices that were created
+    /// between `low` and `high` inclusive, most recently created first.
+    pub fn created_inbound_edges(self, t: Option<Type>, high: Option<DateTime<UTC>>, low: Option<DateTime<UTC>>, limit: u32) -> EdgeQuery {
+        EdgeQuery::CreatedPipe(Box::new(self), QueryTypeConverter::Inbound, t, high, low, limit)
+    }
+
+    /// Gets a page of the outbound edges of this query's vertices, starting
+    /// after `cursor`, or at the beginning if it's `None`. See `EdgeCursor`
+    /// for the order that pages are returned in.
+    pub fn outbound_edge_page(self, t: Option<Type>, cursor: Option<EdgeCursor>, limit: u32) -> EdgeQuery {
+        EdgeQuery::PagedPipe(Box::new(self), QueryTypeConverter::Outbound, t, cursor, limit)
+    }
+
+    /// Gets a page of the inbound edges of this query's vertices, starting
+    /// after `cursor`, or at the beginning if it's `None`. See `EdgeCursor`
+    /// for the order that pages are returned in.
+    pub fn inbound_edge_page(self, t: Option<Type>, cursor: Option<EdgeCursor>, limit: u32) -> EdgeQuery {
+        EdgeQuery::PagedPipe(Box::new(self), QueryTypeConverter::Inbound, t, cursor, limit)
+    }
+
+    /// Gets the outbound edges of this query's vertices whose weight is
+    /// between `low` and `high` inclusive, sorted by `order`.
+    pub fn sorted_outbound_edges(self, t: Option<Type>, high: Option<Weight>, low: Option<Weight>, order: EdgeOrder, limit: u32) -> EdgeQuery {
+        EdgeQuery::SortedPipe(Box::new(self), QueryTypeConverter::Outbound, t, high, low, order, limit)
+    }
+
+    /// Gets the inbound edges of this query's vertices whose weight is
+    /// between `low` and `high` inclusive, sorted by `order`.
+    pub fn sorted_inbound_edges(self, t: Option<Type>, high: Option<Weight>, low: Option<Weight>, order: EdgeOrder, limit: u32) -> EdgeQuery {
+        EdgeQuery::SortedPipe(Box::new(self), QueryTypeConverter::Inbound, t, high, low, order, limit)
+    }
+
+    /// Gets the vertices reachable from this query's vertices by following
+    /// outbound edges. Each vertex is returned at most once, and only if the
+    /// fewest number of hops needed to reach it is between `min_depth` and
+    /// `max_depth` inclusive. The starting vertices are at a depth of 0.
+    pub fn outbound_traversal(self, t: Option<Type>, min_depth: u32, max_depth: u32, limit: u32) -> VertexQuery {
+        VertexQuery::Traverse(Box::new(self), QueryTypeConverter::Outbound, t, min_depth, max_depth, limit)
+    }
+
+    /// Gets the vertices reachable from this query's vertices by following
+    /// inbound edges backwards. See `outbound_traversal` for how depths are
+    /// handled.
+    pub fn inbound_traversal(self, t: Option<Type>, min_depth: u32, max_depth: u32, limit: u32) -> VertexQuery {
+        VertexQuery::Traverse(Box::new(self), QueryTypeConverter::Inbound, t, min_depth, max_depth, limit)
+    }
+
+    /// Gets the vertices returned by either this query or `other`. Like the
+    /// other set operations, each vertex is returned once, ordered by ID.
+    pub fn union(self, other: VertexQuery) -> VertexQuery {
+        VertexQuery::Union(Box::new(self), Box::new(other))
+    }
+
+    /// Gets the vertices returned by both this query and `other`.
+    pub fn intersection(self, other: VertexQuery) -> VertexQuery {
+        VertexQuery::Intersection(Box::new(self), Box::new(other))
+    }
+
+    /// Gets the vertices returned by this query but not by `other`.
+    pub fn difference(self, other: VertexQuery) -> VertexQuery {
+        VertexQuery::Difference(Box::new(self), Box::new(other))
+    }
+
+    /// Gets the vertices returned by this query that have metadata called
+    /// `name` whose value matches `predicate`.
+    pub fn filter(self, name: String, predicate: MetadataPredicate) -> VertexQuery {
+        VertexQuery::Filter(Box::new(self), name, predicate)
+    }
+}
+
+/// The order that edges are returned in by sorted edge queries.
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Serialize, Deserialize, Hash)]
+pub enum EdgeOrder {
+    #[serde(rename="newest_first")]
+    NewestFirst,
+    #[serde(rename="oldest_first")]
+    OldestFirst,
+    #[serde(rename="strongest_first")]
+    StrongestFirst,
+    #[serde(rename="weakest_first")]
+    WeakestFirst
 }
 
 /// A query for edges.
@@ -229,7 +457,25 @@ pub enum EdgeQuery {
     #[serde(rename="edges")]
     Edges(Vec<EdgeKey>),
     #[serde(rename="pipe")]
-    Pipe(Box<VertexQuery>, QueryTypeConverter, Option<Type>, Option<DateTime<UTC>>, Option<DateTime<UTC>>, u32)
+    Pipe(Box<VertexQuery>, QueryTypeConverter, Option<Type>, Option<DateTime<UTC>>, Option<DateTime<UTC>>, u32),
+    #[serde(rename="created_pipe")]
+    CreatedPipe(Box<VertexQuery>, QueryTypeConverter, Option<Type>, Option<DateTime<UTC>>, Option<DateTime<UTC>>, u32),
+    #[serde(rename="sorted_pipe")]
+    SortedPipe(Box<VertexQuery>, QueryTypeConverter, Option<Type>, Option<Weight>, Option<Weight>, EdgeOrder, u32),
+    #[serde(rename="paged_pipe")]
+    PagedPipe(Box<VertexQuery>, QueryTypeConverter, Option<Type>, Option<EdgeCursor>, u32),
+    #[serde(rename="union")]
+    Union(Box<EdgeQuery>, Box<EdgeQuery>),
+    #[serde(rename="intersection")]
+    Intersection(Box<EdgeQuery>, Box<EdgeQuery>),
+    #[serde(rename="difference")]
+    Difference(Box<EdgeQuery>, Box<EdgeQuery>),
+    #[serde(rename="filter")]
+    Filter(Box<EdgeQuery>, String, MetadataPredicate),
+    /// Gets the edges that have metadata with a given name, ordered by key,
+    /// starting after an optional edge key.
+    #[serde(rename="has_metadata")]
+    HasMetadata(String, Option<EdgeKey>, u32)
 }
 
 impl EdgeQuery {
@@ -240,4 +486,493 @@ impl EdgeQuery {
     pub fn inbound_vertices(self, limit: u32) -> VertexQuery {
         VertexQuery::Pipe(Box::new(self), QueryTypeConverter::Inbound, limit)
     }
+
+    /// Gets the edges returned by either this query or `other`. Like the
+    /// other set operations, each edge is returned once, ordered by key.
+    pub fn union(self, other: EdgeQuery) -> EdgeQuery {
+        EdgeQuery::Union(Box::new(self), Box::new(other))
+    }
+
+    /// Gets the edges returned by both this query and `other`.
+    pub fn intersection(self, other: EdgeQuery) -> EdgeQuery {
+        EdgeQuery::Intersection(Box::new(self), Box::new(other))
+    }
+
+    /// Gets the edges returned by this query but not by `other`.
+    pub fn difference(self, other: EdgeQuery) -> EdgeQuery {
+        EdgeQuery::Difference(Box::new(self), Box::new(other))
+    }
+
+    /// Gets the edges returned by this query that have metadata called
+    /// `name` whose value matches `predicate`.
+    pub fn filter(self, name: String, predicate: MetadataPredicate) -> EdgeQuery {
+        EdgeQuery::Filter(Box::new(self), name, predicate)
+    }
+}
+
+/// A predicate on a metadata value, used to filter vertex and edge queries.
+///
+/// Items without the metadata never match. Ordering comparisons only match
+/// numbers, strings and booleans, and only against values of the same JSON
+/// type.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum MetadataPredicate {
+    /// Matches any value.
+    #[serde(rename="exists")]
+    Exists,
+    #[serde(rename="eq")]
+    Equal(JsonValue),
+    #[serde(rename="ne")]
+    NotEqual(JsonValue),
+    #[serde(rename="gt")]
+    GreaterThan(JsonValue),
+    #[serde(rename="gte")]
+    GreaterThanOrEqual(JsonValue),
+    #[serde(rename="lt")]
+    LessThan(JsonValue),
+    #[serde(rename="lte")]
+    LessThanOrEqual(JsonValue),
+    /// Applies a predicate to the value at a path within the metadata,
+    /// made up of object keys and array indexes. Doesn't match if there's
+    /// nothing at the path.
+    #[serde(rename="path")]
+    Path(Vec<String>, Box<MetadataPredicate>)
+}
+
+impl MetadataPredicate {
+    /// Checks whether a metadata value matches the predicate.
+    pub fn matches(&self, value: &JsonValue) -> bool {
+        match *self {
+            MetadataPredicate::Exists => true,
+            MetadataPredicate::Equal(ref operand) => json_equals(value, operand),
+            MetadataPredicate::NotEqual(ref operand) => !json_equals(value, operand),
+            MetadataPredicate::GreaterThan(ref operand) => json_cmp(value, operand) == Some(Ordering::Greater),
+            MetadataPredicate::GreaterThanOrEqual(ref operand) => json_cmp(value, operand).map_or(false, |o| o != Ordering::Less),
+            MetadataPredicate::LessThan(ref operand) => json_cmp(value, operand) == Some(Ordering::Less),
+            MetadataPredicate::LessThanOrEqual(ref operand) => json_cmp(value, operand).map_or(false, |o| o != Ordering::Greater),
+            MetadataPredicate::Path(ref path, ref predicate) => {
+                json_at_path(value, path).map_or(false, |value| predicate.matches(value))
+            }
+        }
+    }
+
+    /// If the predicate only matches values at a path that are equal to an
+    /// operand, returns the full path and the operand. These are the
+    /// predicates that can be answered by a `VertexMetadataIndex`.
+    pub fn equality(&self) -> Option<(Vec<String>, &JsonValue)> {
+        match *self {
+            MetadataPredicate::Equal(ref operand) => Some((vec![], operand)),
+            MetadataPredicate::Path(ref path, ref predicate) => {
+                predicate.equality().map(|(mut rest, operand)| {
+                    let mut full_path = path.clone();
+                    full_path.append(&mut rest);
+                    (full_path, operand)
+                })
+            },
+            _ => None
+        }
+    }
+}
+
+// JSON values can't be NaN, so equality is reflexive. `JsonValue` doesn't
+// implement `Hash`, so predicates are hashed by their serialized form.
+impl Eq for MetadataPredicate {}
+
+impl Hash for MetadataPredicate {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ::serde_json::to_string(self).unwrap().hash(state);
+    }
+}
+
+/// An atomic operation on a metadata value. The current value is read and
+/// the new one written without anything else changing the metadata in
+/// between.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum MetadataOperation {
+    /// Sets the value, but only if it's currently equal to the expected
+    /// value, or isn't set if `None` is expected. Numbers are compared by
+    /// value, as in `MetadataPredicate::Equal`.
+    #[serde(rename="compare_and_swap")]
+    CompareAndSwap(Option<JsonValue>, JsonValue),
+    /// Adds an amount to the number at a path within the value, made up of
+    /// object keys and array indexes. A missing number is treated as 0, and
+    /// missing metadata as an empty object, or as 0 if the path is empty.
+    /// Everything else along the path must already exist.
+    #[serde(rename="increment")]
+    Increment(Vec<String>, f64),
+    /// Applies an RFC 7386 JSON merge patch. Objects in the patch are merged
+    /// into the value recursively, with nulls removing keys, and anything
+    /// else replaces the value outright.
+    #[serde(rename="merge_patch")]
+    MergePatch(JsonValue)
+}
+
+impl MetadataOperation {
+    /// Applies the operation to the current value of some metadata, or
+    /// `None` if it isn't set, returning the new value.
+    ///
+    /// # Errors
+    /// Returns `Error::Conflict` if a compare-and-swap doesn't find the
+    /// expected value, and `Error::InvalidMetadataOperation` if an
+    /// increment's path doesn't lead to a number, or the result can't be
+    /// represented.
+    pub fn apply(&self, current: Option<&JsonValue>) -> Result<JsonValue, Error> {
+        match *self {
+            MetadataOperation::CompareAndSwap(ref expected, ref value) => {
+                let matches = match (current, expected.as_ref()) {
+                    (Some(current), Some(expected)) => json_equals(current, expected),
+                    (None, None) => true,
+                    _ => false
+                };
+
+                if matches {
+                    Ok(value.clone())
+                } else {
+                    Err(Error::Conflict)
+                }
+            },
+            MetadataOperation::Increment(ref path, amount) => {
+                let mut value = match current {
+                    Some(current) => current.clone(),
+                    None if path.is_empty() => JsonValue::from(0),
+                    None => JsonValue::Object(Map::new())
+                };
+
+                json_increment_at_path(&mut value, path, amount)?;
+                Ok(value)
+            },
+            MetadataOperation::MergePatch(ref patch) => {
+                let mut value = current.cloned().unwrap_or(JsonValue::Null);
+                json_merge_patch(&mut value, patch);
+                Ok(value)
+            }
+        }
+    }
+}
+
+/// Adds an amount to the number at a path within a JSON value. Integers stay
+/// integers as long as the amount is a whole number.
+fn json_increment_at_path(value: &mut JsonValue, path: &[String], amount: f64) -> Result<(), Error> {
+    let (component, rest) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            let new_value = match *value {
+                JsonValue::Number(ref number) => {
+                    match number.as_i64() {
+                        Some(integer) if amount.fract() == 0.0 && amount.abs() < i64::MAX as f64 => {
+                            integer.checked_add(amount as i64).map(JsonValue::from)
+                        },
+                        _ => number.as_f64().and_then(|number| Number::from_f64(number + amount)).map(JsonValue::Number)
+                    }
+                },
+                _ => None
+            };
+
+            return match new_value {
+                Some(new_value) => {
+                    *value = new_value;
+                    Ok(())
+                },
+                None => Err(Error::InvalidMetadataOperation)
+            };
+        }
+    };
+
+    let next = match *value {
+        JsonValue::Object(ref mut map) => {
+            if rest.is_empty() && !map.contains_key(component) {
+                map.insert(component.clone(), JsonValue::from(0));
+            }
+
+            map.get_mut(component)
+        },
+        JsonValue::Array(ref mut values) => component.parse::<usize>().ok().and_then(move |i| values.get_mut(i)),
+        _ => None
+    };
+
+    match next {
+        Some(next) => json_increment_at_path(next, rest, amount),
+        None => Err(Error::InvalidMetadataOperation)
+    }
+}
+
+/// Applies an RFC 7386 JSON merge patch to a JSON value.
+fn json_merge_patch(value: &mut JsonValue, patch: &JsonValue) {
+    let patch = match *patch {
+        JsonValue::Object(ref patch) => patch,
+        _ => {
+            *value = patch.clone();
+            return;
+        }
+    };
+
+    if !value.is_object() {
+        *value = JsonValue::Object(Map::new());
+    }
+
+    if let JsonValue::Object(ref mut map) = *value {
+        for (key, patch_value) in patch {
+            if patch_value.is_null() {
+                map.remove(key);
+            } else {
+                if !map.contains_key(key) {
+                    map.insert(key.clone(), JsonValue::Null);
+                }
+
+                json_merge_patch(map.get_mut(key).unwrap(), patch_value);
+            }
+        }
+    }
+}
+
+/// Gets the value at a path of object keys and array indexes within a JSON
+/// value, if there is one.
+fn json_at_path<'a>(value: &'a JsonValue, path: &[String]) -> Option<&'a JsonValue> {
+    let mut cur = value;
+
+    for component in path {
+        let next = match *cur {
+            JsonValue::Object(ref map) => map.get(component),
+            JsonValue::Array(ref values) => component.parse::<usize>().ok().and_then(|i| values.get(i)),
+            _ => None
+        };
+
+        match next {
+            Some(next) => cur = next,
+            None => return None
+        }
+    }
+
+    Some(cur)
+}
+
+/// Compares JSON values for equality, treating numbers as equal if they have
+/// the same value, e.g. `1` and `1.0`.
+fn json_equals(first: &JsonValue, second: &JsonValue) -> bool {
+    match (first, second) {
+        (&JsonValue::Number(_), &JsonValue::Number(_)) => json_cmp(first, second) == Some(Ordering::Equal),
+        _ => first == second
+    }
+}
+
+/// Orders two numbers, strings or booleans of the same JSON type.
+fn json_cmp(first: &JsonValue, second: &JsonValue) -> Option<Ordering> {
+    match (first, second) {
+        (&JsonValue::Number(ref first), &JsonValue::Number(ref second)) => {
+            first.as_f64().and_then(|first| second.as_f64().and_then(|second| first.partial_cmp(&second)))
+        },
+        (&JsonValue::String(ref first), &JsonValue::String(ref second)) => Some(first.cmp(second)),
+        (&JsonValue::Bool(first), &JsonValue::Bool(second)) => Some(first.cmp(&second)),
+        _ => None
+    }
+}
+
+/// An index on vertex metadata.
+///
+/// Indexes cover the metadata called `name` on vertices of type `t`, or the
+/// value at `path` within it if the path isn't empty. They're used by
+/// `VertexQuery::Lookup` queries whose predicate checks for equality at the
+/// same path, which would otherwise have to scan every vertex of the type.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize, Hash, Ord, PartialOrd)]
+pub struct VertexMetadataIndex {
+    /// The type of the indexed vertices.
+    #[serde(rename="type")]
+    pub t: Type,
+
+    /// The name of the indexed metadata.
+    pub name: String,
+
+    /// The path to the indexed value within the metadata.
+    pub path: Vec<String>
+}
+
+impl VertexMetadataIndex {
+    /// Creates a new vertex metadata index.
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - The type of the indexed vertices.
+    /// * `name` - The name of the indexed metadata.
+    /// * `path` - The path to the indexed value within the metadata.
+    pub fn new(t: Type, name: String, path: Vec<String>) -> VertexMetadataIndex {
+        VertexMetadataIndex {
+            t: t,
+            name: name,
+            path: path
+        }
+    }
+
+    /// Gets the value that's indexed for a metadata value, if there is
+    /// anything at the index's path.
+    pub fn indexed_value<'a>(&self, value: &'a JsonValue) -> Option<&'a JsonValue> {
+        json_at_path(value, &self.path[..])
+    }
+}
+
+/// A unique constraint on vertex metadata.
+///
+/// No two vertices of type `t` can have metadata called `name` with equal
+/// values. Numbers are compared by value, so e.g. `1` and `1.0` are equal.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize, Hash, Ord, PartialOrd)]
+pub struct UniqueConstraint {
+    /// The type of the constrained vertices.
+    #[serde(rename="type")]
+    pub t: Type,
+
+    /// The name of the constrained metadata.
+    pub name: String
+}
+
+impl UniqueConstraint {
+    /// Creates a new unique constraint.
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - The type of the constrained vertices.
+    /// * `name` - The name of the constrained metadata.
+    pub fn new(t: Type, name: String) -> UniqueConstraint {
+        UniqueConstraint {
+            t: t,
+            name: name
+        }
+    }
+}
+
+/// A metadata value, along with its version.
+///
+/// Versions start at 1 when the metadata is set for the first time, and go
+/// up by 1 every time it's set again. Deleting the metadata resets its
+/// version.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct VersionedMetadata {
+    /// The metadata value.
+    pub value: JsonValue,
+
+    /// The version of the metadata.
+    pub version: u64
+}
+
+impl VersionedMetadata {
+    /// Creates a new versioned metadata value.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The metadata value.
+    /// * `version` - The version of the metadata.
+    pub fn new(value: JsonValue, version: u64) -> VersionedMetadata {
+        VersionedMetadata {
+            value: value,
+            version: version
+        }
+    }
+}
+
+/// A position in the results of a paged edge query.
+///
+/// Paged edge queries return edges grouped by the vertex they were piped
+/// from, then by type, and then newest first, with ties broken by the ID of
+/// the vertex on the other end. A cursor records the last edge of a page, so
+/// the next page picks up right after it even if many edges share an update
+/// datetime. The contents of a cursor are specific to the datastore that
+/// returned it, and shouldn't be relied upon.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize, Hash)]
+pub struct EdgeCursor {
+    key: EdgeKey,
+    update_datetime: DateTime<UTC>
+}
+
+impl EdgeCursor {
+    /// Creates a cursor that points to an edge.
+    ///
+    /// # Arguments
+    /// * `key` - The key to the edge.
+    /// * `update_datetime` - When the edge was last updated.
+    pub fn new(key: EdgeKey, update_datetime: DateTime<UTC>) -> EdgeCursor {
+        EdgeCursor {
+            key: key,
+            update_datetime: update_datetime
+        }
+    }
+
+    /// The key to the edge that the cursor points to.
+    pub fn key(&self) -> &EdgeKey {
+        &self.key
+    }
+
+    /// When the edge that the cursor points to was last updated.
+    pub fn update_datetime(&self) -> DateTime<UTC> {
+        self.update_datetime
+    }
+}
+
+/// A page of results from a paged edge query.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EdgePage {
+    /// The edges in the page.
+    pub edges: Vec<Edge>,
+
+    /// The cursor to get the next page with, or `None` if this is the last
+    /// page.
+    pub cursor: Option<EdgeCursor>
+}
+
+/// A query for a path between two vertices.
+///
+/// Paths are found by following edges from the first vertex to the second -
+/// either following outbound edges, or following inbound edges backwards.
+/// Paths can optionally be restricted to edges of a given type, and are
+/// never more than the given maximum number of edges long.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize, Hash)]
+pub enum PathQuery {
+    /// The path with the fewest edges.
+    #[serde(rename="shortest")]
+    Shortest(Uuid, Uuid, QueryTypeConverter, Option<Type>, u32),
+    /// The path with the lowest total cost, where each edge costs
+    /// `1 - weight`. Stronger edges are cheaper, so this finds the strongest
+    /// path.
+    #[serde(rename="weighted")]
+    Weighted(Uuid, Uuid, QueryTypeConverter, Option<Type>, u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Edge, EdgeKey, Type, Weight};
+    use chrono::{TimeZone, UTC};
+    use std::collections::hash_map::DefaultHasher;
+    use std::f32;
+    use std::hash::{Hash, Hasher};
+
+    fn hash_weight(weight: &Weight) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        weight.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn should_validate_weights() {
+        assert!(Weight::new(-1.0).is_ok());
+        assert!(Weight::new(1.0).is_ok());
+        assert!(Weight::new(-1.1).is_err());
+        assert!(Weight::new(1.1).is_err());
+        assert!(Weight::new(f32::NAN).is_err());
+        assert!(Weight::new(f32::INFINITY).is_err());
+    }
+
+    #[test]
+    fn should_treat_zero_weights_alike() {
+        let negative_zero = Weight::new(-0.0).unwrap();
+        assert!(negative_zero.0.is_sign_positive());
+        assert_eq!(Weight(-0.0), Weight(0.0));
+        assert_eq!(hash_weight(&Weight(-0.0)), hash_weight(&Weight(0.0)));
+    }
+
+    #[test]
+    fn should_create_new_edges_at_their_first_version() {
+        let key = EdgeKey::new(Default::default(), Type::new("test_edge_type".to_string()).unwrap(), Default::default());
+        let datetime = UTC.ymd(2017, 1, 1).and_hms(0, 0, 0);
+        let edge = Edge::new(key, Weight::new(0.5).unwrap(), datetime);
+        assert_eq!(edge.created_datetime, datetime);
+        assert_eq!(edge.updated_datetime, datetime);
+        assert_eq!(edge.version, 1);
+    }
 }
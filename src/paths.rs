@@ -0,0 +1,209 @@
+//! Path finding, built on top of the `Transaction` trait so that it works
+//! with any datastore.
+
+use errors::Error;
+use models::{Edge, EdgeQuery, PathQuery, QueryTypeConverter, Type, VertexQuery};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::u32;
+use traits::Transaction;
+use uuid::Uuid;
+
+pub fn get_path<T: Transaction + ?Sized>(trans: &T, q: PathQuery) -> Result<Option<Vec<Edge>>, Error> {
+    match q {
+        PathQuery::Shortest(from_id, to_id, converter, t, max_depth) => shortest_path(trans, from_id, to_id, converter, t, max_depth),
+        PathQuery::Weighted(from_id, to_id, converter, t, max_depth) => weighted_path(trans, from_id, to_id, converter, t, max_depth)
+    }
+}
+
+fn edges_query(ids: Vec<Uuid>, converter: &QueryTypeConverter, t: &Option<Type>) -> EdgeQuery {
+    match *converter {
+        QueryTypeConverter::Outbound => VertexQuery::Vertices(ids).outbound_edges(t.clone(), None, None, u32::MAX),
+        QueryTypeConverter::Inbound => VertexQuery::Vertices(ids).inbound_edges(t.clone(), None, None, u32::MAX)
+    }
+}
+
+/// Gets the ID of the vertex that an edge leads from.
+fn near_id(edge: &Edge, converter: &QueryTypeConverter) -> Uuid {
+    match *converter {
+        QueryTypeConverter::Outbound => edge.key.outbound_id,
+        QueryTypeConverter::Inbound => edge.key.inbound_id
+    }
+}
+
+/// Gets the ID of the vertex that an edge leads to.
+fn far_id(edge: &Edge, converter: &QueryTypeConverter) -> Uuid {
+    match *converter {
+        QueryTypeConverter::Outbound => edge.key.inbound_id,
+        QueryTypeConverter::Inbound => edge.key.outbound_id
+    }
+}
+
+/// A breadth-first search, which expands a whole level of the search at a
+/// time with a single edge query.
+fn shortest_path<T: Transaction + ?Sized>(trans: &T, from_id: Uuid, to_id: Uuid, converter: QueryTypeConverter, t: Option<Type>, max_depth: u32) -> Result<Option<Vec<Edge>>, Error> {
+    if from_id == to_id {
+        return Ok(Some(vec![]));
+    }
+
+    // The edge that each visited vertex was first reached by
+    let mut parent_edges: HashMap<Uuid, Edge> = HashMap::new();
+    let mut visited: HashSet<Uuid> = HashSet::new();
+    let mut frontier: Vec<Uuid> = vec![from_id];
+    visited.insert(from_id);
+
+    for _ in 0..max_depth {
+        if frontier.is_empty() {
+            break;
+        }
+
+        let mut next_frontier: Vec<Uuid> = Vec::new();
+
+        for edge in trans.get_edges(edges_query(frontier, &converter, &t))? {
+            let next_id = far_id(&edge, &converter);
+
+            if !visited.insert(next_id) {
+                continue;
+            }
+
+            parent_edges.insert(next_id, edge);
+
+            if next_id == to_id {
+                let mut path: Vec<Edge> = Vec::new();
+                let mut cur_id = to_id;
+
+                while cur_id != from_id {
+                    let edge = parent_edges.remove(&cur_id).unwrap();
+                    cur_id = near_id(&edge, &converter);
+                    path.push(edge);
+                }
+
+                path.reverse();
+                return Ok(Some(path));
+            }
+
+            next_frontier.push(next_id);
+        }
+
+        frontier = next_frontier;
+    }
+
+    Ok(None)
+}
+
+/// An entry in the weighted search's queue. Entries with the lowest cost,
+/// and then the fewest edges, are popped first.
+struct Candidate {
+    cost: f64,
+    depth: u32,
+    step: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Candidate) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Candidate) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Candidate) -> Ordering {
+        // `BinaryHeap` is a max-heap, so everything is reversed
+        compare_costs(other.cost, self.cost).then_with(|| other.depth.cmp(&self.depth))
+    }
+}
+
+/// Compares two costs. Costs are built from weights, which can't be NaN, but
+/// `f64` isn't `Ord`, so NaN is still ordered last rather than panicking.
+fn compare_costs(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b).unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+}
+
+/// Dijkstra's algorithm, where each edge costs `1 - weight`. Because paths
+/// are limited to `max_depth` edges, a vertex can't be settled the first
+/// time it's reached - a more expensive route with fewer edges might still
+/// be needed to get to the destination in time. So a vertex is only skipped
+/// once it has been reached both more cheaply and in as few edges.
+fn weighted_path<T: Transaction + ?Sized>(trans: &T, from_id: Uuid, to_id: Uuid, converter: QueryTypeConverter, t: Option<Type>, max_depth: u32) -> Result<Option<Vec<Edge>>, Error> {
+    // Each step of every path considered, as the vertex that the step
+    // reaches, along with the previous step and the edge followed
+    let mut steps: Vec<(Uuid, Option<(usize, Edge)>)> = vec![(from_id, None)];
+    let mut queue: BinaryHeap<Candidate> = BinaryHeap::new();
+    queue.push(Candidate { cost: 0.0, depth: 0, step: 0 });
+
+    // The fewest number of edges that each vertex has been expanded at
+    let mut expanded_depths: HashMap<Uuid, u32> = HashMap::new();
+    let mut edges_cache: HashMap<Uuid, Vec<Edge>> = HashMap::new();
+
+    while let Some(Candidate { cost, depth, step }) = queue.pop() {
+        let id = steps[step].0;
+
+        if id == to_id {
+            let mut path: Vec<Edge> = Vec::new();
+            let mut cur_step = step;
+
+            while let Some((prev_step, ref edge)) = steps[cur_step].1 {
+                path.push(edge.clone());
+                cur_step = prev_step;
+            }
+
+            path.reverse();
+            return Ok(Some(path));
+        }
+
+        if expanded_depths.get(&id).map_or(false, |&expanded_depth| expanded_depth <= depth) {
+            continue;
+        }
+
+        expanded_depths.insert(id, depth);
+
+        if depth >= max_depth {
+            continue;
+        }
+
+        if !edges_cache.contains_key(&id) {
+            let edges = trans.get_edges(edges_query(vec![id], &converter, &t))?;
+            edges_cache.insert(id, edges);
+        }
+
+        for edge in &edges_cache[&id] {
+            let next_id = far_id(edge, &converter);
+
+            if expanded_depths.get(&next_id).map_or(false, |&expanded_depth| expanded_depth <= depth + 1) {
+                continue;
+            }
+
+            steps.push((next_id, Some((step, edge.clone()))));
+
+            queue.push(Candidate {
+                cost: cost + 1.0 - f64::from(edge.weight.value()),
+                depth: depth + 1,
+                step: steps.len() - 1,
+            });
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compare_costs;
+    use std::cmp::Ordering;
+    use std::f64;
+
+    #[test]
+    fn should_order_nan_costs_last() {
+        assert_eq!(compare_costs(0.5, 1.5), Ordering::Less);
+        assert_eq!(compare_costs(f64::NAN, 1.5), Ordering::Greater);
+        assert_eq!(compare_costs(1.5, f64::NAN), Ordering::Less);
+        assert_eq!(compare_costs(f64::NAN, f64::NAN), Ordering::Equal);
+    }
+}
@@ -0,0 +1,90 @@
+use errors::Error;
+use postgres::rows::Row;
+use postgres::transaction::Transaction;
+use postgres::types::ToSql;
+use std::vec;
+use uuid::Uuid;
+
+/// The number of rows to fetch from the server at a time.
+const FETCH_SIZE: usize = 1000;
+
+/// An iterator over the results of a query, backed by a server-side cursor.
+///
+/// Rather than loading every row up front, rows are fetched from the server
+/// in pages of `FETCH_SIZE` as the iterator is consumed. The cursor lives
+/// inside the transaction, so it sees the transaction's own writes, and is
+/// closed when the iterator is dropped.
+pub struct CursorIterator<'a, T> {
+    trans: &'a Transaction<'static>,
+    name: String,
+    mapper: fn(&Row) -> Result<T, Error>,
+    page: vec::IntoIter<T>,
+    done: bool,
+}
+
+impl<'a, T> CursorIterator<'a, T> {
+    /// Declares a new cursor for a query.
+    ///
+    /// # Arguments
+    /// * `trans` - The transaction to declare the cursor in.
+    /// * `query` - The query to run.
+    /// * `params` - The query parameters.
+    /// * `mapper` - Converts each row into an item.
+    pub fn new(trans: &'a Transaction<'static>, query: &str, params: &[&ToSql], mapper: fn(&Row) -> Result<T, Error>) -> Result<Self, Error> {
+        let name = format!("cursor_{}", Uuid::new_v4().simple());
+        trans.execute(&format!("DECLARE {} NO SCROLL CURSOR FOR {}", name, query)[..], params)?;
+
+        Ok(CursorIterator {
+            trans: trans,
+            name: name,
+            mapper: mapper,
+            page: Vec::new().into_iter(),
+            done: false,
+        })
+    }
+
+    fn fetch_page(&mut self) -> Result<(), Error> {
+        let results = self.trans.query(&format!("FETCH {} FROM {}", FETCH_SIZE, self.name)[..], &[])?;
+        let mut items: Vec<T> = Vec::with_capacity(results.len());
+
+        for row in &results {
+            items.push((self.mapper)(&row)?);
+        }
+
+        if items.len() < FETCH_SIZE {
+            self.done = true;
+        }
+
+        self.page = items.into_iter();
+        Ok(())
+    }
+}
+
+impl<'a, T> Iterator for CursorIterator<'a, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Result<T, Error>> {
+        loop {
+            if let Some(item) = self.page.next() {
+                return Some(Ok(item));
+            }
+
+            if self.done {
+                return None;
+            }
+
+            if let Err(err) = self.fetch_page() {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+impl<'a, T> Drop for CursorIterator<'a, T> {
+    fn drop(&mut self) {
+        // Errors are ignored, since the cursor is closed with the
+        // transaction anyways
+        let _ = self.trans.execute(&format!("CLOSE {}", self.name)[..], &[]);
+    }
+}
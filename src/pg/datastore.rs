@@ -1,13 +1,13 @@
 use r2d2_postgres::{TlsMode, PostgresConnectionManager};
 use r2d2::{Config, Pool, PooledConnection};
 use std::mem;
-use super::super::{Datastore, Transaction, VertexQuery, EdgeQuery, QueryTypeConverter};
+use super::super::{Datastore, Transaction, VertexQuery, EdgeQuery, EdgeOrder, QueryTypeConverter, VertexStream, EdgeStream, VertexMetadataStream};
 use models;
 use errors::Error;
-use util::{generate_random_secret, get_salted_hash, parent_uuid, child_uuid};
+use util::{generate_random_secret, get_salted_hash, parent_uuid, child_uuid, expiry_datetime, check_datetime};
 use postgres;
-use postgres::rows::Rows;
-use chrono::{UTC, DateTime};
+use postgres::rows::{Row, Rows};
+use chrono::{UTC, DateTime, Duration};
 use serde_json::Value as JsonValue;
 use num_cpus;
 use uuid::Uuid;
@@ -15,9 +15,173 @@ use std::collections::HashMap;
 use std::i64;
 use postgres::error as pg_error;
 use super::util::CTEQueryBuilder;
+use super::cursor::CursorIterator;
 use postgres::types::ToSql;
 use super::schema;
 
+/// Builds a SQL condition that checks a metadata predicate.
+///
+/// # Arguments
+/// * `predicate` - The predicate to check.
+/// * `expr` - The SQL expression for the JSONB value to check, which may
+///   contain path parameters.
+/// * `expr_paths` - The path parameters in `expr`, in order. Since `expr` can
+///   be used more than once, these are pushed every time it is.
+/// * `params` - The query parameters to push to.
+fn predicate_to_sql(predicate: &models::MetadataPredicate, expr: &str, expr_paths: &[Vec<String>], params: &mut Vec<Box<ToSql>>) -> String {
+    let push_expr_params = |params: &mut Vec<Box<ToSql>>| {
+        for path in expr_paths {
+            params.push(Box::new(path.clone()));
+        }
+    };
+
+    let (operator, operand) = match *predicate {
+        models::MetadataPredicate::Exists => {
+            push_expr_params(params);
+            return format!("{} IS NOT NULL", expr);
+        },
+        models::MetadataPredicate::Path(ref path, ref predicate) => {
+            let mut paths = expr_paths.to_vec();
+            paths.push(path.clone());
+            return predicate_to_sql(predicate, &format!("({} #> %p)", expr)[..], &paths[..], params);
+        },
+        models::MetadataPredicate::Equal(ref operand) => ("=", operand),
+        models::MetadataPredicate::NotEqual(ref operand) => ("<>", operand),
+        models::MetadataPredicate::GreaterThan(ref operand) => (">", operand),
+        models::MetadataPredicate::GreaterThanOrEqual(ref operand) => (">=", operand),
+        models::MetadataPredicate::LessThan(ref operand) => ("<", operand),
+        models::MetadataPredicate::LessThanOrEqual(ref operand) => ("<=", operand)
+    };
+
+    if operator == "=" || operator == "<>" {
+        push_expr_params(params);
+        params.push(Box::new(operand.clone()));
+        return format!("{} {} %p", expr, operator);
+    }
+
+    // JSONB orders values of different types too, so check the type first
+    let json_type = match *operand {
+        JsonValue::Number(_) => "number",
+        JsonValue::String(_) => "string",
+        JsonValue::Bool(_) => "boolean",
+        _ => return "FALSE".to_string()
+    };
+
+    push_expr_params(params);
+    push_expr_params(params);
+    params.push(Box::new(operand.clone()));
+    format!("(jsonb_typeof({0}) = '{1}' AND {0} {2} %p)", expr, json_type, operator)
+}
+
+/// Quotes a string for use as a SQL literal. This is only needed for DDL,
+/// which can't take parameters.
+fn quote_literal(s: &str) -> String {
+    format!("'{}'", s.replace("'", "''"))
+}
+
+/// Gets the SQL expression that a vertex metadata index is built on. Lookups
+/// compare the same expression, with the path passed as a parameter, so that
+/// postgres can use the index.
+fn vertex_metadata_index_expr(path: &[String]) -> String {
+    if path.is_empty() {
+        "value".to_string()
+    } else {
+        let quoted_path: Vec<String> = path.iter().map(|s| quote_literal(s)).collect();
+        format!("(value #> ARRAY[{}]::text[])", quoted_path.join(", "))
+    }
+}
+
+/// Gets the name of the postgres index backing a vertex metadata index.
+fn vertex_metadata_index_name(id: Uuid) -> String {
+    format!("ix_vertex_metadata_{}", id.simple())
+}
+
+/// Deletes everything that has expired, for `Datastore::delete_expired`.
+/// Expired edges get a version without a weight at the datetime they
+/// expired, if their type has history enabled. Rows locked by another
+/// transaction are skipped rather than waited on, and left for a later
+/// sweep.
+fn delete_expired(trans: &postgres::transaction::Transaction) -> Result<(), Error> {
+    trans.batch_execute("
+        DELETE FROM vertices WHERE id IN (
+            SELECT id FROM vertices WHERE expires_at <= NOW() FOR UPDATE SKIP LOCKED
+        );
+
+        WITH e AS (
+            DELETE FROM edges WHERE id IN (
+                SELECT id FROM edges WHERE expires_at <= NOW() FOR UPDATE SKIP LOCKED
+            )
+            RETURNING outbound_id, type, inbound_id, expires_at
+        )
+        INSERT INTO edge_history (outbound_id, type, inbound_id, update_timestamp, weight, version)
+        SELECT e.outbound_id, e.type, e.inbound_id, e.expires_at, NULL, NULL
+        FROM e JOIN edge_history_types ON e.type=edge_history_types.type
+        ON CONFLICT ON CONSTRAINT edge_history_pkey DO UPDATE SET weight=excluded.weight, version=excluded.version;
+
+        DELETE FROM vertex_metadata WHERE (owner_id, name) IN (
+            SELECT owner_id, name FROM vertex_metadata WHERE expires_at <= NOW() FOR UPDATE SKIP LOCKED
+        );
+
+        DELETE FROM edge_metadata WHERE (owner_id, name) IN (
+            SELECT owner_id, name FROM edge_metadata WHERE expires_at <= NOW() FOR UPDATE SKIP LOCKED
+        );
+    ")?;
+
+    Ok(())
+}
+
+/// Builds a SQL condition for whether a row of a table with an `expires_at`
+/// column hasn't expired. Expired rows stay in the database until
+/// `delete_expired` sweeps them up, so reads have to skip them.
+/// `STATEMENT_TIMESTAMP()` is used rather than `NOW()`, which is fixed for
+/// the whole transaction, so that rows that expire part way through a
+/// transaction are hidden from its later statements, like they are in the
+/// other datastores.
+fn live_sql(table: &str) -> String {
+    format!("({0}.expires_at IS NULL OR {0}.expires_at > STATEMENT_TIMESTAMP())", table)
+}
+
+/// Builds a SQL condition for whether an edge hasn't expired, which it has
+/// if either of its vertices has.
+fn live_edge_sql(table: &str) -> String {
+    format!(
+        "{} AND NOT EXISTS (SELECT 1 FROM vertices expired_vertices WHERE expired_vertices.id IN ({1}.outbound_id, {1}.inbound_id) AND expired_vertices.expires_at <= STATEMENT_TIMESTAMP())",
+        live_sql(table),
+        table
+    )
+}
+
+fn row_to_vertex(row: &Row) -> Result<models::Vertex, Error> {
+    let id: Uuid = row.get(0);
+    let t_str: String = row.get(1);
+    Ok(models::Vertex::new(id, models::Type::new(t_str).unwrap()))
+}
+
+// Weights are validated before they're written, so a bad one means the
+// database was changed from outside.
+fn to_weight(weight_f32: f32) -> Result<models::Weight, Error> {
+    models::Weight::new(weight_f32).map_err(|err| Error::Unexpected(format!("Invalid edge weight {}: {}", weight_f32, err)))
+}
+
+fn row_to_edge(row: &Row) -> Result<models::Edge, Error> {
+    let outbound_id: Uuid = row.get(0);
+    let t_str: String = row.get(1);
+    let inbound_id: Uuid = row.get(2);
+    let weight_f32: f32 = row.get(3);
+    let weight = to_weight(weight_f32)?;
+    let update_datetime: DateTime<UTC> = row.get(4);
+    let created_datetime: DateTime<UTC> = row.get(5);
+    let version: i64 = row.get(6);
+    let key = models::EdgeKey::new(outbound_id, models::Type::new(t_str).unwrap(), inbound_id);
+    Ok(models::Edge::new_with_version(key, weight, created_datetime, update_datetime, version as u64))
+}
+
+fn row_to_vertex_metadata(row: &Row) -> Result<(Uuid, JsonValue), Error> {
+    let id: Uuid = row.get(0);
+    let value: JsonValue = row.get(1);
+    Ok((id, value))
+}
+
 /// A datastore that is backed by a postgres database.
 #[derive(Clone, Debug)]
 pub struct PostgresDatastore {
@@ -82,6 +246,26 @@ impl PostgresDatastore {
 
         Ok(())
     }
+
+    /// Brings the schema of a postgres database created by an earlier
+    /// version up to date. It's safe to run against a schema that's already
+    /// up to date. Requires postgres 9.6 or later.
+    ///
+    /// # Arguments
+    /// * `connection_string` - The postgres database connection string.
+    pub fn migrate_schema(connection_string: String) -> Result<(), Error> {
+        let conn = match postgres::Connection::connect(connection_string, postgres::TlsMode::None) {
+            Ok(conn) => conn,
+            Err(err) => return Err(Error::Unexpected(format!("Could not connect to the postgres database: {}", err)))
+        };
+
+        // The migrations are run in a transaction, so that a failure part
+        // way through doesn't leave the schema half migrated
+        let trans = conn.transaction()?;
+        trans.batch_execute(schema::MIGRATIONS)?;
+        trans.commit()?;
+        Ok(())
+    }
 }
 
 impl Datastore<PostgresTransaction> for PostgresDatastore {
@@ -142,6 +326,194 @@ impl Datastore<PostgresTransaction> for PostgresDatastore {
         let trans = PostgresTransaction::new(conn, account_id, self.secure_uuids)?;
         Ok(trans)
     }
+
+    fn create_vertex_metadata_index(&self, index: models::VertexMetadataIndex) -> Result<(), Error> {
+        let conn = self.pool.get()?;
+        let trans = conn.transaction()?;
+
+        // Lock the table so that concurrent calls can't both create the index
+        trans.execute("LOCK TABLE vertex_metadata_indexes IN SHARE ROW EXCLUSIVE MODE", &[])?;
+
+        let results = trans.query(
+            "SELECT 1 FROM vertex_metadata_indexes WHERE type=$1 AND name=$2 AND path=$3",
+            &[&index.t.0, &index.name, &index.path]
+        )?;
+
+        if !results.is_empty() {
+            return Ok(());
+        }
+
+        let id = parent_uuid();
+
+        trans.execute(
+            "INSERT INTO vertex_metadata_indexes (id, type, name, path) VALUES ($1, $2, $3, $4)",
+            &[&id, &index.t.0, &index.name, &index.path]
+        )?;
+
+        // The index only covers the metadata name, since the vertex type is
+        // in another table
+        trans.execute(&format!(
+            "CREATE INDEX {} ON vertex_metadata USING btree ({}) WHERE name = {}",
+            vertex_metadata_index_name(id),
+            vertex_metadata_index_expr(&index.path[..]),
+            quote_literal(&index.name[..])
+        )[..], &[])?;
+
+        trans.commit()?;
+        Ok(())
+    }
+
+    fn delete_vertex_metadata_index(&self, index: models::VertexMetadataIndex) -> Result<(), Error> {
+        let conn = self.pool.get()?;
+        let trans = conn.transaction()?;
+
+        let results = trans.query(
+            "DELETE FROM vertex_metadata_indexes WHERE type=$1 AND name=$2 AND path=$3 RETURNING id",
+            &[&index.t.0, &index.name, &index.path]
+        )?;
+
+        for row in &results {
+            let id: Uuid = row.get(0);
+            trans.execute(&format!("DROP INDEX {}", vertex_metadata_index_name(id))[..], &[])?;
+        }
+
+        trans.commit()?;
+        Ok(())
+    }
+
+    fn get_vertex_metadata_indexes(&self) -> Result<Vec<models::VertexMetadataIndex>, Error> {
+        let conn = self.pool.get()?;
+        let results = conn.query("SELECT type, name, path FROM vertex_metadata_indexes", &[])?;
+        let mut indexes = vec![];
+
+        for row in &results {
+            let t_str: String = row.get(0);
+            let name: String = row.get(1);
+            let path: Vec<String> = row.get(2);
+            indexes.push(models::VertexMetadataIndex::new(models::Type::new(t_str).unwrap(), name, path));
+        }
+
+        // Sorted here rather than in SQL, since postgres may collate strings
+        // differently
+        indexes.sort();
+        Ok(indexes)
+    }
+
+    fn create_unique_constraint(&self, constraint: models::UniqueConstraint) -> Result<(), Error> {
+        let conn = self.pool.get()?;
+        let trans = conn.transaction()?;
+
+        // Block metadata writes until the constraint is committed, since
+        // they can't see it before then, and would slip past the backfill
+        trans.execute("LOCK TABLE vertex_metadata IN SHARE MODE", &[])?;
+
+        let results = trans.query(
+            "INSERT INTO unique_constraints (type, name) VALUES ($1, $2) ON CONFLICT DO NOTHING RETURNING 1",
+            &[&constraint.t.0, &constraint.name]
+        )?;
+
+        if results.is_empty() {
+            return Ok(());
+        }
+
+        let query = format!("
+            INSERT INTO unique_values (type, name, value, owner_id)
+            SELECT vertices.type, vertex_metadata.name, vertex_metadata.value, vertex_metadata.owner_id
+            FROM vertex_metadata JOIN vertices ON vertex_metadata.owner_id = vertices.id
+            WHERE vertices.type = $1 AND vertex_metadata.name = $2 AND {} AND {}
+        ", live_sql("vertices"), live_sql("vertex_metadata"));
+
+        let results = trans.execute(&query[..], &[&constraint.t.0, &constraint.name]);
+
+        if let Err(pg_error::Error::Db(ref err)) = results {
+            if err.code == pg_error::SqlState::UniqueViolation {
+                return Err(Error::UniqueConstraintViolated);
+            }
+        }
+
+        results?;
+        trans.commit()?;
+        Ok(())
+    }
+
+    fn delete_unique_constraint(&self, constraint: models::UniqueConstraint) -> Result<(), Error> {
+        let conn = self.pool.get()?;
+
+        // The constraint's values are deleted with it
+        conn.execute("DELETE FROM unique_constraints WHERE type=$1 AND name=$2", &[&constraint.t.0, &constraint.name])?;
+        Ok(())
+    }
+
+    fn get_unique_constraints(&self) -> Result<Vec<models::UniqueConstraint>, Error> {
+        let conn = self.pool.get()?;
+        let results = conn.query("SELECT type, name FROM unique_constraints", &[])?;
+        let mut constraints = vec![];
+
+        for row in &results {
+            let t_str: String = row.get(0);
+            let name: String = row.get(1);
+            constraints.push(models::UniqueConstraint::new(models::Type::new(t_str).unwrap(), name));
+        }
+
+        constraints.sort();
+        Ok(constraints)
+    }
+
+    fn enable_edge_history(&self, t: models::Type) -> Result<(), Error> {
+        let conn = self.pool.get()?;
+        let trans = conn.transaction()?;
+
+        // Block edge writes until the type is committed, since they can't
+        // see it before then, and wouldn't be recorded by the backfill
+        trans.execute("LOCK TABLE edges IN SHARE MODE", &[])?;
+
+        let results = trans.query(
+            "INSERT INTO edge_history_types (type) VALUES ($1) ON CONFLICT DO NOTHING RETURNING 1",
+            &[&t.0]
+        )?;
+
+        if results.is_empty() {
+            return Ok(());
+        }
+
+        trans.execute("
+            INSERT INTO edge_history (outbound_id, type, inbound_id, update_timestamp, weight, version)
+            SELECT outbound_id, type, inbound_id, update_timestamp, weight, version FROM edges WHERE type=$1
+        ", &[&t.0])?;
+
+        trans.commit()?;
+        Ok(())
+    }
+
+    fn disable_edge_history(&self, t: models::Type) -> Result<(), Error> {
+        let conn = self.pool.get()?;
+
+        // The type's history is deleted with it
+        conn.execute("DELETE FROM edge_history_types WHERE type=$1", &[&t.0])?;
+        Ok(())
+    }
+
+    fn get_edge_history_types(&self) -> Result<Vec<models::Type>, Error> {
+        let conn = self.pool.get()?;
+        let results = conn.query("SELECT type FROM edge_history_types", &[])?;
+        let mut types = vec![];
+
+        for row in &results {
+            let t_str: String = row.get(0);
+            types.push(models::Type::new(t_str).unwrap());
+        }
+
+        types.sort();
+        Ok(types)
+    }
+
+    fn delete_expired(&self) -> Result<(), Error> {
+        let conn = self.pool.get()?;
+        let trans = conn.transaction()?;
+        delete_expired(&trans)?;
+        trans.commit()?;
+        Ok(())
+    }
 }
 
 /// A postgres-backed datastore transaction.
@@ -172,6 +544,121 @@ impl PostgresTransaction {
         })
     }
 
+    /// Deletes edges that have expired but haven't been swept up yet, so
+    /// that setting them again replaces them, rather than updating them.
+    /// Like the sweep, this gives them a version without a weight at the
+    /// datetime they expired, if their type has history enabled.
+    fn delete_expired_edges(&self, keys: Vec<&models::EdgeKey>) -> Result<(), Error> {
+        let mut outbound_ids: Vec<Uuid> = Vec::with_capacity(keys.len());
+        let mut type_strs: Vec<String> = Vec::with_capacity(keys.len());
+        let mut inbound_ids: Vec<Uuid> = Vec::with_capacity(keys.len());
+
+        for key in keys {
+            outbound_ids.push(key.outbound_id);
+            type_strs.push(key.t.0.clone());
+            inbound_ids.push(key.inbound_id);
+        }
+
+        self.trans.execute("
+            WITH e AS (
+                DELETE FROM edges
+                WHERE (outbound_id, type, inbound_id) IN (SELECT * FROM UNNEST($1::uuid[], $2::text[], $3::uuid[]))
+                    AND expires_at <= STATEMENT_TIMESTAMP()
+                RETURNING outbound_id, type, inbound_id, expires_at
+            )
+            INSERT INTO edge_history (outbound_id, type, inbound_id, update_timestamp, weight, version)
+            SELECT e.outbound_id, e.type, e.inbound_id, e.expires_at, NULL, NULL
+            FROM e JOIN edge_history_types ON e.type=edge_history_types.type
+            ON CONFLICT ON CONSTRAINT edge_history_pkey DO UPDATE SET weight=excluded.weight, version=excluded.version
+        ", &[&outbound_ids, &type_strs, &inbound_ids])?;
+
+        Ok(())
+    }
+
+    /// Deletes the unique values claimed by vertex metadata that has
+    /// expired but hasn't been swept up yet, so that other vertices can
+    /// claim them.
+    fn delete_expired_unique_values(&self, name: &str) -> Result<(), Error> {
+        self.trans.execute("
+            DELETE FROM unique_values WHERE name=$1 AND owner_id IN (
+                SELECT owner_id FROM vertex_metadata WHERE name=$1 AND expires_at <= STATEMENT_TIMESTAMP()
+                UNION ALL
+                SELECT id FROM vertices WHERE expires_at <= STATEMENT_TIMESTAMP()
+            )
+        ", &[&name])?;
+
+        Ok(())
+    }
+
+    /// Creates or updates an edge, stamping it with the given update
+    /// datetime, or the current time if none is given. The edge expires at
+    /// the given expiry datetime, or never if none is given.
+    fn set_edge(&self, key: models::EdgeKey, weight: models::Weight, update_datetime: Option<DateTime<UTC>>, expiry: Option<DateTime<UTC>>) -> Result<(), Error> {
+        let id = if self.secure_uuids {
+            parent_uuid()
+        } else {
+            child_uuid(key.outbound_id)
+        };
+
+        // An expired inbound vertex still satisfies the foreign key, so it's
+        // checked for up front
+        if !self.trans.query("SELECT 1 FROM vertices WHERE id=$1 AND expires_at <= STATEMENT_TIMESTAMP()", &[&key.inbound_id])?.is_empty() {
+            return Err(Error::VertexNotFound);
+        }
+
+        self.delete_expired_edges(vec![&key])?;
+
+        let query = format!("
+                WITH e AS (
+                    INSERT INTO edges (id, outbound_id, type, inbound_id, weight, update_timestamp, created_timestamp, expires_at)
+                    SELECT $1, (SELECT id FROM vertices WHERE id=$2 AND owner_id=$3 AND {}), $4, $5, $6, ts.timestamp, ts.timestamp, $8
+                    FROM (SELECT COALESCE($7, CLOCK_TIMESTAMP()) AS timestamp) AS ts
+                    ON CONFLICT ON CONSTRAINT edges_outbound_id_type_inbound_id_ukey
+                    DO UPDATE SET weight=excluded.weight, update_timestamp=excluded.update_timestamp, version=edges.version+1, expires_at=excluded.expires_at
+                    RETURNING outbound_id, type, inbound_id, update_timestamp, weight, version
+                )
+                INSERT INTO edge_history (outbound_id, type, inbound_id, update_timestamp, weight, version)
+                SELECT e.outbound_id, e.type, e.inbound_id, e.update_timestamp, e.weight, e.version
+                FROM e JOIN edge_history_types ON e.type=edge_history_types.type
+                ON CONFLICT ON CONSTRAINT edge_history_pkey DO UPDATE SET weight=excluded.weight, version=excluded.version
+            ", live_sql("vertices"));
+
+        // Because this command could fail, we need to set a savepoint to roll
+        // back to, rather than spoiling the entire transaction
+        let results = {
+            let trans = self.trans.savepoint("set_edge")?;
+            let results = trans.query(&query[..], &[&id, &key.outbound_id, &self.account_id, &key.t.0, &key.inbound_id, &weight.value(), &update_datetime, &expiry]);
+            
+            match results {
+                Err(err) => {
+                    trans.set_rollback();
+                    Err(err)
+                }
+                Ok(_) => {
+                    trans.set_commit();
+                    Ok(())
+                }
+            }
+        };
+        
+        if let Err(pg_error::Error::Db(ref err)) = results {
+            if err.code == pg_error::SqlState::NotNullViolation {
+                // This should only happen when the inner select fails
+                let v = self.get_vertices(VertexQuery::Vertex(key.outbound_id))?;
+                if v.is_empty() {
+                    return Err(Error::VertexNotFound);
+                } else {
+                    return Err(Error::Unauthorized);
+                }
+            } else if err.code == pg_error::SqlState::ForeignKeyViolation {
+                // This should only happen when there is no vertex with id=inbound_id
+                return Err(Error::VertexNotFound);
+            }
+        }
+        
+        Ok(())
+    }
+
     fn handle_get_metadata(&self, results: Rows) -> Result<JsonValue, Error> {
         for row in &results {
             let value: JsonValue = row.get(0);
@@ -189,6 +676,15 @@ impl PostgresTransaction {
         }
     }
 
+    fn handle_set_metadata_if_version(&self, results: Rows) -> Result<u64, Error> {
+        for row in &results {
+            let version: i64 = row.get(0);
+            return Ok(version as u64);
+        }
+
+        Err(Error::Conflict)
+    }
+
     fn handle_set_metadata_error(&self, err: pg_error::Error, foreign_key_err: Error) -> Error {
         if let pg_error::Error::Db(ref err) = err {
             if err.code == pg_error::SqlState::ForeignKeyViolation || err.code == pg_error::SqlState::NotNullViolation {
@@ -206,21 +702,35 @@ impl PostgresTransaction {
             VertexQuery::All(start_id, limit) => {
                 match start_id {
                     Some(start_id) => {
-                        let query_template = "SELECT id, owner_id, type FROM %t WHERE id > %p ORDER BY id LIMIT %p";
+                        let query_template = format!("SELECT id, owner_id, type FROM %t WHERE id > %p AND {} ORDER BY id LIMIT %p", live_sql("%t"));
                         let params: Vec<Box<ToSql>> = vec![Box::new(start_id), Box::new(limit as i64)];
-                        sql_query_builder.push(query_template, "vertices", params);
+                        sql_query_builder.push(&query_template[..], "vertices", params);
                     },
                     None => {
-                        let query_template = "SELECT id, owner_id, type FROM %t ORDER BY id LIMIT %p";
+                        let query_template = format!("SELECT id, owner_id, type FROM %t WHERE {} ORDER BY id LIMIT %p", live_sql("%t"));
                         let params: Vec<Box<ToSql>> = vec![Box::new(limit as i64)];
-                        sql_query_builder.push(query_template, "vertices", params);
+                        sql_query_builder.push(&query_template[..], "vertices", params);
+                    }
+                }
+            },
+            VertexQuery::AllOfType(t, start_id, limit) => {
+                match start_id {
+                    Some(start_id) => {
+                        let query_template = format!("SELECT id, owner_id, type FROM %t WHERE type = %p AND id > %p AND {} ORDER BY id LIMIT %p", live_sql("%t"));
+                        let params: Vec<Box<ToSql>> = vec![Box::new(t.0), Box::new(start_id), Box::new(limit as i64)];
+                        sql_query_builder.push(&query_template[..], "vertices", params);
+                    },
+                    None => {
+                        let query_template = format!("SELECT id, owner_id, type FROM %t WHERE type = %p AND {} ORDER BY id LIMIT %p", live_sql("%t"));
+                        let params: Vec<Box<ToSql>> = vec![Box::new(t.0), Box::new(limit as i64)];
+                        sql_query_builder.push(&query_template[..], "vertices", params);
                     }
                 }
             },
             VertexQuery::Vertex(id) => {
-                let query_template = "SELECT id, owner_id, type FROM %t WHERE id=%p LIMIT 1";
+                let query_template = format!("SELECT id, owner_id, type FROM %t WHERE id=%p AND {} LIMIT 1", live_sql("%t"));
                 let params: Vec<Box<ToSql>> = vec![Box::new(id)];
-                sql_query_builder.push(query_template, "vertices", params);
+                sql_query_builder.push(&query_template[..], "vertices", params);
             },
             VertexQuery::Vertices(vertices) => {
                 let mut params_template_builder = vec![];
@@ -231,7 +741,7 @@ impl PostgresTransaction {
                     params.push(Box::new(id));
                 }
 
-                let query_template = format!("SELECT id, owner_id, type FROM %t WHERE id IN ({}) ORDER BY id", params_template_builder.join(", "));
+                let query_template = format!("SELECT id, owner_id, type FROM %t WHERE id IN ({}) AND {} ORDER BY id", params_template_builder.join(", "), live_sql("%t"));
                 sql_query_builder.push(&query_template[..], "vertices", params);
             },
             VertexQuery::Pipe(edge_query, converter, limit) => {
@@ -244,20 +754,228 @@ impl PostgresTransaction {
                 };
 
                 sql_query_builder.push(query_template, "", params);
+            },
+            VertexQuery::Traverse(vertex_query, converter, t, min_depth, max_depth, limit) => {
+                self.vertex_query_to_sql(*vertex_query, sql_query_builder);
+
+                // Walk out to `max_depth`, collecting the depths that each
+                // vertex can be reached at. Each row carries the path that
+                // reached it, and never steps back onto a vertex already on
+                // that path, so a cycle ends the walk rather than being
+                // walked around until `max_depth`. This is still one row per
+                // path rather than per vertex, so densely connected graphs
+                // with a large `max_depth` can get expensive.
+                let (next_id_column, join_column) = match converter {
+                    QueryTypeConverter::Outbound => ("inbound_id", "outbound_id"),
+                    QueryTypeConverter::Inbound => ("outbound_id", "inbound_id")
+                };
+
+                let mut params: Vec<Box<ToSql>> = vec![Box::new(max_depth as i64)];
+
+                let type_clause = match t {
+                    Some(t) => {
+                        params.push(Box::new(t.0));
+                        " AND edges.type = %p"
+                    },
+                    None => ""
+                };
+
+                let query_template = format!("
+                    SELECT id, 0::bigint AS depth, ARRAY[id] AS path FROM %t
+                    UNION ALL
+                    SELECT edges.{0}, %r.depth + 1, %r.path || edges.{0} FROM %r JOIN edges ON edges.{1} = %r.id
+                    WHERE %r.depth < %p AND NOT edges.{0} = ANY(%r.path) AND {2}{3}
+                ", next_id_column, join_column, live_edge_sql("edges"), type_clause);
+
+                sql_query_builder.push_recursive(&query_template[..], params);
+
+                // Then keep the vertices whose shortest path is deep enough
+                let params: Vec<Box<ToSql>> = vec![Box::new(min_depth as i64), Box::new(limit as i64)];
+                let query_template = "SELECT id, owner_id, type FROM vertices WHERE id IN (SELECT id FROM %t GROUP BY id HAVING MIN(depth) >= %p) ORDER BY id LIMIT %p";
+                sql_query_builder.push(query_template, "", params);
+            },
+            VertexQuery::Union(left_query, right_query) => self.vertex_set_operation_to_sql(*left_query, *right_query, "UNION", sql_query_builder),
+            VertexQuery::Intersection(left_query, right_query) => self.vertex_set_operation_to_sql(*left_query, *right_query, "INTERSECT", sql_query_builder),
+            VertexQuery::Difference(left_query, right_query) => self.vertex_set_operation_to_sql(*left_query, *right_query, "EXCEPT", sql_query_builder),
+            VertexQuery::Filter(vertex_query, name, predicate) => {
+                self.vertex_query_to_sql(*vertex_query, sql_query_builder);
+                let mut params: Vec<Box<ToSql>> = vec![Box::new(name)];
+                let condition = predicate_to_sql(&predicate, "vertex_metadata.value", &[], &mut params);
+
+                let query_template = format!(
+                    "SELECT id, owner_id, type FROM %t WHERE EXISTS (SELECT 1 FROM vertex_metadata WHERE vertex_metadata.owner_id = %t.id AND vertex_metadata.name = %p AND {} AND {})",
+                    condition,
+                    live_sql("vertex_metadata")
+                );
+
+                sql_query_builder.push(&query_template[..], "", params);
+            },
+            VertexQuery::Lookup(t, name, predicate, start_id, limit) => {
+                let mut params: Vec<Box<ToSql>> = vec![Box::new(t.0)];
+
+                let start_id_condition = match start_id {
+                    Some(start_id) => {
+                        params.push(Box::new(start_id));
+                        "AND id > %p"
+                    },
+                    None => ""
+                };
+
+                params.push(Box::new(name));
+
+                // Equality checks are written the same way as the index
+                // expressions, so that postgres can use the indexes
+                let condition = match predicate.equality() {
+                    Some((path, value)) => {
+                        if path.is_empty() {
+                            params.push(Box::new(value.clone()));
+                            "vertex_metadata.value = %p".to_string()
+                        } else {
+                            params.push(Box::new(path));
+                            params.push(Box::new(value.clone()));
+                            "(vertex_metadata.value #> %p) = %p".to_string()
+                        }
+                    },
+                    None => predicate_to_sql(&predicate, "vertex_metadata.value", &[], &mut params)
+                };
+
+                params.push(Box::new(limit as i64));
+
+                let query_template = format!(
+                    "SELECT id, owner_id, type FROM %t WHERE type = %p {} AND {} AND id IN (SELECT owner_id FROM vertex_metadata WHERE vertex_metadata.name = %p AND {} AND {}) ORDER BY id LIMIT %p",
+                    start_id_condition,
+                    live_sql("%t"),
+                    condition,
+                    live_sql("vertex_metadata")
+                );
+
+                sql_query_builder.push(&query_template[..], "vertices", params);
+            },
+            VertexQuery::HasMetadata(name, start_id, limit) => {
+                let mut params: Vec<Box<ToSql>> = vec![Box::new(name)];
+
+                let start_id_condition = match start_id {
+                    Some(start_id) => {
+                        params.push(Box::new(start_id));
+                        "AND id > %p"
+                    },
+                    None => ""
+                };
+
+                params.push(Box::new(limit as i64));
+
+                let query_template = format!(
+                    "SELECT id, owner_id, type FROM %t WHERE id IN (SELECT owner_id FROM vertex_metadata WHERE vertex_metadata.name = %p AND {}) {} AND {} ORDER BY id LIMIT %p",
+                    live_sql("vertex_metadata"),
+                    start_id_condition,
+                    live_sql("%t")
+                );
+
+                sql_query_builder.push(&query_template[..], "vertices", params);
             }
         }
     }
 
+    fn vertex_set_operation_to_sql(&self, left_query: VertexQuery, right_query: VertexQuery, operator: &str, sql_query_builder: &mut CTEQueryBuilder) {
+        self.vertex_query_to_sql(left_query, sql_query_builder);
+        let left_table_name = sql_query_builder.last_table_name();
+        sql_query_builder.start_chain();
+        self.vertex_query_to_sql(right_query, sql_query_builder);
+        let right_table_name = sql_query_builder.last_table_name();
+
+        let query_template = format!(
+            "SELECT id, owner_id, type FROM {} {} SELECT id, owner_id, type FROM {} ORDER BY id",
+            left_table_name,
+            operator,
+            right_table_name
+        );
+
+        sql_query_builder.push(&query_template[..], "", vec![]);
+    }
+
+    fn edge_set_operation_to_sql(&self, left_query: EdgeQuery, right_query: EdgeQuery, operator: &str, sql_query_builder: &mut CTEQueryBuilder) {
+        self.edge_query_to_sql(left_query, sql_query_builder);
+        let left_table_name = sql_query_builder.last_table_name();
+        sql_query_builder.start_chain();
+        self.edge_query_to_sql(right_query, sql_query_builder);
+        let right_table_name = sql_query_builder.last_table_name();
+
+        let query_template = format!(
+            "SELECT id, outbound_id, type, inbound_id, update_timestamp, weight, created_timestamp, version FROM {} {} SELECT id, outbound_id, type, inbound_id, update_timestamp, weight, created_timestamp, version FROM {} ORDER BY outbound_id, type, inbound_id",
+            left_table_name,
+            operator,
+            right_table_name
+        );
+
+        sql_query_builder.push(&query_template[..], "", vec![]);
+    }
+
+    /// Builds a query for the edges of a vertex query whose datetime in
+    /// `column` is between `low` and `high` inclusive, latest first.
+    fn edge_datetime_range_query_to_sql(&self,
+                                        vertex_query: VertexQuery,
+                                        converter: QueryTypeConverter,
+                                        t: Option<models::Type>,
+                                        high: Option<DateTime<UTC>>,
+                                        low: Option<DateTime<UTC>>,
+                                        limit: u32,
+                                        column: &str,
+                                        sql_query_builder: &mut CTEQueryBuilder) {
+        self.vertex_query_to_sql(vertex_query, sql_query_builder);
+
+        let mut where_clause_template_builder = vec![];
+        let mut params: Vec<Box<ToSql>> = vec![];
+
+        if let Some(t) = t {
+            where_clause_template_builder.push("type = %p".to_string());
+            params.push(Box::new(t.0));
+        }
+
+        if let Some(high) = high {
+            where_clause_template_builder.push(format!("{} <= %p", column));
+            params.push(Box::new(high));
+        }
+
+        if let Some(low) = low {
+            where_clause_template_builder.push(format!("{} >= %p", column));
+            params.push(Box::new(low));
+        }
+
+        params.push(Box::new(limit as i64));
+
+        let id_column = match converter {
+            QueryTypeConverter::Outbound => "outbound_id",
+            QueryTypeConverter::Inbound => "inbound_id"
+        };
+
+        let where_clause = if where_clause_template_builder.is_empty() {
+            "".to_string()
+        } else {
+            format!(" AND {}", where_clause_template_builder.join(" AND "))
+        };
+
+        let query_template = format!(
+            "SELECT id, outbound_id, type, inbound_id, update_timestamp, weight, created_timestamp, version FROM edges WHERE {} IN (SELECT id FROM %t) AND {}{} ORDER BY {} DESC LIMIT %p",
+            id_column,
+            live_edge_sql("edges"),
+            where_clause,
+            column
+        );
+
+        sql_query_builder.push(&query_template[..], "", params);
+    }
+
     fn edge_query_to_sql(&self, q: EdgeQuery, sql_query_builder: &mut CTEQueryBuilder) {
         match q {
             EdgeQuery::Edge(key) => {
                 let params: Vec<Box<ToSql>> = vec![Box::new(key.outbound_id), Box::new(key.t.0), Box::new(key.inbound_id)];
 
-                sql_query_builder.push(
-                    "SELECT id, outbound_id, type, inbound_id, update_timestamp, weight FROM %t WHERE outbound_id=%p AND type=%p AND inbound_id=%p",
-                    "edges",
-                    params
-                )
+                let query_template = format!(
+                    "SELECT id, outbound_id, type, inbound_id, update_timestamp, weight, created_timestamp, version FROM %t WHERE outbound_id=%p AND type=%p AND inbound_id=%p AND {}",
+                    live_edge_sql("%t")
+                );
+
+                sql_query_builder.push(&query_template[..], "edges", params)
             },
             EdgeQuery::Edges(edges) => {
                 let mut params_template_builder = vec![];
@@ -270,49 +988,160 @@ impl PostgresTransaction {
                     params.push(Box::new(key.inbound_id));
                 }
 
-                let query_template = format!("SELECT id, outbound_id, type, inbound_id, update_timestamp, weight FROM %t WHERE (outbound_id, type, inbound_id) IN ({})", params_template_builder.join(", "));
+                let query_template = format!(
+                    "SELECT id, outbound_id, type, inbound_id, update_timestamp, weight, created_timestamp, version FROM %t WHERE (outbound_id, type, inbound_id) IN ({}) AND {}",
+                    params_template_builder.join(", "),
+                    live_edge_sql("%t")
+                );
+
                 sql_query_builder.push(&query_template[..], "edges", params);
             },
             EdgeQuery::Pipe(vertex_query, converter, t, high, low, limit) => {
+                self.edge_datetime_range_query_to_sql(*vertex_query, converter, t, high, low, limit, "update_timestamp", sql_query_builder);
+            },
+            EdgeQuery::CreatedPipe(vertex_query, converter, t, high, low, limit) => {
+                self.edge_datetime_range_query_to_sql(*vertex_query, converter, t, high, low, limit, "created_timestamp", sql_query_builder);
+            },
+            EdgeQuery::SortedPipe(vertex_query, converter, t, high, low, order, limit) => {
                 self.vertex_query_to_sql(*vertex_query, sql_query_builder);
 
+                let live_condition = live_edge_sql("edges");
                 let mut where_clause_template_builder = vec![];
                 let mut params: Vec<Box<ToSql>> = vec![];
 
+                match converter {
+                    QueryTypeConverter::Outbound => where_clause_template_builder.push("outbound_id IN (SELECT id FROM %t)"),
+                    QueryTypeConverter::Inbound => where_clause_template_builder.push("inbound_id IN (SELECT id FROM %t)")
+                }
+
+                where_clause_template_builder.push(&live_condition[..]);
+
                 if let Some(t) = t {
                     where_clause_template_builder.push("type = %p");
                     params.push(Box::new(t.0));
                 }
 
                 if let Some(high) = high {
-                    where_clause_template_builder.push("update_timestamp <= %p");
-                    params.push(Box::new(high));
+                    where_clause_template_builder.push("weight <= %p");
+                    params.push(Box::new(high.value()));
                 }
 
                 if let Some(low) = low {
-                    where_clause_template_builder.push("update_timestamp >= %p");
-                    params.push(Box::new(low));
+                    where_clause_template_builder.push("weight >= %p");
+                    params.push(Box::new(low.value()));
                 }
 
                 params.push(Box::new(limit as i64));
-                let where_clause = where_clause_template_builder.join(" AND ");
 
-                let query_template = match (converter, where_clause.len()) {
-                    (QueryTypeConverter::Outbound, 0) => {
-                        "SELECT id, outbound_id, type, inbound_id, update_timestamp, weight FROM edges WHERE outbound_id IN (SELECT id FROM %t) ORDER BY update_timestamp DESC LIMIT %p".to_string()
-                    },
-                    (QueryTypeConverter::Outbound, _) => {
-                        format!("SELECT id, outbound_id, type, inbound_id, update_timestamp, weight FROM edges WHERE outbound_id IN (SELECT id FROM %t) AND {} ORDER BY update_timestamp DESC LIMIT %p", where_clause)
-                    },
-                    (QueryTypeConverter::Inbound, 0) => {
-                        "SELECT id, outbound_id, type, inbound_id, update_timestamp, weight FROM edges WHERE inbound_id IN (SELECT id FROM %t) ORDER BY update_timestamp DESC LIMIT %p".to_string()
-                    },
-                    (QueryTypeConverter::Inbound, _) => {
-                        format!("SELECT id, outbound_id, type, inbound_id, update_timestamp, weight FROM edges WHERE inbound_id IN (SELECT id FROM %t) AND {} ORDER BY update_timestamp DESC LIMIT %p", where_clause)
-                    }
-                 };
-                
+                // Ties are broken by key to match the other datastores
+                let order_clause = match order {
+                    EdgeOrder::NewestFirst => "update_timestamp DESC",
+                    EdgeOrder::OldestFirst => "update_timestamp ASC",
+                    EdgeOrder::StrongestFirst => "weight DESC",
+                    EdgeOrder::WeakestFirst => "weight ASC"
+                };
+
+                let query_template = format!(
+                    "SELECT id, outbound_id, type, inbound_id, update_timestamp, weight, created_timestamp, version FROM edges WHERE {} ORDER BY {}, outbound_id, type, inbound_id LIMIT %p",
+                    where_clause_template_builder.join(" AND "),
+                    order_clause
+                );
+
+                sql_query_builder.push(&query_template[..], "", params);
+            },
+            EdgeQuery::PagedPipe(vertex_query, converter, t, cursor, limit) => {
+                self.vertex_query_to_sql(*vertex_query, sql_query_builder);
+
+                let (first_column, second_column) = match converter {
+                    QueryTypeConverter::Outbound => ("outbound_id", "inbound_id"),
+                    QueryTypeConverter::Inbound => ("inbound_id", "outbound_id")
+                };
+
+                let mut where_clause_template_builder = vec![format!("{} IN (SELECT id FROM %t)", first_column), live_edge_sql("edges")];
+                let mut params: Vec<Box<ToSql>> = vec![];
+
+                if let Some(t) = t {
+                    where_clause_template_builder.push("type = %p".to_string());
+                    params.push(Box::new(t.0));
+                }
+
+                // Resume after the cursor's edge, in the same order as the
+                // ORDER BY clause below
+                if let Some(cursor) = cursor {
+                    let key = cursor.key().clone();
+
+                    let (first_id, second_id) = match converter {
+                        QueryTypeConverter::Outbound => (key.outbound_id, key.inbound_id),
+                        QueryTypeConverter::Inbound => (key.inbound_id, key.outbound_id)
+                    };
+
+                    where_clause_template_builder.push(format!(
+                        "({0} > %p OR ({0} = %p AND (type > %p OR (type = %p AND (update_timestamp < %p OR (update_timestamp = %p AND {1} > %p))))))",
+                        first_column,
+                        second_column
+                    ));
+
+                    params.push(Box::new(first_id));
+                    params.push(Box::new(first_id));
+                    params.push(Box::new(key.t.0.clone()));
+                    params.push(Box::new(key.t.0));
+                    params.push(Box::new(cursor.update_datetime()));
+                    params.push(Box::new(cursor.update_datetime()));
+                    params.push(Box::new(second_id));
+                }
+
+                params.push(Box::new(limit as i64));
+
+                let query_template = format!(
+                    "SELECT id, outbound_id, type, inbound_id, update_timestamp, weight, created_timestamp, version FROM edges WHERE {} ORDER BY {}, type, update_timestamp DESC, {} LIMIT %p",
+                    where_clause_template_builder.join(" AND "),
+                    first_column,
+                    second_column
+                );
+
+                sql_query_builder.push(&query_template[..], "", params);
+            },
+            EdgeQuery::Union(left_query, right_query) => self.edge_set_operation_to_sql(*left_query, *right_query, "UNION", sql_query_builder),
+            EdgeQuery::Intersection(left_query, right_query) => self.edge_set_operation_to_sql(*left_query, *right_query, "INTERSECT", sql_query_builder),
+            EdgeQuery::Difference(left_query, right_query) => self.edge_set_operation_to_sql(*left_query, *right_query, "EXCEPT", sql_query_builder),
+            EdgeQuery::Filter(edge_query, name, predicate) => {
+                self.edge_query_to_sql(*edge_query, sql_query_builder);
+                let mut params: Vec<Box<ToSql>> = vec![Box::new(name)];
+                let condition = predicate_to_sql(&predicate, "edge_metadata.value", &[], &mut params);
+
+                let query_template = format!(
+                    "SELECT id, outbound_id, type, inbound_id, update_timestamp, weight, created_timestamp, version FROM %t WHERE EXISTS (SELECT 1 FROM edge_metadata WHERE edge_metadata.owner_id = %t.id AND edge_metadata.name = %p AND {} AND {})",
+                    condition,
+                    live_sql("edge_metadata")
+                );
+
                 sql_query_builder.push(&query_template[..], "", params);
+            },
+            EdgeQuery::HasMetadata(name, start_key, limit) => {
+                let mut params: Vec<Box<ToSql>> = vec![Box::new(name)];
+
+                // Types are compared with the "C" collation so that edges are
+                // sorted the same way as `EdgeKey`s
+                let start_key_condition = match start_key {
+                    Some(start_key) => {
+                        params.push(Box::new(start_key.outbound_id));
+                        params.push(Box::new(start_key.t.0));
+                        params.push(Box::new(start_key.inbound_id));
+                        "AND (outbound_id, type COLLATE \"C\", inbound_id) > (%p, %p, %p)"
+                    },
+                    None => ""
+                };
+
+                params.push(Box::new(limit as i64));
+
+                let query_template = format!(
+                    "SELECT id, outbound_id, type, inbound_id, update_timestamp, weight, created_timestamp, version FROM %t WHERE id IN (SELECT owner_id FROM edge_metadata WHERE edge_metadata.name = %p AND {}) {} AND {} ORDER BY outbound_id, type COLLATE \"C\", inbound_id LIMIT %p",
+                    live_sql("edge_metadata"),
+                    start_key_condition,
+                    live_edge_sql("%t")
+                );
+
+                sql_query_builder.push(&query_template[..], "edges", params);
             }
         }
     }
@@ -320,6 +1149,76 @@ impl PostgresTransaction {
     fn ensure_edges_are_owned_by_account(&self, sql_query_builder: &mut CTEQueryBuilder) {
         sql_query_builder.push("SELECT %t.id FROM %t JOIN vertices ON %t.outbound_id=vertices.id WHERE vertices.owner_id=%p", "vertices", vec![Box::new(self.account_id)]);
     }
+
+    /// Sets a vertex metadata value for each vertex matching a query, making
+    /// it expire at a given datetime, or never if it's `None`.
+    fn set_vertex_metadata_until(&self, q: VertexQuery, name: String, value: JsonValue, expiry: Option<DateTime<UTC>>) -> Result<(), Error> {
+        let mut sql_query_builder = CTEQueryBuilder::new();
+        self.vertex_query_to_sql(q.clone(), &mut sql_query_builder);
+        let (query, params) = sql_query_builder.into_query_payload("
+            INSERT INTO vertex_metadata (owner_id, name, value, expires_at)
+            SELECT id, %p, %p, %p FROM %t
+            ON CONFLICT ON CONSTRAINT vertex_metadata_pkey
+            DO UPDATE SET value=EXCLUDED.value, version=vertex_metadata.version+1, expires_at=EXCLUDED.expires_at
+        ", vec![Box::new(name.clone()), Box::new(value.clone()), Box::new(expiry)]);
+        let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
+
+        // Claim the value for any unique constraints on the metadata, which
+        // fails if another vertex already has it
+        self.delete_expired_unique_values(&name[..])?;
+        let mut unique_sql_query_builder = CTEQueryBuilder::new();
+        self.vertex_query_to_sql(q, &mut unique_sql_query_builder);
+        let (unique_query, unique_params) = unique_sql_query_builder.into_query_payload("
+            INSERT INTO unique_values (type, name, value, owner_id)
+            SELECT %t.type, unique_constraints.name, %p, %t.id
+            FROM %t JOIN unique_constraints ON unique_constraints.type = %t.type AND unique_constraints.name = %p
+            ON CONFLICT ON CONSTRAINT unique_values_owner_id_name_ukey
+            DO UPDATE SET value=EXCLUDED.value
+        ", vec![Box::new(value), Box::new(name)]);
+        let unique_params_refs: Vec<&ToSql> = unique_params.iter().map(|x| &**x).collect();
+
+        // Because the unique constraint could be violated, we need to set a
+        // savepoint to roll back to, rather than spoiling the entire
+        // transaction
+        let trans = self.trans.savepoint("set_vertex_metadata")?;
+
+        let results = trans.execute(&query[..], &params_refs[..])
+            .and_then(|_| trans.execute(&unique_query[..], &unique_params_refs[..]));
+
+        match results {
+            Err(err) => {
+                trans.set_rollback();
+
+                if let pg_error::Error::Db(ref err) = err {
+                    if err.code == pg_error::SqlState::UniqueViolation {
+                        return Err(Error::UniqueConstraintViolated);
+                    }
+                }
+
+                Err(Error::from(err))
+            }
+            Ok(_) => {
+                trans.set_commit();
+                Ok(())
+            }
+        }
+    }
+
+    /// Sets an edge metadata value for each edge matching a query, making it
+    /// expire at a given datetime, or never if it's `None`.
+    fn set_edge_metadata_until(&self, q: EdgeQuery, name: String, value: JsonValue, expiry: Option<DateTime<UTC>>) -> Result<(), Error> {
+        let mut sql_query_builder = CTEQueryBuilder::new();
+        self.edge_query_to_sql(q, &mut sql_query_builder);
+        let (query, params) = sql_query_builder.into_query_payload("
+            INSERT INTO edge_metadata (owner_id, name, value, expires_at)
+            SELECT id, %p, %p, %p FROM %t
+            ON CONFLICT ON CONSTRAINT edge_metadata_pkey
+            DO UPDATE SET value=EXCLUDED.value, version=edge_metadata.version+1, expires_at=EXCLUDED.expires_at
+        ", vec![Box::new(name), Box::new(value), Box::new(expiry)]);
+        let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
+        self.trans.execute(&query[..], &params_refs[..])?;
+        Ok(())
+    }
 }
 
 impl Transaction for PostgresTransaction {
@@ -334,6 +1233,58 @@ impl Transaction for PostgresTransaction {
         Ok(id)
     }
 
+    fn create_vertex_with_id(&self, id: Uuid, t: models::Type) -> Result<(), Error> {
+        // Replace a vertex that's expired but hasn't been swept up yet
+        self.trans.execute("DELETE FROM vertices WHERE id=$1 AND expires_at <= STATEMENT_TIMESTAMP()", &[&id])?;
+
+        let inserted = self.trans.execute(
+            "INSERT INTO vertices (id, type, owner_id) VALUES ($1, $2, $3) ON CONFLICT (id) DO NOTHING",
+            &[&id, &t.0, &self.account_id]
+        )?;
+
+        if inserted == 0 {
+            Err(Error::VertexAlreadyExists)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn create_vertices(&self, types: Vec<models::Type>) -> Result<Vec<Uuid>, Error> {
+        let mut ids: Vec<Uuid> = Vec::with_capacity(types.len());
+        let mut type_strs: Vec<String> = Vec::with_capacity(types.len());
+
+        for t in types {
+            let id = if self.secure_uuids {
+                parent_uuid()
+            } else {
+                child_uuid(self.account_id)
+            };
+
+            ids.push(id);
+            type_strs.push(t.0);
+        }
+
+        self.trans.execute("
+            INSERT INTO vertices (id, type, owner_id)
+            SELECT id, type, $3::uuid FROM UNNEST($1::uuid[], $2::text[]) AS v(id, type)
+        ", &[&ids, &type_strs, &self.account_id])?;
+
+        Ok(ids)
+    }
+
+    fn create_vertex_with_ttl(&self, t: models::Type, ttl: Duration) -> Result<Uuid, Error> {
+        let expiry = expiry_datetime(ttl)?;
+
+        let id = if self.secure_uuids {
+            parent_uuid()
+        } else {
+            child_uuid(self.account_id)
+        };
+
+        self.trans.execute("INSERT INTO vertices (id, type, owner_id, expires_at) VALUES ($1, $2, $3, $4)", &[&id, &t.0, &self.account_id, &expiry])?;
+        Ok(id)
+    }
+
     fn get_vertices(&self, q: VertexQuery) -> Result<Vec<models::Vertex>, Error> {
         let mut sql_query_builder = CTEQueryBuilder::new();
         self.vertex_query_to_sql(q, &mut sql_query_builder);
@@ -341,16 +1292,16 @@ impl Transaction for PostgresTransaction {
         let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
 
         let results = self.trans.query(&query[..], &params_refs[..])?;
-        let mut vertices: Vec<models::Vertex> = Vec::new();
-
-        for row in &results {
-            let id: Uuid = row.get(0);
-            let t_str: String = row.get(1);
-            let v = models::Vertex::new(id, models::Type::new(t_str).unwrap());
-            vertices.push(v);
-        }
+        results.iter().map(|row| row_to_vertex(&row)).collect()
+    }
 
-        Ok(vertices)
+    fn stream_vertices<'a>(&'a self, q: VertexQuery) -> Result<VertexStream<'a>, Error> {
+        let mut sql_query_builder = CTEQueryBuilder::new();
+        self.vertex_query_to_sql(q, &mut sql_query_builder);
+        let (query, params) = sql_query_builder.into_query_payload("SELECT id, type FROM %t", vec![]);
+        let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
+        let cursor = CursorIterator::new(&self.trans, &query[..], &params_refs[..], row_to_vertex)?;
+        Ok(Box::new(cursor))
     }
 
     fn delete_vertices(&self, q: VertexQuery) -> Result<(), Error> {
@@ -363,72 +1314,282 @@ impl Transaction for PostgresTransaction {
     }
 
     fn create_edge(&self, key: models::EdgeKey, weight: models::Weight) -> Result<(), Error> {
+        self.set_edge(key, weight, None, None)
+    }
+
+    fn create_edge_at(&self, key: models::EdgeKey, weight: models::Weight, update_datetime: DateTime<UTC>) -> Result<(), Error> {
+        self.set_edge(key, weight, Some(check_datetime(update_datetime, "update_datetime")?), None)
+    }
+
+    fn create_edge_with_ttl(&self, key: models::EdgeKey, weight: models::Weight, ttl: Duration) -> Result<(), Error> {
+        self.set_edge(key, weight, None, Some(expiry_datetime(ttl)?))
+    }
+
+    fn create_edge_if_version(&self, key: models::EdgeKey, weight: models::Weight, expected_version: Option<u64>) -> Result<u64, Error> {
+        // Check the vertices up front, so that nothing being written below
+        // can only mean a version conflict. The rows are locked so that the
+        // vertices can't be deleted out from under the write.
+        let query = format!("SELECT id, owner_id FROM vertices WHERE id = ANY($1) AND {} FOR SHARE", live_sql("vertices"));
+        let owner_results = self.trans.query(&query[..], &[&vec![key.outbound_id, key.inbound_id]])?;
+        let mut owners: HashMap<Uuid, Uuid> = HashMap::new();
+
+        for row in &owner_results {
+            let id: Uuid = row.get(0);
+            let owner_id: Uuid = row.get(1);
+            owners.insert(id, owner_id);
+        }
+
+        match (owners.get(&key.outbound_id), owners.get(&key.inbound_id)) {
+            (None, _) => return Err(Error::VertexNotFound),
+            (Some(owner_id), _) if *owner_id != self.account_id => return Err(Error::Unauthorized),
+            (_, None) => return Err(Error::VertexNotFound),
+            _ => ()
+        }
+
+        self.delete_expired_edges(vec![&key])?;
+
         let id = if self.secure_uuids {
             parent_uuid()
         } else {
             child_uuid(key.outbound_id)
         };
 
-        // Because this command could fail, we need to set a savepoint to roll
-        // back to, rather than spoiling the entire transaction
-        let results = {
-            let trans = self.trans.savepoint("set_edge")?;
-            let results = trans.query("
-                INSERT INTO edges (id, outbound_id, type, inbound_id, weight, update_timestamp)
-                VALUES ($1, (SELECT id FROM vertices WHERE id=$2 AND owner_id=$3), $4, $5, $6, CLOCK_TIMESTAMP())
-                ON CONFLICT ON CONSTRAINT edges_outbound_id_type_inbound_id_ukey
-                DO UPDATE SET weight=$6, update_timestamp=CLOCK_TIMESTAMP()
-            ", &[&id, &key.outbound_id, &self.account_id, &key.t.0, &key.inbound_id, &weight.0]);
-            
-            match results {
-                Err(err) => {
-                    trans.set_rollback();
-                    Err(err)
-                }
-                Ok(_) => {
-                    trans.set_commit();
-                    Ok(())
-                }
-            }
-        };
-        
-        if let Err(pg_error::Error::Db(ref err)) = results {
-            if err.code == pg_error::SqlState::NotNullViolation {
-                // This should only happen when the inner select fails
-                let v = self.get_vertices(VertexQuery::Vertex(key.outbound_id))?;
-                if v.is_empty() {
-                    return Err(Error::VertexNotFound);
-                } else {
-                    return Err(Error::Unauthorized);
-                }
-            } else if err.code == pg_error::SqlState::ForeignKeyViolation {
-                // This should only happen when there is no vertex with id=inbound_id
-                return Err(Error::VertexNotFound);
+        // The edge is only inserted if no version is expected, and only
+        // updated if it's at the expected version
+        let expected_version = expected_version.map(|version| version as i64);
+        let results = self.trans.query("
+            WITH inserted AS (
+                INSERT INTO edges (id, outbound_id, type, inbound_id, weight, update_timestamp, created_timestamp)
+                SELECT $1, $2, $3, $4, $5, ts.timestamp, ts.timestamp
+                FROM (SELECT CLOCK_TIMESTAMP() AS timestamp) AS ts
+                WHERE $6::BIGINT IS NULL
+                ON CONFLICT ON CONSTRAINT edges_outbound_id_type_inbound_id_ukey DO NOTHING
+                RETURNING outbound_id, type, inbound_id, update_timestamp, weight, version
+            ), updated AS (
+                UPDATE edges SET weight=$5, update_timestamp=CLOCK_TIMESTAMP(), version=version+1, expires_at=NULL
+                WHERE outbound_id=$2 AND type=$3 AND inbound_id=$4 AND version=$6
+                RETURNING outbound_id, type, inbound_id, update_timestamp, weight, version
+            ), e AS (
+                SELECT * FROM inserted UNION ALL SELECT * FROM updated
+            ), h AS (
+                INSERT INTO edge_history (outbound_id, type, inbound_id, update_timestamp, weight, version)
+                SELECT e.outbound_id, e.type, e.inbound_id, e.update_timestamp, e.weight, e.version
+                FROM e JOIN edge_history_types ON e.type=edge_history_types.type
+                ON CONFLICT ON CONSTRAINT edge_history_pkey DO UPDATE SET weight=excluded.weight, version=excluded.version
+            )
+            SELECT version FROM e
+        ", &[&id, &key.outbound_id, &key.t.0, &key.inbound_id, &weight.value(), &expected_version])?;
+
+        for row in &results {
+            let version: i64 = row.get(0);
+            return Ok(version as u64);
+        }
+
+        Err(Error::Conflict)
+    }
+
+    fn create_edges(&self, items: Vec<(models::EdgeKey, models::Weight)>) -> Result<Vec<Result<(), Error>>, Error> {
+        // Look up the owners of all of the vertices up front. The rows are
+        // locked so that the vertices can't be deleted out from under the
+        // insert below.
+        let mut vertex_ids: Vec<Uuid> = Vec::with_capacity(items.len() * 2);
+
+        for &(ref key, _) in &items {
+            vertex_ids.push(key.outbound_id);
+            vertex_ids.push(key.inbound_id);
+        }
+
+        vertex_ids.sort();
+        vertex_ids.dedup();
+        let query = format!("SELECT id, owner_id FROM vertices WHERE id = ANY($1) AND {} FOR SHARE", live_sql("vertices"));
+        let owner_results = self.trans.query(&query[..], &[&vertex_ids])?;
+        let mut owners: HashMap<Uuid, Uuid> = HashMap::new();
+
+        for row in &owner_results {
+            let id: Uuid = row.get(0);
+            let owner_id: Uuid = row.get(1);
+            owners.insert(id, owner_id);
+        }
+
+        // Collapse repeated edges down to their last weight, since a single
+        // upsert can't touch the same row twice
+        let mut weights: HashMap<models::EdgeKey, models::Weight> = HashMap::new();
+        let mut results: Vec<Result<(), Error>> = Vec::with_capacity(items.len());
+
+        for (key, weight) in items {
+            let result = match (owners.get(&key.outbound_id), owners.get(&key.inbound_id)) {
+                (None, _) => Err(Error::VertexNotFound),
+                (Some(owner_id), _) if *owner_id != self.account_id => Err(Error::Unauthorized),
+                (_, None) => Err(Error::VertexNotFound),
+                _ => Ok(())
+            };
+
+            if result.is_ok() {
+                weights.insert(key, weight);
             }
+
+            results.push(result);
         }
-        
-        Ok(())
+
+        if weights.is_empty() {
+            return Ok(results);
+        }
+
+        self.delete_expired_edges(weights.keys().collect())?;
+
+        let mut ids: Vec<Uuid> = Vec::with_capacity(weights.len());
+        let mut outbound_ids: Vec<Uuid> = Vec::with_capacity(weights.len());
+        let mut type_strs: Vec<String> = Vec::with_capacity(weights.len());
+        let mut inbound_ids: Vec<Uuid> = Vec::with_capacity(weights.len());
+        let mut weight_f32s: Vec<f32> = Vec::with_capacity(weights.len());
+
+        for (key, weight) in weights {
+            let id = if self.secure_uuids {
+                parent_uuid()
+            } else {
+                child_uuid(key.outbound_id)
+            };
+
+            ids.push(id);
+            outbound_ids.push(key.outbound_id);
+            type_strs.push(key.t.0);
+            inbound_ids.push(key.inbound_id);
+            weight_f32s.push(weight.value());
+        }
+
+        self.trans.execute("
+            WITH e AS (
+                INSERT INTO edges (id, outbound_id, type, inbound_id, weight, update_timestamp, created_timestamp)
+                SELECT id, outbound_id, type, inbound_id, weight, ts.timestamp, ts.timestamp
+                FROM UNNEST($1::uuid[], $2::uuid[], $3::text[], $4::uuid[], $5::real[]) AS e(id, outbound_id, type, inbound_id, weight)
+                CROSS JOIN (SELECT CLOCK_TIMESTAMP() AS timestamp) AS ts
+                ON CONFLICT ON CONSTRAINT edges_outbound_id_type_inbound_id_ukey
+                DO UPDATE SET weight=excluded.weight, update_timestamp=excluded.update_timestamp, version=edges.version+1, expires_at=NULL
+                RETURNING outbound_id, type, inbound_id, update_timestamp, weight, version
+            )
+            INSERT INTO edge_history (outbound_id, type, inbound_id, update_timestamp, weight, version)
+            SELECT e.outbound_id, e.type, e.inbound_id, e.update_timestamp, e.weight, e.version
+            FROM e JOIN edge_history_types ON e.type=edge_history_types.type
+            ON CONFLICT ON CONSTRAINT edge_history_pkey DO UPDATE SET weight=excluded.weight, version=excluded.version
+        ", &[&ids, &outbound_ids, &type_strs, &inbound_ids, &weight_f32s])?;
+
+        Ok(results)
     }
 
     fn get_edges(&self, q: EdgeQuery) -> Result<Vec<models::Edge>, Error> {
         let mut sql_query_builder = CTEQueryBuilder::new();
         self.edge_query_to_sql(q, &mut sql_query_builder);
-        let (query, params) = sql_query_builder.into_query_payload("SELECT outbound_id, type, inbound_id, weight, update_timestamp FROM %t", vec![]);
+        let (query, params) = sql_query_builder.into_query_payload("SELECT outbound_id, type, inbound_id, weight, update_timestamp, created_timestamp, version FROM %t", vec![]);
         let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
 
+        let results = self.trans.query(&query[..], &params_refs[..])?;
+        results.iter().map(|row| row_to_edge(&row)).collect()
+    }
+
+    fn stream_edges<'a>(&'a self, q: EdgeQuery) -> Result<EdgeStream<'a>, Error> {
+        let mut sql_query_builder = CTEQueryBuilder::new();
+        self.edge_query_to_sql(q, &mut sql_query_builder);
+        let (query, params) = sql_query_builder.into_query_payload("SELECT outbound_id, type, inbound_id, weight, update_timestamp, created_timestamp, version FROM %t", vec![]);
+        let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
+        let cursor = CursorIterator::new(&self.trans, &query[..], &params_refs[..], row_to_edge)?;
+        Ok(Box::new(cursor))
+    }
+
+    fn get_edge_history(&self, key: models::EdgeKey) -> Result<Vec<models::EdgeVersion>, Error> {
+        // An expired edge that hasn't been swept up yet gets the version it
+        // will be given when it is, and the history goes along with either
+        // of its vertices
+        let results = self.trans.query("
+            SELECT update_timestamp, weight, version FROM (
+                SELECT update_timestamp, weight, version FROM edge_history
+                WHERE outbound_id=$1 AND type=$2 AND inbound_id=$3
+                UNION ALL
+                SELECT edges.expires_at, NULL, NULL FROM edges JOIN edge_history_types ON edges.type=edge_history_types.type
+                WHERE edges.outbound_id=$1 AND edges.type=$2 AND edges.inbound_id=$3 AND edges.expires_at <= STATEMENT_TIMESTAMP()
+            ) versions
+            WHERE NOT EXISTS (SELECT 1 FROM vertices WHERE id IN ($1, $3) AND expires_at <= STATEMENT_TIMESTAMP())
+            ORDER BY update_timestamp
+        ", &[&key.outbound_id, &key.t.0, &key.inbound_id])?;
+
+        let mut versions: Vec<models::EdgeVersion> = Vec::with_capacity(results.len());
+
+        for row in &results {
+            let datetime: DateTime<UTC> = row.get(0);
+            let weight_f32: Option<f32> = row.get(1);
+            let version: Option<i64> = row.get(2);
+            let weight = match weight_f32 {
+                Some(weight_f32) => Some(to_weight(weight_f32)?),
+                None => None
+            };
+            versions.push(models::EdgeVersion::new(weight, version.map(|version| version as u64), datetime));
+        }
+
+        Ok(versions)
+    }
+
+    fn get_edges_as_of(&self,
+                       q: VertexQuery,
+                       converter: QueryTypeConverter,
+                       t: models::Type,
+                       datetime: DateTime<UTC>,
+                       limit: u32)
+                       -> Result<Vec<models::Edge>, Error> {
+        let mut sql_query_builder = CTEQueryBuilder::new();
+        self.vertex_query_to_sql(q, &mut sql_query_builder);
+
+        let id_column = match converter {
+            QueryTypeConverter::Outbound => "outbound_id",
+            QueryTypeConverter::Inbound => "inbound_id"
+        };
+
+        // Each edge is as of its latest version up to the datetime, and was
+        // created by the earliest version since it was last deleted. Expired
+        // edges that haven't been swept up yet get the version they'll be
+        // given when they are.
+        let query_template = format!("
+            SELECT latest.outbound_id, latest.inbound_id, latest.update_timestamp, latest.weight, latest.version, (
+                SELECT MIN(created.update_timestamp) FROM edge_history created
+                WHERE created.outbound_id=latest.outbound_id AND created.type=latest.type AND created.inbound_id=latest.inbound_id
+                    AND created.update_timestamp <= latest.update_timestamp
+                    AND created.update_timestamp > COALESCE((
+                        SELECT MAX(deleted.update_timestamp) FROM edge_history deleted
+                        WHERE deleted.outbound_id=latest.outbound_id AND deleted.type=latest.type AND deleted.inbound_id=latest.inbound_id
+                            AND deleted.update_timestamp < latest.update_timestamp AND deleted.weight IS NULL
+                    ), '-infinity')
+            )
+            FROM (
+                SELECT DISTINCT ON (outbound_id, inbound_id) outbound_id, type, inbound_id, update_timestamp, weight, version FROM (
+                    SELECT outbound_id, type, inbound_id, update_timestamp, weight, version FROM edge_history
+                    UNION ALL
+                    SELECT outbound_id, type, inbound_id, expires_at, NULL, NULL FROM edges WHERE expires_at <= STATEMENT_TIMESTAMP()
+                ) versions
+                WHERE {} IN (SELECT id FROM %t) AND type=%p AND update_timestamp <= %p
+                ORDER BY outbound_id, inbound_id, update_timestamp DESC
+            ) latest
+            WHERE latest.weight IS NOT NULL AND NOT EXISTS (
+                SELECT 1 FROM vertices expired_vertices
+                WHERE expired_vertices.id IN (latest.outbound_id, latest.inbound_id) AND expired_vertices.expires_at <= STATEMENT_TIMESTAMP()
+            )
+            ORDER BY latest.update_timestamp DESC, latest.outbound_id, latest.inbound_id
+            LIMIT %p
+        ", id_column);
+
+        let params: Vec<Box<ToSql>> = vec![Box::new(t.0.clone()), Box::new(datetime), Box::new(limit as i64)];
+        let (query, params) = sql_query_builder.into_query_payload(&query_template[..], params);
+        let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
         let results = self.trans.query(&query[..], &params_refs[..])?;
         let mut edges: Vec<models::Edge> = Vec::new();
 
         for row in &results {
             let outbound_id: Uuid = row.get(0);
-            let t_str: String = row.get(1);
-            let inbound_id: Uuid = row.get(2);
+            let inbound_id: Uuid = row.get(1);
+            let update_datetime: DateTime<UTC> = row.get(2);
             let weight_f32: f32 = row.get(3);
-            let weight = models::Weight::new(weight_f32).unwrap();
-            let update_datetime: DateTime<UTC> = row.get(4);
-            let key = models::EdgeKey::new(outbound_id, models::Type::new(t_str).unwrap(), inbound_id);
-            let edge = models::Edge::new(key, weight, update_datetime);
-            edges.push(edge);
+            let version: i64 = row.get(4);
+            let created_datetime: DateTime<UTC> = row.get(5);
+            let key = models::EdgeKey::new(outbound_id, t.clone(), inbound_id);
+            let weight = to_weight(weight_f32)?;
+            edges.push(models::Edge::new_with_version(key, weight, created_datetime, update_datetime, version as u64));
         }
 
         Ok(edges)
@@ -438,7 +1599,16 @@ impl Transaction for PostgresTransaction {
         let mut sql_query_builder = CTEQueryBuilder::new();
         self.edge_query_to_sql(q, &mut sql_query_builder);
         self.ensure_edges_are_owned_by_account(&mut sql_query_builder);
-        let (query, params) = sql_query_builder.into_query_payload("DELETE FROM edges WHERE id IN (SELECT id FROM %t)", vec![]);
+        sql_query_builder.push("DELETE FROM edges WHERE id IN (SELECT id FROM %t) RETURNING outbound_id, type, inbound_id", "", vec![]);
+
+        // Deleted edges get a version without a weight if their type has
+        // history enabled
+        let (query, params) = sql_query_builder.into_query_payload("
+            INSERT INTO edge_history (outbound_id, type, inbound_id, update_timestamp, weight, version)
+            SELECT %t.outbound_id, %t.type, %t.inbound_id, CLOCK_TIMESTAMP(), NULL, NULL
+            FROM %t JOIN edge_history_types ON %t.type=edge_history_types.type
+            ON CONFLICT ON CONSTRAINT edge_history_pkey DO UPDATE SET weight=excluded.weight, version=excluded.version
+        ", vec![]);
         let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
         self.trans.execute(&query[..], &params_refs[..])?;
         Ok(())
@@ -464,6 +1634,19 @@ impl Transaction for PostgresTransaction {
         self.handle_get_metadata(results)
     }
 
+    fn get_all_global_metadata(&self, prefix: String, start_name: Option<String>, limit: u32) -> Result<Vec<(String, JsonValue)>, Error> {
+        // Names are compared with the "C" collation so that they're sorted
+        // bytewise, like the other datastores
+        let results = self.trans.query("
+            SELECT name, value FROM global_metadata
+            WHERE left(name, char_length($1)) = $1 AND ($2::VARCHAR IS NULL OR name COLLATE \"C\" > $2)
+            ORDER BY name COLLATE \"C\"
+            LIMIT $3
+        ", &[&prefix, &start_name, &(limit as i64)])?;
+
+        Ok(results.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
     fn set_global_metadata(&self, name: String, value: JsonValue) -> Result<(), Error> {
         // Because this command could fail, we need to set a savepoint to roll
         // back to, rather than spoiling the entire transaction
@@ -473,7 +1656,7 @@ impl Transaction for PostgresTransaction {
             INSERT INTO global_metadata (name, value)
             VALUES ($1, $2)
             ON CONFLICT ON CONSTRAINT global_metadata_pkey
-            DO UPDATE SET value=$2
+            DO UPDATE SET value=$2, version=global_metadata.version+1
             RETURNING 1
         ", &[&name, &value]);
         
@@ -489,6 +1672,17 @@ impl Transaction for PostgresTransaction {
         }
     }
 
+    fn update_global_metadata(&self, name: String, operation: models::MetadataOperation) -> Result<JsonValue, Error> {
+        // There's no row to lock until the metadata is set, so updates are
+        // serialized with an advisory lock on the name instead
+        self.trans.execute("SELECT pg_advisory_xact_lock(hashtext('global_metadata:' || $1))", &[&name])?;
+        let results = self.trans.query("SELECT value FROM global_metadata WHERE name=$1 FOR UPDATE", &[&name])?;
+        let current: Option<JsonValue> = results.iter().next().map(|row| row.get(0));
+        let value = operation.apply(current.as_ref())?;
+        self.set_global_metadata(name, value.clone())?;
+        Ok(value)
+    }
+
     fn delete_global_metadata(&self, name: String) -> Result<(), Error> {
         let results = self.trans.query(
             "DELETE FROM global_metadata WHERE name=$1 RETURNING 1",
@@ -498,6 +1692,39 @@ impl Transaction for PostgresTransaction {
         self.handle_delete_metadata(results)
     }
 
+    fn get_versioned_global_metadata(&self, name: String) -> Result<models::VersionedMetadata, Error> {
+        let results = self.trans.query("SELECT value, version FROM global_metadata WHERE name=$1", &[&name])?;
+
+        for row in &results {
+            let version: i64 = row.get(1);
+            return Ok(models::VersionedMetadata::new(row.get(0), version as u64));
+        }
+
+        Err(Error::MetadataNotFound)
+    }
+
+    fn set_global_metadata_if_version(&self, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error> {
+        // The metadata is only inserted if no version is expected, and only
+        // updated if it's at the expected version
+        let expected_version = expected_version.map(|version| version as i64);
+        let results = self.trans.query("
+            WITH inserted AS (
+                INSERT INTO global_metadata (name, value)
+                SELECT $1, $2
+                WHERE $3::BIGINT IS NULL
+                ON CONFLICT ON CONSTRAINT global_metadata_pkey DO NOTHING
+                RETURNING version
+            ), updated AS (
+                UPDATE global_metadata SET value=$2, version=version+1
+                WHERE name=$1 AND version=$3
+                RETURNING version
+            )
+            SELECT version FROM inserted UNION ALL SELECT version FROM updated
+        ", &[&name, &value, &expected_version])?;
+
+        self.handle_set_metadata_if_version(results)
+    }
+
     fn get_account_metadata(&self, owner_id: Uuid, name: String) -> Result<JsonValue, Error> {
         let results = self.trans.query(
             "SELECT value FROM account_metadata WHERE owner_id=$1 AND name=$2",
@@ -507,6 +1734,19 @@ impl Transaction for PostgresTransaction {
         self.handle_get_metadata(results)
     }
 
+    fn get_all_account_metadata(&self, owner_id: Uuid) -> Result<Vec<(String, JsonValue)>, Error> {
+        if self.trans.query("SELECT 1 FROM accounts WHERE id=$1", &[&owner_id])?.is_empty() {
+            return Err(Error::AccountNotFound);
+        }
+
+        let results = self.trans.query(
+            "SELECT name, value FROM account_metadata WHERE owner_id=$1 ORDER BY name COLLATE \"C\"",
+            &[&owner_id]
+        )?;
+
+        Ok(results.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
     fn set_account_metadata(&self, owner_id: Uuid, name: String, value: JsonValue) -> Result<(), Error> {
         // Because this command could fail, we need to set a savepoint to roll
         // back to, rather than spoiling the entire transaction
@@ -516,7 +1756,7 @@ impl Transaction for PostgresTransaction {
             INSERT INTO account_metadata (owner_id, name, value)
             VALUES ($1, $2, $3)
             ON CONFLICT ON CONSTRAINT account_metadata_pkey
-            DO UPDATE SET value=$3
+            DO UPDATE SET value=$3, version=account_metadata.version+1
             RETURNING 1
         ", &[&owner_id, &name, &value]);
         
@@ -537,6 +1777,20 @@ impl Transaction for PostgresTransaction {
         }
     }
 
+    fn update_account_metadata(&self, owner_id: Uuid, name: String, operation: models::MetadataOperation) -> Result<JsonValue, Error> {
+        // Locking the account serializes updates to metadata that isn't set
+        // yet
+        if self.trans.query("SELECT 1 FROM accounts WHERE id=$1 FOR NO KEY UPDATE", &[&owner_id])?.is_empty() {
+            return Err(Error::AccountNotFound);
+        }
+
+        let results = self.trans.query("SELECT value FROM account_metadata WHERE owner_id=$1 AND name=$2 FOR UPDATE", &[&owner_id, &name])?;
+        let current: Option<JsonValue> = results.iter().next().map(|row| row.get(0));
+        let value = operation.apply(current.as_ref())?;
+        self.set_account_metadata(owner_id, name, value.clone())?;
+        Ok(value)
+    }
+
     fn delete_account_metadata(&self, owner_id: Uuid, name: String) -> Result<(), Error> {
         let results = self.trans.query(
             "DELETE FROM account_metadata WHERE owner_id=$1 AND name=$2 RETURNING 1",
@@ -545,35 +1799,159 @@ impl Transaction for PostgresTransaction {
         self.handle_delete_metadata(results)
     }
 
+    fn get_versioned_account_metadata(&self, owner_id: Uuid, name: String) -> Result<models::VersionedMetadata, Error> {
+        if self.trans.query("SELECT 1 FROM accounts WHERE id=$1", &[&owner_id])?.is_empty() {
+            return Err(Error::AccountNotFound);
+        }
+
+        let results = self.trans.query(
+            "SELECT value, version FROM account_metadata WHERE owner_id=$1 AND name=$2",
+            &[&owner_id, &name]
+        )?;
+
+        for row in &results {
+            let version: i64 = row.get(1);
+            return Ok(models::VersionedMetadata::new(row.get(0), version as u64));
+        }
+
+        Err(Error::MetadataNotFound)
+    }
+
+    fn set_account_metadata_if_version(&self, owner_id: Uuid, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error> {
+        // Check the account up front, so that nothing being written below
+        // can only mean a version conflict
+        if self.trans.query("SELECT 1 FROM accounts WHERE id=$1 FOR SHARE", &[&owner_id])?.is_empty() {
+            return Err(Error::AccountNotFound);
+        }
+
+        let expected_version = expected_version.map(|version| version as i64);
+        let results = self.trans.query("
+            WITH inserted AS (
+                INSERT INTO account_metadata (owner_id, name, value)
+                SELECT $1, $2, $3
+                WHERE $4::BIGINT IS NULL
+                ON CONFLICT ON CONSTRAINT account_metadata_pkey DO NOTHING
+                RETURNING version
+            ), updated AS (
+                UPDATE account_metadata SET value=$3, version=version+1
+                WHERE owner_id=$1 AND name=$2 AND version=$4
+                RETURNING version
+            )
+            SELECT version FROM inserted UNION ALL SELECT version FROM updated
+        ", &[&owner_id, &name, &value, &expected_version])?;
+
+        self.handle_set_metadata_if_version(results)
+    }
+
     fn get_vertex_metadata(&self, q: VertexQuery, name: String) -> Result<HashMap<Uuid, JsonValue>, Error> {
         let mut sql_query_builder = CTEQueryBuilder::new();
         self.vertex_query_to_sql(q, &mut sql_query_builder);
-        let (query, params) = sql_query_builder.into_query_payload("SELECT owner_id, value FROM vertex_metadata WHERE owner_id IN (SELECT id FROM %t) AND name=%p", vec![Box::new(name)]);
+        let query_template = format!("SELECT owner_id, value FROM vertex_metadata WHERE owner_id IN (SELECT id FROM %t) AND name=%p AND {}", live_sql("vertex_metadata"));
+        let (query, params) = sql_query_builder.into_query_payload(&query_template[..], vec![Box::new(name)]);
         let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
         let results = self.trans.query(&query[..], &params_refs[..])?;
-        let mut metadata: HashMap<Uuid, JsonValue> = HashMap::new();
+        results.iter().map(|row| row_to_vertex_metadata(&row)).collect()
+    }
 
-        for row in &results {
-            let id: Uuid = row.get(0);
-            let value: JsonValue = row.get(1);
-            metadata.insert(id, value);
+    fn stream_vertex_metadata<'a>(&'a self, q: VertexQuery, name: String) -> Result<VertexMetadataStream<'a>, Error> {
+        let mut sql_query_builder = CTEQueryBuilder::new();
+        self.vertex_query_to_sql(q, &mut sql_query_builder);
+        let query_template = format!("SELECT owner_id, value FROM vertex_metadata WHERE owner_id IN (SELECT id FROM %t) AND name=%p AND {}", live_sql("vertex_metadata"));
+        let (query, params) = sql_query_builder.into_query_payload(&query_template[..], vec![Box::new(name)]);
+        let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
+        let cursor = CursorIterator::new(&self.trans, &query[..], &params_refs[..], row_to_vertex_metadata)?;
+        Ok(Box::new(cursor))
+    }
+
+    fn get_all_vertex_metadata(&self, id: Uuid) -> Result<Vec<(String, JsonValue)>, Error> {
+        let query = format!("SELECT 1 FROM vertices WHERE id=$1 AND {}", live_sql("vertices"));
+
+        if self.trans.query(&query[..], &[&id])?.is_empty() {
+            return Err(Error::VertexNotFound);
         }
 
-        Ok(metadata)
+        let query = format!("SELECT name, value FROM vertex_metadata WHERE owner_id=$1 AND {} ORDER BY name COLLATE \"C\"", live_sql("vertex_metadata"));
+        let results = self.trans.query(&query[..], &[&id])?;
+
+        Ok(results.iter().map(|row| (row.get(0), row.get(1))).collect())
     }
 
     fn set_vertex_metadata(&self, q: VertexQuery, name: String, value: JsonValue) -> Result<(), Error> {
+        self.set_vertex_metadata_until(q, name, value, None)
+    }
+
+    fn set_vertex_metadata_with_ttl(&self, q: VertexQuery, name: String, value: JsonValue, ttl: Duration) -> Result<(), Error> {
+        self.set_vertex_metadata_until(q, name, value, Some(expiry_datetime(ttl)?))
+    }
+
+    fn update_vertex_metadata(&self, q: VertexQuery, name: String, operation: models::MetadataOperation) -> Result<HashMap<Uuid, JsonValue>, Error> {
+        // Locking the vertices serializes updates to metadata that isn't set
+        // yet
         let mut sql_query_builder = CTEQueryBuilder::new();
         self.vertex_query_to_sql(q, &mut sql_query_builder);
-        let (query, params) = sql_query_builder.into_query_payload("
+        let (query, params) = sql_query_builder.into_query_payload("SELECT id FROM vertices WHERE id IN (SELECT id FROM %t) ORDER BY id FOR NO KEY UPDATE", vec![]);
+        let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
+        let ids: Vec<Uuid> = self.trans.query(&query[..], &params_refs[..])?.iter().map(|row| row.get(0)).collect();
+
+        let mut current: HashMap<Uuid, JsonValue> = HashMap::new();
+
+        let query = format!("SELECT owner_id, value, {} FROM vertex_metadata WHERE owner_id = ANY($1) AND name=$2 FOR UPDATE", live_sql("vertex_metadata"));
+
+        for row in &self.trans.query(&query[..], &[&ids, &name])? {
+            // Expired values are locked too, since they're about to be
+            // replaced
+            let live: bool = row.get(2);
+
+            if live {
+                current.insert(row.get(0), row.get(1));
+            }
+        }
+
+        let mut values: Vec<JsonValue> = Vec::with_capacity(ids.len());
+
+        for id in &ids {
+            values.push(operation.apply(current.get(id))?);
+        }
+
+        self.delete_expired_unique_values(&name[..])?;
+
+        // Because the unique constraint could be violated, we need to set a
+        // savepoint to roll back to, rather than spoiling the entire
+        // transaction
+        let trans = self.trans.savepoint("update_vertex_metadata")?;
+
+        let results = trans.execute("
             INSERT INTO vertex_metadata (owner_id, name, value)
-            SELECT id, %p, %p FROM %t
+            SELECT owner_id, $2, value FROM UNNEST($1::uuid[], $3::jsonb[]) AS m(owner_id, value)
             ON CONFLICT ON CONSTRAINT vertex_metadata_pkey
-            DO UPDATE SET value=%p
-        ", vec![Box::new(name), Box::new(value.clone()), Box::new(value)]);
-        let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
-        self.trans.execute(&query[..], &params_refs[..])?;
-        Ok(())
+            DO UPDATE SET value=EXCLUDED.value, version=vertex_metadata.version+1, expires_at=NULL
+        ", &[&ids, &name, &values]).and_then(|_| trans.execute("
+            INSERT INTO unique_values (type, name, value, owner_id)
+            SELECT vertices.type, unique_constraints.name, m.value, vertices.id
+            FROM UNNEST($1::uuid[], $3::jsonb[]) AS m(owner_id, value)
+            JOIN vertices ON vertices.id = m.owner_id
+            JOIN unique_constraints ON unique_constraints.type = vertices.type AND unique_constraints.name = $2
+            ON CONFLICT ON CONSTRAINT unique_values_owner_id_name_ukey
+            DO UPDATE SET value=EXCLUDED.value
+        ", &[&ids, &name, &values]));
+
+        match results {
+            Err(err) => {
+                trans.set_rollback();
+
+                if let pg_error::Error::Db(ref err) = err {
+                    if err.code == pg_error::SqlState::UniqueViolation {
+                        return Err(Error::UniqueConstraintViolated);
+                    }
+                }
+
+                Err(Error::from(err))
+            }
+            Ok(_) => {
+                trans.set_commit();
+                Ok(ids.into_iter().zip(values.into_iter()).collect())
+            }
+        }
     }
 
     fn delete_vertex_metadata(&self, q: VertexQuery, name: String) -> Result<(), Error> {
@@ -585,15 +1963,114 @@ impl Transaction for PostgresTransaction {
         Ok(())
     }
 
+    fn get_versioned_vertex_metadata(&self, q: VertexQuery, name: String) -> Result<HashMap<Uuid, models::VersionedMetadata>, Error> {
+        let mut sql_query_builder = CTEQueryBuilder::new();
+        self.vertex_query_to_sql(q, &mut sql_query_builder);
+        let query_template = format!("SELECT owner_id, value, version FROM vertex_metadata WHERE owner_id IN (SELECT id FROM %t) AND name=%p AND {}", live_sql("vertex_metadata"));
+        let (query, params) = sql_query_builder.into_query_payload(&query_template[..], vec![Box::new(name)]);
+        let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
+        let results = self.trans.query(&query[..], &params_refs[..])?;
+        let mut metadata = HashMap::new();
+
+        for row in &results {
+            let id: Uuid = row.get(0);
+            let value: JsonValue = row.get(1);
+            let version: i64 = row.get(2);
+            metadata.insert(id, models::VersionedMetadata::new(value, version as u64));
+        }
+
+        Ok(metadata)
+    }
+
+    fn set_vertex_metadata_if_version(&self, id: Uuid, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error> {
+        // Check the vertex up front, so that nothing being written below can
+        // only mean a version conflict
+        let query = format!("SELECT 1 FROM vertices WHERE id=$1 AND {} FOR SHARE", live_sql("vertices"));
+
+        if self.trans.query(&query[..], &[&id])?.is_empty() {
+            return Err(Error::VertexNotFound);
+        }
+
+        self.delete_expired_unique_values(&name[..])?;
+
+        // Because the unique constraint could be violated, we need to set a
+        // savepoint to roll back to, rather than spoiling the entire
+        // transaction
+        let trans = self.trans.savepoint("set_vertex_metadata_if_version")?;
+
+        // The metadata is only inserted if no version is expected, and only
+        // updated if it's at the expected version. Expired metadata that
+        // hasn't been swept up yet counts as not being set.
+        let expected_version = expected_version.map(|version| version as i64);
+        let results = trans.query("
+            WITH inserted AS (
+                INSERT INTO vertex_metadata (owner_id, name, value)
+                SELECT $1, $2, $3
+                WHERE $4::BIGINT IS NULL
+                ON CONFLICT ON CONSTRAINT vertex_metadata_pkey DO NOTHING
+                RETURNING version
+            ), updated AS (
+                UPDATE vertex_metadata SET value=$3, version=version+1, expires_at=NULL
+                WHERE owner_id=$1 AND name=$2 AND CASE
+                    WHEN expires_at <= STATEMENT_TIMESTAMP() THEN $4::BIGINT IS NULL
+                    ELSE version=$4
+                END
+                RETURNING version
+            )
+            SELECT version FROM inserted UNION ALL SELECT version FROM updated
+        ", &[&id, &name, &value, &expected_version]).and_then(|results| {
+            if !results.is_empty() {
+                // Claim the value for any unique constraint on the metadata,
+                // which fails if another vertex already has it
+                trans.execute("
+                    INSERT INTO unique_values (type, name, value, owner_id)
+                    SELECT vertices.type, unique_constraints.name, $3, vertices.id
+                    FROM vertices JOIN unique_constraints ON unique_constraints.type = vertices.type AND unique_constraints.name = $2
+                    WHERE vertices.id = $1
+                    ON CONFLICT ON CONSTRAINT unique_values_owner_id_name_ukey
+                    DO UPDATE SET value=EXCLUDED.value
+                ", &[&id, &name, &value])?;
+            }
+
+            Ok(results)
+        });
+
+        match results {
+            Err(err) => {
+                trans.set_rollback();
+
+                if let pg_error::Error::Db(ref err) = err {
+                    if err.code == pg_error::SqlState::UniqueViolation {
+                        return Err(Error::UniqueConstraintViolated);
+                    }
+                }
+
+                Err(Error::from(err))
+            }
+            Ok(results) => {
+                trans.set_commit();
+
+                for row in &results {
+                    let version: i64 = row.get(0);
+                    return Ok(version as u64);
+                }
+
+                Err(Error::Conflict)
+            }
+        }
+    }
+
     fn get_edge_metadata(&self, q: EdgeQuery, name: String) -> Result<HashMap<models::EdgeKey, JsonValue>, Error> {
         let mut sql_query_builder = CTEQueryBuilder::new();
         self.edge_query_to_sql(q, &mut sql_query_builder);
 
-        let (query, params) = sql_query_builder.into_query_payload("
+        let query_template = format!("
             SELECT edges.outbound_id, edges.type, edges.inbound_id, edge_metadata.value
             FROM edge_metadata JOIN edges ON edge_metadata.owner_id=edges.id
-            WHERE owner_id IN (SELECT id FROM %t) AND name=%p
-        ", vec![Box::new(name)]);
+            WHERE owner_id IN (SELECT id FROM %t) AND name=%p AND {}
+        ", live_sql("edge_metadata"));
+
+        let (query, params) = sql_query_builder.into_query_payload(&query_template[..], vec![Box::new(name)]);
         
         let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
         let results = self.trans.query(&query[..], &params_refs[..])?;
@@ -611,18 +2088,73 @@ impl Transaction for PostgresTransaction {
         Ok(metadata)
     }
 
+    fn get_all_edge_metadata(&self, key: models::EdgeKey) -> Result<Vec<(String, JsonValue)>, Error> {
+        let query = format!("SELECT id FROM edges WHERE outbound_id=$1 AND type=$2 AND inbound_id=$3 AND {}", live_edge_sql("edges"));
+        let edge_results = self.trans.query(&query[..], &[&key.outbound_id, &key.t.0, &key.inbound_id])?;
+
+        let edge_id: Uuid = match edge_results.iter().next() {
+            Some(row) => row.get(0),
+            None => return Err(Error::EdgeNotFound)
+        };
+
+        let query = format!("SELECT name, value FROM edge_metadata WHERE owner_id=$1 AND {} ORDER BY name COLLATE \"C\"", live_sql("edge_metadata"));
+        let results = self.trans.query(&query[..], &[&edge_id])?;
+
+        Ok(results.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
     fn set_edge_metadata(&self, q: EdgeQuery, name: String, value: JsonValue) -> Result<(), Error> {
+        self.set_edge_metadata_until(q, name, value, None)
+    }
+
+    fn set_edge_metadata_with_ttl(&self, q: EdgeQuery, name: String, value: JsonValue, ttl: Duration) -> Result<(), Error> {
+        self.set_edge_metadata_until(q, name, value, Some(expiry_datetime(ttl)?))
+    }
+
+    fn update_edge_metadata(&self, q: EdgeQuery, name: String, operation: models::MetadataOperation) -> Result<HashMap<models::EdgeKey, JsonValue>, Error> {
+        // Locking the edges serializes updates to metadata that isn't set
+        // yet
         let mut sql_query_builder = CTEQueryBuilder::new();
         self.edge_query_to_sql(q, &mut sql_query_builder);
-        let (query, params) = sql_query_builder.into_query_payload("
+        let (query, params) = sql_query_builder.into_query_payload("SELECT id, outbound_id, type, inbound_id FROM edges WHERE id IN (SELECT id FROM %t) ORDER BY id FOR NO KEY UPDATE", vec![]);
+        let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
+        let mut ids: Vec<Uuid> = Vec::new();
+        let mut keys: Vec<models::EdgeKey> = Vec::new();
+
+        for row in &self.trans.query(&query[..], &params_refs[..])? {
+            let t_str: String = row.get(2);
+            ids.push(row.get(0));
+            keys.push(models::EdgeKey::new(row.get(1), models::Type::new(t_str).unwrap(), row.get(3)));
+        }
+
+        let mut current: HashMap<Uuid, JsonValue> = HashMap::new();
+
+        let query = format!("SELECT owner_id, value, {} FROM edge_metadata WHERE owner_id = ANY($1) AND name=$2 FOR UPDATE", live_sql("edge_metadata"));
+
+        for row in &self.trans.query(&query[..], &[&ids, &name])? {
+            // Expired values are locked too, since they're about to be
+            // replaced
+            let live: bool = row.get(2);
+
+            if live {
+                current.insert(row.get(0), row.get(1));
+            }
+        }
+
+        let mut values: Vec<JsonValue> = Vec::with_capacity(ids.len());
+
+        for id in &ids {
+            values.push(operation.apply(current.get(id))?);
+        }
+
+        self.trans.execute("
             INSERT INTO edge_metadata (owner_id, name, value)
-            SELECT id, %p, %p FROM %t
+            SELECT owner_id, $2, value FROM UNNEST($1::uuid[], $3::jsonb[]) AS m(owner_id, value)
             ON CONFLICT ON CONSTRAINT edge_metadata_pkey
-            DO UPDATE SET value=%p
-        ", vec![Box::new(name), Box::new(value.clone()), Box::new(value)]);
-        let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
-        self.trans.execute(&query[..], &params_refs[..])?;
-        Ok(())
+            DO UPDATE SET value=EXCLUDED.value, version=edge_metadata.version+1, expires_at=NULL
+        ", &[&ids, &name, &values])?;
+
+        Ok(keys.into_iter().zip(values.into_iter()).collect())
     }
 
     fn delete_edge_metadata(&self, q: EdgeQuery, name: String) -> Result<(), Error> {
@@ -634,6 +2166,70 @@ impl Transaction for PostgresTransaction {
         Ok(())
     }
 
+    fn get_versioned_edge_metadata(&self, q: EdgeQuery, name: String) -> Result<HashMap<models::EdgeKey, models::VersionedMetadata>, Error> {
+        let mut sql_query_builder = CTEQueryBuilder::new();
+        self.edge_query_to_sql(q, &mut sql_query_builder);
+
+        let query_template = format!("
+            SELECT edges.outbound_id, edges.type, edges.inbound_id, edge_metadata.value, edge_metadata.version
+            FROM edge_metadata JOIN edges ON edge_metadata.owner_id=edges.id
+            WHERE owner_id IN (SELECT id FROM %t) AND name=%p AND {}
+        ", live_sql("edge_metadata"));
+
+        let (query, params) = sql_query_builder.into_query_payload(&query_template[..], vec![Box::new(name)]);
+
+        let params_refs: Vec<&ToSql> = params.iter().map(|x| &**x).collect();
+        let results = self.trans.query(&query[..], &params_refs[..])?;
+        let mut metadata: HashMap<models::EdgeKey, models::VersionedMetadata> = HashMap::new();
+
+        for row in &results {
+            let outbound_id: Uuid = row.get(0);
+            let t_str: String = row.get(1);
+            let inbound_id: Uuid = row.get(2);
+            let value: JsonValue = row.get(3);
+            let version: i64 = row.get(4);
+            let key = models::EdgeKey::new(outbound_id, models::Type::new(t_str).unwrap(), inbound_id);
+            metadata.insert(key, models::VersionedMetadata::new(value, version as u64));
+        }
+
+        Ok(metadata)
+    }
+
+    fn set_edge_metadata_if_version(&self, key: models::EdgeKey, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error> {
+        // Check the edge up front, so that nothing being written below can
+        // only mean a version conflict
+        let query = format!("SELECT id FROM edges WHERE outbound_id=$1 AND type=$2 AND inbound_id=$3 AND {} FOR SHARE", live_edge_sql("edges"));
+        let edge_results = self.trans.query(&query[..], &[&key.outbound_id, &key.t.0, &key.inbound_id])?;
+
+        let edge_id: Uuid = match edge_results.iter().next() {
+            Some(row) => row.get(0),
+            None => return Err(Error::EdgeNotFound)
+        };
+
+        // Expired metadata that hasn't been swept up yet counts as not being
+        // set
+        let expected_version = expected_version.map(|version| version as i64);
+        let results = self.trans.query("
+            WITH inserted AS (
+                INSERT INTO edge_metadata (owner_id, name, value)
+                SELECT $1, $2, $3
+                WHERE $4::BIGINT IS NULL
+                ON CONFLICT ON CONSTRAINT edge_metadata_pkey DO NOTHING
+                RETURNING version
+            ), updated AS (
+                UPDATE edge_metadata SET value=$3, version=version+1, expires_at=NULL
+                WHERE owner_id=$1 AND name=$2 AND CASE
+                    WHEN expires_at <= STATEMENT_TIMESTAMP() THEN $4::BIGINT IS NULL
+                    ELSE version=$4
+                END
+                RETURNING version
+            )
+            SELECT version FROM inserted UNION ALL SELECT version FROM updated
+        ", &[&edge_id, &name, &value, &expected_version])?;
+
+        self.handle_set_metadata_if_version(results)
+    }
+
     fn commit(self) -> Result<(), Error> {
         self.trans.set_commit();
         self.trans.commit()?;
@@ -1,4 +1,5 @@
 mod converters;
+mod cursor;
 mod datastore;
 mod schema;
 mod tests;
@@ -13,13 +13,17 @@ ALTER TABLE accounts
 CREATE TABLE vertices (
     id UUID NOT NULL,
     owner_id UUID NOT NULL,
-    type VARCHAR(1000) NOT NULL
+    type VARCHAR(1000) NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE
 );
 
 ALTER TABLE vertices
     ADD CONSTRAINT vertices_pkey PRIMARY KEY (id),
     ADD CONSTRAINT vertices_owner_fkey FOREIGN KEY (owner_id) REFERENCES accounts (id) ON DELETE CASCADE;
 
+CREATE INDEX ix_vertices_type_id ON vertices USING btree (type, id);
+CREATE INDEX ix_vertices_expires_at ON vertices USING btree (expires_at) WHERE expires_at IS NOT NULL;
+
 /* Edges */
 CREATE TABLE edges (
     id UUID NOT NULL,
@@ -27,7 +31,10 @@ CREATE TABLE edges (
     type VARCHAR(1000) NOT NULL,
     inbound_id UUID NOT NULL,
     update_timestamp TIMESTAMP WITH TIME ZONE NOT NULL,
-    weight REAL NOT NULL
+    weight REAL NOT NULL,
+    created_timestamp TIMESTAMP WITH TIME ZONE NOT NULL,
+    version BIGINT NOT NULL DEFAULT 1,
+    expires_at TIMESTAMP WITH TIME ZONE
 );
 
 ALTER TABLE edges
@@ -38,11 +45,44 @@ ALTER TABLE edges
 
 CREATE INDEX ix_edges_update_timestamp ON edges USING btree (update_timestamp);
 CREATE INDEX ix_edges_inbound_id ON edges USING btree (inbound_id);
+CREATE INDEX ix_edges_outbound_id_type_weight ON edges USING btree (outbound_id, type, weight);
+CREATE INDEX ix_edges_inbound_id_type_weight ON edges USING btree (inbound_id, type, weight);
+CREATE INDEX ix_edges_outbound_id_type_update_timestamp ON edges USING btree (outbound_id, type, update_timestamp DESC, inbound_id);
+CREATE INDEX ix_edges_inbound_id_type_update_timestamp ON edges USING btree (inbound_id, type, update_timestamp DESC, outbound_id);
+CREATE INDEX ix_edges_outbound_id_type_created_timestamp ON edges USING btree (outbound_id, type, created_timestamp DESC);
+CREATE INDEX ix_edges_inbound_id_type_created_timestamp ON edges USING btree (inbound_id, type, created_timestamp DESC);
+CREATE INDEX ix_edges_expires_at ON edges USING btree (expires_at) WHERE expires_at IS NOT NULL;
+
+/* Edge history */
+CREATE TABLE edge_history_types (
+    type VARCHAR(1000) NOT NULL
+);
+
+ALTER TABLE edge_history_types
+    ADD CONSTRAINT edge_history_types_pkey PRIMARY KEY (type);
+
+CREATE TABLE edge_history (
+    outbound_id UUID NOT NULL,
+    type VARCHAR(1000) NOT NULL,
+    inbound_id UUID NOT NULL,
+    update_timestamp TIMESTAMP WITH TIME ZONE NOT NULL,
+    weight REAL,
+    version BIGINT
+);
+
+ALTER TABLE edge_history
+    ADD CONSTRAINT edge_history_pkey PRIMARY KEY (outbound_id, type, inbound_id, update_timestamp),
+    ADD CONSTRAINT edge_history_type_fkey FOREIGN KEY (type) REFERENCES edge_history_types (type) ON DELETE CASCADE,
+    ADD CONSTRAINT edge_history_outbound_id_fkey FOREIGN KEY (outbound_id) REFERENCES vertices (id) ON DELETE CASCADE,
+    ADD CONSTRAINT edge_history_inbound_id_fkey FOREIGN KEY (inbound_id) REFERENCES vertices (id) ON DELETE CASCADE;
+
+CREATE INDEX ix_edge_history_inbound_id_type ON edge_history USING btree (inbound_id, type);
 
 /* Global metadata */
 CREATE TABLE global_metadata (
     name VARCHAR(1024) NOT NULL,
-    value JSONB NOT NULL
+    value JSONB NOT NULL,
+    version BIGINT NOT NULL DEFAULT 1
 );
 
 ALTER TABLE global_metadata
@@ -52,7 +92,8 @@ ALTER TABLE global_metadata
 CREATE TABLE account_metadata (
     owner_id UUID NOT NULL,
     name VARCHAR(1024) NOT NULL,
-    value JSONB NOT NULL
+    value JSONB NOT NULL,
+    version BIGINT NOT NULL DEFAULT 1
 );
 
 ALTER TABLE account_metadata
@@ -63,22 +104,158 @@ ALTER TABLE account_metadata
 CREATE TABLE vertex_metadata (
     owner_id UUID NOT NULL,
     name VARCHAR(1024) NOT NULL,
-    value JSONB NOT NULL
+    value JSONB NOT NULL,
+    version BIGINT NOT NULL DEFAULT 1,
+    expires_at TIMESTAMP WITH TIME ZONE
 );
 
 ALTER TABLE vertex_metadata
     ADD CONSTRAINT vertex_metadata_pkey PRIMARY KEY (owner_id, name),
     ADD CONSTRAINT vertex_metadata_owner_id_fkey FOREIGN KEY (owner_id) REFERENCES vertices (id) ON DELETE CASCADE;
 
+CREATE INDEX ix_vertex_metadata_name_owner_id ON vertex_metadata USING btree (name, owner_id);
+CREATE INDEX ix_vertex_metadata_expires_at ON vertex_metadata USING btree (expires_at) WHERE expires_at IS NOT NULL;
+
+/* Unique constraints */
+CREATE TABLE unique_constraints (
+    type VARCHAR(1000) NOT NULL,
+    name VARCHAR(1024) NOT NULL
+);
+
+ALTER TABLE unique_constraints
+    ADD CONSTRAINT unique_constraints_pkey PRIMARY KEY (type, name);
+
+CREATE TABLE unique_values (
+    type VARCHAR(1000) NOT NULL,
+    name VARCHAR(1024) NOT NULL,
+    value JSONB NOT NULL,
+    owner_id UUID NOT NULL
+);
+
+ALTER TABLE unique_values
+    ADD CONSTRAINT unique_values_pkey PRIMARY KEY (type, name, value),
+    ADD CONSTRAINT unique_values_owner_id_name_ukey UNIQUE (owner_id, name),
+    ADD CONSTRAINT unique_values_type_name_fkey FOREIGN KEY (type, name) REFERENCES unique_constraints (type, name) ON DELETE CASCADE,
+    ADD CONSTRAINT unique_values_owner_id_name_fkey FOREIGN KEY (owner_id, name) REFERENCES vertex_metadata (owner_id, name) ON DELETE CASCADE;
+
+/* Vertex metadata indexes */
+CREATE TABLE vertex_metadata_indexes (
+    id UUID NOT NULL,
+    type VARCHAR(1000) NOT NULL,
+    name VARCHAR(1024) NOT NULL,
+    path TEXT[] NOT NULL
+);
+
+ALTER TABLE vertex_metadata_indexes
+    ADD CONSTRAINT vertex_metadata_indexes_pkey PRIMARY KEY (id),
+    ADD CONSTRAINT vertex_metadata_indexes_type_name_path_ukey UNIQUE (type, name, path);
+
 /* Metadata */
 CREATE TABLE edge_metadata (
     owner_id UUID NOT NULL,
     name VARCHAR(1024) NOT NULL,
-    value JSONB NOT NULL
+    value JSONB NOT NULL,
+    version BIGINT NOT NULL DEFAULT 1,
+    expires_at TIMESTAMP WITH TIME ZONE
 );
 
 ALTER TABLE edge_metadata
     ADD CONSTRAINT edge_metadata_pkey PRIMARY KEY (owner_id, name),
     ADD CONSTRAINT edge_metadata_owner_id_fkey FOREIGN KEY (owner_id) REFERENCES edges (id) ON DELETE CASCADE;
 
+CREATE INDEX ix_edge_metadata_name_owner_id ON edge_metadata USING btree (name, owner_id);
+CREATE INDEX ix_edge_metadata_expires_at ON edge_metadata USING btree (expires_at) WHERE expires_at IS NOT NULL;
+
+";
+
+/// Brings a schema created by an earlier version up to date. Every statement
+/// is safe to run against a schema that's already up to date.
+pub const MIGRATIONS: &'static str = "
+/* Vertex type index */
+CREATE INDEX IF NOT EXISTS ix_vertices_type_id ON vertices USING btree (type, id);
+
+/* Edge weight indexes */
+CREATE INDEX IF NOT EXISTS ix_edges_outbound_id_type_weight ON edges USING btree (outbound_id, type, weight);
+CREATE INDEX IF NOT EXISTS ix_edges_inbound_id_type_weight ON edges USING btree (inbound_id, type, weight);
+
+/* Edge page indexes */
+CREATE INDEX IF NOT EXISTS ix_edges_outbound_id_type_update_timestamp ON edges USING btree (outbound_id, type, update_timestamp DESC, inbound_id);
+CREATE INDEX IF NOT EXISTS ix_edges_inbound_id_type_update_timestamp ON edges USING btree (inbound_id, type, update_timestamp DESC, outbound_id);
+
+/* Vertex metadata indexes */
+CREATE TABLE IF NOT EXISTS vertex_metadata_indexes (
+    id UUID NOT NULL,
+    type VARCHAR(1000) NOT NULL,
+    name VARCHAR(1024) NOT NULL,
+    path TEXT[] NOT NULL,
+    CONSTRAINT vertex_metadata_indexes_pkey PRIMARY KEY (id),
+    CONSTRAINT vertex_metadata_indexes_type_name_path_ukey UNIQUE (type, name, path)
+);
+
+/* Unique constraints */
+CREATE TABLE IF NOT EXISTS unique_constraints (
+    type VARCHAR(1000) NOT NULL,
+    name VARCHAR(1024) NOT NULL,
+    CONSTRAINT unique_constraints_pkey PRIMARY KEY (type, name)
+);
+
+CREATE TABLE IF NOT EXISTS unique_values (
+    type VARCHAR(1000) NOT NULL,
+    name VARCHAR(1024) NOT NULL,
+    value JSONB NOT NULL,
+    owner_id UUID NOT NULL,
+    CONSTRAINT unique_values_pkey PRIMARY KEY (type, name, value),
+    CONSTRAINT unique_values_owner_id_name_ukey UNIQUE (owner_id, name),
+    CONSTRAINT unique_values_type_name_fkey FOREIGN KEY (type, name) REFERENCES unique_constraints (type, name) ON DELETE CASCADE,
+    CONSTRAINT unique_values_owner_id_name_fkey FOREIGN KEY (owner_id, name) REFERENCES vertex_metadata (owner_id, name) ON DELETE CASCADE
+);
+
+/* Edge created datetimes, which existing edges take from their update datetimes */
+ALTER TABLE edges ADD COLUMN IF NOT EXISTS created_timestamp TIMESTAMP WITH TIME ZONE;
+UPDATE edges SET created_timestamp=update_timestamp WHERE created_timestamp IS NULL;
+ALTER TABLE edges ALTER COLUMN created_timestamp SET NOT NULL;
+CREATE INDEX IF NOT EXISTS ix_edges_outbound_id_type_created_timestamp ON edges USING btree (outbound_id, type, created_timestamp DESC);
+CREATE INDEX IF NOT EXISTS ix_edges_inbound_id_type_created_timestamp ON edges USING btree (inbound_id, type, created_timestamp DESC);
+
+/* Edge history */
+CREATE TABLE IF NOT EXISTS edge_history_types (
+    type VARCHAR(1000) NOT NULL,
+    CONSTRAINT edge_history_types_pkey PRIMARY KEY (type)
+);
+
+CREATE TABLE IF NOT EXISTS edge_history (
+    outbound_id UUID NOT NULL,
+    type VARCHAR(1000) NOT NULL,
+    inbound_id UUID NOT NULL,
+    update_timestamp TIMESTAMP WITH TIME ZONE NOT NULL,
+    weight REAL,
+    version BIGINT,
+    CONSTRAINT edge_history_pkey PRIMARY KEY (outbound_id, type, inbound_id, update_timestamp),
+    CONSTRAINT edge_history_type_fkey FOREIGN KEY (type) REFERENCES edge_history_types (type) ON DELETE CASCADE,
+    CONSTRAINT edge_history_outbound_id_fkey FOREIGN KEY (outbound_id) REFERENCES vertices (id) ON DELETE CASCADE,
+    CONSTRAINT edge_history_inbound_id_fkey FOREIGN KEY (inbound_id) REFERENCES vertices (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS ix_edge_history_inbound_id_type ON edge_history USING btree (inbound_id, type);
+
+/* Versions, which start at 1 for everything that already exists */
+ALTER TABLE edges ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE global_metadata ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE account_metadata ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE vertex_metadata ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE edge_metadata ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+
+/* Metadata name indexes */
+CREATE INDEX IF NOT EXISTS ix_vertex_metadata_name_owner_id ON vertex_metadata USING btree (name, owner_id);
+CREATE INDEX IF NOT EXISTS ix_edge_metadata_name_owner_id ON edge_metadata USING btree (name, owner_id);
+
+/* Expiry, which nothing that already exists has */
+ALTER TABLE vertices ADD COLUMN IF NOT EXISTS expires_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE edges ADD COLUMN IF NOT EXISTS expires_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE vertex_metadata ADD COLUMN IF NOT EXISTS expires_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE edge_metadata ADD COLUMN IF NOT EXISTS expires_at TIMESTAMP WITH TIME ZONE;
+CREATE INDEX IF NOT EXISTS ix_vertices_expires_at ON vertices USING btree (expires_at) WHERE expires_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS ix_edges_expires_at ON edges USING btree (expires_at) WHERE expires_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS ix_vertex_metadata_expires_at ON vertex_metadata USING btree (expires_at) WHERE expires_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS ix_edge_metadata_expires_at ON edge_metadata USING btree (expires_at) WHERE expires_at IS NOT NULL;
 ";
@@ -12,6 +12,9 @@ fn datastore() -> PostgresDatastore {
 
     START.call_once(|| {
         PostgresDatastore::create_schema(connection_string.clone()).unwrap();
+
+        // Migrating an up to date schema should do nothing
+        PostgresDatastore::migrate_schema(connection_string.clone()).unwrap();
     });
 
     let secret = "OME88YorohonzPNWEFsi0dIsouXWqeO$".to_string();
@@ -21,3 +24,4 @@ fn datastore() -> PostgresDatastore {
 test_account_impl!(datastore());
 test_transaction_impl!(datastore());
 test_metadata_impl!(datastore());
+test_rollback_impl!(datastore());
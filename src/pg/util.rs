@@ -14,11 +14,8 @@ pub fn pg_error_to_description(err: pg_error::Error) -> String {
     }
 }
 
-fn get_from_table_name(root_table_name: &str, table_number: usize) -> String {
-    match table_number {
-        0 => root_table_name.to_string(),
-        _ => format!("pipe_{}", table_number)
-    }
+fn get_table_name(table_number: usize) -> String {
+    format!("pipe_{}", table_number)
 }
 
 fn format_query(template: &str, from_table_name: &str, cur_params_length: usize, param_number: usize) -> String {
@@ -35,26 +32,58 @@ fn format_query(template: &str, from_table_name: &str, cur_params_length: usize,
 
 pub struct CTEQueryBuilder {
     queries: Vec<String>,
-    params: Vec<Box<ToSql>>
+    params: Vec<Box<ToSql>>,
+    recursive: bool,
+    /// Whether the next query starts a new chain, and so should select from
+    /// its root table rather than from the previous query.
+    new_chain: bool
 }
 
 impl CTEQueryBuilder {
     pub fn new() -> CTEQueryBuilder {
         CTEQueryBuilder{
             queries: Vec::new(),
-            params: Vec::new()
+            params: Vec::new(),
+            recursive: false,
+            new_chain: true
         }
     }
 
+    /// Makes the next query start a new chain, so that queries can combine
+    /// the results of more than one chain, e.g. with `INTERSECT`.
+    pub fn start_chain(&mut self) {
+        self.new_chain = true;
+    }
+
+    /// Gets the name of the last query's results.
+    pub fn last_table_name(&self) -> String {
+        get_table_name(self.queries.len())
+    }
+
     pub fn push(&mut self, query_template: &str, root_table_name: &str, params: Vec<Box<ToSql>>) {
         // TODO: because we don't support query parameter numbers, there are a
         // couple of times where we have to pass the same parameter multiple
         // times. Fix this.
 
-        let from_table_name = get_from_table_name(root_table_name, self.queries.len());
+        let from_table_name = if self.new_chain {
+            root_table_name.to_string()
+        } else {
+            self.last_table_name()
+        };
+
         let query = format_query(query_template, &from_table_name[..], params.len(), self.params.len());
         self.queries.push(query);
         self.params.extend(params);
+        self.new_chain = false;
+    }
+
+    /// Pushes a recursive query, which can refer to its own results via
+    /// `%r`. Like any other query, `%t` refers to the results of the
+    /// previous query.
+    pub fn push_recursive(&mut self, query_template: &str, params: Vec<Box<ToSql>>) {
+        let self_table_name = get_table_name(self.queries.len() + 1);
+        self.recursive = true;
+        self.push(&query_template.replace("%r", &self_table_name[..])[..], "", params);
     }
 
     pub fn into_query_payload(self, query_template: &str, params: Vec<Box<ToSql>>) -> (String, Vec<Box<ToSql>>) {
@@ -62,14 +91,18 @@ impl CTEQueryBuilder {
             panic!("No queries");
         }
 
-        let from_table_name = get_from_table_name("", self.queries.len());
+        let from_table_name = self.last_table_name();
         let query = format_query(query_template, &from_table_name[..], params.len(), self.params.len());
 
         let mut full_params = self.params;
         full_params.extend(params);
         
         let mut buffer: Vec<String> = Vec::new();
-        buffer.push("WITH ".to_string());
+        if self.recursive {
+            buffer.push("WITH RECURSIVE ".to_string());
+        } else {
+            buffer.push("WITH ".to_string());
+        }
 
         for (i, query) in self.queries.into_iter().enumerate() {
             if i > 0 {
@@ -0,0 +1,330 @@
+use errors::Error;
+use rocksdb::{DB, IteratorMode, Direction, WriteBatch, DBIterator, ColumnFamily};
+#[cfg(test)]
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::iter::Peekable;
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+
+pub type DBIteratorItem = (Box<[u8]>, Box<[u8]>);
+
+/// Pending writes, keyed by column family name and then by key. A value of
+/// `None` represents a pending delete.
+type PendingWrites = HashMap<&'static str, BTreeMap<Box<[u8]>, Option<Box<[u8]>>>>;
+
+lazy_static! {
+    /// Held while a buffer checks its watched keys and commits, so that no
+    /// other commit can change a watched key in between. Shared by every
+    /// database, since commits are quick.
+    static ref COMMIT_LOCK: Mutex<()> = Mutex::new(());
+}
+
+#[cfg(test)]
+thread_local! {
+    /// If set, the number of writes that a commit on this thread can make
+    /// before a crash is simulated, by failing the commit there. Used to
+    /// check that a failure partway through a commit never leaves column
+    /// families out of step.
+    pub static CRASH_AFTER_WRITES: Cell<Option<usize>> = Cell::new(None);
+}
+
+/// A set of writes that should be applied together.
+///
+/// Every logical operation in the managers stages all of its writes, across
+/// all of the column families it touches, into a single batch. The batch is
+/// only handed to the `WriteBuffer` once the operation has succeeded, so an
+/// operation either applies in full or not at all.
+#[derive(Debug, Default)]
+pub struct Batch {
+    writes: Vec<(&'static str, Box<[u8]>, Option<Box<[u8]>>)>,
+}
+
+impl Batch {
+    pub fn put_cf(&mut self, cf: &'static str, key: Box<[u8]>, value: Box<[u8]>) {
+        self.writes.push((cf, key, Some(value)));
+    }
+
+    pub fn delete_cf(&mut self, cf: &'static str, key: Box<[u8]>) {
+        self.writes.push((cf, key, None));
+    }
+
+    /// Gets the last write staged for a key, if there is one. A value of
+    /// `Some(None)` represents a staged delete.
+    pub fn get_cf(&self, cf: &str, key: &[u8]) -> Option<Option<&[u8]>> {
+        self.writes
+            .iter()
+            .rev()
+            .find(|&&(write_cf, ref write_key, _)| write_cf == cf && &write_key[..] == key)
+            .map(|&(_, _, ref value)| value.as_ref().map(|value| &value[..]))
+    }
+}
+
+#[cfg(test)]
+fn simulate_crash() -> Result<(), Error> {
+    CRASH_AFTER_WRITES.with(|crash_after_writes| {
+        match crash_after_writes.get() {
+            Some(0) => {
+                crash_after_writes.set(None);
+                Err(Error::Unexpected("Simulated crash".to_string()))
+            },
+            Some(remaining) => {
+                crash_after_writes.set(Some(remaining - 1));
+                Ok(())
+            },
+            None => Ok(())
+        }
+    })
+}
+
+#[cfg(not(test))]
+fn simulate_crash() -> Result<(), Error> {
+    Ok(())
+}
+
+/// Buffers writes on top of the database until they are committed.
+///
+/// Reads made through the buffer see its pending writes, so a transaction
+/// can read back what it has written. Nothing reaches the database until
+/// `commit` is called, at which point all of the pending writes are applied
+/// in a single atomic rocksdb write batch. Clones share the same pending
+/// writes.
+///
+/// Buffers are not isolated from each other, but keys can be watched with
+/// `watch_cf`, in which case `commit` fails if they were changed by another
+/// commit after they were watched.
+#[derive(Clone, Debug)]
+pub struct WriteBuffer {
+    db: Arc<DB>,
+    pending: Arc<Mutex<PendingWrites>>,
+    /// The values that watched keys had in the database when they were
+    /// watched, in the same layout as the pending writes.
+    watched: Arc<Mutex<PendingWrites>>,
+}
+
+impl WriteBuffer {
+    pub fn new(db: Arc<DB>) -> Self {
+        WriteBuffer {
+            db: db,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            watched: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn cf_handle(&self, cf: &str) -> ColumnFamily {
+        self.db.cf_handle(cf).unwrap()
+    }
+
+    pub fn get_cf(&self, cf: &'static str, key: &[u8]) -> Result<Option<Box<[u8]>>, Error> {
+        {
+            let pending = self.pending.lock().unwrap();
+
+            if let Some(value) = pending.get(cf).and_then(|writes| writes.get(key)) {
+                return Ok(value.clone());
+            }
+        }
+
+        match self.db.get_cf(self.cf_handle(cf), key)? {
+            Some(value) => Ok(Some(value.to_vec().into_boxed_slice())),
+            None => Ok(None),
+        }
+    }
+
+    /// Iterates over a column family in key order, starting at `from`.
+    pub fn iterator_cf(&self, cf: &'static str, from: &[u8]) -> Result<BufferedIterator, Error> {
+        let db_iterator = self.db.iterator_cf(self.cf_handle(cf), IteratorMode::From(from, Direction::Forward))?;
+
+        Ok(BufferedIterator {
+            pending: self.pending.clone(),
+            cf: cf,
+            db_iterator: db_iterator.peekable(),
+            pending_item: None,
+            pending_bound: Bound::Included(from.to_vec().into_boxed_slice()),
+            reverse: false,
+        })
+    }
+
+    /// Iterates over a column family in reverse key order, starting at the
+    /// last key before `before`.
+    pub fn reverse_iterator_cf(&self, cf: &'static str, before: &[u8]) -> Result<BufferedIterator, Error> {
+        let handle = self.cf_handle(cf);
+        let mut db_iterator = self.db.iterator_cf(handle, IteratorMode::From(before, Direction::Reverse))?.peekable();
+
+        if db_iterator.peek().is_none() {
+            // Seeking past the last key leaves the iterator invalid, rather
+            // than on the last key, so start from the end instead
+            db_iterator = self.db.iterator_cf(handle, IteratorMode::End)?.peekable();
+        }
+
+        // The seek can land on a key at or after `before`
+        while db_iterator.peek().map_or(false, |&(ref key, _)| &key[..] >= before) {
+            db_iterator.next();
+        }
+
+        Ok(BufferedIterator {
+            pending: self.pending.clone(),
+            cf: cf,
+            db_iterator: db_iterator,
+            pending_item: None,
+            pending_bound: Bound::Excluded(before.to_vec().into_boxed_slice()),
+            reverse: true,
+        })
+    }
+
+    /// Adds the writes in a batch to the pending writes.
+    pub fn write(&self, batch: Batch) {
+        let mut pending = self.pending.lock().unwrap();
+
+        for (cf, key, value) in batch.writes {
+            pending.entry(cf).or_insert_with(BTreeMap::new).insert(key, value);
+        }
+    }
+
+    /// Watches a key, so that `commit` fails if another commit changes it
+    /// first. Keys that already have a pending write in this buffer were
+    /// read from it rather than the database, so they're left alone, as are
+    /// keys that are already being watched.
+    pub fn watch_cf(&self, cf: &'static str, key: &[u8]) -> Result<(), Error> {
+        if self.pending.lock().unwrap().get(cf).map_or(false, |writes| writes.contains_key(key)) {
+            return Ok(());
+        }
+
+        let mut watched = self.watched.lock().unwrap();
+        let watched_values = watched.entry(cf).or_insert_with(BTreeMap::new);
+
+        if !watched_values.contains_key(key) {
+            let value = self.db.get_cf(self.cf_handle(cf), key)?.map(|value| value.to_vec().into_boxed_slice());
+            watched_values.insert(key.to_vec().into_boxed_slice(), value);
+        }
+
+        Ok(())
+    }
+
+    /// Atomically applies all of the pending writes to the database.
+    ///
+    /// # Errors
+    /// Returns `Error::Conflict` if any watched key has changed since it was
+    /// watched, in which case nothing is written.
+    pub fn commit(&self) -> Result<(), Error> {
+        let _commit_lock = COMMIT_LOCK.lock().unwrap();
+        let mut pending = self.pending.lock().unwrap();
+        let mut watched = self.watched.lock().unwrap();
+
+        for (cf, watched_values) in watched.iter() {
+            let handle = self.cf_handle(cf);
+
+            for (key, value) in watched_values.iter() {
+                let current_value = self.db.get_cf(handle, key)?;
+
+                if current_value.as_ref().map(|value| &value[..]) != value.as_ref().map(|value| &value[..]) {
+                    return Err(Error::Conflict);
+                }
+            }
+        }
+
+        let mut batch = WriteBatch::default();
+
+        for (cf, writes) in pending.iter() {
+            let handle = self.cf_handle(cf);
+
+            for (key, value) in writes.iter() {
+                simulate_crash()?;
+
+                match *value {
+                    Some(ref value) => batch.put_cf(handle, key, value)?,
+                    None => batch.delete_cf(handle, key)?,
+                }
+            }
+        }
+
+        self.db.write(batch)?;
+        pending.clear();
+        watched.clear();
+        Ok(())
+    }
+
+    /// Discards all of the pending writes, and stops watching any keys.
+    pub fn rollback(&self) {
+        self.pending.lock().unwrap().clear();
+        self.watched.lock().unwrap().clear();
+    }
+}
+
+/// An iterator over a column family that merges in pending writes.
+///
+/// Iteration reflects the buffer as of when the iterator was created: the
+/// database is read from a snapshot taken then, and pending writes made
+/// since may or may not be seen. Pending writes are looked up one key at a
+/// time rather than copied up front, so creating an iterator is cheap even
+/// when there are a lot of them.
+pub struct BufferedIterator {
+    pending: Arc<Mutex<PendingWrites>>,
+    cf: &'static str,
+    db_iterator: Peekable<DBIterator>,
+    pending_item: Option<(Box<[u8]>, Option<Box<[u8]>>)>,
+    /// The bound that the next pending write must be after, or before if
+    /// iterating in reverse.
+    pending_bound: Bound<Box<[u8]>>,
+    reverse: bool,
+}
+
+impl BufferedIterator {
+    fn fill_pending_item(&mut self) {
+        if self.pending_item.is_some() {
+            return;
+        }
+
+        let pending = self.pending.lock().unwrap();
+
+        if let Some(writes) = pending.get(self.cf) {
+            let bound = match self.pending_bound {
+                Bound::Included(ref key) => Bound::Included(&key[..]),
+                Bound::Excluded(ref key) => Bound::Excluded(&key[..]),
+                Bound::Unbounded => Bound::Unbounded,
+            };
+
+            let item = if self.reverse {
+                writes.range::<[u8], _>((Bound::Unbounded, bound)).next_back()
+            } else {
+                writes.range::<[u8], _>((bound, Bound::Unbounded)).next()
+            };
+
+            self.pending_item = item.map(|(key, value)| (key.clone(), value.clone()));
+        }
+    }
+}
+
+impl Iterator for BufferedIterator {
+    type Item = DBIteratorItem;
+
+    fn next(&mut self) -> Option<DBIteratorItem> {
+        loop {
+            self.fill_pending_item();
+
+            // `Less` means that the database item comes first
+            let ordering = match (self.db_iterator.peek(), &self.pending_item) {
+                (None, &None) => return None,
+                (Some(_), &None) => Ordering::Less,
+                (None, &Some(_)) => Ordering::Greater,
+                (Some(&(ref db_key, _)), &Some((ref pending_key, _))) if self.reverse => pending_key.cmp(db_key),
+                (Some(&(ref db_key, _)), &Some((ref pending_key, _))) => db_key.cmp(pending_key),
+            };
+
+            if ordering == Ordering::Less {
+                return self.db_iterator.next();
+            } else if ordering == Ordering::Equal {
+                // The pending write shadows the value in the database
+                self.db_iterator.next();
+            }
+
+            let (key, value) = self.pending_item.take().unwrap();
+            self.pending_bound = Bound::Excluded(key.clone());
+
+            // Skip over pending deletes
+            if let Some(value) = value {
+                return Some((key, value));
+            }
+        }
+    }
+}
@@ -1,20 +1,25 @@
-use super::super::{Datastore, Transaction, VertexQuery, EdgeQuery, QueryTypeConverter};
+use super::super::{Datastore, Transaction, VertexQuery, EdgeQuery, EdgeOrder, QueryTypeConverter, VertexStream, EdgeStream, VertexMetadataStream};
 use models;
 use uuid::Uuid;
 use errors::Error;
-use util::{get_salted_hash, next_uuid};
+use util::{get_salted_hash, next_uuid, edge_as_of, expiry_datetime, check_datetime};
 use serde_json::Value as JsonValue;
-use chrono::{UTC};
-use rocksdb::{DB, Options, WriteBatch, DBCompactionStyle};
+use chrono::{DateTime, Duration, UTC};
+use rocksdb::{DB, Options, DBCompactionStyle};
 use std::sync::Arc;
 use std::usize;
 use std::i32;
 use std::u64;
 use super::managers::*;
+use super::buffer::{Batch, WriteBuffer};
+use super::models::{ExpiringItem, VertexValue, EdgeValue};
 use core::fmt::Debug;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::iter;
+use std::mem;
+use std::cmp::Ordering;
 
-const CF_NAMES: [&'static str; 9] = [
+pub const CF_NAMES: [&'static str; 32] = [
     "accounts:v1",
     "vertices:v1",
     "edges:v1",
@@ -23,9 +28,44 @@ const CF_NAMES: [&'static str; 9] = [
     "global_metadata:v1",
     "account_metadata:v1",
     "vertex_metadata:v1",
-    "edge_metadata:v1"
+    "edge_metadata:v1",
+    "vertex_owners:v1",
+    "vertex_types:v1",
+    "edge_weight_ranges:v1",
+    "reversed_edge_weight_ranges:v1",
+    "vertex_metadata_indexes:v1",
+    "vertex_metadata_index_values:v1",
+    "unique_constraints:v1",
+    "unique_values:v1",
+    "edge_created_datetimes:v1",
+    "edge_created_ranges:v1",
+    "reversed_edge_created_ranges:v1",
+    "edge_history_types:v1",
+    "edge_history:v1",
+    "reversed_edge_history:v1",
+    "edge_version_numbers:v1",
+    "vertex_metadata_version_numbers:v1",
+    "vertex_metadata_names:v1",
+    "edge_metadata_names:v1",
+    "expiries:v1",
+    "expiry_ranges:v1",
+    "global_metadata_version_numbers:v1",
+    "account_metadata_version_numbers:v1",
+    "edge_metadata_version_numbers:v1"
 ];
 
+/// The number of column families that databases were originally created
+/// with. Column families after these in `CF_NAMES` were added later, and are
+/// created and backfilled when an older database is opened.
+const ORIGINAL_CF_COUNT: usize = 9;
+
+/// How many writes to make per commit when backfilling a column family.
+const BACKFILL_BATCH_SIZE: usize = 10000;
+
+/// The key in the default column family that holds the name of the first
+/// column family still waiting to be backfilled, if any.
+pub const PENDING_BACKFILL_KEY: &'static [u8] = b"pending_backfill";
+
 fn get_options(max_open_files: Option<i32>) -> Options {
     // Current tuning based off of the total ordered example, flash
     // storage example on
@@ -48,6 +88,462 @@ fn get_options(max_open_files: Option<i32>) -> Options {
     opts
 }
 
+/// Inconsistencies found between the column families of a rocksdb
+/// datastore.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConsistencyReport {
+    /// `edge_ranges:v1` entries that do not match an edge.
+    pub orphaned_edge_ranges: Vec<(models::EdgeKey, DateTime<UTC>)>,
+    /// `reversed_edge_ranges:v1` entries that do not match an edge.
+    pub orphaned_reversed_edge_ranges: Vec<(models::EdgeKey, DateTime<UTC>)>,
+    /// Edges that are missing their `edge_ranges:v1` entry.
+    pub missing_edge_ranges: Vec<models::EdgeKey>,
+    /// Edges that are missing their `reversed_edge_ranges:v1` entry.
+    pub missing_reversed_edge_ranges: Vec<models::EdgeKey>,
+    /// `edge_weight_ranges:v1` entries that do not match an edge.
+    pub orphaned_edge_weight_ranges: Vec<(models::EdgeKey, models::Weight)>,
+    /// `reversed_edge_weight_ranges:v1` entries that do not match an edge.
+    pub orphaned_reversed_edge_weight_ranges: Vec<(models::EdgeKey, models::Weight)>,
+    /// Edges that are missing their `edge_weight_ranges:v1` entry.
+    pub missing_edge_weight_ranges: Vec<models::EdgeKey>,
+    /// Edges that are missing their `reversed_edge_weight_ranges:v1` entry.
+    pub missing_reversed_edge_weight_ranges: Vec<models::EdgeKey>,
+    /// Edges that are missing their `edge_created_datetimes:v1` entry.
+    pub missing_edge_created_datetimes: Vec<models::EdgeKey>,
+    /// `edge_created_datetimes:v1` entries that do not match an edge.
+    pub orphaned_edge_created_datetimes: Vec<models::EdgeKey>,
+    /// `edge_created_ranges:v1` entries that do not match an edge.
+    pub orphaned_edge_created_ranges: Vec<(models::EdgeKey, DateTime<UTC>)>,
+    /// `reversed_edge_created_ranges:v1` entries that do not match an edge.
+    pub orphaned_reversed_edge_created_ranges: Vec<(models::EdgeKey, DateTime<UTC>)>,
+    /// Edges that are missing their `edge_created_ranges:v1` entry.
+    pub missing_edge_created_ranges: Vec<models::EdgeKey>,
+    /// Edges that are missing their `reversed_edge_created_ranges:v1` entry.
+    pub missing_reversed_edge_created_ranges: Vec<models::EdgeKey>,
+    /// Vertices that are missing their `vertex_owners:v1` entry.
+    pub missing_vertex_owners: Vec<Uuid>,
+    /// `vertex_owners:v1` entries, as `(owner_id, vertex_id)`, that do not
+    /// match a vertex.
+    pub orphaned_vertex_owners: Vec<(Uuid, Uuid)>,
+    /// Vertices that are missing their `vertex_types:v1` entry.
+    pub missing_vertex_types: Vec<Uuid>,
+    /// `vertex_types:v1` entries that do not match a vertex.
+    pub orphaned_vertex_types: Vec<(models::Type, Uuid)>,
+    /// Indexed vertex metadata that is missing its
+    /// `vertex_metadata_index_values:v1` entry, as `(index_id, vertex_id)`.
+    pub missing_vertex_metadata_index_values: Vec<(Uuid, Uuid)>,
+    /// `vertex_metadata_index_values:v1` entries, as `(index_id,
+    /// encoded_value, vertex_id)`, that do not match a vertex's metadata.
+    pub orphaned_vertex_metadata_index_values: Vec<(Uuid, String, Uuid)>,
+    /// Vertex metadata under a unique constraint that is missing its
+    /// `unique_values:v1` entry, as `(constraint_id, vertex_id)`.
+    pub missing_unique_values: Vec<(Uuid, Uuid)>,
+    /// `unique_values:v1` entries, as `(constraint_id, encoded_value,
+    /// vertex_id)`, that do not match a vertex's metadata.
+    pub orphaned_unique_values: Vec<(Uuid, String, Uuid)>,
+    /// Vertex metadata, as `(vertex_id, name)`, that is missing its
+    /// `vertex_metadata_names:v1` entry.
+    pub missing_vertex_metadata_names: Vec<(Uuid, String)>,
+    /// `vertex_metadata_names:v1` entries, as `(vertex_id, name)`, that do
+    /// not match a vertex's metadata.
+    pub orphaned_vertex_metadata_names: Vec<(Uuid, String)>,
+    /// Edge metadata that is missing its `edge_metadata_names:v1` entry.
+    pub missing_edge_metadata_names: Vec<(models::EdgeKey, String)>,
+    /// `edge_metadata_names:v1` entries that do not match an edge's
+    /// metadata.
+    pub orphaned_edge_metadata_names: Vec<(models::EdgeKey, String)>,
+    /// Edges whose outbound or inbound vertex no longer exists.
+    pub orphaned_edges: Vec<models::EdgeKey>,
+    /// Account metadata whose account no longer exists.
+    pub orphaned_account_metadata: Vec<(Uuid, String)>,
+    /// Vertex metadata whose vertex no longer exists.
+    pub orphaned_vertex_metadata: Vec<(Uuid, String)>,
+    /// Edge metadata whose edge no longer exists.
+    pub orphaned_edge_metadata: Vec<(models::EdgeKey, String)>,
+}
+
+impl ConsistencyReport {
+    /// Returns whether no inconsistencies were found.
+    pub fn is_consistent(&self) -> bool {
+        *self == ConsistencyReport::default()
+    }
+}
+
+/// Opens an existing database, returning it along with how many of the
+/// column families in `CF_NAMES` it has.
+fn open_existing(opts: &Options, path: &str) -> Option<(DB, usize)> {
+    for cf_count in (ORIGINAL_CF_COUNT..CF_NAMES.len() + 1).rev() {
+        if let Ok(db) = DB::open_cf(opts, path, &CF_NAMES[..cf_count]) {
+            return Some((db, cf_count));
+        }
+    }
+
+    None
+}
+
+/// Gets the index in `CF_NAMES` of the first column family that's still
+/// waiting to be backfilled, if a backfill was interrupted.
+fn get_pending_backfill(db: &DB) -> Result<Option<usize>, Error> {
+    match db.get(PENDING_BACKFILL_KEY)? {
+        Some(value) => {
+            match CF_NAMES.iter().position(|cf_name| cf_name.as_bytes() == &*value) {
+                Some(cf_index) => Ok(Some(cf_index)),
+                None => Err(Error::Unexpected(format!("Unknown pending backfill: {}", String::from_utf8_lossy(&*value))))
+            }
+        },
+        None => Ok(None)
+    }
+}
+
+/// Counts a write to a backfill batch, and commits the batch once it's full.
+fn count_backfill_write(buffer: &WriteBuffer, batch: &mut Batch, batch_size: &mut usize) -> Result<(), Error> {
+    *batch_size += 1;
+
+    if *batch_size == BACKFILL_BATCH_SIZE {
+        buffer.write(mem::replace(batch, Batch::default()));
+        buffer.commit()?;
+        *batch_size = 0;
+    }
+
+    Ok(())
+}
+
+/// Converts a stored version of an edge, which has no weight or version
+/// number if the edge was deleted, into an `EdgeVersion`.
+fn to_edge_version(value: Option<(models::Weight, u64)>, datetime: DateTime<UTC>) -> models::EdgeVersion {
+    models::EdgeVersion::new(value.map(|(weight, _)| weight), value.map(|(_, version)| version), datetime)
+}
+
+/// Returns the lowest vertex ID that should be included in a page of results
+/// that starts after `start_id`, or `None` if there can't be any results.
+fn first_page_uuid(start_id: Option<Uuid>) -> Option<Uuid> {
+    match start_id {
+        // If we get an error back, it's because `start_id` is the maximum
+        // possible value. We know that no vertices exist whose ID is greater
+        // than the maximum possible value, so there are no results.
+        Some(start_id) => next_uuid(start_id).ok(),
+        None => Some(Uuid::default())
+    }
+}
+
+/// Gets the encoded value that a vertex's metadata should have in an index,
+/// or `None` if it shouldn't be in the index.
+fn get_indexed_value(vertex_metadata_manager: &VertexMetadataManager,
+                     index: &models::VertexMetadataIndex,
+                     id: Uuid)
+                     -> Result<Option<String>, Error> {
+    match vertex_metadata_manager.get(id, &index.name[..])? {
+        Some(value) => match index.indexed_value(&value) {
+            Some(value) => Ok(Some(encode_metadata_value(value)?)),
+            None => Ok(None)
+        },
+        None => Ok(None)
+    }
+}
+
+/// Returns whether an edge exists with the given update datetime.
+fn edge_matches(edge_manager: &EdgeManager,
+                outbound_id: Uuid,
+                t: &models::Type,
+                update_datetime: DateTime<UTC>,
+                inbound_id: Uuid)
+                -> Result<bool, Error> {
+    match edge_manager.get(outbound_id, t, inbound_id)? {
+        Some(value) => Ok(value.update_datetime == update_datetime),
+        None => Ok(false),
+    }
+}
+
+/// Gets when an edge was created. Edges that are missing their created
+/// datetime are treated as having been created when they were last updated.
+fn get_created_datetime(edge_created_datetime_manager: &EdgeCreatedDatetimeManager,
+                        outbound_id: Uuid,
+                        t: &models::Type,
+                        inbound_id: Uuid,
+                        update_datetime: DateTime<UTC>)
+                        -> Result<DateTime<UTC>, Error> {
+    Ok(edge_created_datetime_manager.get(outbound_id, t, inbound_id)?.unwrap_or(update_datetime))
+}
+
+/// Returns whether an edge exists with the given created datetime.
+fn edge_created_matches(edge_manager: &EdgeManager,
+                        edge_created_datetime_manager: &EdgeCreatedDatetimeManager,
+                        outbound_id: Uuid,
+                        t: &models::Type,
+                        created_datetime: DateTime<UTC>,
+                        inbound_id: Uuid)
+                        -> Result<bool, Error> {
+    match edge_manager.get(outbound_id, t, inbound_id)? {
+        Some(value) => {
+            let expected_created_datetime = get_created_datetime(edge_created_datetime_manager, outbound_id, t, inbound_id, value.update_datetime)?;
+            Ok(expected_created_datetime == created_datetime)
+        },
+        None => Ok(false),
+    }
+}
+
+fn edge_weight_matches(edge_manager: &EdgeManager,
+                       outbound_id: Uuid,
+                       t: &models::Type,
+                       weight: models::Weight,
+                       inbound_id: Uuid)
+                       -> Result<bool, Error> {
+    match edge_manager.get(outbound_id, t, inbound_id)? {
+        Some(value) => Ok(value.weight == weight),
+        None => Ok(false),
+    }
+}
+
+/// A set operation to combine the results of two queries with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SetOperation {
+    Union,
+    Intersection,
+    Difference
+}
+
+/// Combines two lists of items that are sorted by key, and have no repeated
+/// keys, by walking through them together. Where both lists have an item
+/// with the same key, the item from `left` is kept.
+fn merge_sorted<K: Ord, V>(left: Vec<(K, V)>, right: Vec<(K, V)>, operation: SetOperation) -> Vec<(K, V)> {
+    let mut merged: Vec<(K, V)> = Vec::new();
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+
+    loop {
+        let ordering = match (left.peek(), right.peek()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(&(ref left_key, _)), Some(&(ref right_key, _))) => left_key.cmp(right_key)
+        };
+
+        match ordering {
+            Ordering::Less => {
+                let item = left.next().unwrap();
+
+                if operation != SetOperation::Intersection {
+                    merged.push(item);
+                }
+            },
+            Ordering::Greater => {
+                let item = right.next().unwrap();
+
+                if operation == SetOperation::Union {
+                    merged.push(item);
+                }
+            },
+            Ordering::Equal => {
+                let item = left.next().unwrap();
+                right.next();
+
+                if operation != SetOperation::Difference {
+                    merged.push(item);
+                }
+            }
+        }
+    }
+
+    merged
+}
+
+/// Stages the deletion of everything that has expired as of `now` in a
+/// buffer. Expired edges get a version without a weight at the datetime they
+/// expired, if their type has history enabled.
+///
+/// A compaction filter can't do this, since it would drop entries from one
+/// column family at a time, leaving the others out of step.
+fn delete_expired(buffer: &WriteBuffer, secure_uuids: bool, now: DateTime<UTC>) -> Result<(), Error> {
+    let vertex_manager = VertexManager::new(buffer.clone(), secure_uuids);
+    let edge_manager = EdgeManager::new(buffer.clone());
+    let vertex_metadata_manager = VertexMetadataManager::new(buffer.clone());
+    let edge_metadata_manager = EdgeMetadataManager::new(buffer.clone());
+    let expiry_manager = ExpiryManager::new(buffer.clone());
+    let expiry_range_manager = ExpiryRangeManager::new(buffer.clone());
+
+    let expired: Vec<(DateTime<UTC>, ExpiringItem)> = expiry_range_manager
+        .iterate_for_expired(now)?
+        .collect::<Result<_, Error>>()?;
+
+    for (expiry, item) in expired {
+        let mut batch = Batch::default();
+
+        // Items can be deleted along with something that expired before
+        // them, e.g. the edges of an expired vertex
+        if expiry_manager.get(&item)? != Some(expiry) {
+            expiry_range_manager.delete(&mut batch, expiry, &item)?;
+            buffer.write(batch);
+            continue;
+        }
+
+        match item {
+            ExpiringItem::Vertex(id) => {
+                vertex_manager.delete(&mut batch, id)?;
+            },
+            ExpiringItem::Edge(outbound_id, ref t, inbound_id) => {
+                if !expire_edge(&edge_manager, &mut batch, outbound_id, t, inbound_id, expiry)? {
+                    expiry_manager.delete(&mut batch, &item)?;
+                }
+            },
+            ExpiringItem::VertexMetadata(id, ref name) => {
+                match vertex_manager.get(id)? {
+                    Some(value) => vertex_metadata_manager.delete(&mut batch, id, &value.t, &name[..])?,
+                    None => expiry_manager.delete(&mut batch, &item)?
+                }
+            },
+            ExpiringItem::EdgeMetadata(outbound_id, ref t, inbound_id, ref name) => {
+                edge_metadata_manager.delete(&mut batch, outbound_id, t, inbound_id, &name[..])?;
+            }
+        }
+
+        buffer.write(batch);
+    }
+
+    Ok(())
+}
+
+/// Stages the deletion of an expired edge, giving it a version without a
+/// weight at the datetime it expired, if its type has history enabled.
+/// Returns whether the edge existed.
+fn expire_edge(edge_manager: &EdgeManager,
+               batch: &mut Batch,
+               outbound_id: Uuid,
+               t: &models::Type,
+               inbound_id: Uuid,
+               expiry: DateTime<UTC>)
+               -> Result<bool, Error> {
+    match edge_manager.get(outbound_id, t, inbound_id)? {
+        Some(value) => {
+            edge_manager.delete(batch, outbound_id, t, inbound_id, value.update_datetime, value.weight)?;
+            edge_manager.set_history_version(batch, outbound_id, t, inbound_id, expiry, None)?;
+            Ok(true)
+        },
+        None => Ok(false)
+    }
+}
+
+/// Drops the items of an iterator that have expired, as decided by
+/// `has_expired`.
+fn remove_expired<'a, T: 'a, F>(iterator: Box<Iterator<Item = Result<T, Error>> + 'a>, has_expired: F) -> Box<Iterator<Item = Result<T, Error>> + 'a>
+    where F: Fn(&T) -> Result<bool, Error> + 'a
+{
+    let filtered = iterator.filter_map(move |item| {
+        let value = match item {
+            Ok(value) => value,
+            Err(err) => return Some(Err(err))
+        };
+
+        match has_expired(&value) {
+            Ok(true) => None,
+            Ok(false) => Some(Ok(value)),
+            Err(err) => Some(Err(err))
+        }
+    });
+
+    Box::new(filtered)
+}
+
+/// Reads items while skipping the ones that have expired. Expired items
+/// stay in the database until `delete_expired` sweeps them up, so reads have
+/// to skip them, along with everything that belongs to them.
+#[derive(Clone)]
+struct ExpiryFilter {
+    buffer: WriteBuffer,
+    now: DateTime<UTC>,
+    /// Whether anything has expired at all. Usually nothing has, which saves
+    /// looking up the expiry of every item that's read.
+    any_expired: bool
+}
+
+impl ExpiryFilter {
+    fn new(buffer: WriteBuffer) -> Result<Self, Error> {
+        let now = UTC::now();
+        let any_expired = ExpiryRangeManager::new(buffer.clone()).iterate_for_expired(now)?.next().is_some();
+
+        Ok(ExpiryFilter {
+            buffer: buffer,
+            now: now,
+            any_expired: any_expired
+        })
+    }
+
+    /// Gets when an item expired, or `None` if it hasn't.
+    fn expired_at(&self, item: &ExpiringItem) -> Result<Option<DateTime<UTC>>, Error> {
+        if !self.any_expired {
+            return Ok(None);
+        }
+
+        match ExpiryManager::new(self.buffer.clone()).get(item)? {
+            Some(expiry) if expiry <= self.now => Ok(Some(expiry)),
+            _ => Ok(None)
+        }
+    }
+
+    fn vertex_has_expired(&self, id: Uuid) -> Result<bool, Error> {
+        Ok(self.expired_at(&ExpiringItem::Vertex(id))?.is_some())
+    }
+
+    /// Returns whether an edge, or either of its vertices, has expired.
+    fn edge_has_expired(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Result<bool, Error> {
+        Ok(self.expired_at(&ExpiringItem::Edge(outbound_id, t.clone(), inbound_id))?.is_some() ||
+           self.vertex_has_expired(outbound_id)? || self.vertex_has_expired(inbound_id)?)
+    }
+
+    /// Like `edge_has_expired`, but for an edge read from an index, which
+    /// has its IDs flipped around if the index is reversed.
+    fn indexed_edge_has_expired(&self, first_id: Uuid, t: &models::Type, second_id: Uuid, reversed: bool) -> Result<bool, Error> {
+        if reversed {
+            self.edge_has_expired(second_id, t, first_id)
+        } else {
+            self.edge_has_expired(first_id, t, second_id)
+        }
+    }
+
+    /// Returns whether a vertex's metadata, or the vertex itself, has
+    /// expired.
+    fn vertex_metadata_has_expired(&self, id: Uuid, name: &str) -> Result<bool, Error> {
+        Ok(self.expired_at(&ExpiringItem::VertexMetadata(id, name.to_string()))?.is_some() || self.vertex_has_expired(id)?)
+    }
+
+    /// Returns whether an edge's metadata, or the edge itself, has expired.
+    fn edge_metadata_has_expired(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid, name: &str) -> Result<bool, Error> {
+        Ok(self.expired_at(&ExpiringItem::EdgeMetadata(outbound_id, t.clone(), inbound_id, name.to_string()))?.is_some() ||
+           self.edge_has_expired(outbound_id, t, inbound_id)?)
+    }
+
+    fn get_vertex(&self, id: Uuid) -> Result<Option<VertexValue>, Error> {
+        if self.vertex_has_expired(id)? {
+            return Ok(None);
+        }
+
+        VertexManager::new(self.buffer.clone(), false).get(id)
+    }
+
+    fn get_edge(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Result<Option<EdgeValue>, Error> {
+        if self.edge_has_expired(outbound_id, t, inbound_id)? {
+            return Ok(None);
+        }
+
+        EdgeManager::new(self.buffer.clone()).get(outbound_id, t, inbound_id)
+    }
+
+    fn get_vertex_metadata(&self, id: Uuid, name: &str) -> Result<Option<JsonValue>, Error> {
+        if self.vertex_metadata_has_expired(id, name)? {
+            return Ok(None);
+        }
+
+        VertexMetadataManager::new(self.buffer.clone()).get(id, name)
+    }
+
+    fn get_edge_metadata(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid, name: &str) -> Result<Option<JsonValue>, Error> {
+        if self.edge_metadata_has_expired(outbound_id, t, inbound_id, name)? {
+            return Ok(None);
+        }
+
+        EdgeMetadataManager::new(self.buffer.clone()).get(outbound_id, t, inbound_id, name)
+    }
+}
+
 /// A datastore that is backed by rocksdb.
 #[derive(Debug)]
 pub struct RocksdbDatastore {
@@ -70,23 +566,52 @@ impl RocksdbDatastore {
     pub fn new(path: &str, max_open_files: Option<i32>, secure_uuids: bool) -> Result<RocksdbDatastore, Error> {
         let opts = get_options(max_open_files);
 
-        let db = match DB::open_cf(&opts, path, &CF_NAMES) {
-            Ok(db) => db,
-            Err(_) => {
+        // Column families are backfilled in separate commits after they're
+        // created, so a marker is written first, and only cleared once
+        // they're all filled in. If the backfill is interrupted, it's
+        // restarted from the marker the next time the database is opened.
+        let (db, backfill_from) = match open_existing(&opts, path) {
+            Some((mut db, cf_count)) => {
+                let backfill_from = match get_pending_backfill(&db)? {
+                    Some(cf_index) => cf_index,
+                    None if cf_count < CF_NAMES.len() => {
+                        db.put(PENDING_BACKFILL_KEY, CF_NAMES[cf_count].as_bytes())?;
+                        cf_count
+                    },
+                    None => CF_NAMES.len()
+                };
+
+                for cf_name in &CF_NAMES[cf_count..] {
+                    db.create_cf(cf_name, &opts)?;
+                }
+
+                (db, backfill_from)
+            },
+            None => {
                 let mut db = DB::open(&opts, path)?;
 
                 for cf_name in &CF_NAMES {
                     db.create_cf(cf_name, &opts)?;
                 }
 
-                db
+                (db, CF_NAMES.len())
             }
         };
 
-        Ok(RocksdbDatastore {
+        let datastore = RocksdbDatastore {
             db: Arc::new(db),
             secure_uuids: secure_uuids
-        })
+        };
+
+        if backfill_from < CF_NAMES.len() {
+            for cf_name in &CF_NAMES[backfill_from..] {
+                datastore.backfill(cf_name)?;
+            }
+
+            datastore.db.delete(PENDING_BACKFILL_KEY)?;
+        }
+
+        Ok(datastore)
     }
 
     /// Runs a repair operation on the rocksdb database.
@@ -100,32 +625,604 @@ impl RocksdbDatastore {
         DB::repair(opts, path)?;
         Ok(())
     }
+
+    /// Checks that braid's column families agree with each other.
+    ///
+    /// This walks every vertex, edge, index entry and owned metadata entry,
+    /// so it can take a while on large databases. Writes made while it is
+    /// running may show up as inconsistencies.
+    pub fn verify(&self) -> Result<ConsistencyReport, Error> {
+        let buffer = self.buffer();
+        let account_manager = AccountManager::new(buffer.clone(), self.secure_uuids);
+        let vertex_manager = VertexManager::new(buffer.clone(), self.secure_uuids);
+        let edge_manager = EdgeManager::new(buffer.clone());
+        let edge_range_manager = EdgeRangeManager::new(buffer.clone());
+        let reversed_edge_range_manager = EdgeRangeManager::new_reversed(buffer.clone());
+        let edge_weight_range_manager = EdgeWeightRangeManager::new(buffer.clone());
+        let reversed_edge_weight_range_manager = EdgeWeightRangeManager::new_reversed(buffer.clone());
+        let vertex_owner_manager = VertexOwnerManager::new(buffer.clone());
+        let vertex_type_manager = VertexTypeManager::new(buffer.clone());
+        let edge_created_datetime_manager = EdgeCreatedDatetimeManager::new(buffer.clone());
+        let edge_created_range_manager = EdgeRangeManager::new_created(buffer.clone());
+        let reversed_edge_created_range_manager = EdgeRangeManager::new_reversed_created(buffer.clone());
+        let mut report = ConsistencyReport::default();
+
+        for item in vertex_manager.iterate_all()? {
+            let (id, value) = item?;
+
+            if !vertex_owner_manager.exists(value.owner_id, id)? {
+                report.missing_vertex_owners.push(id);
+            }
+
+            if !vertex_type_manager.exists(&value.t, id)? {
+                report.missing_vertex_types.push(id);
+            }
+        }
+
+        for item in vertex_owner_manager.iterate_all()? {
+            let (owner_id, id) = item?;
+
+            match vertex_manager.get(id)? {
+                Some(ref value) if value.owner_id == owner_id => (),
+                _ => report.orphaned_vertex_owners.push((owner_id, id))
+            }
+        }
+
+        for item in vertex_type_manager.iterate_all()? {
+            let (t, id) = item?;
+
+            match vertex_manager.get(id)? {
+                Some(ref value) if value.t == t => (),
+                _ => report.orphaned_vertex_types.push((t, id))
+            }
+        }
+
+        for item in edge_manager.iterate_all()? {
+            let ((outbound_id, t, inbound_id), value) = item?;
+            let key = models::EdgeKey::new(outbound_id, t.clone(), inbound_id);
+
+            if !vertex_manager.exists(outbound_id)? || !vertex_manager.exists(inbound_id)? {
+                report.orphaned_edges.push(key.clone());
+            }
+
+            if !edge_range_manager.exists(outbound_id, &t, value.update_datetime, inbound_id)? {
+                report.missing_edge_ranges.push(key.clone());
+            }
+
+            if !reversed_edge_range_manager.exists(inbound_id, &t, value.update_datetime, outbound_id)? {
+                report.missing_reversed_edge_ranges.push(key.clone());
+            }
+
+            if !edge_weight_range_manager.exists(outbound_id, &t, value.weight, inbound_id)? {
+                report.missing_edge_weight_ranges.push(key.clone());
+            }
+
+            if !reversed_edge_weight_range_manager.exists(inbound_id, &t, value.weight, outbound_id)? {
+                report.missing_reversed_edge_weight_ranges.push(key.clone());
+            }
+
+            let created_datetime = match edge_created_datetime_manager.get(outbound_id, &t, inbound_id)? {
+                Some(created_datetime) => created_datetime,
+                None => {
+                    report.missing_edge_created_datetimes.push(key.clone());
+                    value.update_datetime
+                }
+            };
+
+            if !edge_created_range_manager.exists(outbound_id, &t, created_datetime, inbound_id)? {
+                report.missing_edge_created_ranges.push(key.clone());
+            }
+
+            if !reversed_edge_created_range_manager.exists(inbound_id, &t, created_datetime, outbound_id)? {
+                report.missing_reversed_edge_created_ranges.push(key);
+            }
+        }
+
+        for item in edge_created_datetime_manager.iterate_all()? {
+            let ((outbound_id, t, inbound_id), _) = item?;
+
+            if edge_manager.get(outbound_id, &t, inbound_id)?.is_none() {
+                report.orphaned_edge_created_datetimes.push(models::EdgeKey::new(outbound_id, t, inbound_id));
+            }
+        }
+
+        for item in edge_created_range_manager.iterate_all()? {
+            let ((outbound_id, t, created_datetime, inbound_id), _) = item?;
+
+            if !edge_created_matches(&edge_manager, &edge_created_datetime_manager, outbound_id, &t, created_datetime, inbound_id)? {
+                report.orphaned_edge_created_ranges.push((models::EdgeKey::new(outbound_id, t, inbound_id), created_datetime));
+            }
+        }
+
+        for item in reversed_edge_created_range_manager.iterate_all()? {
+            let ((inbound_id, t, created_datetime, outbound_id), _) = item?;
+
+            if !edge_created_matches(&edge_manager, &edge_created_datetime_manager, outbound_id, &t, created_datetime, inbound_id)? {
+                report.orphaned_reversed_edge_created_ranges.push((models::EdgeKey::new(outbound_id, t, inbound_id), created_datetime));
+            }
+        }
+
+        for item in edge_range_manager.iterate_all()? {
+            let ((outbound_id, t, update_datetime, inbound_id), _) = item?;
+
+            if !edge_matches(&edge_manager, outbound_id, &t, update_datetime, inbound_id)? {
+                report.orphaned_edge_ranges.push((models::EdgeKey::new(outbound_id, t, inbound_id), update_datetime));
+            }
+        }
+
+        for item in reversed_edge_range_manager.iterate_all()? {
+            let ((inbound_id, t, update_datetime, outbound_id), _) = item?;
+
+            if !edge_matches(&edge_manager, outbound_id, &t, update_datetime, inbound_id)? {
+                report.orphaned_reversed_edge_ranges.push((models::EdgeKey::new(outbound_id, t, inbound_id), update_datetime));
+            }
+        }
+
+        for item in edge_weight_range_manager.iterate_all()? {
+            let ((outbound_id, t, weight, inbound_id), _) = item?;
+
+            if !edge_weight_matches(&edge_manager, outbound_id, &t, weight, inbound_id)? {
+                report.orphaned_edge_weight_ranges.push((models::EdgeKey::new(outbound_id, t, inbound_id), weight));
+            }
+        }
+
+        for item in reversed_edge_weight_range_manager.iterate_all()? {
+            let ((inbound_id, t, weight, outbound_id), _) = item?;
+
+            if !edge_weight_matches(&edge_manager, outbound_id, &t, weight, inbound_id)? {
+                report.orphaned_reversed_edge_weight_ranges.push((models::EdgeKey::new(outbound_id, t, inbound_id), weight));
+            }
+        }
+
+        for item in AccountMetadataManager::new(buffer.clone()).iterate_all()? {
+            let ((owner_id, name), _) = item?;
+
+            if !account_manager.exists(owner_id)? {
+                report.orphaned_account_metadata.push((owner_id, name));
+            }
+        }
+
+        let vertex_metadata_manager = VertexMetadataManager::new(buffer.clone());
+        let vertex_metadata_name_manager = VertexMetadataNameManager::new(buffer.clone());
+
+        for item in vertex_metadata_manager.iterate_all()? {
+            let ((owner_id, name), _) = item?;
+
+            if !vertex_metadata_name_manager.exists(&name[..], owner_id)? {
+                report.missing_vertex_metadata_names.push((owner_id, name.clone()));
+            }
+
+            if !vertex_manager.exists(owner_id)? {
+                report.orphaned_vertex_metadata.push((owner_id, name));
+            }
+        }
+
+        for item in vertex_metadata_name_manager.iterate_all()? {
+            let (name, owner_id) = item?;
+
+            if vertex_metadata_manager.get(owner_id, &name[..])?.is_none() {
+                report.orphaned_vertex_metadata_names.push((owner_id, name));
+            }
+        }
+
+        let vertex_metadata_index_value_manager = VertexMetadataIndexValueManager::new(buffer.clone());
+        let mut vertex_metadata_indexes = HashMap::new();
+
+        for item in VertexMetadataIndexManager::new(buffer.clone()).iterate_all()? {
+            let (index_id, index) = item?;
+
+            for item in vertex_type_manager.iterate_for_range(&index.t, Uuid::default())? {
+                let (_, id) = item?;
+
+                if let Some(value) = get_indexed_value(&vertex_metadata_manager, &index, id)? {
+                    if !vertex_metadata_index_value_manager.exists(index_id, &value[..], id)? {
+                        report.missing_vertex_metadata_index_values.push((index_id, id));
+                    }
+                }
+            }
+
+            vertex_metadata_indexes.insert(index_id, index);
+        }
+
+        for item in vertex_metadata_index_value_manager.iterate_all()? {
+            let (index_id, value, id) = item?;
+
+            let matches = match vertex_metadata_indexes.get(&index_id) {
+                Some(index) => match vertex_manager.get(id)? {
+                    Some(ref vertex_value) if vertex_value.t == index.t => {
+                        get_indexed_value(&vertex_metadata_manager, index, id)?.map_or(false, |expected_value| expected_value == value)
+                    },
+                    _ => false
+                },
+                None => false
+            };
+
+            if !matches {
+                report.orphaned_vertex_metadata_index_values.push((index_id, value, id));
+            }
+        }
+
+        let unique_value_manager = UniqueValueManager::new(buffer.clone());
+        let mut unique_constraints = HashMap::new();
+
+        for item in UniqueConstraintManager::new(buffer.clone()).iterate_all()? {
+            let (constraint_id, constraint) = item?;
+
+            for item in vertex_type_manager.iterate_for_range(&constraint.t, Uuid::default())? {
+                let (_, id) = item?;
+
+                if let Some(value) = vertex_metadata_manager.get(id, &constraint.name[..])? {
+                    if unique_value_manager.get(constraint_id, &encode_metadata_value(&value)?[..])? != Some(id) {
+                        report.missing_unique_values.push((constraint_id, id));
+                    }
+                }
+            }
+
+            unique_constraints.insert(constraint_id, constraint);
+        }
+
+        for item in unique_value_manager.iterate_all()? {
+            let (constraint_id, value, id) = item?;
+
+            let matches = match unique_constraints.get(&constraint_id) {
+                Some(constraint) => match vertex_manager.get(id)? {
+                    Some(ref vertex_value) if vertex_value.t == constraint.t => {
+                        match vertex_metadata_manager.get(id, &constraint.name[..])? {
+                            Some(expected_value) => encode_metadata_value(&expected_value)? == value,
+                            None => false
+                        }
+                    },
+                    _ => false
+                },
+                None => false
+            };
+
+            if !matches {
+                report.orphaned_unique_values.push((constraint_id, value, id));
+            }
+        }
+
+        let edge_metadata_manager = EdgeMetadataManager::new(buffer.clone());
+        let edge_metadata_name_manager = EdgeMetadataNameManager::new(buffer.clone());
+
+        for item in edge_metadata_manager.iterate_all()? {
+            let ((outbound_id, t, inbound_id, name), _) = item?;
+
+            if !edge_metadata_name_manager.exists(&name[..], outbound_id, &t, inbound_id)? {
+                report.missing_edge_metadata_names.push((models::EdgeKey::new(outbound_id, t.clone(), inbound_id), name.clone()));
+            }
+
+            if edge_manager.get(outbound_id, &t, inbound_id)?.is_none() {
+                report.orphaned_edge_metadata.push((models::EdgeKey::new(outbound_id, t, inbound_id), name));
+            }
+        }
+
+        for item in edge_metadata_name_manager.iterate_all()? {
+            let (name, outbound_id, t, inbound_id) = item?;
+
+            if edge_metadata_manager.get(outbound_id, &t, inbound_id, &name[..])?.is_none() {
+                report.orphaned_edge_metadata_names.push((models::EdgeKey::new(outbound_id, t, inbound_id), name));
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Rebuilds the edge range, edge weight range, edge created range, vertex
+    /// owner, vertex type, vertex metadata and metadata name indexes, and the
+    /// unique constraint values, so that they match the edges, vertices and
+    /// metadata.
+    ///
+    /// Orphaned index entries are removed, and missing ones are recreated
+    /// from `edges:v1`, `vertices:v1`, `vertex_metadata:v1` and
+    /// `edge_metadata:v1`. Edges missing
+    /// their created datetime are given their last update datetime. Orphaned
+    /// edges and metadata are
+    /// primary data rather than indexes, so they are reported but left alone.
+    /// Returns the report from before the repairs were made.
+    pub fn repair_indexes(&self) -> Result<ConsistencyReport, Error> {
+        let report = self.verify()?;
+        let buffer = self.buffer();
+        let edge_manager = EdgeManager::new(buffer.clone());
+        let edge_range_manager = EdgeRangeManager::new(buffer.clone());
+        let reversed_edge_range_manager = EdgeRangeManager::new_reversed(buffer.clone());
+        let edge_weight_range_manager = EdgeWeightRangeManager::new(buffer.clone());
+        let reversed_edge_weight_range_manager = EdgeWeightRangeManager::new_reversed(buffer.clone());
+        let vertex_manager = VertexManager::new(buffer.clone(), self.secure_uuids);
+        let vertex_owner_manager = VertexOwnerManager::new(buffer.clone());
+        let vertex_type_manager = VertexTypeManager::new(buffer.clone());
+        let vertex_metadata_manager = VertexMetadataManager::new(buffer.clone());
+        let vertex_metadata_index_value_manager = VertexMetadataIndexValueManager::new(buffer.clone());
+        let edge_created_datetime_manager = EdgeCreatedDatetimeManager::new(buffer.clone());
+        let edge_created_range_manager = EdgeRangeManager::new_created(buffer.clone());
+        let reversed_edge_created_range_manager = EdgeRangeManager::new_reversed_created(buffer.clone());
+        let mut batch = Batch::default();
+
+        for id in &report.missing_vertex_owners {
+            if let Some(value) = vertex_manager.get(*id)? {
+                vertex_owner_manager.set(&mut batch, value.owner_id, *id)?;
+            }
+        }
+
+        for &(owner_id, id) in &report.orphaned_vertex_owners {
+            vertex_owner_manager.delete(&mut batch, owner_id, id)?;
+        }
+
+        for id in &report.missing_vertex_types {
+            if let Some(value) = vertex_manager.get(*id)? {
+                vertex_type_manager.set(&mut batch, &value.t, *id)?;
+            }
+        }
+
+        for &(ref t, id) in &report.orphaned_vertex_types {
+            vertex_type_manager.delete(&mut batch, t, id)?;
+        }
+
+        for &(ref key, update_datetime) in &report.orphaned_edge_ranges {
+            edge_range_manager.delete(&mut batch, key.outbound_id, &key.t, update_datetime, key.inbound_id)?;
+        }
+
+        for &(ref key, update_datetime) in &report.orphaned_reversed_edge_ranges {
+            reversed_edge_range_manager.delete(&mut batch, key.inbound_id, &key.t, update_datetime, key.outbound_id)?;
+        }
+
+        for key in &report.missing_edge_ranges {
+            if let Some(value) = edge_manager.get(key.outbound_id, &key.t, key.inbound_id)? {
+                edge_range_manager.set(&mut batch, key.outbound_id, &key.t, value.update_datetime, key.inbound_id, value.weight)?;
+            }
+        }
+
+        for key in &report.missing_reversed_edge_ranges {
+            if let Some(value) = edge_manager.get(key.outbound_id, &key.t, key.inbound_id)? {
+                reversed_edge_range_manager.set(&mut batch, key.inbound_id, &key.t, value.update_datetime, key.outbound_id, value.weight)?;
+            }
+        }
+
+        for &(ref key, weight) in &report.orphaned_edge_weight_ranges {
+            edge_weight_range_manager.delete(&mut batch, key.outbound_id, &key.t, weight, key.inbound_id)?;
+        }
+
+        for &(ref key, weight) in &report.orphaned_reversed_edge_weight_ranges {
+            reversed_edge_weight_range_manager.delete(&mut batch, key.inbound_id, &key.t, weight, key.outbound_id)?;
+        }
+
+        for key in &report.missing_edge_weight_ranges {
+            if let Some(value) = edge_manager.get(key.outbound_id, &key.t, key.inbound_id)? {
+                edge_weight_range_manager.set(&mut batch, key.outbound_id, &key.t, value.weight, key.inbound_id, value.update_datetime)?;
+            }
+        }
+
+        for key in &report.missing_reversed_edge_weight_ranges {
+            if let Some(value) = edge_manager.get(key.outbound_id, &key.t, key.inbound_id)? {
+                reversed_edge_weight_range_manager.set(&mut batch, key.inbound_id, &key.t, value.weight, key.outbound_id, value.update_datetime)?;
+            }
+        }
+
+        // The original created datetime of an edge missing it can't be
+        // recovered, so the last update datetime stands in for it
+        for key in &report.orphaned_edge_created_datetimes {
+            edge_created_datetime_manager.delete(&mut batch, key.outbound_id, &key.t, key.inbound_id)?;
+        }
+
+        for key in &report.missing_edge_created_datetimes {
+            if let Some(value) = edge_manager.get(key.outbound_id, &key.t, key.inbound_id)? {
+                edge_created_datetime_manager.set(&mut batch, key.outbound_id, &key.t, key.inbound_id, value.update_datetime)?;
+            }
+        }
+
+        for &(ref key, created_datetime) in &report.orphaned_edge_created_ranges {
+            edge_created_range_manager.delete(&mut batch, key.outbound_id, &key.t, created_datetime, key.inbound_id)?;
+        }
+
+        for &(ref key, created_datetime) in &report.orphaned_reversed_edge_created_ranges {
+            reversed_edge_created_range_manager.delete(&mut batch, key.inbound_id, &key.t, created_datetime, key.outbound_id)?;
+        }
+
+        for key in &report.missing_edge_created_ranges {
+            if let Some(value) = edge_manager.get(key.outbound_id, &key.t, key.inbound_id)? {
+                let created_datetime = get_created_datetime(&edge_created_datetime_manager, key.outbound_id, &key.t, key.inbound_id, value.update_datetime)?;
+                edge_created_range_manager.set(&mut batch, key.outbound_id, &key.t, created_datetime, key.inbound_id, value.weight)?;
+            }
+        }
+
+        for key in &report.missing_reversed_edge_created_ranges {
+            if let Some(value) = edge_manager.get(key.outbound_id, &key.t, key.inbound_id)? {
+                let created_datetime = get_created_datetime(&edge_created_datetime_manager, key.outbound_id, &key.t, key.inbound_id, value.update_datetime)?;
+                reversed_edge_created_range_manager.set(&mut batch, key.inbound_id, &key.t, created_datetime, key.outbound_id, value.weight)?;
+            }
+        }
+
+        for &(index_id, ref value, id) in &report.orphaned_vertex_metadata_index_values {
+            vertex_metadata_index_value_manager.delete(&mut batch, index_id, &value[..], id)?;
+        }
+
+        let vertex_metadata_indexes: HashMap<Uuid, models::VertexMetadataIndex> = VertexMetadataIndexManager::new(buffer.clone())
+            .iterate_all()?
+            .collect::<Result<_, Error>>()?;
+
+        for &(index_id, id) in &report.missing_vertex_metadata_index_values {
+            if let Some(index) = vertex_metadata_indexes.get(&index_id) {
+                if let Some(value) = get_indexed_value(&vertex_metadata_manager, index, id)? {
+                    vertex_metadata_index_value_manager.set(&mut batch, index_id, &value[..], id)?;
+                }
+            }
+        }
+
+        let unique_value_manager = UniqueValueManager::new(buffer.clone());
+
+        for &(constraint_id, ref value, _) in &report.orphaned_unique_values {
+            unique_value_manager.delete(&mut batch, constraint_id, &value[..])?;
+        }
+
+        let unique_constraints: HashMap<Uuid, models::UniqueConstraint> = UniqueConstraintManager::new(buffer.clone())
+            .iterate_all()?
+            .collect::<Result<_, Error>>()?;
+
+        for &(constraint_id, id) in &report.missing_unique_values {
+            if let Some(constraint) = unique_constraints.get(&constraint_id) {
+                if let Some(value) = vertex_metadata_manager.get(id, &constraint.name[..])? {
+                    unique_value_manager.set(&mut batch, constraint_id, &encode_metadata_value(&value)?[..], id)?;
+                }
+            }
+        }
+
+        let vertex_metadata_name_manager = VertexMetadataNameManager::new(buffer.clone());
+
+        for &(id, ref name) in &report.orphaned_vertex_metadata_names {
+            vertex_metadata_name_manager.delete(&mut batch, &name[..], id)?;
+        }
+
+        for &(id, ref name) in &report.missing_vertex_metadata_names {
+            vertex_metadata_name_manager.set(&mut batch, &name[..], id)?;
+        }
+
+        let edge_metadata_name_manager = EdgeMetadataNameManager::new(buffer.clone());
+
+        for &(ref key, ref name) in &report.orphaned_edge_metadata_names {
+            edge_metadata_name_manager.delete(&mut batch, &name[..], key.outbound_id, &key.t, key.inbound_id)?;
+        }
+
+        for &(ref key, ref name) in &report.missing_edge_metadata_names {
+            edge_metadata_name_manager.set(&mut batch, &name[..], key.outbound_id, &key.t, key.inbound_id)?;
+        }
+
+        buffer.write(batch);
+        buffer.commit()?;
+        Ok(report)
+    }
+
+    /// Fills in a column family that was added to an existing database.
+    /// Every write is idempotent, so an interrupted backfill can safely be
+    /// run again from the start.
+    fn backfill(&self, cf_name: &str) -> Result<(), Error> {
+        let buffer = self.buffer();
+        let vertex_owner_manager = VertexOwnerManager::new(buffer.clone());
+        let vertex_type_manager = VertexTypeManager::new(buffer.clone());
+        let edge_weight_range_manager = EdgeWeightRangeManager::new(buffer.clone());
+        let reversed_edge_weight_range_manager = EdgeWeightRangeManager::new_reversed(buffer.clone());
+        let mut batch = Batch::default();
+        let mut batch_size = 0;
+
+        match cf_name {
+            "vertex_owners:v1" | "vertex_types:v1" => {
+                for item in VertexManager::new(buffer.clone(), self.secure_uuids).iterate_all()? {
+                    let (id, value) = item?;
+
+                    if cf_name == "vertex_owners:v1" {
+                        vertex_owner_manager.set(&mut batch, value.owner_id, id)?;
+                    } else {
+                        vertex_type_manager.set(&mut batch, &value.t, id)?;
+                    }
+
+                    count_backfill_write(&buffer, &mut batch, &mut batch_size)?;
+                }
+            },
+            "edge_weight_ranges:v1" | "reversed_edge_weight_ranges:v1" => {
+                for item in EdgeManager::new(buffer.clone()).iterate_all()? {
+                    let ((outbound_id, t, inbound_id), value) = item?;
+
+                    if cf_name == "edge_weight_ranges:v1" {
+                        edge_weight_range_manager.set(&mut batch, outbound_id, &t, value.weight, inbound_id, value.update_datetime)?;
+                    } else {
+                        reversed_edge_weight_range_manager.set(&mut batch, inbound_id, &t, value.weight, outbound_id, value.update_datetime)?;
+                    }
+
+                    count_backfill_write(&buffer, &mut batch, &mut batch_size)?;
+                }
+            },
+            "edge_created_datetimes:v1" | "edge_created_ranges:v1" | "reversed_edge_created_ranges:v1" => {
+                // Databases from before created datetimes were tracked only
+                // know when each edge was last updated, so that has to do
+                let edge_created_datetime_manager = EdgeCreatedDatetimeManager::new(buffer.clone());
+                let edge_created_range_manager = EdgeRangeManager::new_created(buffer.clone());
+                let reversed_edge_created_range_manager = EdgeRangeManager::new_reversed_created(buffer.clone());
+
+                for item in EdgeManager::new(buffer.clone()).iterate_all()? {
+                    let ((outbound_id, t, inbound_id), value) = item?;
+
+                    if cf_name == "edge_created_datetimes:v1" {
+                        edge_created_datetime_manager.set(&mut batch, outbound_id, &t, inbound_id, value.update_datetime)?;
+                    } else if cf_name == "edge_created_ranges:v1" {
+                        edge_created_range_manager.set(&mut batch, outbound_id, &t, value.update_datetime, inbound_id, value.weight)?;
+                    } else {
+                        reversed_edge_created_range_manager.set(&mut batch, inbound_id, &t, value.update_datetime, outbound_id, value.weight)?;
+                    }
+
+                    count_backfill_write(&buffer, &mut batch, &mut batch_size)?;
+                }
+            },
+            // There are no indexes in a database that predates them, so
+            // there's nothing to fill in
+            "vertex_metadata_indexes:v1" | "vertex_metadata_index_values:v1" |
+            "unique_constraints:v1" | "unique_values:v1" => (),
+            // Likewise, no edge types have history enabled yet
+            "edge_history_types:v1" | "edge_history:v1" | "reversed_edge_history:v1" => (),
+            // Records without a version number are at version 1
+            "edge_version_numbers:v1" | "vertex_metadata_version_numbers:v1" |
+            "global_metadata_version_numbers:v1" | "account_metadata_version_numbers:v1" |
+            "edge_metadata_version_numbers:v1" => (),
+            // Nothing expires in a database that predates time-to-lives
+            "expiries:v1" | "expiry_ranges:v1" => (),
+            "vertex_metadata_names:v1" => {
+                let vertex_metadata_name_manager = VertexMetadataNameManager::new(buffer.clone());
+
+                for item in VertexMetadataManager::new(buffer.clone()).iterate_all()? {
+                    let ((id, name), _) = item?;
+                    vertex_metadata_name_manager.set(&mut batch, &name[..], id)?;
+                    count_backfill_write(&buffer, &mut batch, &mut batch_size)?;
+                }
+            },
+            "edge_metadata_names:v1" => {
+                let edge_metadata_name_manager = EdgeMetadataNameManager::new(buffer.clone());
+
+                for item in EdgeMetadataManager::new(buffer.clone()).iterate_all()? {
+                    let ((outbound_id, t, inbound_id, name), _) = item?;
+                    edge_metadata_name_manager.set(&mut batch, &name[..], outbound_id, &t, inbound_id)?;
+                    count_backfill_write(&buffer, &mut batch, &mut batch_size)?;
+                }
+            },
+            _ => panic!("No backfill defined for column family `{}`", cf_name)
+        }
+
+        buffer.write(batch);
+        buffer.commit()
+    }
+
+    fn buffer(&self) -> WriteBuffer {
+        WriteBuffer::new(self.db.clone())
+    }
 }
 
 impl Datastore<RocksdbTransaction> for RocksdbDatastore {
     fn has_account(&self, account_id: Uuid) -> Result<bool, Error> {
-        AccountManager::new(self.db.clone(), self.secure_uuids).exists(account_id)
+        AccountManager::new(self.buffer(), self.secure_uuids).exists(account_id)
     }
 
     fn create_account(&self) -> Result<(Uuid, String), Error> {
-        AccountManager::new(self.db.clone(), self.secure_uuids).create()
+        let buffer = self.buffer();
+        let mut batch = Batch::default();
+        let result = AccountManager::new(buffer.clone(), self.secure_uuids).create(&mut batch)?;
+        buffer.write(batch);
+        buffer.commit()?;
+        Ok(result)
     }
 
     fn delete_account(&self, account_id: Uuid) -> Result<(), Error> {
-        let manager = AccountManager::new(self.db.clone(), self.secure_uuids);
+        let buffer = self.buffer();
+        let manager = AccountManager::new(buffer.clone(), self.secure_uuids);
 
         if !manager.exists(account_id)? {
             return Err(Error::AccountNotFound);
         }
 
-        let mut batch = WriteBatch::default();
+        let mut batch = Batch::default();
         manager.delete(&mut batch, account_id)?;
-        self.db.write(batch)?;
-        Ok(())
+        buffer.write(batch);
+        buffer.commit()
     }
 
     fn auth(&self, account_id: Uuid, secret: String) -> Result<bool, Error> {
-        match AccountManager::new(self.db.clone(), self.secure_uuids).get(account_id)? {
+        match AccountManager::new(self.buffer(), self.secure_uuids).get(account_id)? {
             Some(value) => {
                 let expected_hash = get_salted_hash(&value.salt[..], None, &secret[..]);
                 Ok(expected_hash == value.hash)
@@ -141,13 +1238,204 @@ impl Datastore<RocksdbTransaction> for RocksdbDatastore {
     fn transaction(&self, account_id: Uuid) -> Result<RocksdbTransaction, Error> {
         RocksdbTransaction::new(self.db.clone(), account_id, self.secure_uuids)
     }
+
+    fn create_vertex_metadata_index(&self, index: models::VertexMetadataIndex) -> Result<(), Error> {
+        let buffer = self.buffer();
+        let vertex_metadata_index_manager = VertexMetadataIndexManager::new(buffer.clone());
+
+        if vertex_metadata_index_manager.find(&index)?.is_some() {
+            return Ok(());
+        }
+
+        // The index is created and filled in with a single write, so that
+        // lookups never see it half-filled
+        let vertex_metadata_manager = VertexMetadataManager::new(buffer.clone());
+        let vertex_metadata_index_value_manager = VertexMetadataIndexValueManager::new(buffer.clone());
+        let mut batch = Batch::default();
+        let index_id = vertex_metadata_index_manager.create(&mut batch, &index)?;
+
+        for item in VertexTypeManager::new(buffer.clone()).iterate_for_range(&index.t, Uuid::default())? {
+            let (_, id) = item?;
+
+            if let Some(value) = get_indexed_value(&vertex_metadata_manager, &index, id)? {
+                vertex_metadata_index_value_manager.set(&mut batch, index_id, &value[..], id)?;
+            }
+        }
+
+        buffer.write(batch);
+        buffer.commit()
+    }
+
+    fn delete_vertex_metadata_index(&self, index: models::VertexMetadataIndex) -> Result<(), Error> {
+        let buffer = self.buffer();
+        let vertex_metadata_index_manager = VertexMetadataIndexManager::new(buffer.clone());
+
+        let index_id = match vertex_metadata_index_manager.find(&index)? {
+            Some(index_id) => index_id,
+            None => return Ok(())
+        };
+
+        let vertex_metadata_index_value_manager = VertexMetadataIndexValueManager::new(buffer.clone());
+        let mut batch = Batch::default();
+        vertex_metadata_index_manager.delete(&mut batch, &index, index_id)?;
+
+        for item in vertex_metadata_index_value_manager.iterate_for_index(index_id)? {
+            let (_, value, id) = item?;
+            vertex_metadata_index_value_manager.delete(&mut batch, index_id, &value[..], id)?;
+        }
+
+        buffer.write(batch);
+        buffer.commit()
+    }
+
+    fn get_vertex_metadata_indexes(&self) -> Result<Vec<models::VertexMetadataIndex>, Error> {
+        let mut indexes = vec![];
+
+        for item in VertexMetadataIndexManager::new(self.buffer()).iterate_all()? {
+            let (_, index) = item?;
+            indexes.push(index);
+        }
+
+        indexes.sort();
+        Ok(indexes)
+    }
+
+    fn create_unique_constraint(&self, constraint: models::UniqueConstraint) -> Result<(), Error> {
+        let buffer = self.buffer();
+        let unique_constraint_manager = UniqueConstraintManager::new(buffer.clone());
+
+        if unique_constraint_manager.find(&constraint)?.is_some() {
+            return Ok(());
+        }
+
+        let expiry_filter = ExpiryFilter::new(buffer.clone())?;
+        let unique_value_manager = UniqueValueManager::new(buffer.clone());
+        let mut batch = Batch::default();
+        let constraint_id = unique_constraint_manager.create(&mut batch, &constraint)?;
+        let mut values = HashSet::new();
+
+        for item in VertexTypeManager::new(buffer.clone()).iterate_for_range(&constraint.t, Uuid::default())? {
+            let (_, id) = item?;
+
+            if let Some(value) = expiry_filter.get_vertex_metadata(id, &constraint.name[..])? {
+                let value = encode_metadata_value(&value)?;
+                unique_value_manager.set(&mut batch, constraint_id, &value[..], id)?;
+
+                if !values.insert(value) {
+                    return Err(Error::UniqueConstraintViolated);
+                }
+            }
+        }
+
+        buffer.write(batch);
+        buffer.commit()
+    }
+
+    fn delete_unique_constraint(&self, constraint: models::UniqueConstraint) -> Result<(), Error> {
+        let buffer = self.buffer();
+        let unique_constraint_manager = UniqueConstraintManager::new(buffer.clone());
+
+        let constraint_id = match unique_constraint_manager.find(&constraint)? {
+            Some(constraint_id) => constraint_id,
+            None => return Ok(())
+        };
+
+        let unique_value_manager = UniqueValueManager::new(buffer.clone());
+        let mut batch = Batch::default();
+        unique_constraint_manager.delete(&mut batch, &constraint)?;
+
+        for item in unique_value_manager.iterate_for_constraint(constraint_id)? {
+            let (_, value, _) = item?;
+            unique_value_manager.delete(&mut batch, constraint_id, &value[..])?;
+        }
+
+        buffer.write(batch);
+        buffer.commit()
+    }
+
+    fn get_unique_constraints(&self) -> Result<Vec<models::UniqueConstraint>, Error> {
+        let mut constraints = vec![];
+
+        for item in UniqueConstraintManager::new(self.buffer()).iterate_all()? {
+            let (_, constraint) = item?;
+            constraints.push(constraint);
+        }
+
+        constraints.sort();
+        Ok(constraints)
+    }
+
+    fn enable_edge_history(&self, t: models::Type) -> Result<(), Error> {
+        let buffer = self.buffer();
+        let edge_history_type_manager = EdgeHistoryTypeManager::new(buffer.clone());
+
+        if edge_history_type_manager.exists(&t)? {
+            return Ok(());
+        }
+
+        let edge_history_manager = EdgeHistoryManager::new(buffer.clone());
+        let reversed_edge_history_manager = EdgeHistoryManager::new_reversed(buffer.clone());
+        let edge_version_number_manager = EdgeVersionNumberManager::new(buffer.clone());
+        let mut batch = Batch::default();
+        edge_history_type_manager.set(&mut batch, &t)?;
+
+        for item in EdgeManager::new(buffer.clone()).iterate_all()? {
+            let ((outbound_id, edge_t, inbound_id), value) = item?;
+
+            if edge_t == t {
+                let version = edge_version_number_manager.get(outbound_id, &t, inbound_id)?.unwrap_or(1);
+                edge_history_manager.set(&mut batch, outbound_id, &t, inbound_id, value.update_datetime, Some((value.weight, version)))?;
+                reversed_edge_history_manager.set(&mut batch, inbound_id, &t, outbound_id, value.update_datetime, Some((value.weight, version)))?;
+            }
+        }
+
+        buffer.write(batch);
+        buffer.commit()
+    }
+
+    fn disable_edge_history(&self, t: models::Type) -> Result<(), Error> {
+        let buffer = self.buffer();
+        let edge_history_manager = EdgeHistoryManager::new(buffer.clone());
+        let reversed_edge_history_manager = EdgeHistoryManager::new_reversed(buffer.clone());
+        let mut batch = Batch::default();
+        EdgeHistoryTypeManager::new(buffer.clone()).delete(&mut batch, &t)?;
+
+        for item in edge_history_manager.iterate_all()? {
+            let ((outbound_id, edge_t, inbound_id, datetime), _) = item?;
+
+            if edge_t == t {
+                edge_history_manager.delete(&mut batch, outbound_id, &t, inbound_id, datetime)?;
+                reversed_edge_history_manager.delete(&mut batch, inbound_id, &t, outbound_id, datetime)?;
+            }
+        }
+
+        buffer.write(batch);
+        buffer.commit()
+    }
+
+    fn get_edge_history_types(&self) -> Result<Vec<models::Type>, Error> {
+        let types: Result<Vec<models::Type>, Error> = EdgeHistoryTypeManager::new(self.buffer()).iterate_all()?.collect();
+        let mut types = types?;
+        types.sort();
+        Ok(types)
+    }
+
+    fn delete_expired(&self) -> Result<(), Error> {
+        let buffer = self.buffer();
+        delete_expired(&buffer, self.secure_uuids, UTC::now())?;
+        buffer.commit()
+    }
 }
 
 /// A transaction that is backed by rocksdb.
+///
+/// Writes are buffered in memory, and are visible to reads made through the
+/// same transaction. They are atomically applied to the database when the
+/// transaction is committed, and discarded if it is rolled back or dropped.
 #[derive(Debug)]
 pub struct RocksdbTransaction {
-    /// A reference to the rocksdb database.
-    db: Arc<DB>,
+    /// The buffer of writes on top of the rocksdb database.
+    buffer: WriteBuffer,
     /// The ID of the account that's triggering this transaction.
     account_id: Uuid,
     /// Whether to use secure UUIDs.
@@ -157,15 +1445,14 @@ pub struct RocksdbTransaction {
 impl RocksdbTransaction {
     fn new(db: Arc<DB>, account_id: Uuid, secure_uuids: bool) -> Result<Self, Error> {
         Ok(RocksdbTransaction {
-            db: db,
+            buffer: WriteBuffer::new(db),
             account_id: account_id,
             secure_uuids: secure_uuids
         })
     }
 
     fn check_write_permissions(&self, id: Uuid, not_found_err: Error) -> Result<(), Error> {
-        let vertex_manager = VertexManager::new(self.db.clone(), self.secure_uuids);
-        let vertex_value = vertex_manager.get(id)?;
+        let vertex_value = self.expiry_filter()?.get_vertex(id)?;
 
         match vertex_value {
             None => Err(not_found_err),
@@ -179,33 +1466,120 @@ impl RocksdbTransaction {
         }
     }
 
+    fn expiry_filter(&self) -> Result<ExpiryFilter, Error> {
+        ExpiryFilter::new(self.buffer.clone())
+    }
+
+    /// Deletes an edge if it has expired but hasn't been deleted yet, so
+    /// that setting it again replaces it, rather than updating it.
+    fn delete_expired_edge(&self, key: &models::EdgeKey) -> Result<(), Error> {
+        let item = ExpiringItem::Edge(key.outbound_id, key.t.clone(), key.inbound_id);
+
+        if let Some(expiry) = self.expiry_filter()?.expired_at(&item)? {
+            let mut batch = Batch::default();
+            expire_edge(&EdgeManager::new(self.buffer.clone()), &mut batch, key.outbound_id, &key.t, key.inbound_id, expiry)?;
+            self.buffer.write(batch);
+        }
+
+        Ok(())
+    }
+
+    /// Creates or updates an edge, making it expire at a given datetime, or
+    /// never if it's `None`.
+    fn set_edge(&self, key: models::EdgeKey, weight: models::Weight, update_datetime: DateTime<UTC>, expiry: Option<DateTime<UTC>>) -> Result<(), Error> {
+        // Verify that the vertices exist and that we own the vertex with the outbound ID
+        self.check_write_permissions(key.outbound_id, Error::VertexNotFound)?;
+        if self.expiry_filter()?.get_vertex(key.inbound_id)?.is_none() {
+            return Err(Error::VertexNotFound);
+        }
+
+        self.delete_expired_edge(&key)?;
+        let mut batch = Batch::default();
+        EdgeManager::new(self.buffer.clone()).set(&mut batch,
+                                                   key.outbound_id,
+                                                   &key.t,
+                                                   key.inbound_id,
+                                                   update_datetime,
+                                                   weight)?;
+
+        if let Some(expiry) = expiry {
+            ExpiryManager::new(self.buffer.clone()).set(&mut batch, &ExpiringItem::Edge(key.outbound_id, key.t, key.inbound_id), expiry)?;
+        }
+
+        self.buffer.write(batch);
+        Ok(())
+    }
+
+    /// Sets a vertex metadata value for each vertex matching a query, making
+    /// it expire at a given datetime, or never if it's `None`.
+    fn set_vertex_metadata_until(&self, q: VertexQuery, name: String, value: JsonValue, expiry: Option<DateTime<UTC>>) -> Result<(), Error> {
+        let manager = VertexMetadataManager::new(self.buffer.clone());
+        let expiry_manager = ExpiryManager::new(self.buffer.clone());
+        let mut batch = Batch::default();
+
+        for item in self.vertex_query_to_iterator(q)? {
+            let (id, vertex_value) = item?;
+            manager.set(&mut batch, id, &vertex_value.t, &name[..], &value)?;
+
+            if let Some(expiry) = expiry {
+                expiry_manager.set(&mut batch, &ExpiringItem::VertexMetadata(id, name.clone()), expiry)?;
+            }
+        }
+
+        self.buffer.write(batch);
+        Ok(())
+    }
+
+    /// Sets an edge metadata value for each edge matching a query, making it
+    /// expire at a given datetime, or never if it's `None`.
+    fn set_edge_metadata_until(&self, q: EdgeQuery, name: String, value: JsonValue, expiry: Option<DateTime<UTC>>) -> Result<(), Error> {
+        let manager = EdgeMetadataManager::new(self.buffer.clone());
+        let expiry_manager = ExpiryManager::new(self.buffer.clone());
+        let mut batch = Batch::default();
+
+        for item in self.edge_query_to_iterator(q)? {
+            let ((outbound_id, t, _, inbound_id), _) = item?;
+            manager.set(&mut batch, outbound_id, &t, inbound_id, &name[..], &value)?;
+
+            if let Some(expiry) = expiry {
+                expiry_manager.set(&mut batch, &ExpiringItem::EdgeMetadata(outbound_id, t, inbound_id, name.clone()), expiry)?;
+            }
+        }
+
+        self.buffer.write(batch);
+        Ok(())
+    }
+
     fn vertex_query_to_iterator(&self, q: VertexQuery) -> Result<Box<Iterator<Item = VertexItem>>, Error> {
-        let vertex_manager = VertexManager::new(self.db.clone(), self.secure_uuids);
+        let vertex_manager = VertexManager::new(self.buffer.clone(), self.secure_uuids);
 
         match q {
             VertexQuery::All(start_id, limit) => {
-                let next_uuid = match start_id {
-                    Some(start_id) => {
-                        match next_uuid(start_id) {
-                            Ok(next_uuid) => next_uuid,
-                            // If we get an error back, it's because
-                            // `start_id` is the maximum possible value. We
-                            // know that no vertices exist whose ID is greater
-                            // than the maximum possible value, so just return
-                            // an empty list.
-                            Err(_) => {
-                                return Ok(Box::new(vec![].into_iter()))
-                            }
-                        }
-                    },
-                    None => Uuid::default()
+                let next_uuid = match first_page_uuid(start_id) {
+                    Some(next_uuid) => next_uuid,
+                    None => return Ok(Box::new(vec![].into_iter()))
                 };
 
-                let iterator = vertex_manager.iterate_for_range(next_uuid)?;
+                let expiry_filter = self.expiry_filter()?;
+                let iterator = remove_expired(vertex_manager.iterate_for_range(next_uuid)?, move |&(id, _)| expiry_filter.vertex_has_expired(id));
                 Ok(Box::new(iterator.take(limit as usize)))
             },
+            VertexQuery::AllOfType(t, start_id, limit) => {
+                let next_uuid = match first_page_uuid(start_id) {
+                    Some(next_uuid) => next_uuid,
+                    None => return Ok(Box::new(vec![].into_iter()))
+                };
+
+                let vertex_type_manager = VertexTypeManager::new(self.buffer.clone());
+                let vertex_id_iterator = Box::new(vertex_type_manager.iterate_for_range(&t, next_uuid)?.map(|item| {
+                    let (_, id) = item?;
+                    Ok(id)
+                }));
+
+                Ok(Box::new(self.handle_vertex_id_iterator(vertex_id_iterator)?.take(limit as usize)))
+            },
             VertexQuery::Vertex(id) => {
-                match vertex_manager.get(id)? {
+                match self.expiry_filter()?.get_vertex(id)? {
                     Some(value) => Ok(Box::new(vec![Ok((id, value))].into_iter())),
                     None => Ok(Box::new(vec![].into_iter()))
                 }
@@ -215,7 +1589,7 @@ impl RocksdbTransaction {
                     Ok(item)
                 }));
 
-                Ok(self.handle_vertex_id_iterator(iterator))
+                self.handle_vertex_id_iterator(iterator)
             },
             VertexQuery::Pipe(edge_query, converter, limit) => {
                 let edge_iterator = self.edge_query_to_iterator(*edge_query)?;
@@ -229,17 +1603,269 @@ impl RocksdbTransaction {
                     }
                 }));
 
-                Ok(Box::new(self.handle_vertex_id_iterator(vertex_id_iterator).take(limit as usize)))
+                Ok(Box::new(self.handle_vertex_id_iterator(vertex_id_iterator)?.take(limit as usize)))
+            },
+            VertexQuery::Traverse(vertex_query, converter, t, min_depth, max_depth, limit) => {
+                let edge_range_manager = match converter {
+                    QueryTypeConverter::Outbound => EdgeRangeManager::new(self.buffer.clone()),
+                    QueryTypeConverter::Inbound => EdgeRangeManager::new_reversed(self.buffer.clone())
+                };
+
+                // Breadth-first search, so that each vertex is first visited
+                // at the fewest number of hops needed to reach it
+                let expiry_filter = self.expiry_filter()?;
+                let reversed = converter == QueryTypeConverter::Inbound;
+                let mut visited: HashSet<Uuid> = HashSet::new();
+                let mut found: Vec<Uuid> = Vec::new();
+                let mut frontier: Vec<Uuid> = Vec::new();
+
+                for item in self.vertex_query_to_iterator(*vertex_query)? {
+                    let (id, _) = item?;
+
+                    if visited.insert(id) {
+                        frontier.push(id);
+                    }
+                }
+
+                let mut depth = 0;
+
+                loop {
+                    if depth >= min_depth {
+                        found.extend(frontier.iter().cloned());
+                    }
+
+                    if depth == max_depth || frontier.is_empty() {
+                        break;
+                    }
+
+                    let mut next_frontier: Vec<Uuid> = Vec::new();
+
+                    for id in frontier {
+                        for item in edge_range_manager.iterate_for_range(id, &t, None)? {
+                            let ((_, edge_t, _, next_id), _) = item?;
+
+                            if expiry_filter.indexed_edge_has_expired(id, &edge_t, next_id, reversed)? {
+                                continue;
+                            }
+
+                            if visited.insert(next_id) {
+                                next_frontier.push(next_id);
+                            }
+                        }
+                    }
+
+                    frontier = next_frontier;
+                    depth += 1;
+                }
+
+                found.sort();
+                found.truncate(limit as usize);
+                let vertex_id_iterator = Box::new(found.into_iter().map(Ok));
+                self.handle_vertex_id_iterator(vertex_id_iterator)
+            },
+            VertexQuery::Union(left_query, right_query) => self.merge_vertex_queries(*left_query, *right_query, SetOperation::Union),
+            VertexQuery::Intersection(left_query, right_query) => self.merge_vertex_queries(*left_query, *right_query, SetOperation::Intersection),
+            VertexQuery::Difference(left_query, right_query) => self.merge_vertex_queries(*left_query, *right_query, SetOperation::Difference),
+            VertexQuery::Filter(vertex_query, name, predicate) => {
+                let expiry_filter = self.expiry_filter()?;
+                let iterator = self.vertex_query_to_iterator(*vertex_query)?;
+
+                let filtered = iterator.filter_map(move |item| {
+                    let (id, value) = match item {
+                        Ok(item) => item,
+                        Err(err) => return Some(Err(err))
+                    };
+
+                    match expiry_filter.get_vertex_metadata(id, &name[..]) {
+                        Ok(Some(ref metadata)) if predicate.matches(metadata) => Some(Ok((id, value))),
+                        Ok(_) => None,
+                        Err(err) => Some(Err(err))
+                    }
+                });
+
+                Ok(Box::new(filtered))
+            },
+            VertexQuery::Lookup(t, name, predicate, start_id, limit) => {
+                let next_uuid = match first_page_uuid(start_id) {
+                    Some(next_uuid) => next_uuid,
+                    None => return Ok(Box::new(vec![].into_iter()))
+                };
+
+                // Values of expired metadata can still be in the unique
+                // constraints and indexes, so they're checked as they're read
+                let expiry_filter = self.expiry_filter()?;
+
+                // Use a unique constraint or an index if there's one for the
+                // predicate
+                if let Some((path, value)) = predicate.equality() {
+                    if path.is_empty() {
+                        let constraint = models::UniqueConstraint::new(t.clone(), name.clone());
+
+                        if let Some(constraint_id) = UniqueConstraintManager::new(self.buffer.clone()).find(&constraint)? {
+                            let unique_value_manager = UniqueValueManager::new(self.buffer.clone());
+
+                            let ids = match unique_value_manager.get(constraint_id, &encode_metadata_value(value)?[..])? {
+                                Some(id) if id >= next_uuid && limit > 0 && !expiry_filter.vertex_metadata_has_expired(id, &name[..])? => vec![Ok(id)],
+                                _ => vec![]
+                            };
+
+                            return self.handle_vertex_id_iterator(Box::new(ids.into_iter()));
+                        }
+                    }
+
+                    let index = models::VertexMetadataIndex::new(t.clone(), name.clone(), path);
+
+                    if let Some(index_id) = VertexMetadataIndexManager::new(self.buffer.clone()).find(&index)? {
+                        let vertex_metadata_index_value_manager = VertexMetadataIndexValueManager::new(self.buffer.clone());
+                        let vertex_id_iterator = Box::new(vertex_metadata_index_value_manager.iterate_for_value(index_id, &encode_metadata_value(value)?[..], next_uuid)?.map(|item| {
+                            let (_, _, id) = item?;
+                            Ok(id)
+                        }));
+
+                        let vertex_id_iterator = remove_expired(vertex_id_iterator, move |&id| expiry_filter.vertex_metadata_has_expired(id, &name[..]));
+
+                        return Ok(Box::new(self.handle_vertex_id_iterator(vertex_id_iterator)?.take(limit as usize)));
+                    }
+                }
+
+                // Otherwise, scan every vertex of the type
+                let vertex_type_manager = VertexTypeManager::new(self.buffer.clone());
+
+                let vertex_id_iterator = Box::new(vertex_type_manager.iterate_for_range(&t, next_uuid)?.filter_map(move |item| {
+                    let id = match item {
+                        Ok((_, id)) => id,
+                        Err(err) => return Some(Err(err))
+                    };
+
+                    match expiry_filter.get_vertex_metadata(id, &name[..]) {
+                        Ok(Some(ref metadata)) if predicate.matches(metadata) => Some(Ok(id)),
+                        Ok(_) => None,
+                        Err(err) => Some(Err(err))
+                    }
+                }));
+
+                Ok(Box::new(self.handle_vertex_id_iterator(vertex_id_iterator)?.take(limit as usize)))
+            },
+            VertexQuery::HasMetadata(name, start_id, limit) => {
+                let next_uuid = match first_page_uuid(start_id) {
+                    Some(next_uuid) => next_uuid,
+                    None => return Ok(Box::new(vec![].into_iter()))
+                };
+
+                let expiry_filter = self.expiry_filter()?;
+                let vertex_metadata_name_manager = VertexMetadataNameManager::new(self.buffer.clone());
+                let vertex_id_iterator = Box::new(vertex_metadata_name_manager.iterate_for_name(&name[..], next_uuid)?.map(|item| {
+                    let (_, id) = item?;
+                    Ok(id)
+                }));
+
+                let vertex_id_iterator = remove_expired(vertex_id_iterator, move |&id| expiry_filter.vertex_metadata_has_expired(id, &name[..]));
+
+                Ok(Box::new(self.handle_vertex_id_iterator(vertex_id_iterator)?.take(limit as usize)))
             }
         }
     }
 
+    fn sorted_vertex_values(&self, q: VertexQuery) -> Result<Vec<(Uuid, VertexValue)>, Error> {
+        let mut values: Vec<(Uuid, VertexValue)> = Vec::new();
+
+        for item in self.vertex_query_to_iterator(q)? {
+            values.push(item?);
+        }
+
+        values.sort_by_key(|&(id, _)| id);
+        values.dedup_by_key(|&mut (id, _)| id);
+        Ok(values)
+    }
+
+    fn merge_vertex_queries(&self, left_query: VertexQuery, right_query: VertexQuery, operation: SetOperation) -> Result<Box<Iterator<Item = VertexItem>>, Error> {
+        let left = self.sorted_vertex_values(left_query)?;
+        let right = self.sorted_vertex_values(right_query)?;
+        Ok(Box::new(merge_sorted(left, right, operation).into_iter().map(Ok)))
+    }
+
+    /// Gets the results of an edge query, sorted and deduplicated by edge
+    /// key.
+    fn sorted_edge_values(&self, q: EdgeQuery) -> Result<Vec<((Uuid, models::Type, Uuid), (DateTime<UTC>, models::Weight))>, Error> {
+        let mut values = Vec::new();
+
+        for item in self.edge_query_to_iterator(q)? {
+            let ((outbound_id, t, update_datetime, inbound_id), weight) = item?;
+            values.push(((outbound_id, t, inbound_id), (update_datetime, weight)));
+        }
+
+        values.sort_by(|&(ref a, _), &(ref b, _)| a.cmp(b));
+        values.dedup_by(|&mut (ref a, _), &mut (ref b, _)| a == b);
+        Ok(values)
+    }
+
+    fn merge_edge_queries(&self, left_query: EdgeQuery, right_query: EdgeQuery, operation: SetOperation) -> Result<Box<Iterator<Item = EdgeRangeItem>>, Error> {
+        let left = self.sorted_edge_values(left_query)?;
+        let right = self.sorted_edge_values(right_query)?;
+
+        let mapped = merge_sorted(left, right, operation).into_iter().map(|((outbound_id, t, inbound_id), (update_datetime, weight))| {
+            Ok(((outbound_id, t, update_datetime, inbound_id), weight))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
+    /// Lazily gets the edges of a vertex query from a datetime range index,
+    /// latest first for each vertex, with the edge keys flipped back around
+    /// if the index is reversed. Only one vertex's range is read at a time,
+    /// so this runs in bounded memory.
+    fn get_edges_in_datetime_range(&self,
+                                   vertex_query: VertexQuery,
+                                   converter: QueryTypeConverter,
+                                   edge_range_manager: EdgeRangeManager,
+                                   t: Option<models::Type>,
+                                   high: Option<DateTime<UTC>>,
+                                   low: Option<DateTime<UTC>>,
+                                   limit: u32)
+                                   -> Result<Box<Iterator<Item = EdgeRangeItem>>, Error> {
+        let vertex_iterator = self.vertex_query_to_iterator(vertex_query)?;
+        let reversed = converter == QueryTypeConverter::Inbound;
+
+        let edges = vertex_iterator.flat_map(move |item| -> Box<Iterator<Item = EdgeRangeItem>> {
+            let edge_iterator = match item.and_then(|(id, _)| edge_range_manager.iterate_for_range(id, &t, high)) {
+                Ok(edge_iterator) => edge_iterator,
+                Err(err) => return Box::new(iter::once(Err(err)))
+            };
+
+            // The range is latest first, so everything after the first edge
+            // that's too old is too
+            match low {
+                Some(low) => Box::new(edge_iterator.take_while(move |item| {
+                    match *item {
+                        Ok(((_, _, edge_range_datetime, _), _)) => edge_range_datetime >= low,
+                        Err(_) => true
+                    }
+                })),
+                None => edge_iterator
+            }
+        });
+
+        let edges: Box<Iterator<Item = EdgeRangeItem>> = Box::new(edges.map(move |item| {
+            // Reversed edge range keys have the inbound ID first, so
+            // flip them back around
+            let ((first_id, t, edge_range_datetime, second_id), weight) = item?;
+
+            if reversed {
+                Ok(((second_id, t, edge_range_datetime, first_id), weight))
+            } else {
+                Ok(((first_id, t, edge_range_datetime, second_id), weight))
+            }
+        }));
+
+        let expiry_filter = self.expiry_filter()?;
+        let edges = remove_expired(edges, move |&((outbound_id, ref t, _, inbound_id), _)| expiry_filter.edge_has_expired(outbound_id, t, inbound_id));
+        Ok(Box::new(edges.take(limit as usize)))
+    }
+
     fn edge_query_to_iterator(&self, q: EdgeQuery) -> Result<Box<Iterator<Item = EdgeRangeItem>>, Error> {
         match q {
             EdgeQuery::Edge(key) => {
-                let edge_manager = EdgeManager::new(self.db.clone());
-
-                match edge_manager.get(key.outbound_id, &key.t, key.inbound_id)? {
+                match self.expiry_filter()?.get_edge(key.outbound_id, &key.t, key.inbound_id)? {
                     Some(value) => {
                         let item = Ok(((key.outbound_id, key.t, value.update_datetime, key.inbound_id), value.weight));
                         Ok(Box::new(vec![item].into_iter()))
@@ -248,10 +1874,10 @@ impl RocksdbTransaction {
                 }
             },
             EdgeQuery::Edges(edges) => {
-                let edge_manager = EdgeManager::new(self.db.clone());
+                let expiry_filter = self.expiry_filter()?;
 
                 let iterator = edges.into_iter().map(move |key| {
-                    match edge_manager.get(key.outbound_id, &key.t, key.inbound_id)? {
+                    match expiry_filter.get_edge(key.outbound_id, &key.t, key.inbound_id)? {
                         Some(value) => {
                             Ok(Some(((key.outbound_id, key.t, value.update_datetime, key.inbound_id), value.weight)))
                         },
@@ -262,62 +1888,236 @@ impl RocksdbTransaction {
                 Ok(self.remove_nones_from_iterator(Box::new(iterator)))
             },
             EdgeQuery::Pipe(vertex_query, converter, t, high, low, limit) => {
+                let edge_range_manager = match converter {
+                    QueryTypeConverter::Outbound => EdgeRangeManager::new(self.buffer.clone()),
+                    QueryTypeConverter::Inbound => EdgeRangeManager::new_reversed(self.buffer.clone())
+                };
+
+                self.get_edges_in_datetime_range(*vertex_query, converter, edge_range_manager, t, high, low, limit)
+            },
+            EdgeQuery::CreatedPipe(vertex_query, converter, t, high, low, limit) => {
+                let edge_range_manager = match converter {
+                    QueryTypeConverter::Outbound => EdgeRangeManager::new_created(self.buffer.clone()),
+                    QueryTypeConverter::Inbound => EdgeRangeManager::new_reversed_created(self.buffer.clone())
+                };
+
+                let edges = self.get_edges_in_datetime_range(*vertex_query, converter, edge_range_manager, t, high, low, limit)?;
+
+                // Swap the created datetimes for update datetimes, which is
+                // what the rest of the query pipeline expects
+                let edge_manager = EdgeManager::new(self.buffer.clone());
+
+                let mapped = edges.map(move |item| {
+                    let ((outbound_id, t, _, inbound_id), weight) = item?;
+
+                    match edge_manager.get(outbound_id, &t, inbound_id)? {
+                        Some(value) => Ok(Some(((outbound_id, t, value.update_datetime, inbound_id), weight))),
+                        None => Ok(None)
+                    }
+                });
+
+                Ok(self.remove_nones_from_iterator(Box::new(mapped)))
+            },
+            EdgeQuery::SortedPipe(vertex_query, converter, t, high, low, order, limit) => {
                 let vertex_iterator = self.vertex_query_to_iterator(*vertex_query)?;
+                let reversed = converter == QueryTypeConverter::Inbound;
+                let limit = limit as usize;
+
+                let (edge_range_manager, edge_weight_range_manager) = match converter {
+                    QueryTypeConverter::Outbound => (EdgeRangeManager::new(self.buffer.clone()), EdgeWeightRangeManager::new(self.buffer.clone())),
+                    QueryTypeConverter::Inbound => (EdgeRangeManager::new_reversed(self.buffer.clone()), EdgeWeightRangeManager::new_reversed(self.buffer.clone()))
+                };
+
+                // Each vertex (and for weight orders, each of its edge types)
+                // has its own sorted run of edges. Only the first `limit`
+                // edges of each run can make it into the results, so those
+                // are collected and then sorted together.
+                let expiry_filter = self.expiry_filter()?;
+                let mut edges: Vec<((Uuid, models::Type, DateTime<UTC>, Uuid), models::Weight)> = Vec::new();
+
+                for item in vertex_iterator {
+                    let (id, _) = item?;
+
+                    match order {
+                        EdgeOrder::NewestFirst | EdgeOrder::OldestFirst => {
+                            let edge_iterator = if order == EdgeOrder::NewestFirst {
+                                edge_range_manager.iterate_for_range(id, &t, None)?
+                            } else {
+                                edge_range_manager.reverse_iterate_for_range(id, &t)?
+                            };
+
+                            let edge_iterator = remove_expired(edge_iterator, |&((first_id, ref t, _, second_id), _)| {
+                                expiry_filter.indexed_edge_has_expired(first_id, t, second_id, reversed)
+                            });
+
+                            let filtered = edge_iterator.filter(|item| {
+                                match *item {
+                                    Ok((_, weight)) => {
+                                        high.map_or(true, |high| weight <= high) && low.map_or(true, |low| weight >= low)
+                                    },
+                                    Err(_) => true
+                                }
+                            });
+
+                            for item in filtered.take(limit) {
+                                edges.push(item?);
+                            }
+                        },
+                        EdgeOrder::StrongestFirst | EdgeOrder::WeakestFirst => {
+                            let types = match t {
+                                Some(ref t) => vec![t.clone()],
+                                None => edge_weight_range_manager.get_types_for_owner(id)?
+                            };
+
+                            for t in types {
+                                let edge_iterator = edge_weight_range_manager.iterate_for_range(id, &t, high, low, order == EdgeOrder::WeakestFirst)?;
+
+                                let edge_iterator = remove_expired(edge_iterator, |&((first_id, ref t, _, second_id), _)| {
+                                    expiry_filter.indexed_edge_has_expired(first_id, t, second_id, reversed)
+                                });
+
+                                for item in edge_iterator.take(limit) {
+                                    let ((first_id, t, weight, second_id), update_datetime) = item?;
+                                    edges.push(((first_id, t, update_datetime, second_id), weight));
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // Reversed index keys have the inbound ID first, so flip
+                // them back around
+                if reversed {
+                    for &mut ((ref mut first_id, _, _, ref mut second_id), _) in &mut edges {
+                        mem::swap(first_id, second_id);
+                    }
+                }
+
+                edges.sort_by(|&((ref a_outbound_id, ref a_t, a_update_datetime, ref a_inbound_id), a_weight),
+                               &((ref b_outbound_id, ref b_t, b_update_datetime, ref b_inbound_id), b_weight)| {
+                    let ordering = match order {
+                        EdgeOrder::NewestFirst => b_update_datetime.cmp(&a_update_datetime),
+                        EdgeOrder::OldestFirst => a_update_datetime.cmp(&b_update_datetime),
+                        EdgeOrder::StrongestFirst => b_weight.cmp(&a_weight),
+                        EdgeOrder::WeakestFirst => a_weight.cmp(&b_weight)
+                    };
+
+                    // Break ties by key, so that results are deterministic
+                    ordering.then_with(|| (a_outbound_id, a_t, a_inbound_id).cmp(&(b_outbound_id, b_t, b_inbound_id)))
+                });
+
+                edges.truncate(limit);
+                Ok(Box::new(edges.into_iter().map(Ok)))
+            },
+            EdgeQuery::PagedPipe(vertex_query, converter, t, cursor, limit) => {
+                let reversed = converter == QueryTypeConverter::Inbound;
+                let limit = limit as usize;
+
+                let edge_range_manager = match converter {
+                    QueryTypeConverter::Outbound => EdgeRangeManager::new(self.buffer.clone()),
+                    QueryTypeConverter::Inbound => EdgeRangeManager::new_reversed(self.buffer.clone())
+                };
+
+                // Pages follow the key order of the edge range indexes, so
+                // the vertices have to be visited in order too
+                let mut ids: Vec<Uuid> = Vec::new();
+
+                for item in self.vertex_query_to_iterator(*vertex_query)? {
+                    let (id, _) = item?;
+                    ids.push(id);
+                }
+
+                ids.sort();
+                ids.dedup();
 
-                let edge_range_manager = match converter {
-                    QueryTypeConverter::Outbound => EdgeRangeManager::new(self.db.clone()),
-                    QueryTypeConverter::Inbound => EdgeRangeManager::new_reversed(self.db.clone())
-                };
+                // The cursor's key is for the edge as stored, so flip it
+                // around to match the reversed index
+                let cursor = cursor.map(|cursor| {
+                    let key = cursor.key();
 
-                // Ideally we'd use iterators all the way down, but things
-                // start breaking apart due to conditional expressions not
-                // returning the same type signature, issues with `Result`s
-                // and some of the iterators, etc. So at this point, we'll
-                // just resort to building a vector.
-                let mut edges: Vec<EdgeRangeItem> = Vec::new();
+                    if reversed {
+                        (key.inbound_id, key.t.clone(), cursor.update_datetime(), key.outbound_id)
+                    } else {
+                        (key.outbound_id, key.t.clone(), cursor.update_datetime(), key.inbound_id)
+                    }
+                });
 
-                if let Some(low) = low {
-                    for item in vertex_iterator {
-                        let (id, _) = item?;
-                        let edge_iterator = edge_range_manager.iterate_for_range(id, &t, high)?;
-
-                        for item in edge_iterator {
-                            match item {
-                                Ok(((_, _, edge_range_update_datetime, _), _)) => {
-                                    if edge_range_update_datetime >= low {
-                                        edges.push(item);
-                                    } else {
-                                        break;
-                                    }
-                                },
-                                Err(_) => edges.push(item)
-                            }
+                let expiry_filter = self.expiry_filter()?;
+                let mut edges: Vec<EdgeRangeItem> = Vec::new();
 
-                            if edges.len() == limit as usize {
-                                break;
-                            }
-                        }
+                for id in ids {
+                    if edges.len() == limit {
+                        break;
                     }
-                } else {
-                    for item in vertex_iterator {
-                        let (id, _) = item?;
-                        let edge_iterator = edge_range_manager.iterate_for_range(id, &t, high)?;
 
-                        for edge in edge_iterator {
-                            edges.push(edge);
+                    let edge_iterator = match cursor {
+                        Some((cursor_id, _, _, _)) if id < cursor_id => continue,
+                        Some((cursor_id, ref cursor_t, cursor_update_datetime, cursor_second_id)) if id == cursor_id => {
+                            edge_range_manager.iterate_for_range_after(id, &t, cursor_t, cursor_update_datetime, cursor_second_id)?
+                        },
+                        _ => edge_range_manager.iterate_for_range(id, &t, None)?
+                    };
 
-                            if edges.len() == limit as usize {
-                                break;
-                            }
-                        }
+                    let edge_iterator = remove_expired(edge_iterator, |&((first_id, ref t, _, second_id), _)| {
+                        expiry_filter.indexed_edge_has_expired(first_id, t, second_id, reversed)
+                    });
+
+                    for item in edge_iterator.take(limit - edges.len()) {
+                        edges.push(item);
+                    }
+                }
 
-                        if edges.len() == limit as usize {
-                            break;
+                if reversed {
+                    for item in &mut edges {
+                        if let Ok(((ref mut first_id, _, _, ref mut second_id), _)) = *item {
+                            mem::swap(first_id, second_id);
                         }
                     }
                 }
 
                 Ok(Box::new(edges.into_iter()))
+            },
+            EdgeQuery::Union(left_query, right_query) => self.merge_edge_queries(*left_query, *right_query, SetOperation::Union),
+            EdgeQuery::Intersection(left_query, right_query) => self.merge_edge_queries(*left_query, *right_query, SetOperation::Intersection),
+            EdgeQuery::Difference(left_query, right_query) => self.merge_edge_queries(*left_query, *right_query, SetOperation::Difference),
+            EdgeQuery::Filter(edge_query, name, predicate) => {
+                let expiry_filter = self.expiry_filter()?;
+                let iterator = self.edge_query_to_iterator(*edge_query)?;
+
+                let filtered = iterator.filter_map(move |item| {
+                    let ((outbound_id, t, update_datetime, inbound_id), weight) = match item {
+                        Ok(item) => item,
+                        Err(err) => return Some(Err(err))
+                    };
+
+                    match expiry_filter.get_edge_metadata(outbound_id, &t, inbound_id, &name[..]) {
+                        Ok(Some(ref metadata)) if predicate.matches(metadata) => Some(Ok(((outbound_id, t, update_datetime, inbound_id), weight))),
+                        Ok(_) => None,
+                        Err(err) => Some(Err(err))
+                    }
+                });
+
+                Ok(Box::new(filtered))
+            },
+            EdgeQuery::HasMetadata(name, start_key, limit) => {
+                let edge_manager = EdgeManager::new(self.buffer.clone());
+                let expiry_filter = self.expiry_filter()?;
+                let edge_metadata_name_manager = EdgeMetadataNameManager::new(self.buffer.clone());
+
+                let iterator = edge_metadata_name_manager.iterate_for_name(&name[..], start_key.as_ref())?.map(move |item| {
+                    let (_, outbound_id, t, inbound_id) = item?;
+
+                    if expiry_filter.edge_metadata_has_expired(outbound_id, &t, inbound_id, &name[..])? {
+                        return Ok(None);
+                    }
+
+                    match edge_manager.get(outbound_id, &t, inbound_id)? {
+                        Some(value) => Ok(Some(((outbound_id, t, value.update_datetime, inbound_id), value.weight))),
+                        None => Ok(None)
+                    }
+                });
+
+                Ok(Box::new(self.remove_nones_from_iterator(Box::new(iterator)).take(limit as usize)))
             }
         }
     }
@@ -341,12 +2141,12 @@ impl RocksdbTransaction {
         Box::new(mapped)
     }
 
-    fn handle_vertex_id_iterator(&self, iterator: Box<Iterator<Item = Result<Uuid, Error>>>) -> Box<Iterator<Item = VertexItem>> {
-        let vertex_manager = VertexManager::new(self.db.clone(), self.secure_uuids);
+    fn handle_vertex_id_iterator(&self, iterator: Box<Iterator<Item = Result<Uuid, Error>>>) -> Result<Box<Iterator<Item = VertexItem>>, Error> {
+        let expiry_filter = self.expiry_filter()?;
 
         let mapped = iterator.map(move |item| {
             let id = item?;
-            let value = vertex_manager.get(id)?;
+            let value = expiry_filter.get_vertex(id)?;
 
             match value {
                 Some(value) => Ok(Some((id, value))),
@@ -354,16 +2154,67 @@ impl RocksdbTransaction {
             }
         });
 
-        self.remove_nones_from_iterator(Box::new(mapped))
+        Ok(self.remove_nones_from_iterator(Box::new(mapped)))
     }
 }
 
 impl Transaction for RocksdbTransaction {
     fn create_vertex(&self, t: models::Type) -> Result<Uuid, Error> {
-        VertexManager::new(self.db.clone(), self.secure_uuids).create(t, self.account_id)
+        let mut batch = Batch::default();
+        let id = VertexManager::new(self.buffer.clone(), self.secure_uuids).create(&mut batch, t, self.account_id)?;
+        self.buffer.write(batch);
+        Ok(id)
+    }
+
+    fn create_vertex_with_id(&self, id: Uuid, t: models::Type) -> Result<(), Error> {
+        let vertex_manager = VertexManager::new(self.buffer.clone(), self.secure_uuids);
+        vertex_manager.watch(id)?;
+
+        let mut batch = Batch::default();
+
+        if vertex_manager.exists(id)? {
+            if !self.expiry_filter()?.vertex_has_expired(id)? {
+                return Err(Error::VertexAlreadyExists);
+            }
+
+            // Replace the expired vertex, which hasn't been deleted yet
+            vertex_manager.delete(&mut batch, id)?;
+            self.buffer.write(batch);
+            batch = Batch::default();
+        }
+
+        vertex_manager.create_with_id(&mut batch, id, t, self.account_id)?;
+        self.buffer.write(batch);
+        Ok(())
+    }
+
+    fn create_vertices(&self, types: Vec<models::Type>) -> Result<Vec<Uuid>, Error> {
+        let vertex_manager = VertexManager::new(self.buffer.clone(), self.secure_uuids);
+        let mut batch = Batch::default();
+        let mut ids: Vec<Uuid> = Vec::with_capacity(types.len());
+
+        for t in types {
+            ids.push(vertex_manager.create(&mut batch, t, self.account_id)?);
+        }
+
+        self.buffer.write(batch);
+        Ok(ids)
+    }
+
+    fn create_vertex_with_ttl(&self, t: models::Type, ttl: Duration) -> Result<Uuid, Error> {
+        let expiry = expiry_datetime(ttl)?;
+        let mut batch = Batch::default();
+        let id = VertexManager::new(self.buffer.clone(), self.secure_uuids).create(&mut batch, t, self.account_id)?;
+        ExpiryManager::new(self.buffer.clone()).set(&mut batch, &ExpiringItem::Vertex(id), expiry)?;
+        self.buffer.write(batch);
+        Ok(id)
     }
 
     fn get_vertices(&self, q: VertexQuery) -> Result<Vec<models::Vertex>, Error> {
+        self.stream_vertices(q)?.collect()
+    }
+
+    fn stream_vertices<'a>(&'a self, q: VertexQuery) -> Result<VertexStream<'a>, Error> {
         let iterator = self.vertex_query_to_iterator(q)?;
 
         let mapped = iterator.map(move |item| {
@@ -372,13 +2223,13 @@ impl Transaction for RocksdbTransaction {
             Ok(vertex)
         });
 
-        mapped.collect()
+        Ok(Box::new(mapped))
     }
 
     fn delete_vertices(&self, q: VertexQuery) -> Result<(), Error> {
         let iterator = self.vertex_query_to_iterator(q)?;
-        let vertex_manager = VertexManager::new(self.db.clone(), self.secure_uuids);
-        let mut batch = WriteBatch::default();
+        let vertex_manager = VertexManager::new(self.buffer.clone(), self.secure_uuids);
+        let mut batch = Batch::default();
 
         for item in iterator {
             let (id, old_value) = item?;
@@ -390,59 +2241,221 @@ impl Transaction for RocksdbTransaction {
             vertex_manager.delete(&mut batch, id)?;
         }
 
-        self.db.write(batch)?;
+        self.buffer.write(batch);
         Ok(())
     }
 
     fn create_edge(&self, key: models::EdgeKey, weight: models::Weight) -> Result<(), Error> {
+        self.create_edge_at(key, weight, UTC::now())
+    }
+
+    fn create_edge_at(&self, key: models::EdgeKey, weight: models::Weight, update_datetime: DateTime<UTC>) -> Result<(), Error> {
+        let update_datetime = check_datetime(update_datetime, "update_datetime")?;
+        self.set_edge(key, weight, update_datetime, None)
+    }
+
+    fn create_edge_with_ttl(&self, key: models::EdgeKey, weight: models::Weight, ttl: Duration) -> Result<(), Error> {
+        let expiry = expiry_datetime(ttl)?;
+        self.set_edge(key, weight, UTC::now(), Some(expiry))
+    }
+
+    fn create_edge_if_version(&self, key: models::EdgeKey, weight: models::Weight, expected_version: Option<u64>) -> Result<u64, Error> {
         // Verify that the vertices exist and that we own the vertex with the outbound ID
         self.check_write_permissions(key.outbound_id, Error::VertexNotFound)?;
-        if !VertexManager::new(self.db.clone(), self.secure_uuids).exists(key.inbound_id)? {
+        if self.expiry_filter()?.get_vertex(key.inbound_id)?.is_none() {
             return Err(Error::VertexNotFound);
         }
 
+        // Watch the edge before checking its version, so that the commit
+        // fails if another transaction changes it in between
+        let edge_manager = EdgeManager::new(self.buffer.clone());
+        edge_manager.watch(key.outbound_id, &key.t, key.inbound_id)?;
+        self.delete_expired_edge(&key)?;
+
+        if edge_manager.get_version_number(key.outbound_id, &key.t, key.inbound_id)? != expected_version {
+            return Err(Error::Conflict);
+        }
+
+        let mut batch = Batch::default();
+        let version = edge_manager.set(&mut batch, key.outbound_id, &key.t, key.inbound_id, UTC::now(), weight)?;
+        self.buffer.write(batch);
+        Ok(version)
+    }
+
+    fn create_edges(&self, items: Vec<(models::EdgeKey, models::Weight)>) -> Result<Vec<Result<(), Error>>, Error> {
+        let expiry_filter = self.expiry_filter()?;
+        let edge_manager = EdgeManager::new(self.buffer.clone());
+
+        // Look up each vertex's owner once, rather than once per edge
+        let mut owners: HashMap<Uuid, Option<Uuid>> = HashMap::new();
+
+        for &(ref key, _) in &items {
+            for &id in &[key.outbound_id, key.inbound_id] {
+                if !owners.contains_key(&id) {
+                    let owner_id = expiry_filter.get_vertex(id)?.map(|value| value.owner_id);
+                    owners.insert(id, owner_id);
+                }
+            }
+        }
+
+        // Collapse repeated edges down to their last weight, since
+        // `EdgeManager::set` can only see writes that have already left the
+        // batch
+        let mut weights: HashMap<models::EdgeKey, models::Weight> = HashMap::new();
+        let mut results: Vec<Result<(), Error>> = Vec::with_capacity(items.len());
+
+        for (key, weight) in items {
+            let result = match (owners[&key.outbound_id], owners[&key.inbound_id]) {
+                (None, _) => Err(Error::VertexNotFound),
+                (Some(owner_id), _) if owner_id != self.account_id => Err(Error::Unauthorized),
+                (_, None) => Err(Error::VertexNotFound),
+                _ => Ok(())
+            };
+
+            if result.is_ok() {
+                weights.insert(key, weight);
+            }
+
+            results.push(result);
+        }
+
+        for key in weights.keys() {
+            self.delete_expired_edge(key)?;
+        }
+
         let new_update_datetime = UTC::now();
-        let mut batch = WriteBatch::default();
-        EdgeManager::new(self.db.clone()).set(&mut batch,
-                                                   key.outbound_id,
-                                                   &key.t,
-                                                   key.inbound_id,
-                                                   new_update_datetime,
-                                                   weight)?;
-        self.db.write(batch)?;
-        Ok(())
+        let mut batch = Batch::default();
+
+        for (key, weight) in weights {
+            edge_manager.set(&mut batch, key.outbound_id, &key.t, key.inbound_id, new_update_datetime, weight)?;
+        }
+
+        self.buffer.write(batch);
+        Ok(results)
     }
 
     fn get_edges(&self, q: EdgeQuery) -> Result<Vec<models::Edge>, Error> {
+        self.stream_edges(q)?.collect()
+    }
+
+    fn stream_edges<'a>(&'a self, q: EdgeQuery) -> Result<EdgeStream<'a>, Error> {
         let iterator = self.edge_query_to_iterator(q)?;
+        let edge_created_datetime_manager = EdgeCreatedDatetimeManager::new(self.buffer.clone());
+        let edge_version_number_manager = EdgeVersionNumberManager::new(self.buffer.clone());
 
         let mapped = iterator.map(move |item| {
             let ((outbound_id, t, update_datetime, inbound_id), weight) = item?;
+            let created_datetime = get_created_datetime(&edge_created_datetime_manager, outbound_id, &t, inbound_id, update_datetime)?;
+            let version = edge_version_number_manager.get(outbound_id, &t, inbound_id)?.unwrap_or(1);
             let key = models::EdgeKey::new(outbound_id, t, inbound_id);
-            let edge = models::Edge::new(key, weight, update_datetime);
+            let edge = models::Edge::new_with_version(key, weight, created_datetime, update_datetime, version);
             Ok(edge)
         });
 
-        mapped.collect()
+        Ok(Box::new(mapped))
+    }
+
+    fn get_edge_history(&self, key: models::EdgeKey) -> Result<Vec<models::EdgeVersion>, Error> {
+        // The history of an edge goes along with either of its vertices
+        let expiry_filter = self.expiry_filter()?;
+
+        if expiry_filter.vertex_has_expired(key.outbound_id)? || expiry_filter.vertex_has_expired(key.inbound_id)? {
+            return Ok(vec![]);
+        }
+
+        let edge_history_manager = EdgeHistoryManager::new(self.buffer.clone());
+        let mut versions = Vec::new();
+
+        for item in edge_history_manager.iterate_for_edge(key.outbound_id, &key.t, key.inbound_id)? {
+            let ((_, _, _, datetime), value) = item?;
+            versions.push(to_edge_version(value, datetime));
+        }
+
+        // Versions are stored newest first
+        versions.reverse();
+
+        // An expired edge gets its last version when it's deleted
+        if !versions.is_empty() {
+            if let Some(expiry) = expiry_filter.expired_at(&ExpiringItem::Edge(key.outbound_id, key.t, key.inbound_id))? {
+                versions.push(models::EdgeVersion::new(None, None, expiry));
+            }
+        }
+
+        Ok(versions)
+    }
+
+    fn get_edges_as_of(&self,
+                       q: VertexQuery,
+                       converter: QueryTypeConverter,
+                       t: models::Type,
+                       datetime: DateTime<UTC>,
+                       limit: u32)
+                       -> Result<Vec<models::Edge>, Error> {
+        let edge_history_manager = match converter {
+            QueryTypeConverter::Outbound => EdgeHistoryManager::new(self.buffer.clone()),
+            QueryTypeConverter::Inbound => EdgeHistoryManager::new_reversed(self.buffer.clone())
+        };
+
+        let expiry_filter = self.expiry_filter()?;
+        let mut edges = Vec::new();
+
+        for item in self.vertex_query_to_iterator(q)? {
+            let (id, _) = item?;
+            let mut history: BTreeMap<Uuid, Vec<models::EdgeVersion>> = BTreeMap::new();
+
+            for item in edge_history_manager.iterate_for_type(id, &t)? {
+                let ((_, _, other_id, version_datetime), value) = item?;
+                history.entry(other_id).or_insert_with(Vec::new).push(to_edge_version(value, version_datetime));
+            }
+
+            for (other_id, mut versions) in history {
+                if expiry_filter.vertex_has_expired(other_id)? {
+                    continue;
+                }
+
+                // Versions are stored newest first
+                versions.reverse();
+
+                let key = match converter {
+                    QueryTypeConverter::Outbound => models::EdgeKey::new(id, t.clone(), other_id),
+                    QueryTypeConverter::Inbound => models::EdgeKey::new(other_id, t.clone(), id)
+                };
+
+                // An expired edge gets its last version when it's deleted
+                if let Some(expiry) = expiry_filter.expired_at(&ExpiringItem::Edge(key.outbound_id, key.t.clone(), key.inbound_id))? {
+                    versions.push(models::EdgeVersion::new(None, None, expiry));
+                }
+
+                if let Some(edge) = edge_as_of(key, &versions, datetime) {
+                    edges.push(edge);
+                }
+            }
+        }
+
+        edges.sort_by(|a, b| b.updated_datetime.cmp(&a.updated_datetime));
+        edges.truncate(limit as usize);
+        Ok(edges)
     }
 
     fn delete_edges(&self, q: EdgeQuery) -> Result<(), Error> {
-        let edge_manager = EdgeManager::new(self.db.clone());
-        let vertex_manager = VertexManager::new(self.db.clone(), self.secure_uuids);
+        let edge_manager = EdgeManager::new(self.buffer.clone());
+        let vertex_manager = VertexManager::new(self.buffer.clone(), self.secure_uuids);
         let iterator = self.edge_query_to_iterator(q)?;
-        let mut batch = WriteBatch::default();
+        let deleted_datetime = UTC::now();
+        let mut batch = Batch::default();
 
         for item in iterator {
-            let ((outbound_id, t, update_datetime, inbound_id), _) = item?;
+            let ((outbound_id, t, update_datetime, inbound_id), weight) = item?;
 
             if let Some(vertex_value) = vertex_manager.get(outbound_id)? {
                 if vertex_value.owner_id == self.account_id {
-                    edge_manager.delete(&mut batch, outbound_id, &t, inbound_id, update_datetime)?;
+                    edge_manager.delete(&mut batch, outbound_id, &t, inbound_id, update_datetime, weight)?;
+                    edge_manager.set_history_version(&mut batch, outbound_id, &t, inbound_id, deleted_datetime, None)?;
                 }
             };
         }
 
-        self.db.write(batch)?;
+        self.buffer.write(batch);
         Ok(())
     }
 
@@ -452,64 +2465,204 @@ impl Transaction for RocksdbTransaction {
     }
 
     fn get_global_metadata(&self, name: String) -> Result<JsonValue, Error> {
-        let manager = GlobalMetadataManager::new(self.db.clone());
+        let manager = GlobalMetadataManager::new(self.buffer.clone());
         manager.get(&name[..])?.ok_or_else(|| Error::MetadataNotFound)
     }
 
+    fn get_all_global_metadata(&self, prefix: String, start_name: Option<String>, limit: u32) -> Result<Vec<(String, JsonValue)>, Error> {
+        let manager = GlobalMetadataManager::new(self.buffer.clone());
+        let start_name = start_name.as_ref().map(|start_name| &start_name[..]);
+        manager.iterate_for_prefix(&prefix[..], start_name)?.take(limit as usize).collect()
+    }
+
     fn set_global_metadata(&self, name: String, value: JsonValue) -> Result<(), Error> {
-        let manager = GlobalMetadataManager::new(self.db.clone());
-        manager.set(&name[..], &value)
+        let manager = GlobalMetadataManager::new(self.buffer.clone());
+        let mut batch = Batch::default();
+        manager.set(&mut batch, &name[..], &value)?;
+        self.buffer.write(batch);
+        Ok(())
+    }
+
+    fn update_global_metadata(&self, name: String, operation: models::MetadataOperation) -> Result<JsonValue, Error> {
+        let manager = GlobalMetadataManager::new(self.buffer.clone());
+        manager.watch(&name[..])?;
+        let value = operation.apply(manager.get(&name[..])?.as_ref())?;
+        let mut batch = Batch::default();
+        manager.set(&mut batch, &name[..], &value)?;
+        self.buffer.write(batch);
+        Ok(value)
     }
 
     fn delete_global_metadata(&self, name: String) -> Result<(), Error> {
-        let mut batch = WriteBatch::default();
-        GlobalMetadataManager::new(self.db.clone()).delete(&mut batch, &name[..])?;
-        self.db.write(batch)?;
+        let mut batch = Batch::default();
+        GlobalMetadataManager::new(self.buffer.clone()).delete(&mut batch, &name[..])?;
+        self.buffer.write(batch);
         Ok(())
     }
 
+    fn get_versioned_global_metadata(&self, name: String) -> Result<models::VersionedMetadata, Error> {
+        let manager = GlobalMetadataManager::new(self.buffer.clone());
+
+        match manager.get(&name[..])? {
+            Some(value) => {
+                let version = manager.get_version_number(&name[..])?.unwrap_or(1);
+                Ok(models::VersionedMetadata::new(value, version))
+            },
+            None => Err(Error::MetadataNotFound)
+        }
+    }
+
+    fn set_global_metadata_if_version(&self, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error> {
+        // Watch the metadata before checking its version, so that the commit
+        // fails if another transaction changes it in between
+        let manager = GlobalMetadataManager::new(self.buffer.clone());
+        manager.watch(&name[..])?;
+
+        if manager.get_version_number(&name[..])? != expected_version {
+            return Err(Error::Conflict);
+        }
+
+        let mut batch = Batch::default();
+        let version = manager.set(&mut batch, &name[..], &value)?;
+        self.buffer.write(batch);
+        Ok(version)
+    }
+
     fn get_account_metadata(&self, owner_id: Uuid, name: String) -> Result<JsonValue, Error> {
-        if !AccountManager::new(self.db.clone(), self.secure_uuids).exists(owner_id)? {
+        if !AccountManager::new(self.buffer.clone(), self.secure_uuids).exists(owner_id)? {
             return Err(Error::AccountNotFound);
         }
 
-        let manager = AccountMetadataManager::new(self.db.clone());
+        let manager = AccountMetadataManager::new(self.buffer.clone());
         manager.get(owner_id, &name[..])?.ok_or_else(|| Error::MetadataNotFound)
     }
 
+    fn get_all_account_metadata(&self, owner_id: Uuid) -> Result<Vec<(String, JsonValue)>, Error> {
+        if !AccountManager::new(self.buffer.clone(), self.secure_uuids).exists(owner_id)? {
+            return Err(Error::AccountNotFound);
+        }
+
+        let manager = AccountMetadataManager::new(self.buffer.clone());
+        let mut metadata: Vec<(String, JsonValue)> = Vec::new();
+
+        for item in manager.iterate_for_owner(owner_id)? {
+            let ((_, name), value) = item?;
+            metadata.push((name, value));
+        }
+
+        Ok(metadata)
+    }
+
     fn set_account_metadata(&self, owner_id: Uuid, name: String, value: JsonValue) -> Result<(), Error> {
-        if !AccountManager::new(self.db.clone(), self.secure_uuids).exists(owner_id)? {
+        if !AccountManager::new(self.buffer.clone(), self.secure_uuids).exists(owner_id)? {
             return Err(Error::AccountNotFound);
         }
 
-        let manager = AccountMetadataManager::new(self.db.clone());
-        manager.set(owner_id, &name[..], &value)?;
+        let manager = AccountMetadataManager::new(self.buffer.clone());
+        let mut batch = Batch::default();
+        manager.set(&mut batch, owner_id, &name[..], &value)?;
+        self.buffer.write(batch);
         Ok(())
     }
 
+    fn update_account_metadata(&self, owner_id: Uuid, name: String, operation: models::MetadataOperation) -> Result<JsonValue, Error> {
+        if !AccountManager::new(self.buffer.clone(), self.secure_uuids).exists(owner_id)? {
+            return Err(Error::AccountNotFound);
+        }
+
+        let manager = AccountMetadataManager::new(self.buffer.clone());
+        manager.watch(owner_id, &name[..])?;
+        let value = operation.apply(manager.get(owner_id, &name[..])?.as_ref())?;
+        let mut batch = Batch::default();
+        manager.set(&mut batch, owner_id, &name[..], &value)?;
+        self.buffer.write(batch);
+        Ok(value)
+    }
+
     fn delete_account_metadata(&self, owner_id: Uuid, name: String) -> Result<(), Error> {
-        let manager = AccountMetadataManager::new(self.db.clone());
+        let manager = AccountMetadataManager::new(self.buffer.clone());
 
         if !manager.exists(owner_id, &name)? {
             return Err(Error::MetadataNotFound);
         }
 
-        let mut batch = WriteBatch::default();
+        let mut batch = Batch::default();
         manager.delete(&mut batch, owner_id, &name[..])?;
-        self.db.write(batch)?;
+        self.buffer.write(batch);
         Ok(())
     }
 
+    fn get_versioned_account_metadata(&self, owner_id: Uuid, name: String) -> Result<models::VersionedMetadata, Error> {
+        if !AccountManager::new(self.buffer.clone(), self.secure_uuids).exists(owner_id)? {
+            return Err(Error::AccountNotFound);
+        }
+
+        let manager = AccountMetadataManager::new(self.buffer.clone());
+
+        match manager.get(owner_id, &name[..])? {
+            Some(value) => {
+                let version = manager.get_version_number(owner_id, &name[..])?.unwrap_or(1);
+                Ok(models::VersionedMetadata::new(value, version))
+            },
+            None => Err(Error::MetadataNotFound)
+        }
+    }
+
+    fn set_account_metadata_if_version(&self, owner_id: Uuid, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error> {
+        if !AccountManager::new(self.buffer.clone(), self.secure_uuids).exists(owner_id)? {
+            return Err(Error::AccountNotFound);
+        }
+
+        // Watch the metadata before checking its version, so that the commit
+        // fails if another transaction changes it in between
+        let manager = AccountMetadataManager::new(self.buffer.clone());
+        manager.watch(owner_id, &name[..])?;
+
+        if manager.get_version_number(owner_id, &name[..])? != expected_version {
+            return Err(Error::Conflict);
+        }
+
+        let mut batch = Batch::default();
+        let version = manager.set(&mut batch, owner_id, &name[..], &value)?;
+        self.buffer.write(batch);
+        Ok(version)
+    }
+
     fn get_vertex_metadata(&self, q: VertexQuery, name: String) -> Result<HashMap<Uuid, JsonValue>, Error> {
-        let manager = VertexMetadataManager::new(self.db.clone());
-        let mut metadata: HashMap<Uuid, JsonValue> = HashMap::new();
+        self.stream_vertex_metadata(q, name)?.collect()
+    }
 
-        for item in self.vertex_query_to_iterator(q)? {
+    fn stream_vertex_metadata<'a>(&'a self, q: VertexQuery, name: String) -> Result<VertexMetadataStream<'a>, Error> {
+        let expiry_filter = self.expiry_filter()?;
+        let iterator = self.vertex_query_to_iterator(q)?;
+
+        let mapped = iterator.map(move |item| {
             let (id, _) = item?;
-            let value = manager.get(id, &name[..])?;
 
-            if let Some(value) = value {
-                metadata.insert(id, value);
+            match expiry_filter.get_vertex_metadata(id, &name[..])? {
+                Some(value) => Ok(Some((id, value))),
+                None => Ok(None)
+            }
+        });
+
+        Ok(self.remove_nones_from_iterator(Box::new(mapped)))
+    }
+
+    fn get_all_vertex_metadata(&self, id: Uuid) -> Result<Vec<(String, JsonValue)>, Error> {
+        let expiry_filter = self.expiry_filter()?;
+
+        if expiry_filter.get_vertex(id)?.is_none() {
+            return Err(Error::VertexNotFound);
+        }
+
+        let manager = VertexMetadataManager::new(self.buffer.clone());
+        let mut metadata: Vec<(String, JsonValue)> = Vec::new();
+
+        for item in manager.iterate_for_owner(id)? {
+            let ((_, name), value) = item?;
+
+            if !expiry_filter.vertex_metadata_has_expired(id, &name[..])? {
+                metadata.push((name, value));
             }
         }
 
@@ -517,38 +2670,99 @@ impl Transaction for RocksdbTransaction {
     }
 
     fn set_vertex_metadata(&self, q: VertexQuery, name: String, value: JsonValue) -> Result<(), Error> {
-        let manager = VertexMetadataManager::new(self.db.clone());
-        let mut batch = WriteBatch::default();
+        self.set_vertex_metadata_until(q, name, value, None)
+    }
+
+    fn set_vertex_metadata_with_ttl(&self, q: VertexQuery, name: String, value: JsonValue, ttl: Duration) -> Result<(), Error> {
+        let expiry = expiry_datetime(ttl)?;
+        self.set_vertex_metadata_until(q, name, value, Some(expiry))
+    }
+
+    fn update_vertex_metadata(&self, q: VertexQuery, name: String, operation: models::MetadataOperation) -> Result<HashMap<Uuid, JsonValue>, Error> {
+        let manager = VertexMetadataManager::new(self.buffer.clone());
+        let mut metadata: HashMap<Uuid, JsonValue> = HashMap::new();
+        let mut batch = Batch::default();
+
+        let expiry_filter = self.expiry_filter()?;
 
         for item in self.vertex_query_to_iterator(q)? {
-            let (id, _) = item?;
-            manager.set(&mut batch, id, &name[..], &value)?;
+            let (id, vertex_value) = item?;
+            manager.watch(id, &name[..])?;
+            let value = operation.apply(expiry_filter.get_vertex_metadata(id, &name[..])?.as_ref())?;
+            manager.set(&mut batch, id, &vertex_value.t, &name[..], &value)?;
+            metadata.insert(id, value);
         }
 
-        self.db.write(batch)?;
-        Ok(())
+        self.buffer.write(batch);
+        Ok(metadata)
     }
 
     fn delete_vertex_metadata(&self, q: VertexQuery, name: String) -> Result<(), Error> {
-        let manager = VertexMetadataManager::new(self.db.clone());
-        let mut batch = WriteBatch::default();
+        let manager = VertexMetadataManager::new(self.buffer.clone());
+        let mut batch = Batch::default();
 
         for item in self.vertex_query_to_iterator(q)? {
-            let (id, _) = item?;
-            manager.delete(&mut batch, id, &name[..])?;
+            let (id, vertex_value) = item?;
+            manager.delete(&mut batch, id, &vertex_value.t, &name[..])?;
         }
 
-        self.db.write(batch)?;
+        self.buffer.write(batch);
         Ok(())
     }
 
+    fn get_versioned_vertex_metadata(&self, q: VertexQuery, name: String) -> Result<HashMap<Uuid, models::VersionedMetadata>, Error> {
+        let manager = VertexMetadataManager::new(self.buffer.clone());
+        let expiry_filter = self.expiry_filter()?;
+        let mut metadata = HashMap::new();
+
+        for item in self.vertex_query_to_iterator(q)? {
+            let (id, _) = item?;
+
+            if let Some(value) = expiry_filter.get_vertex_metadata(id, &name[..])? {
+                let version = manager.get_version_number(id, &name[..])?.unwrap_or(1);
+                metadata.insert(id, models::VersionedMetadata::new(value, version));
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    fn set_vertex_metadata_if_version(&self, id: Uuid, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error> {
+        let expiry_filter = self.expiry_filter()?;
+
+        let vertex_value = match expiry_filter.get_vertex(id)? {
+            Some(vertex_value) => vertex_value,
+            None => return Err(Error::VertexNotFound)
+        };
+
+        // Watch the metadata before checking its version, so that the commit
+        // fails if another transaction changes it in between
+        let manager = VertexMetadataManager::new(self.buffer.clone());
+        manager.watch(id, &name[..])?;
+
+        let version = if expiry_filter.vertex_metadata_has_expired(id, &name[..])? {
+            None
+        } else {
+            manager.get_version_number(id, &name[..])?
+        };
+
+        if version != expected_version {
+            return Err(Error::Conflict);
+        }
+
+        let mut batch = Batch::default();
+        let version = manager.set(&mut batch, id, &vertex_value.t, &name[..], &value)?;
+        self.buffer.write(batch);
+        Ok(version)
+    }
+
     fn get_edge_metadata(&self, q: EdgeQuery, name: String) -> Result<HashMap<models::EdgeKey, JsonValue>, Error> {
-        let manager = EdgeMetadataManager::new(self.db.clone());
+        let expiry_filter = self.expiry_filter()?;
         let mut metadata: HashMap<models::EdgeKey, JsonValue> = HashMap::new();
 
         for item in self.edge_query_to_iterator(q)? {
             let ((outbound_id, t, _, inbound_id), _) = item?;
-            let value = manager.get(outbound_id, &t, inbound_id, &name[..])?;
+            let value = expiry_filter.get_edge_metadata(outbound_id, &t, inbound_id, &name[..])?;
 
             if let Some(value) = value {
                 let key = models::EdgeKey::new(outbound_id, t, inbound_id);
@@ -559,39 +2773,123 @@ impl Transaction for RocksdbTransaction {
         Ok(metadata)
     }
 
+    fn get_all_edge_metadata(&self, key: models::EdgeKey) -> Result<Vec<(String, JsonValue)>, Error> {
+        let expiry_filter = self.expiry_filter()?;
+
+        if expiry_filter.get_edge(key.outbound_id, &key.t, key.inbound_id)?.is_none() {
+            return Err(Error::EdgeNotFound);
+        }
+
+        let manager = EdgeMetadataManager::new(self.buffer.clone());
+        let mut metadata: Vec<(String, JsonValue)> = Vec::new();
+
+        for item in manager.iterate_for_owner(key.outbound_id, &key.t, key.inbound_id)? {
+            let ((_, _, _, name), value) = item?;
+
+            if !expiry_filter.edge_metadata_has_expired(key.outbound_id, &key.t, key.inbound_id, &name[..])? {
+                metadata.push((name, value));
+            }
+        }
+
+        Ok(metadata)
+    }
+
     fn set_edge_metadata(&self, q: EdgeQuery, name: String, value: JsonValue) -> Result<(), Error> {
-        let manager = EdgeMetadataManager::new(self.db.clone());
-        let mut batch = WriteBatch::default();
+        self.set_edge_metadata_until(q, name, value, None)
+    }
+
+    fn set_edge_metadata_with_ttl(&self, q: EdgeQuery, name: String, value: JsonValue, ttl: Duration) -> Result<(), Error> {
+        let expiry = expiry_datetime(ttl)?;
+        self.set_edge_metadata_until(q, name, value, Some(expiry))
+    }
+
+    fn update_edge_metadata(&self, q: EdgeQuery, name: String, operation: models::MetadataOperation) -> Result<HashMap<models::EdgeKey, JsonValue>, Error> {
+        let manager = EdgeMetadataManager::new(self.buffer.clone());
+        let mut metadata: HashMap<models::EdgeKey, JsonValue> = HashMap::new();
+        let mut batch = Batch::default();
+
+        let expiry_filter = self.expiry_filter()?;
 
         for item in self.edge_query_to_iterator(q)? {
             let ((outbound_id, t, _, inbound_id), _) = item?;
+            manager.watch(outbound_id, &t, inbound_id, &name[..])?;
+            let value = operation.apply(expiry_filter.get_edge_metadata(outbound_id, &t, inbound_id, &name[..])?.as_ref())?;
             manager.set(&mut batch, outbound_id, &t, inbound_id, &name[..], &value)?;
+            metadata.insert(models::EdgeKey::new(outbound_id, t, inbound_id), value);
         }
 
-        self.db.write(batch)?;
-        Ok(())
+        self.buffer.write(batch);
+        Ok(metadata)
     }
 
     fn delete_edge_metadata(&self, q: EdgeQuery, name: String) -> Result<(), Error> {
-        let manager = EdgeMetadataManager::new(self.db.clone());
-        let mut batch = WriteBatch::default();
+        let manager = EdgeMetadataManager::new(self.buffer.clone());
+        let mut batch = Batch::default();
 
         for item in self.edge_query_to_iterator(q)? {
             let ((outbound_id, t, _, inbound_id), _) = item?;
             manager.delete(&mut batch, outbound_id, &t, inbound_id, &name[..])?;
         }
 
-        self.db.write(batch)?;
+        self.buffer.write(batch);
         Ok(())
     }
 
+    fn get_versioned_edge_metadata(&self, q: EdgeQuery, name: String) -> Result<HashMap<models::EdgeKey, models::VersionedMetadata>, Error> {
+        let manager = EdgeMetadataManager::new(self.buffer.clone());
+        let expiry_filter = self.expiry_filter()?;
+        let mut metadata = HashMap::new();
+
+        for item in self.edge_query_to_iterator(q)? {
+            let ((outbound_id, t, _, inbound_id), _) = item?;
+
+            if let Some(value) = expiry_filter.get_edge_metadata(outbound_id, &t, inbound_id, &name[..])? {
+                let version = manager.get_version_number(outbound_id, &t, inbound_id, &name[..])?.unwrap_or(1);
+                metadata.insert(models::EdgeKey::new(outbound_id, t, inbound_id), models::VersionedMetadata::new(value, version));
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    fn set_edge_metadata_if_version(&self, key: models::EdgeKey, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error> {
+        // The edge is watched too, so that the metadata isn't left behind if
+        // another transaction deletes the edge
+        let edge_manager = EdgeManager::new(self.buffer.clone());
+        edge_manager.watch(key.outbound_id, &key.t, key.inbound_id)?;
+        let expiry_filter = self.expiry_filter()?;
+
+        if expiry_filter.get_edge(key.outbound_id, &key.t, key.inbound_id)?.is_none() {
+            return Err(Error::EdgeNotFound);
+        }
+
+        // Watch the metadata before checking its version, so that the commit
+        // fails if another transaction changes it in between
+        let manager = EdgeMetadataManager::new(self.buffer.clone());
+        manager.watch(key.outbound_id, &key.t, key.inbound_id, &name[..])?;
+
+        let version = if expiry_filter.edge_metadata_has_expired(key.outbound_id, &key.t, key.inbound_id, &name[..])? {
+            None
+        } else {
+            manager.get_version_number(key.outbound_id, &key.t, key.inbound_id, &name[..])?
+        };
+
+        if version != expected_version {
+            return Err(Error::Conflict);
+        }
+
+        let mut batch = Batch::default();
+        let version = manager.set(&mut batch, key.outbound_id, &key.t, key.inbound_id, &name[..], &value)?;
+        self.buffer.write(batch);
+        Ok(version)
+    }
+
     fn commit(self) -> Result<(), Error> {
-        Ok(())
+        self.buffer.commit()
     }
 
     fn rollback(self) -> Result<(), Error> {
-        Err(Error::Unexpected(
-            "Transactions cannot be rolled back in the rocksdb datastore implementation"
-        .to_string()))
+        self.buffer.rollback();
+        Ok(())
     }
 }
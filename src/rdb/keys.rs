@@ -1,26 +1,24 @@
 use uuid::Uuid;
 use std::io::Write;
-use std::i32;
 use std::i64;
 use std::str;
 use std::u8;
-use std::io::Read;
+use std::io::{BufRead, Read};
 use std::io::{Cursor, Error as IoError};
 use models;
-use chrono::{NaiveDateTime, DateTime, UTC};
+use chrono::{DateTime, UTC};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use util::nanos_since_epoch;
-use chrono::{Timelike, Duration};
-
-lazy_static! {
-    pub static ref MAX_DATETIME: DateTime<UTC> = DateTime::from_utc(NaiveDateTime::from_timestamp(i32::MAX as i64, 0), UTC).with_nanosecond(1999999999u32).unwrap();
-}
+use util::{nanos_since_epoch, MAX_DATETIME};
+use chrono::Duration;
 
 pub enum KeyComponent<'a> {
     Uuid(Uuid),
     UnsizedString(&'a str),
+    NullTerminatedString(&'a str),
+    SizedString(&'a str),
     Type(&'a models::Type),
     DateTime(DateTime<UTC>),
+    Weight(models::Weight),
 }
 
 impl<'a> KeyComponent<'a> {
@@ -28,8 +26,11 @@ impl<'a> KeyComponent<'a> {
         match *self {
             KeyComponent::Uuid(_) => 16,
             KeyComponent::UnsizedString(s) => s.len(),
+            KeyComponent::NullTerminatedString(s) => s.len() + 1,
+            KeyComponent::SizedString(s) => s.len() + 4,
             KeyComponent::Type(t) => t.0.len() + 1, 
             KeyComponent::DateTime(_) => 8,
+            KeyComponent::Weight(_) => 4,
         }
     }
 
@@ -41,6 +42,17 @@ impl<'a> KeyComponent<'a> {
             KeyComponent::UnsizedString(s) => {
                 cursor.write_all(s.as_bytes())?;
             }
+            KeyComponent::NullTerminatedString(s) => {
+                debug_assert!(!s.contains('\0'));
+                cursor.write_all(s.as_bytes())?;
+                cursor.write_all(&[0])?;
+            }
+            KeyComponent::SizedString(s) => {
+                // Unlike null-terminated strings, these can hold any string,
+                // but don't sort in string order
+                cursor.write_u32::<BigEndian>(s.len() as u32)?;
+                cursor.write_all(s.as_bytes())?;
+            }
             KeyComponent::Type(t) => {
                 cursor.write_all(&[t.0.len() as u8])?;
                 cursor.write_all(t.0.as_bytes())?;
@@ -49,12 +61,27 @@ impl<'a> KeyComponent<'a> {
                 let time_to_end = nanos_since_epoch(&MAX_DATETIME) - nanos_since_epoch(&datetime);
                 cursor.write_u64::<BigEndian>(time_to_end)?;
             }
+            KeyComponent::Weight(weight) => {
+                // Like datetimes, weights are sorted in descending order
+                cursor.write_u32::<BigEndian>(!ordered_weight_bits(weight))?;
+            }
         };
 
         Ok(())
     }
 }
 
+/// Maps a weight to bits that sort in the same order as the weight.
+fn ordered_weight_bits(weight: models::Weight) -> u32 {
+    let bits = weight.value().to_bits();
+
+    if bits & 0x8000_0000 == 0 {
+        bits | 0x8000_0000
+    } else {
+        !bits
+    }
+}
+
 pub fn build_key(components: Vec<KeyComponent>) -> Box<[u8]> {
     let len = components.iter().fold(0, |len, component| len + component.len());
     let mut cursor: Cursor<Vec<u8>> = Cursor::new(Vec::with_capacity(len));
@@ -92,6 +119,13 @@ pub fn read_short_sized_string(cursor: &mut Cursor<Box<[u8]>>) -> String {
     str::from_utf8(&buf).unwrap().to_string()
 }
 
+pub fn read_sized_string(cursor: &mut Cursor<Box<[u8]>>) -> String {
+    let len = cursor.read_u32::<BigEndian>().unwrap() as usize;
+    let mut buf = vec![0u8; len];
+    cursor.read_exact(&mut buf).unwrap();
+    str::from_utf8(&buf).unwrap().to_string()
+}
+
 pub fn read_type(mut cursor: &mut Cursor<Box<[u8]>>) -> models::Type {
     models::Type::new(read_short_sized_string(&mut cursor)).unwrap()
 }
@@ -103,8 +137,35 @@ pub fn read_unsized_string(cursor: &mut Cursor<Box<[u8]>>) -> String {
 
 }
 
+pub fn read_null_terminated_string(cursor: &mut Cursor<Box<[u8]>>) -> String {
+    let mut buf = vec![];
+    cursor.read_until(0, &mut buf).unwrap();
+    buf.pop();
+    String::from_utf8(buf).unwrap()
+}
+
 pub fn read_datetime(cursor: &mut Cursor<Box<[u8]>>) -> DateTime<UTC> {
     let time_to_end = cursor.read_u64::<BigEndian>().unwrap();
     assert!(time_to_end <= i64::MAX as u64);
     *MAX_DATETIME - Duration::nanoseconds(time_to_end as i64)
 }
+
+pub fn read_weight(cursor: &mut Cursor<Box<[u8]>>) -> models::Weight {
+    let ordered_bits = !cursor.read_u32::<BigEndian>().unwrap();
+
+    let bits = if ordered_bits & 0x8000_0000 == 0 {
+        !ordered_bits
+    } else {
+        ordered_bits & !0x8000_0000
+    };
+
+    models::Weight::new(f32::from_bits(bits)).unwrap()
+}
+
+/// Returns a key that sorts after every key that starts with `prefix` and is
+/// followed by `suffix_len` more bytes.
+pub fn prefix_upper_bound(prefix: &[u8], suffix_len: usize) -> Box<[u8]> {
+    let mut key = prefix.to_vec();
+    key.extend(vec![u8::MAX; suffix_len + 1]);
+    key.into_boxed_slice()
+}
@@ -1,12 +1,11 @@
 use models;
 use uuid::Uuid;
 use errors::Error;
-use util::{generate_random_secret, get_salted_hash, parent_uuid, child_uuid};
+use util::{generate_random_secret, get_salted_hash, parent_uuid, child_uuid, MAX_DATETIME};
 use serde_json::Value as JsonValue;
 use chrono::{DateTime, UTC};
-use rocksdb::{DB, IteratorMode, Direction, WriteBatch, DBIterator, ColumnFamily};
-use super::models::{AccountValue, EdgeValue, VertexValue};
-use std::sync::Arc;
+use super::buffer::{Batch, BufferedIterator, DBIteratorItem, WriteBuffer};
+use super::models::{AccountValue, EdgeValue, ExpiringItem, VertexValue};
 use std::u8;
 use serde_json;
 use super::keys::*;
@@ -14,11 +13,25 @@ use std::io::Cursor;
 use bincode;
 use serde::Serialize;
 
-pub type DBIteratorItem = (Box<[u8]>, Box<[u8]>);
+pub type GlobalMetadataItem = Result<(String, JsonValue), Error>;
 pub type OwnedMetadataItem = Result<((Uuid, String), JsonValue), Error>;
+pub type VertexOwnerItem = Result<(Uuid, Uuid), Error>;
+pub type VertexTypeItem = Result<(models::Type, Uuid), Error>;
 pub type VertexItem = Result<(Uuid, VertexValue), Error>;
+pub type EdgeItem = Result<((Uuid, models::Type, Uuid), EdgeValue), Error>;
+pub type EdgeCreatedDatetimeItem = Result<((Uuid, models::Type, Uuid), DateTime<UTC>), Error>;
 pub type EdgeRangeItem = Result<((Uuid, models::Type, DateTime<UTC>, Uuid), models::Weight), Error>;
+pub type EdgeWeightRangeItem = Result<((Uuid, models::Type, models::Weight, Uuid), DateTime<UTC>), Error>;
+pub type EdgeHistoryTypeItem = Result<models::Type, Error>;
+pub type EdgeVersionItem = Result<((Uuid, models::Type, Uuid, DateTime<UTC>), Option<(models::Weight, u64)>), Error>;
 pub type EdgeMetadataItem = Result<((Uuid, models::Type, Uuid, String), JsonValue), Error>;
+pub type VertexMetadataNameItem = Result<(String, Uuid), Error>;
+pub type EdgeMetadataNameItem = Result<(String, Uuid, models::Type, Uuid), Error>;
+pub type VertexMetadataIndexItem = Result<(Uuid, models::VertexMetadataIndex), Error>;
+pub type VertexMetadataIndexValueItem = Result<(Uuid, String, Uuid), Error>;
+pub type UniqueConstraintItem = Result<(Uuid, models::UniqueConstraint), Error>;
+pub type UniqueValueItem = Result<(Uuid, String, Uuid), Error>;
+pub type ExpiryRangeItem = Result<(DateTime<UTC>, ExpiringItem), Error>;
 
 fn bincode_serialize_value<T: Serialize>(value: &T) -> Result<Box<[u8]>, Error> {
     let result = bincode::serialize(value, bincode::Infinite)?;
@@ -35,37 +48,39 @@ fn json_deserialize_value(value: &[u8]) -> Result<JsonValue, Error> {
     Ok(result)
 }
 
-fn exists(db: &DB, cf: ColumnFamily, key: Box<[u8]>) -> Result<bool, Error> {
-    match db.get_cf(cf, &key)? {
+fn exists(buffer: &WriteBuffer, cf: &'static str, key: Box<[u8]>) -> Result<bool, Error> {
+    match buffer.get_cf(cf, &key)? {
         Some(_) => Ok(true),
         None => Ok(false),
     }
 }
 
-fn set_bincode<T: Serialize>(db: &DB,
-                             cf: ColumnFamily,
+fn set_bincode<T: Serialize>(batch: &mut Batch,
+                             cf: &'static str,
                              key: Box<[u8]>,
                              value: &T)
                              -> Result<(), Error> {
-    db.put_cf(cf, &key, &bincode_serialize_value(value)?)?;
+    batch.put_cf(cf, key, bincode_serialize_value(value)?);
     Ok(())
 }
 
-fn get_json(db: &DB, cf: ColumnFamily, key: Box<[u8]>) -> Result<Option<JsonValue>, Error> {
-    match db.get_cf(cf, &key)? {
+fn get_json(buffer: &WriteBuffer, cf: &'static str, key: Box<[u8]>) -> Result<Option<JsonValue>, Error> {
+    match buffer.get_cf(cf, &key)? {
         Some(value_bytes) => Ok(Some(json_deserialize_value(&value_bytes)?)),
         None => Ok(None),
     }
 }
 
-fn set_json(db: &DB, cf: ColumnFamily, key: Box<[u8]>, value: &JsonValue) -> Result<(), Error> {
-    db.put_cf(cf, &key, &json_serialize_value(value)?)?;
+fn set_json(batch: &mut Batch, cf: &'static str, key: Box<[u8]>, value: &JsonValue) -> Result<(), Error> {
+    batch.put_cf(cf, key, json_serialize_value(value)?);
     Ok(())
 }
 
-fn take_while_prefixed<'a>(iterator: DBIterator,
-                           prefix: Box<[u8]>)
-                           -> Box<Iterator<Item = DBIteratorItem> + 'a> {
+fn take_while_prefixed<'a, I>(iterator: I,
+                              prefix: Box<[u8]>)
+                              -> Box<Iterator<Item = DBIteratorItem> + 'a>
+    where I: Iterator<Item = DBIteratorItem> + 'a
+{
     let filtered = iterator.take_while(move |item| -> bool {
         let (ref k, _) = *item;
         k.starts_with(&prefix)
@@ -74,20 +89,18 @@ fn take_while_prefixed<'a>(iterator: DBIterator,
     Box::new(filtered)
 }
 
-fn iterate_metadata_for_owner<'a>
-    (db: &DB,
-     cf: ColumnFamily,
-     id: Uuid)
+fn iterate_metadata<'a>
+    (buffer: &WriteBuffer,
+     cf: &'static str,
+     prefix: Box<[u8]>)
      -> Result<Box<Iterator<Item = OwnedMetadataItem> + 'a>, Error> {
-    let prefix = build_key(vec![KeyComponent::Uuid(id)]);
-    let iterator = db.iterator_cf(cf, IteratorMode::From(&prefix, Direction::Forward))?;
+    let iterator = buffer.iterator_cf(cf, &prefix)?;
     let filtered = take_while_prefixed(iterator, prefix);
 
     let mapped = filtered.map(move |item| -> Result<((Uuid, String), JsonValue), Error> {
         let (k, v) = item;
         let mut cursor = Cursor::new(k);
         let owner_id = read_uuid(&mut cursor);
-        debug_assert_eq!(id, owner_id);
         let name = read_unsized_string(&mut cursor);
         let value = json_deserialize_value(&v.to_owned()[..])?;
         Ok(((owner_id, name), value))
@@ -96,17 +109,25 @@ fn iterate_metadata_for_owner<'a>
     Ok(Box::new(mapped))
 }
 
+fn iterate_metadata_for_owner<'a>
+    (buffer: &WriteBuffer,
+     cf: &'static str,
+     id: Uuid)
+     -> Result<Box<Iterator<Item = OwnedMetadataItem> + 'a>, Error> {
+    iterate_metadata(buffer, cf, build_key(vec![KeyComponent::Uuid(id)]))
+}
+
 pub struct AccountManager {
-    pub db: Arc<DB>,
-    pub cf: ColumnFamily,
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
     secure_uuids: bool
 }
 
 impl AccountManager {
-    pub fn new(db: Arc<DB>, secure_uuids: bool) -> Self {
+    pub fn new(buffer: WriteBuffer, secure_uuids: bool) -> Self {
         AccountManager {
-            cf: db.cf_handle("accounts:v1").unwrap(),
-            db: db,
+            cf: "accounts:v1",
+            buffer: buffer,
             secure_uuids: secure_uuids
         }
     }
@@ -116,41 +137,37 @@ impl AccountManager {
     }
 
     pub fn exists(&self, id: Uuid) -> Result<bool, Error> {
-        exists(&self.db, self.cf, self.key(id))
+        exists(&self.buffer, self.cf, self.key(id))
     }
 
     pub fn get(&self, id: Uuid) -> Result<Option<AccountValue>, Error> {
-        match self.db.get_cf(self.cf, &self.key(id))? {
+        match self.buffer.get_cf(self.cf, &self.key(id))? {
             Some(value_bytes) => Ok(Some(bincode::deserialize(&value_bytes)?)),
             None => Ok(None),
         }
     }
 
-    pub fn create(&self) -> Result<(Uuid, String), Error> {
+    pub fn create(&self, mut batch: &mut Batch) -> Result<(Uuid, String), Error> {
         let id = parent_uuid();
         let salt = generate_random_secret();
         let secret = generate_random_secret();
         let hash = get_salted_hash(&salt[..], None, &secret[..]);
         let value = AccountValue::new(salt, hash);
-        set_bincode(&self.db, self.cf, self.key(id), &value)?;
+        set_bincode(&mut batch, self.cf, self.key(id), &value)?;
         Ok((id, secret))
     }
 
-    pub fn delete(&self, mut batch: &mut WriteBatch, id: Uuid) -> Result<(), Error> {
-        batch.delete_cf(self.cf, &self.key(id))?;
-
-        // NOTE: This currently does a sequential scan through all keys to
-        // find which vertices to delete. This could be more efficient.
-        let vertex_manager = VertexManager::new(self.db.clone(), self.secure_uuids);
-        for item in vertex_manager.iterate_all()? {
-            let (vertex_id, vertex_value) = item?;
+    pub fn delete(&self, mut batch: &mut Batch, id: Uuid) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(id));
 
-            if vertex_value.owner_id == id {
-                vertex_manager.delete(&mut batch, vertex_id)?;
-            }
+        let vertex_manager = VertexManager::new(self.buffer.clone(), self.secure_uuids);
+        let vertex_owner_manager = VertexOwnerManager::new(self.buffer.clone());
+        for item in vertex_owner_manager.iterate_for_owner(id)? {
+            let (_, vertex_id) = item?;
+            vertex_manager.delete(&mut batch, vertex_id)?;
         }
 
-        let account_metadata_manager = AccountMetadataManager::new(self.db.clone());
+        let account_metadata_manager = AccountMetadataManager::new(self.buffer.clone());
 
         for item in account_metadata_manager.iterate_for_owner(id)? {
             let ((account_metadata_owner_id, account_metadata_name), _) = item?;
@@ -164,16 +181,16 @@ impl AccountManager {
 }
 
 pub struct VertexManager {
-    pub db: Arc<DB>,
-    pub cf: ColumnFamily,
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
     secure_uuids: bool
 }
 
 impl VertexManager {
-    pub fn new(db: Arc<DB>, secure_uuids: bool) -> Self {
+    pub fn new(buffer: WriteBuffer, secure_uuids: bool) -> Self {
         VertexManager {
-            cf: db.cf_handle("vertices:v1").unwrap(),
-            db: db,
+            cf: "vertices:v1",
+            buffer: buffer,
             secure_uuids: secure_uuids
         }
     }
@@ -183,17 +200,23 @@ impl VertexManager {
     }
 
     pub fn exists(&self, id: Uuid) -> Result<bool, Error> {
-        exists(&self.db, self.cf, self.key(id))
+        exists(&self.buffer, self.cf, self.key(id))
+    }
+
+    /// Watches a vertex, so that committing fails with `Error::Conflict` if
+    /// another transaction creates or deletes it first.
+    pub fn watch(&self, id: Uuid) -> Result<(), Error> {
+        self.buffer.watch_cf(self.cf, &self.key(id))
     }
 
     pub fn get(&self, id: Uuid) -> Result<Option<VertexValue>, Error> {
-        match self.db.get_cf(self.cf, &self.key(id))? {
+        match self.buffer.get_cf(self.cf, &self.key(id))? {
             Some(value_bytes) => Ok(Some(bincode::deserialize(&value_bytes)?)),
             None => Ok(None),
         }
     }
 
-    fn iterate<'a>(&self, iterator: DBIterator) -> Result<Box<Iterator<Item=VertexItem> + 'a>, Error> {
+    fn iterate<'a>(&self, iterator: BufferedIterator) -> Result<Box<Iterator<Item=VertexItem> + 'a>, Error> {
         let mapped = iterator.map(|item| -> VertexItem {
             let (k, v) = item;
             let id = parse_uuid_key(k);
@@ -205,91 +228,233 @@ impl VertexManager {
     }
 
     pub fn iterate_all(&self) -> Result<Box<Iterator<Item=VertexItem>>, Error> {
-        let iterator = self.db
-            .iterator_cf(self.cf, IteratorMode::From(b"", Direction::Forward))?;
+        let iterator = self.buffer.iterator_cf(self.cf, b"")?;
         self.iterate(iterator)
     }
 
     pub fn iterate_for_range<'a>(&self, id: Uuid) -> Result<Box<Iterator<Item = VertexItem> + 'a>, Error> {
         let low_key = build_key(vec![KeyComponent::Uuid(id)]);
-        let iterator = self.db
-            .iterator_cf(self.cf, IteratorMode::From(&low_key, Direction::Forward))?;
+        let iterator = self.buffer.iterator_cf(self.cf, &low_key)?;
         self.iterate(iterator)
     }
     
-    pub fn create(&self, t: models::Type, account_id: Uuid) -> Result<Uuid, Error> {
+    pub fn create(&self, mut batch: &mut Batch, t: models::Type, account_id: Uuid) -> Result<Uuid, Error> {
         let id = if self.secure_uuids {
             parent_uuid()
         } else {
             child_uuid(account_id)
         };
 
-        let value = VertexValue::new(account_id, t);
-        set_bincode(&self.db, self.cf, self.key(id), &value)?;
+        self.create_with_id(&mut batch, id, t, account_id)?;
         Ok(id)
     }
 
-    pub fn delete(&self, mut batch: &mut WriteBatch, id: Uuid) -> Result<(), Error> {
-        batch.delete_cf(self.cf, &self.key(id))?;
+    pub fn create_with_id(&self, mut batch: &mut Batch, id: Uuid, t: models::Type, account_id: Uuid) -> Result<(), Error> {
+        let value = VertexValue::new(account_id, t);
+        set_bincode(&mut batch, self.cf, self.key(id), &value)?;
+        VertexOwnerManager::new(self.buffer.clone()).set(&mut batch, account_id, id)?;
+        VertexTypeManager::new(self.buffer.clone()).set(&mut batch, &value.t, id)?;
+        Ok(())
+    }
 
-        let vertex_metadata_manager = VertexMetadataManager::new(self.db.clone());
-        for item in vertex_metadata_manager.iterate_for_owner(id)? {
-            let ((vertex_metadata_owner_id, vertex_metadata_name), _) = item?;
-            vertex_metadata_manager.delete(&mut batch,
-                                                vertex_metadata_owner_id,
-                                                &vertex_metadata_name[..])?;
+    pub fn delete(&self, mut batch: &mut Batch, id: Uuid) -> Result<(), Error> {
+        if let Some(value) = self.get(id)? {
+            VertexOwnerManager::new(self.buffer.clone()).delete(&mut batch, value.owner_id, id)?;
+            VertexTypeManager::new(self.buffer.clone()).delete(&mut batch, &value.t, id)?;
+
+            let vertex_metadata_manager = VertexMetadataManager::new(self.buffer.clone());
+            for item in vertex_metadata_manager.iterate_for_owner(id)? {
+                let ((vertex_metadata_owner_id, vertex_metadata_name), _) = item?;
+                vertex_metadata_manager.delete(&mut batch,
+                                                    vertex_metadata_owner_id,
+                                                    &value.t,
+                                                    &vertex_metadata_name[..])?;
+            }
         }
 
-        let edge_manager = EdgeManager::new(self.db.clone());
+        batch.delete_cf(self.cf, self.key(id));
+        ExpiryManager::new(self.buffer.clone()).delete(&mut batch, &ExpiringItem::Vertex(id))?;
+
+        let edge_manager = EdgeManager::new(self.buffer.clone());
 
         {
-            let edge_range_manager = EdgeRangeManager::new(self.db.clone());
+            let edge_range_manager = EdgeRangeManager::new(self.buffer.clone());
             for item in edge_range_manager.iterate_for_owner(id)? {
                 let ((edge_range_outbound_id,
                       edge_range_t,
                       edge_range_update_datetime,
                       edge_range_inbound_id),
-                     _) = item?;
+                     edge_range_weight) = item?;
                 debug_assert_eq!(edge_range_outbound_id, id);
                 edge_manager.delete(&mut batch,
                                          edge_range_outbound_id,
                                          &edge_range_t,
                                          edge_range_inbound_id,
-                                         edge_range_update_datetime)?;
+                                         edge_range_update_datetime,
+                                         edge_range_weight)?;
             }
         }
 
         {
-            let reversed_edge_range_manager = EdgeRangeManager::new_reversed(self.db.clone());
+            let reversed_edge_range_manager = EdgeRangeManager::new_reversed(self.buffer.clone());
             for item in reversed_edge_range_manager.iterate_for_owner(id)? {
                 let ((reversed_edge_range_inbound_id,
                       reversed_edge_range_t,
                       reversed_edge_range_update_datetime,
                       reversed_edge_range_outbound_id),
-                     _) = item?;
+                     reversed_edge_range_weight) = item?;
                 debug_assert_eq!(reversed_edge_range_inbound_id, id);
                 edge_manager.delete(&mut batch,
                                          reversed_edge_range_outbound_id,
                                          &reversed_edge_range_t,
                                          reversed_edge_range_inbound_id,
-                                         reversed_edge_range_update_datetime)?;
+                                         reversed_edge_range_update_datetime,
+                                         reversed_edge_range_weight)?;
             }
         }
 
+        // Edge history outlives deleted edges, but not their vertices
+        let edge_history_manager = EdgeHistoryManager::new(self.buffer.clone());
+        let reversed_edge_history_manager = EdgeHistoryManager::new_reversed(self.buffer.clone());
+
+        for item in edge_history_manager.iterate_for_owner(id)? {
+            let ((outbound_id, t, inbound_id, datetime), _) = item?;
+            edge_history_manager.delete(&mut batch, outbound_id, &t, inbound_id, datetime)?;
+            reversed_edge_history_manager.delete(&mut batch, inbound_id, &t, outbound_id, datetime)?;
+        }
+
+        for item in reversed_edge_history_manager.iterate_for_owner(id)? {
+            let ((inbound_id, t, outbound_id, datetime), _) = item?;
+            edge_history_manager.delete(&mut batch, outbound_id, &t, inbound_id, datetime)?;
+            reversed_edge_history_manager.delete(&mut batch, inbound_id, &t, outbound_id, datetime)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct VertexOwnerManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl VertexOwnerManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        VertexOwnerManager {
+            cf: "vertex_owners:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, owner_id: Uuid, vertex_id: Uuid) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Uuid(owner_id), KeyComponent::Uuid(vertex_id)])
+    }
+
+    fn iterate<'a>(&self, prefix: Box<[u8]>) -> Result<Box<Iterator<Item = VertexOwnerItem> + 'a>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, &prefix)?;
+        let filtered = take_while_prefixed(iterator, prefix);
+
+        let mapped = filtered.map(|item| -> VertexOwnerItem {
+            let (k, _) = item;
+            let mut cursor = Cursor::new(k);
+            let owner_id = read_uuid(&mut cursor);
+            let vertex_id = read_uuid(&mut cursor);
+            Ok((owner_id, vertex_id))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
+    pub fn iterate_for_owner<'a>(&self, owner_id: Uuid) -> Result<Box<Iterator<Item = VertexOwnerItem> + 'a>, Error> {
+        self.iterate(build_key(vec![KeyComponent::Uuid(owner_id)]))
+    }
+
+    pub fn iterate_all<'a>(&self) -> Result<Box<Iterator<Item = VertexOwnerItem> + 'a>, Error> {
+        self.iterate(Box::new([]))
+    }
+
+    pub fn exists(&self, owner_id: Uuid, vertex_id: Uuid) -> Result<bool, Error> {
+        exists(&self.buffer, self.cf, self.key(owner_id, vertex_id))
+    }
+
+    pub fn set(&self, mut batch: &mut Batch, owner_id: Uuid, vertex_id: Uuid) -> Result<(), Error> {
+        batch.put_cf(self.cf, self.key(owner_id, vertex_id), Box::new([]));
+        Ok(())
+    }
+
+    pub fn delete(&self, mut batch: &mut Batch, owner_id: Uuid, vertex_id: Uuid) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(owner_id, vertex_id));
+        Ok(())
+    }
+}
+
+pub struct VertexTypeManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl VertexTypeManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        VertexTypeManager {
+            cf: "vertex_types:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, t: &models::Type, vertex_id: Uuid) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Type(t), KeyComponent::Uuid(vertex_id)])
+    }
+
+    fn iterate<'a>(&self, iterator: BufferedIterator, prefix: Box<[u8]>) -> Result<Box<Iterator<Item = VertexTypeItem> + 'a>, Error> {
+        let filtered = take_while_prefixed(iterator, prefix);
+
+        let mapped = filtered.map(|item| -> VertexTypeItem {
+            let (k, _) = item;
+            let mut cursor = Cursor::new(k);
+            let t = read_type(&mut cursor);
+            let vertex_id = read_uuid(&mut cursor);
+            Ok((t, vertex_id))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
+    pub fn iterate_for_range<'a>(&self, t: &models::Type, vertex_id: Uuid) -> Result<Box<Iterator<Item = VertexTypeItem> + 'a>, Error> {
+        let prefix = build_key(vec![KeyComponent::Type(t)]);
+        let iterator = self.buffer.iterator_cf(self.cf, &self.key(t, vertex_id))?;
+        self.iterate(iterator, prefix)
+    }
+
+    pub fn iterate_all<'a>(&self) -> Result<Box<Iterator<Item = VertexTypeItem> + 'a>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, b"")?;
+        self.iterate(iterator, Box::new([]))
+    }
+
+    pub fn exists(&self, t: &models::Type, vertex_id: Uuid) -> Result<bool, Error> {
+        exists(&self.buffer, self.cf, self.key(t, vertex_id))
+    }
+
+    pub fn set(&self, mut batch: &mut Batch, t: &models::Type, vertex_id: Uuid) -> Result<(), Error> {
+        batch.put_cf(self.cf, self.key(t, vertex_id), Box::new([]));
+        Ok(())
+    }
+
+    pub fn delete(&self, mut batch: &mut Batch, t: &models::Type, vertex_id: Uuid) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(t, vertex_id));
         Ok(())
     }
 }
 
 pub struct EdgeManager {
-    pub db: Arc<DB>,
-    pub cf: ColumnFamily,
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
 }
 
 impl EdgeManager {
-    pub fn new(db: Arc<DB>) -> Self {
+    pub fn new(buffer: WriteBuffer) -> Self {
         EdgeManager {
-            cf: db.cf_handle("edges:v1").unwrap(),
-            db: db,
+            cf: "edges:v1",
+            buffer: buffer,
         }
     }
 
@@ -304,33 +469,68 @@ impl EdgeManager {
                t: &models::Type,
                inbound_id: Uuid)
                -> Result<Option<EdgeValue>, Error> {
-        match self.db.get_cf(self.cf, &self.key(outbound_id, t, inbound_id))? {
+        match self.buffer.get_cf(self.cf, &self.key(outbound_id, t, inbound_id))? {
             Some(value_bytes) => Ok(Some(bincode::deserialize(&value_bytes)?)),
             None => Ok(None),
         }
     }
 
+    pub fn iterate_all(&self) -> Result<Box<Iterator<Item = EdgeItem>>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, b"")?;
+
+        let mapped = iterator.map(|item| -> EdgeItem {
+            let (k, v) = item;
+            let mut cursor = Cursor::new(k);
+            let outbound_id = read_uuid(&mut cursor);
+            let t = read_type(&mut cursor);
+            let inbound_id = read_uuid(&mut cursor);
+            let value: EdgeValue = bincode::deserialize(&v.to_owned()[..])?;
+            Ok(((outbound_id, t, inbound_id), value))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
     pub fn set(&self,
-               mut batch: &mut WriteBatch,
+               mut batch: &mut Batch,
                outbound_id: Uuid,
                t: &models::Type,
                inbound_id: Uuid,
                new_update_datetime: DateTime<UTC>,
                weight: models::Weight)
-               -> Result<(), Error> {
-        let edge_range_manager = EdgeRangeManager::new(self.db.clone());
-        let reversed_edge_range_manager = EdgeRangeManager::new_reversed(self.db.clone());
-
-        if let Some(existing_edge_value) = self.get(outbound_id, t, inbound_id)? {
-            edge_range_manager.delete(&mut batch, outbound_id, t, existing_edge_value.update_datetime, inbound_id)?;
-            reversed_edge_range_manager.delete(&mut batch, inbound_id, t, existing_edge_value.update_datetime, outbound_id)?;
-        }
+               -> Result<u64, Error> {
+        let edge_range_manager = EdgeRangeManager::new(self.buffer.clone());
+        let reversed_edge_range_manager = EdgeRangeManager::new_reversed(self.buffer.clone());
+
+        let edge_weight_range_manager = EdgeWeightRangeManager::new(self.buffer.clone());
+        let reversed_edge_weight_range_manager = EdgeWeightRangeManager::new_reversed(self.buffer.clone());
+
+        let edge_created_datetime_manager = EdgeCreatedDatetimeManager::new(self.buffer.clone());
+        let edge_created_range_manager = EdgeRangeManager::new_created(self.buffer.clone());
+        let reversed_edge_created_range_manager = EdgeRangeManager::new_reversed_created(self.buffer.clone());
+
+        let edge_version_number_manager = EdgeVersionNumberManager::new(self.buffer.clone());
+
+        // Updated edges keep their original created datetime, so their
+        // created range entries only need their weight updated
+        let (created_datetime, version) = match self.get(outbound_id, t, inbound_id)? {
+            Some(existing_edge_value) => {
+                edge_range_manager.delete(&mut batch, outbound_id, t, existing_edge_value.update_datetime, inbound_id)?;
+                reversed_edge_range_manager.delete(&mut batch, inbound_id, t, existing_edge_value.update_datetime, outbound_id)?;
+                edge_weight_range_manager.delete(&mut batch, outbound_id, t, existing_edge_value.weight, inbound_id)?;
+                reversed_edge_weight_range_manager.delete(&mut batch, inbound_id, t, existing_edge_value.weight, outbound_id)?;
+                let created_datetime = edge_created_datetime_manager.get(outbound_id, t, inbound_id)?.unwrap_or(existing_edge_value.update_datetime);
+                let version = edge_version_number_manager.get(outbound_id, t, inbound_id)?.unwrap_or(1);
+                (created_datetime, version + 1)
+            },
+            None => (new_update_datetime, 1)
+        };
 
         let new_edge_value = EdgeValue::new(new_update_datetime, weight);
-        set_bincode(&self.db,
-                         self.cf,
-                         self.key(outbound_id, t, inbound_id),
-                         &new_edge_value)?;
+        set_bincode(&mut batch,
+                    self.cf,
+                    self.key(outbound_id, t, inbound_id),
+                    &new_edge_value)?;
         edge_range_manager.set(&mut batch,
                                     outbound_id,
                                     t,
@@ -343,25 +543,97 @@ impl EdgeManager {
                                              new_update_datetime,
                                              outbound_id,
                                              weight)?;
+        edge_weight_range_manager.set(&mut batch,
+                                      outbound_id,
+                                      t,
+                                      weight,
+                                      inbound_id,
+                                      new_update_datetime)?;
+        reversed_edge_weight_range_manager.set(&mut batch,
+                                               inbound_id,
+                                               t,
+                                               weight,
+                                               outbound_id,
+                                               new_update_datetime)?;
+        edge_created_datetime_manager.set(&mut batch, outbound_id, t, inbound_id, created_datetime)?;
+        edge_created_range_manager.set(&mut batch, outbound_id, t, created_datetime, inbound_id, weight)?;
+        reversed_edge_created_range_manager.set(&mut batch, inbound_id, t, created_datetime, outbound_id, weight)?;
+        edge_version_number_manager.set(&mut batch, outbound_id, t, inbound_id, version)?;
+        self.set_history_version(&mut batch, outbound_id, t, inbound_id, new_update_datetime, Some((weight, version)))?;
+        ExpiryManager::new(self.buffer.clone()).delete(&mut batch, &ExpiringItem::Edge(outbound_id, t.clone(), inbound_id))?;
+        Ok(version)
+    }
+
+    /// Gets the version number of an edge, or `None` if it doesn't exist.
+    pub fn get_version_number(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Result<Option<u64>, Error> {
+        if self.get(outbound_id, t, inbound_id)?.is_none() {
+            return Ok(None);
+        }
+
+        let version = EdgeVersionNumberManager::new(self.buffer.clone()).get(outbound_id, t, inbound_id)?;
+        Ok(Some(version.unwrap_or(1)))
+    }
+
+    /// Watches an edge, so that committing fails with `Error::Conflict` if
+    /// another transaction writes to it first.
+    pub fn watch(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Result<(), Error> {
+        let edge_version_number_manager = EdgeVersionNumberManager::new(self.buffer.clone());
+        self.buffer.watch_cf(self.cf, &self.key(outbound_id, t, inbound_id))?;
+        self.buffer.watch_cf(edge_version_number_manager.cf, &edge_version_number_manager.key(outbound_id, t, inbound_id))
+    }
+
+    /// Keeps a version of an edge, if history is enabled for its type. A
+    /// version without a weight and version number marks the edge as
+    /// deleted.
+    pub fn set_history_version(&self,
+                               mut batch: &mut Batch,
+                               outbound_id: Uuid,
+                               t: &models::Type,
+                               inbound_id: Uuid,
+                               datetime: DateTime<UTC>,
+                               value: Option<(models::Weight, u64)>)
+                               -> Result<(), Error> {
+        if EdgeHistoryTypeManager::new(self.buffer.clone()).exists(t)? {
+            EdgeHistoryManager::new(self.buffer.clone()).set(&mut batch, outbound_id, t, inbound_id, datetime, value)?;
+            EdgeHistoryManager::new_reversed(self.buffer.clone()).set(&mut batch, inbound_id, t, outbound_id, datetime, value)?;
+        }
+
         Ok(())
     }
 
     pub fn delete(&self,
-                  mut batch: &mut WriteBatch,
+                  mut batch: &mut Batch,
                   outbound_id: Uuid,
                   t: &models::Type,
                   inbound_id: Uuid,
-                  update_datetime: DateTime<UTC>)
+                  update_datetime: DateTime<UTC>,
+                  weight: models::Weight)
                   -> Result<(), Error> {
-        batch.delete_cf(self.cf, &self.key(outbound_id, t, inbound_id))?;
+        batch.delete_cf(self.cf, self.key(outbound_id, t, inbound_id));
 
-        let edge_range_manager = EdgeRangeManager::new(self.db.clone());
+        let edge_range_manager = EdgeRangeManager::new(self.buffer.clone());
         edge_range_manager.delete(&mut batch, outbound_id, t, update_datetime, inbound_id)?;
 
-        let reversed_edge_range_manager = EdgeRangeManager::new_reversed(self.db.clone());
+        let reversed_edge_range_manager = EdgeRangeManager::new_reversed(self.buffer.clone());
         reversed_edge_range_manager.delete(&mut batch, inbound_id, t, update_datetime, outbound_id)?;
 
-        let edge_metadata_manager = EdgeMetadataManager::new(self.db.clone());
+        let edge_weight_range_manager = EdgeWeightRangeManager::new(self.buffer.clone());
+        edge_weight_range_manager.delete(&mut batch, outbound_id, t, weight, inbound_id)?;
+
+        let reversed_edge_weight_range_manager = EdgeWeightRangeManager::new_reversed(self.buffer.clone());
+        reversed_edge_weight_range_manager.delete(&mut batch, inbound_id, t, weight, outbound_id)?;
+
+        let edge_created_datetime_manager = EdgeCreatedDatetimeManager::new(self.buffer.clone());
+        if let Some(created_datetime) = edge_created_datetime_manager.get(outbound_id, t, inbound_id)? {
+            edge_created_datetime_manager.delete(&mut batch, outbound_id, t, inbound_id)?;
+            EdgeRangeManager::new_created(self.buffer.clone()).delete(&mut batch, outbound_id, t, created_datetime, inbound_id)?;
+            EdgeRangeManager::new_reversed_created(self.buffer.clone()).delete(&mut batch, inbound_id, t, created_datetime, outbound_id)?;
+        }
+
+        EdgeVersionNumberManager::new(self.buffer.clone()).delete(&mut batch, outbound_id, t, inbound_id)?;
+        ExpiryManager::new(self.buffer.clone()).delete(&mut batch, &ExpiringItem::Edge(outbound_id, t.clone(), inbound_id))?;
+
+        let edge_metadata_manager = EdgeMetadataManager::new(self.buffer.clone());
         for item in edge_metadata_manager.iterate_for_owner(outbound_id, t, inbound_id)? {
             let ((edge_metadata_outbound_id,
                   edge_metadata_t,
@@ -379,23 +651,143 @@ impl EdgeManager {
     }
 }
 
+/// Tracks when each edge was first created. `edges:v1` only records when
+/// an edge was last updated.
+pub struct EdgeCreatedDatetimeManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl EdgeCreatedDatetimeManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        EdgeCreatedDatetimeManager {
+            cf: "edge_created_datetimes:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Uuid(outbound_id),
+                       KeyComponent::Type(t),
+                       KeyComponent::Uuid(inbound_id)])
+    }
+
+    pub fn get(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Result<Option<DateTime<UTC>>, Error> {
+        match self.buffer.get_cf(self.cf, &self.key(outbound_id, t, inbound_id))? {
+            Some(value_bytes) => Ok(Some(bincode::deserialize(&value_bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn iterate_all(&self) -> Result<Box<Iterator<Item = EdgeCreatedDatetimeItem>>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, b"")?;
+
+        let mapped = iterator.map(|item| -> EdgeCreatedDatetimeItem {
+            let (k, v) = item;
+            let mut cursor = Cursor::new(k);
+            let outbound_id = read_uuid(&mut cursor);
+            let t = read_type(&mut cursor);
+            let inbound_id = read_uuid(&mut cursor);
+            let created_datetime: DateTime<UTC> = bincode::deserialize(&v.to_owned()[..])?;
+            Ok(((outbound_id, t, inbound_id), created_datetime))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
+    pub fn set(&self,
+               mut batch: &mut Batch,
+               outbound_id: Uuid,
+               t: &models::Type,
+               inbound_id: Uuid,
+               created_datetime: DateTime<UTC>)
+               -> Result<(), Error> {
+        set_bincode(&mut batch, self.cf, self.key(outbound_id, t, inbound_id), &created_datetime)
+    }
+
+    pub fn delete(&self, batch: &mut Batch, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(outbound_id, t, inbound_id));
+        Ok(())
+    }
+}
+
+/// Tracks the version number of each edge. Edges that were last written
+/// before version numbers were tracked have no entry, and are at version 1.
+pub struct EdgeVersionNumberManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl EdgeVersionNumberManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        EdgeVersionNumberManager {
+            cf: "edge_version_numbers:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Uuid(outbound_id),
+                       KeyComponent::Type(t),
+                       KeyComponent::Uuid(inbound_id)])
+    }
+
+    pub fn get(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Result<Option<u64>, Error> {
+        match self.buffer.get_cf(self.cf, &self.key(outbound_id, t, inbound_id))? {
+            Some(value_bytes) => Ok(Some(bincode::deserialize(&value_bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set(&self,
+               mut batch: &mut Batch,
+               outbound_id: Uuid,
+               t: &models::Type,
+               inbound_id: Uuid,
+               version: u64)
+               -> Result<(), Error> {
+        set_bincode(&mut batch, self.cf, self.key(outbound_id, t, inbound_id), &version)
+    }
+
+    pub fn delete(&self, batch: &mut Batch, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(outbound_id, t, inbound_id));
+        Ok(())
+    }
+}
+
+/// Indexes edges by datetime. The plain and reversed managers cover when
+/// edges were last updated, and the created ones when they were created.
 pub struct EdgeRangeManager {
-    pub db: Arc<DB>,
-    pub cf: ColumnFamily,
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
 }
 
 impl EdgeRangeManager {
-    pub fn new(db: Arc<DB>) -> Self {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        EdgeRangeManager {
+            cf: "edge_ranges:v1",
+            buffer: buffer,
+        }
+    }
+
+    pub fn new_reversed(buffer: WriteBuffer) -> Self {
+        EdgeRangeManager {
+            cf: "reversed_edge_ranges:v1",
+            buffer: buffer,
+        }
+    }
+
+    pub fn new_created(buffer: WriteBuffer) -> Self {
         EdgeRangeManager {
-            cf: db.cf_handle("edge_ranges:v1").unwrap(),
-            db: db,
+            cf: "edge_created_ranges:v1",
+            buffer: buffer,
         }
     }
 
-    pub fn new_reversed(db: Arc<DB>) -> Self {
+    pub fn new_reversed_created(buffer: WriteBuffer) -> Self {
         EdgeRangeManager {
-            cf: db.cf_handle("reversed_edge_ranges:v1").unwrap(),
-            db: db,
+            cf: "reversed_edge_created_ranges:v1",
+            buffer: buffer,
         }
     }
 
@@ -411,7 +803,9 @@ impl EdgeRangeManager {
                        KeyComponent::Uuid(second_id)])
     }
 
-    fn iterate<'a>(&self, iterator: DBIterator, prefix: Box<[u8]>) -> Result<Box<Iterator<Item=EdgeRangeItem> + 'a>, Error> {
+    fn iterate<'a, I>(&self, iterator: I, prefix: Box<[u8]>) -> Result<Box<Iterator<Item=EdgeRangeItem> + 'a>, Error>
+        where I: Iterator<Item = DBIteratorItem> + 'a
+    {
         let filtered = take_while_prefixed(iterator, prefix);
 
         let mapped =
@@ -437,14 +831,12 @@ impl EdgeRangeManager {
                 let low_key = build_key(vec![KeyComponent::Uuid(id),
                                             KeyComponent::Type(t),
                                             KeyComponent::DateTime(high)]);
-                let iterator = self.db
-                    .iterator_cf(self.cf, IteratorMode::From(&low_key, Direction::Forward))?;
+                let iterator = self.buffer.iterator_cf(self.cf, &low_key)?;
                 self.iterate(iterator, prefix)
             },
             None => {
                 let prefix = build_key(vec![KeyComponent::Uuid(id)]);
-                let iterator = self.db
-                    .iterator_cf(self.cf, IteratorMode::From(&prefix, Direction::Forward))?;
+                let iterator = self.buffer.iterator_cf(self.cf, &prefix)?;
                 let mapped = self.iterate(iterator, prefix)?;
 
                 if let Some(high) = high {
@@ -467,15 +859,73 @@ impl EdgeRangeManager {
         }
     }
 
+    /// Iterates over the edges of a vertex in key order, starting after the
+    /// edge with the given type, update datetime and other vertex ID.
+    pub fn iterate_for_range_after<'a>(&self,
+                                       id: Uuid,
+                                       t: &Option<models::Type>,
+                                       after_t: &models::Type,
+                                       after_update_datetime: DateTime<UTC>,
+                                       after_second_id: Uuid)
+                                       -> Result<Box<Iterator<Item=EdgeRangeItem> + 'a>, Error> {
+        let prefix = match *t {
+            Some(ref t) => build_key(vec![KeyComponent::Uuid(id), KeyComponent::Type(t)]),
+            None => build_key(vec![KeyComponent::Uuid(id)])
+        };
+
+        let after_key = self.key(id, after_t, after_update_datetime, after_second_id);
+
+        // If the cursor is before the prefix, start at the beginning of it;
+        // if it's after the prefix, the iterator will immediately run out
+        let from = if after_key > prefix { after_key.clone() } else { prefix.clone() };
+        let iterator = self.buffer.iterator_cf(self.cf, &from)?;
+        let filtered = iterator.skip_while(move |&(ref k, _)| *k <= after_key);
+        self.iterate(filtered, prefix)
+    }
+
+    /// Iterates over the edges of a vertex from oldest to newest.
+    pub fn reverse_iterate_for_range<'a>(&self, id: Uuid, t: &Option<models::Type>) -> Result<Box<Iterator<Item=EdgeRangeItem> + 'a>, Error> {
+        let (prefix, before) = match *t {
+            Some(ref t) => {
+                let prefix = build_key(vec![KeyComponent::Uuid(id), KeyComponent::Type(t)]);
+                let before = prefix_upper_bound(&prefix, 24);
+                (prefix, before)
+            },
+            None => {
+                // The rest of the key is at most a max length type, a
+                // datetime and a uuid
+                let prefix = build_key(vec![KeyComponent::Uuid(id)]);
+                let before = prefix_upper_bound(&prefix, 256 + 24);
+                (prefix, before)
+            }
+        };
+
+        let iterator = self.buffer.reverse_iterator_cf(self.cf, &before)?;
+        self.iterate(iterator, prefix)
+    }
+
     pub fn iterate_for_owner<'a>(&self, id: Uuid) -> Result<Box<Iterator<Item=EdgeRangeItem> + 'a>, Error> {
         let prefix = build_key(vec![KeyComponent::Uuid(id)]);
-        let iterator = self.db
-            .iterator_cf(self.cf, IteratorMode::From(&prefix, Direction::Forward))?;
+        let iterator = self.buffer.iterator_cf(self.cf, &prefix)?;
         self.iterate(iterator, prefix)
     }
 
+    pub fn iterate_all<'a>(&self) -> Result<Box<Iterator<Item=EdgeRangeItem> + 'a>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, b"")?;
+        self.iterate(iterator, Box::new([]))
+    }
+
+    pub fn exists(&self,
+                  first_id: Uuid,
+                  t: &models::Type,
+                  update_datetime: DateTime<UTC>,
+                  second_id: Uuid)
+                  -> Result<bool, Error> {
+        exists(&self.buffer, self.cf, self.key(first_id, t, update_datetime, second_id))
+    }
+
     pub fn set(&self,
-               mut batch: &mut WriteBatch,
+               mut batch: &mut Batch,
                first_id: Uuid,
                t: &models::Type,
                update_datetime: DateTime<UTC>,
@@ -484,154 +934,1005 @@ impl EdgeRangeManager {
                -> Result<(), Error> {
         let key = self.key(first_id, t, update_datetime, second_id);
         let value = bincode_serialize_value(&weight)?;
-        batch.put_cf(self.cf, &key, &value)?;
+        batch.put_cf(self.cf, key, value);
         Ok(())
     }
 
     pub fn delete(&self,
-                  mut batch: &mut WriteBatch,
+                  mut batch: &mut Batch,
                   first_id: Uuid,
                   t: &models::Type,
                   update_datetime: DateTime<UTC>,
                   second_id: Uuid)
                   -> Result<(), Error> {
-        batch.delete_cf(self.cf, &self.key(first_id, t, update_datetime, second_id))?;
+        batch.delete_cf(self.cf, self.key(first_id, t, update_datetime, second_id));
         Ok(())
     }
 }
 
-pub struct GlobalMetadataManager {
-    pub db: Arc<DB>,
-    pub cf: ColumnFamily,
+pub struct EdgeWeightRangeManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
 }
 
-impl GlobalMetadataManager {
-    pub fn new(db: Arc<DB>) -> Self {
-        GlobalMetadataManager {
-            cf: db.cf_handle("global_metadata:v1").unwrap(),
-            db: db,
+impl EdgeWeightRangeManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        EdgeWeightRangeManager {
+            cf: "edge_weight_ranges:v1",
+            buffer: buffer,
         }
     }
 
-    fn key(&self, name: &str) -> Box<[u8]> {
-        build_key(vec![KeyComponent::UnsizedString(name)])
-    }
-
-    pub fn get(&self, name: &str) -> Result<Option<JsonValue>, Error> {
-        get_json(&self.db, self.cf, self.key(name))
-    }
-
-    pub fn set(&self, name: &str, value: &JsonValue) -> Result<(), Error> {
-        set_json(&self.db, self.cf, self.key(name), value)
+    pub fn new_reversed(buffer: WriteBuffer) -> Self {
+        EdgeWeightRangeManager {
+            cf: "reversed_edge_weight_ranges:v1",
+            buffer: buffer,
+        }
     }
 
-    pub fn delete(&self, mut batch: &mut WriteBatch, name: &str) -> Result<(), Error> {
-        batch.delete_cf(self.cf, &self.key(name))?;
-        Ok(())
+    fn key(&self,
+           first_id: Uuid,
+           t: &models::Type,
+           weight: models::Weight,
+           second_id: Uuid)
+           -> Box<[u8]> {
+        build_key(vec![KeyComponent::Uuid(first_id),
+                       KeyComponent::Type(t),
+                       KeyComponent::Weight(weight),
+                       KeyComponent::Uuid(second_id)])
     }
-}
 
-pub struct AccountMetadataManager {
-    pub db: Arc<DB>,
-    pub cf: ColumnFamily,
-}
+    fn iterate<'a>(&self, iterator: BufferedIterator, prefix: Box<[u8]>) -> Result<Box<Iterator<Item=EdgeWeightRangeItem> + 'a>, Error> {
+        let filtered = take_while_prefixed(iterator, prefix);
 
-impl AccountMetadataManager {
-    pub fn new(db: Arc<DB>) -> Self {
-        AccountMetadataManager {
-            cf: db.cf_handle("account_metadata:v1").unwrap(),
-            db: db,
-        }
-    }
+        let mapped =
+            filtered.map(move |item| -> EdgeWeightRangeItem {
+                let (k, v) = item;
+                let mut cursor = Cursor::new(k);
+                let first_id = read_uuid(&mut cursor);
+                let t = read_type(&mut cursor);
+                let weight = read_weight(&mut cursor);
+                let second_id = read_uuid(&mut cursor);
+                let update_datetime: DateTime<UTC> = bincode::deserialize(&v.to_owned()[..])?;
+                Ok(((first_id, t, weight, second_id), update_datetime))
+            });
 
-    fn key(&self, account_id: Uuid, name: &str) -> Box<[u8]> {
-        build_key(vec![KeyComponent::Uuid(account_id), KeyComponent::UnsizedString(name)])
+        Ok(Box::new(mapped))
     }
 
-    pub fn iterate_for_owner
-        (&self,
-         account_id: Uuid)
-         -> Result<Box<Iterator<Item = OwnedMetadataItem>>, Error> {
-        iterate_metadata_for_owner(&self.db, self.cf, account_id)
+    /// Iterates over the edges of a vertex with a given type whose weight is
+    /// between `low` and `high` inclusive. Edges are returned strongest
+    /// first, or weakest first if `reverse` is set.
+    pub fn iterate_for_range<'a>(&self,
+                                 id: Uuid,
+                                 t: &models::Type,
+                                 high: Option<models::Weight>,
+                                 low: Option<models::Weight>,
+                                 reverse: bool)
+                                 -> Result<Box<Iterator<Item=EdgeWeightRangeItem> + 'a>, Error> {
+        let prefix = build_key(vec![KeyComponent::Uuid(id), KeyComponent::Type(t)]);
+
+        if reverse {
+            let before = match low {
+                Some(low) => {
+                    let low_prefix = build_key(vec![KeyComponent::Uuid(id), KeyComponent::Type(t), KeyComponent::Weight(low)]);
+                    prefix_upper_bound(&low_prefix, 16)
+                },
+                None => prefix_upper_bound(&prefix, 20)
+            };
+
+            let iterator = self.buffer.reverse_iterator_cf(self.cf, &before)?;
+            let mapped = self.iterate(iterator, prefix)?;
+
+            match high {
+                Some(high) => Ok(Box::new(mapped.take_while(move |item| {
+                    match *item {
+                        Ok(((_, _, weight, _), _)) => weight <= high,
+                        Err(_) => true
+                    }
+                }))),
+                None => Ok(mapped)
+            }
+        } else {
+            let from = match high {
+                Some(high) => build_key(vec![KeyComponent::Uuid(id), KeyComponent::Type(t), KeyComponent::Weight(high)]),
+                None => prefix.clone()
+            };
+
+            let iterator = self.buffer.iterator_cf(self.cf, &from)?;
+            let mapped = self.iterate(iterator, prefix)?;
+
+            match low {
+                Some(low) => Ok(Box::new(mapped.take_while(move |item| {
+                    match *item {
+                        Ok(((_, _, weight, _), _)) => weight >= low,
+                        Err(_) => true
+                    }
+                }))),
+                None => Ok(mapped)
+            }
+        }
     }
 
-    pub fn exists(&self, account_id: Uuid, name: &str) -> Result<bool, Error> {
-        exists(&self.db, self.cf, self.key(account_id, name))
+    /// Gets the types of the edges of a vertex, seeking past the edges of
+    /// each type rather than reading them all.
+    pub fn get_types_for_owner(&self, id: Uuid) -> Result<Vec<models::Type>, Error> {
+        let prefix = build_key(vec![KeyComponent::Uuid(id)]);
+        let mut types: Vec<models::Type> = Vec::new();
+        let mut from = prefix.clone();
+
+        loop {
+            let mut iterator = self.iterate(self.buffer.iterator_cf(self.cf, &from)?, prefix.clone())?;
+
+            match iterator.next() {
+                Some(item) => {
+                    let ((_, t, _, _), _) = item?;
+                    let type_prefix = build_key(vec![KeyComponent::Uuid(id), KeyComponent::Type(&t)]);
+                    from = prefix_upper_bound(&type_prefix, 20);
+                    types.push(t);
+                },
+                None => return Ok(types)
+            }
+        }
     }
 
-    pub fn get(&self, account_id: Uuid, name: &str) -> Result<Option<JsonValue>, Error> {
-        get_json(&self.db, self.cf, self.key(account_id, name))
+    pub fn iterate_all<'a>(&self) -> Result<Box<Iterator<Item=EdgeWeightRangeItem> + 'a>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, b"")?;
+        self.iterate(iterator, Box::new([]))
     }
 
-    pub fn set(&self, account_id: Uuid, name: &str, value: &JsonValue) -> Result<(), Error> {
-        set_json(&self.db, self.cf, self.key(account_id, name), value)
+    pub fn exists(&self,
+                  first_id: Uuid,
+                  t: &models::Type,
+                  weight: models::Weight,
+                  second_id: Uuid)
+                  -> Result<bool, Error> {
+        exists(&self.buffer, self.cf, self.key(first_id, t, weight, second_id))
+    }
+
+    pub fn set(&self,
+               mut batch: &mut Batch,
+               first_id: Uuid,
+               t: &models::Type,
+               weight: models::Weight,
+               second_id: Uuid,
+               update_datetime: DateTime<UTC>)
+               -> Result<(), Error> {
+        let key = self.key(first_id, t, weight, second_id);
+        let value = bincode_serialize_value(&update_datetime)?;
+        batch.put_cf(self.cf, key, value);
+        Ok(())
     }
 
     pub fn delete(&self,
-                  mut batch: &mut WriteBatch,
+                  mut batch: &mut Batch,
+                  first_id: Uuid,
+                  t: &models::Type,
+                  weight: models::Weight,
+                  second_id: Uuid)
+                  -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(first_id, t, weight, second_id));
+        Ok(())
+    }
+}
+
+/// Tracks which edge types have history enabled.
+pub struct EdgeHistoryTypeManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl EdgeHistoryTypeManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        EdgeHistoryTypeManager {
+            cf: "edge_history_types:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, t: &models::Type) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Type(t)])
+    }
+
+    pub fn iterate_all<'a>(&self) -> Result<Box<Iterator<Item = EdgeHistoryTypeItem> + 'a>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, b"")?;
+
+        let mapped = iterator.map(|item| -> EdgeHistoryTypeItem {
+            let (k, _) = item;
+            let mut cursor = Cursor::new(k);
+            Ok(read_type(&mut cursor))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
+    pub fn exists(&self, t: &models::Type) -> Result<bool, Error> {
+        exists(&self.buffer, self.cf, self.key(t))
+    }
+
+    pub fn set(&self, batch: &mut Batch, t: &models::Type) -> Result<(), Error> {
+        batch.put_cf(self.cf, self.key(t), Box::new([]));
+        Ok(())
+    }
+
+    pub fn delete(&self, batch: &mut Batch, t: &models::Type) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(t));
+        Ok(())
+    }
+}
+
+/// Keeps the versions of edges whose type has history enabled. The plain
+/// manager is keyed by outbound ID and the reversed one by inbound ID, and
+/// both sort each edge's versions newest first.
+pub struct EdgeHistoryManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl EdgeHistoryManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        EdgeHistoryManager {
+            cf: "edge_history:v1",
+            buffer: buffer,
+        }
+    }
+
+    pub fn new_reversed(buffer: WriteBuffer) -> Self {
+        EdgeHistoryManager {
+            cf: "reversed_edge_history:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, first_id: Uuid, t: &models::Type, second_id: Uuid, datetime: DateTime<UTC>) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Uuid(first_id),
+                       KeyComponent::Type(t),
+                       KeyComponent::Uuid(second_id),
+                       KeyComponent::DateTime(datetime)])
+    }
+
+    fn iterate<'a>(&self, prefix: Box<[u8]>) -> Result<Box<Iterator<Item = EdgeVersionItem> + 'a>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, &prefix)?;
+        let filtered = take_while_prefixed(iterator, prefix);
+
+        let mapped = filtered.map(|item| -> EdgeVersionItem {
+            let (k, v) = item;
+            let mut cursor = Cursor::new(k);
+            let first_id = read_uuid(&mut cursor);
+            let t = read_type(&mut cursor);
+            let second_id = read_uuid(&mut cursor);
+            let datetime = read_datetime(&mut cursor);
+            let value: Option<(models::Weight, u64)> = bincode::deserialize(&v.to_owned()[..])?;
+            Ok(((first_id, t, second_id, datetime), value))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
+    pub fn iterate_for_edge<'a>(&self, first_id: Uuid, t: &models::Type, second_id: Uuid) -> Result<Box<Iterator<Item = EdgeVersionItem> + 'a>, Error> {
+        self.iterate(build_key(vec![KeyComponent::Uuid(first_id), KeyComponent::Type(t), KeyComponent::Uuid(second_id)]))
+    }
+
+    pub fn iterate_for_type<'a>(&self, first_id: Uuid, t: &models::Type) -> Result<Box<Iterator<Item = EdgeVersionItem> + 'a>, Error> {
+        self.iterate(build_key(vec![KeyComponent::Uuid(first_id), KeyComponent::Type(t)]))
+    }
+
+    pub fn iterate_for_owner<'a>(&self, first_id: Uuid) -> Result<Box<Iterator<Item = EdgeVersionItem> + 'a>, Error> {
+        self.iterate(build_key(vec![KeyComponent::Uuid(first_id)]))
+    }
+
+    pub fn iterate_all<'a>(&self) -> Result<Box<Iterator<Item = EdgeVersionItem> + 'a>, Error> {
+        self.iterate(Box::new([]))
+    }
+
+    pub fn set(&self,
+               mut batch: &mut Batch,
+               first_id: Uuid,
+               t: &models::Type,
+               second_id: Uuid,
+               datetime: DateTime<UTC>,
+               value: Option<(models::Weight, u64)>)
+               -> Result<(), Error> {
+        set_bincode(&mut batch, self.cf, self.key(first_id, t, second_id, datetime), &value)
+    }
+
+    pub fn delete(&self,
+                  batch: &mut Batch,
+                  first_id: Uuid,
+                  t: &models::Type,
+                  second_id: Uuid,
+                  datetime: DateTime<UTC>)
+                  -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(first_id, t, second_id, datetime));
+        Ok(())
+    }
+}
+
+pub struct GlobalMetadataManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl GlobalMetadataManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        GlobalMetadataManager {
+            cf: "global_metadata:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, name: &str) -> Box<[u8]> {
+        build_key(vec![KeyComponent::UnsizedString(name)])
+    }
+
+    /// Iterates over the metadata with names starting with a prefix, in name
+    /// order, optionally starting after a given name.
+    pub fn iterate_for_prefix<'a>(&self, prefix: &str, start_name: Option<&str>) -> Result<Box<Iterator<Item = GlobalMetadataItem> + 'a>, Error> {
+        let prefix = self.key(prefix);
+        let after_key = start_name.map(|start_name| self.key(start_name));
+
+        // If the cursor is before the prefix, start at the beginning of it;
+        // if it's after the prefix, the iterator will immediately run out
+        let from = match after_key {
+            Some(ref after_key) if *after_key > prefix => after_key.clone(),
+            _ => prefix.clone()
+        };
+
+        let iterator = self.buffer.iterator_cf(self.cf, &from)?;
+        let filtered = iterator.skip_while(move |&(ref k, _)| match after_key {
+            Some(ref after_key) => k <= after_key,
+            None => false
+        });
+
+        let mapped = take_while_prefixed(filtered, prefix).map(|item| -> GlobalMetadataItem {
+            let (k, v) = item;
+            let mut cursor = Cursor::new(k);
+            let name = read_unsized_string(&mut cursor);
+            let value = json_deserialize_value(&v.to_owned()[..])?;
+            Ok((name, value))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<JsonValue>, Error> {
+        get_json(&self.buffer, self.cf, self.key(name))
+    }
+
+    /// Gets the version number of a metadata value, or `None` if it isn't
+    /// set.
+    pub fn get_version_number(&self, name: &str) -> Result<Option<u64>, Error> {
+        if self.get(name)?.is_none() {
+            return Ok(None);
+        }
+
+        let version = GlobalMetadataVersionNumberManager::new(self.buffer.clone()).get(name)?;
+        Ok(Some(version.unwrap_or(1)))
+    }
+
+    /// Watches a metadata value, so that committing fails with
+    /// `Error::Conflict` if another transaction writes to it first.
+    pub fn watch(&self, name: &str) -> Result<(), Error> {
+        let version_number_manager = GlobalMetadataVersionNumberManager::new(self.buffer.clone());
+        self.buffer.watch_cf(self.cf, &self.key(name))?;
+        self.buffer.watch_cf(version_number_manager.cf, &version_number_manager.key(name))
+    }
+
+    pub fn set(&self, mut batch: &mut Batch, name: &str, value: &JsonValue) -> Result<u64, Error> {
+        let version = match self.get_version_number(name)? {
+            Some(version) => version + 1,
+            None => 1
+        };
+
+        set_json(&mut batch, self.cf, self.key(name), value)?;
+        GlobalMetadataVersionNumberManager::new(self.buffer.clone()).set(&mut batch, name, version)?;
+        Ok(version)
+    }
+
+    pub fn delete(&self, mut batch: &mut Batch, name: &str) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(name));
+        GlobalMetadataVersionNumberManager::new(self.buffer.clone()).delete(&mut batch, name)
+    }
+}
+
+/// Tracks the version number of each global metadata value. Metadata that
+/// was last written before version numbers were tracked has no entry, and is
+/// at version 1.
+pub struct GlobalMetadataVersionNumberManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl GlobalMetadataVersionNumberManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        GlobalMetadataVersionNumberManager {
+            cf: "global_metadata_version_numbers:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, name: &str) -> Box<[u8]> {
+        build_key(vec![KeyComponent::UnsizedString(name)])
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<u64>, Error> {
+        match self.buffer.get_cf(self.cf, &self.key(name))? {
+            Some(value_bytes) => Ok(Some(bincode::deserialize(&value_bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set(&self, mut batch: &mut Batch, name: &str, version: u64) -> Result<(), Error> {
+        set_bincode(&mut batch, self.cf, self.key(name), &version)
+    }
+
+    pub fn delete(&self, batch: &mut Batch, name: &str) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(name));
+        Ok(())
+    }
+}
+
+pub struct AccountMetadataManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl AccountMetadataManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        AccountMetadataManager {
+            cf: "account_metadata:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, account_id: Uuid, name: &str) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Uuid(account_id), KeyComponent::UnsizedString(name)])
+    }
+
+    pub fn iterate_for_owner
+        (&self,
+         account_id: Uuid)
+         -> Result<Box<Iterator<Item = OwnedMetadataItem>>, Error> {
+        iterate_metadata_for_owner(&self.buffer, self.cf, account_id)
+    }
+
+    pub fn iterate_all(&self) -> Result<Box<Iterator<Item = OwnedMetadataItem>>, Error> {
+        iterate_metadata(&self.buffer, self.cf, Box::new([]))
+    }
+
+    pub fn exists(&self, account_id: Uuid, name: &str) -> Result<bool, Error> {
+        exists(&self.buffer, self.cf, self.key(account_id, name))
+    }
+
+    pub fn get(&self, account_id: Uuid, name: &str) -> Result<Option<JsonValue>, Error> {
+        get_json(&self.buffer, self.cf, self.key(account_id, name))
+    }
+
+    /// Gets the version number of an account's metadata, or `None` if it
+    /// isn't set.
+    pub fn get_version_number(&self, account_id: Uuid, name: &str) -> Result<Option<u64>, Error> {
+        if self.get(account_id, name)?.is_none() {
+            return Ok(None);
+        }
+
+        let version = AccountMetadataVersionNumberManager::new(self.buffer.clone()).get(account_id, name)?;
+        Ok(Some(version.unwrap_or(1)))
+    }
+
+    /// Watches a metadata value, so that committing fails with
+    /// `Error::Conflict` if another transaction writes to it first.
+    pub fn watch(&self, account_id: Uuid, name: &str) -> Result<(), Error> {
+        let version_number_manager = AccountMetadataVersionNumberManager::new(self.buffer.clone());
+        self.buffer.watch_cf(self.cf, &self.key(account_id, name))?;
+        self.buffer.watch_cf(version_number_manager.cf, &version_number_manager.key(account_id, name))
+    }
+
+    pub fn set(&self,
+               mut batch: &mut Batch,
+               account_id: Uuid,
+               name: &str,
+               value: &JsonValue)
+               -> Result<u64, Error> {
+        let version = match self.get_version_number(account_id, name)? {
+            Some(version) => version + 1,
+            None => 1
+        };
+
+        set_json(&mut batch, self.cf, self.key(account_id, name), value)?;
+        AccountMetadataVersionNumberManager::new(self.buffer.clone()).set(&mut batch, account_id, name, version)?;
+        Ok(version)
+    }
+
+    pub fn delete(&self,
+                  mut batch: &mut Batch,
                   account_id: Uuid,
                   name: &str)
                   -> Result<(), Error> {
-        batch.delete_cf(self.cf, &self.key(account_id, name))?;
+        batch.delete_cf(self.cf, self.key(account_id, name));
+        AccountMetadataVersionNumberManager::new(self.buffer.clone()).delete(&mut batch, account_id, name)
+    }
+}
+
+/// Tracks the version number of each piece of account metadata. Metadata
+/// that was last written before version numbers were tracked has no entry,
+/// and is at version 1.
+pub struct AccountMetadataVersionNumberManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl AccountMetadataVersionNumberManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        AccountMetadataVersionNumberManager {
+            cf: "account_metadata_version_numbers:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, account_id: Uuid, name: &str) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Uuid(account_id), KeyComponent::UnsizedString(name)])
+    }
+
+    pub fn get(&self, account_id: Uuid, name: &str) -> Result<Option<u64>, Error> {
+        match self.buffer.get_cf(self.cf, &self.key(account_id, name))? {
+            Some(value_bytes) => Ok(Some(bincode::deserialize(&value_bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set(&self, mut batch: &mut Batch, account_id: Uuid, name: &str, version: u64) -> Result<(), Error> {
+        set_bincode(&mut batch, self.cf, self.key(account_id, name), &version)
+    }
+
+    pub fn delete(&self, batch: &mut Batch, account_id: Uuid, name: &str) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(account_id, name));
+        Ok(())
+    }
+}
+
+pub struct VertexMetadataManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl VertexMetadataManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        VertexMetadataManager {
+            cf: "vertex_metadata:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, vertex_id: Uuid, name: &str) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Uuid(vertex_id), KeyComponent::UnsizedString(name)])
+    }
+
+    pub fn iterate_for_owner
+        (&self,
+         vertex_id: Uuid)
+         -> Result<Box<Iterator<Item = OwnedMetadataItem>>, Error> {
+        iterate_metadata_for_owner(&self.buffer, self.cf, vertex_id)
+    }
+
+    pub fn iterate_all(&self) -> Result<Box<Iterator<Item = OwnedMetadataItem>>, Error> {
+        iterate_metadata(&self.buffer, self.cf, Box::new([]))
+    }
+
+    pub fn get(&self, vertex_id: Uuid, name: &str) -> Result<Option<JsonValue>, Error> {
+        get_json(&self.buffer, self.cf, self.key(vertex_id, name))
+    }
+
+    /// Moves the entries for a vertex's metadata in every index that covers
+    /// it from the current value to `new_value`.
+    fn update_indexes(&self,
+                      mut batch: &mut Batch,
+                      vertex_id: Uuid,
+                      t: &models::Type,
+                      name: &str,
+                      new_value: Option<&JsonValue>)
+                      -> Result<(), Error> {
+        let indexes = VertexMetadataIndexManager::new(self.buffer.clone()).get_for_metadata(t, name)?;
+
+        if indexes.is_empty() {
+            return Ok(());
+        }
+
+        let index_value_manager = VertexMetadataIndexValueManager::new(self.buffer.clone());
+        let old_value = self.get(vertex_id, name)?;
+
+        for (index_id, index) in indexes {
+            if let Some(old_value) = old_value.as_ref().and_then(|value| index.indexed_value(value)) {
+                index_value_manager.delete(&mut batch, index_id, &encode_metadata_value(old_value)?, vertex_id)?;
+            }
+
+            if let Some(new_value) = new_value.and_then(|value| index.indexed_value(value)) {
+                index_value_manager.set(&mut batch, index_id, &encode_metadata_value(new_value)?, vertex_id)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves a vertex's claim on its metadata value under the unique
+    /// constraint that covers it, if there is one, from the current value to
+    /// `new_value`.
+    ///
+    /// # Errors
+    /// Returns `Error::UniqueConstraintViolated` if another vertex has
+    /// already claimed `new_value`, including in earlier writes to `batch`.
+    fn update_unique_values(&self,
+                            mut batch: &mut Batch,
+                            vertex_id: Uuid,
+                            t: &models::Type,
+                            name: &str,
+                            new_value: Option<&JsonValue>)
+                            -> Result<(), Error> {
+        let constraint = models::UniqueConstraint::new(t.clone(), name.to_string());
+
+        let constraint_id = match UniqueConstraintManager::new(self.buffer.clone()).find(&constraint)? {
+            Some(constraint_id) => constraint_id,
+            None => return Ok(())
+        };
+
+        let unique_value_manager = UniqueValueManager::new(self.buffer.clone());
+
+        let new_value = match new_value {
+            Some(new_value) => {
+                let new_value = encode_metadata_value(new_value)?;
+
+                // Another transaction could claim the value before this one
+                // commits, so make sure that the commit fails if it does
+                unique_value_manager.watch(constraint_id, &new_value[..])?;
+
+                match unique_value_manager.get_staged(batch, constraint_id, &new_value[..])? {
+                    Some(owner_id) if owner_id != vertex_id && !self.has_expired(owner_id, name)? => return Err(Error::UniqueConstraintViolated),
+                    _ => Some(new_value)
+                }
+            },
+            None => None
+        };
+
+        if let Some(old_value) = self.get(vertex_id, name)? {
+            let old_value = encode_metadata_value(&old_value)?;
+
+            if unique_value_manager.get_staged(batch, constraint_id, &old_value[..])? == Some(vertex_id) {
+                unique_value_manager.delete(&mut batch, constraint_id, &old_value[..])?;
+            }
+        }
+
+        if let Some(new_value) = new_value {
+            unique_value_manager.set(&mut batch, constraint_id, &new_value[..], vertex_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether a vertex's metadata, or the vertex itself, has
+    /// expired. Values claimed by expired metadata are free to be claimed
+    /// again, even before the expired metadata is deleted.
+    fn has_expired(&self, vertex_id: Uuid, name: &str) -> Result<bool, Error> {
+        let expiry_manager = ExpiryManager::new(self.buffer.clone());
+        let now = UTC::now();
+        Ok(expiry_manager.has_expired(&ExpiringItem::VertexMetadata(vertex_id, name.to_string()), now)? ||
+            expiry_manager.has_expired(&ExpiringItem::Vertex(vertex_id), now)?)
+    }
+
+    /// Gets the version number of a vertex's metadata, or `None` if it
+    /// isn't set.
+    pub fn get_version_number(&self, vertex_id: Uuid, name: &str) -> Result<Option<u64>, Error> {
+        if self.get(vertex_id, name)?.is_none() {
+            return Ok(None);
+        }
+
+        let version = VertexMetadataVersionNumberManager::new(self.buffer.clone()).get(vertex_id, name)?;
+        Ok(Some(version.unwrap_or(1)))
+    }
+
+    /// Watches a vertex's metadata, so that committing fails with
+    /// `Error::Conflict` if another transaction writes to it first.
+    pub fn watch(&self, vertex_id: Uuid, name: &str) -> Result<(), Error> {
+        let version_number_manager = VertexMetadataVersionNumberManager::new(self.buffer.clone());
+        self.buffer.watch_cf(self.cf, &self.key(vertex_id, name))?;
+        self.buffer.watch_cf(version_number_manager.cf, &version_number_manager.key(vertex_id, name))
+    }
+
+    pub fn set(&self,
+               mut batch: &mut Batch,
+               vertex_id: Uuid,
+               t: &models::Type,
+               name: &str,
+               value: &JsonValue)
+               -> Result<u64, Error> {
+        let version = match self.get_version_number(vertex_id, name)? {
+            Some(version) => version + 1,
+            None => 1
+        };
+
+        self.update_unique_values(&mut batch, vertex_id, t, name, Some(value))?;
+        self.update_indexes(&mut batch, vertex_id, t, name, Some(value))?;
+        let key = self.key(vertex_id, name);
+        let value_json = json_serialize_value(value)?;
+        batch.put_cf(self.cf, key, value_json);
+        VertexMetadataVersionNumberManager::new(self.buffer.clone()).set(&mut batch, vertex_id, name, version)?;
+        VertexMetadataNameManager::new(self.buffer.clone()).set(&mut batch, name, vertex_id)?;
+        ExpiryManager::new(self.buffer.clone()).delete(&mut batch, &ExpiringItem::VertexMetadata(vertex_id, name.to_string()))?;
+        Ok(version)
+    }
+
+    pub fn delete(&self,
+                  mut batch: &mut Batch,
+                  vertex_id: Uuid,
+                  t: &models::Type,
+                  name: &str)
+                  -> Result<(), Error> {
+        self.update_unique_values(&mut batch, vertex_id, t, name, None)?;
+        self.update_indexes(&mut batch, vertex_id, t, name, None)?;
+        batch.delete_cf(self.cf, self.key(vertex_id, name));
+        VertexMetadataVersionNumberManager::new(self.buffer.clone()).delete(&mut batch, vertex_id, name)?;
+        VertexMetadataNameManager::new(self.buffer.clone()).delete(&mut batch, name, vertex_id)?;
+        ExpiryManager::new(self.buffer.clone()).delete(&mut batch, &ExpiringItem::VertexMetadata(vertex_id, name.to_string()))
+    }
+}
+
+/// Tracks the version number of each piece of vertex metadata. Metadata that
+/// was last written before version numbers were tracked has no entry, and is
+/// at version 1.
+pub struct VertexMetadataVersionNumberManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl VertexMetadataVersionNumberManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        VertexMetadataVersionNumberManager {
+            cf: "vertex_metadata_version_numbers:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, vertex_id: Uuid, name: &str) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Uuid(vertex_id), KeyComponent::UnsizedString(name)])
+    }
+
+    pub fn get(&self, vertex_id: Uuid, name: &str) -> Result<Option<u64>, Error> {
+        match self.buffer.get_cf(self.cf, &self.key(vertex_id, name))? {
+            Some(value_bytes) => Ok(Some(bincode::deserialize(&value_bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set(&self, mut batch: &mut Batch, vertex_id: Uuid, name: &str, version: u64) -> Result<(), Error> {
+        set_bincode(&mut batch, self.cf, self.key(vertex_id, name), &version)
+    }
+
+    pub fn delete(&self, batch: &mut Batch, vertex_id: Uuid, name: &str) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(vertex_id, name));
+        Ok(())
+    }
+}
+
+/// Indexes vertex metadata by name, so that the vertices with metadata of a
+/// given name can be found without scanning every vertex.
+pub struct VertexMetadataNameManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl VertexMetadataNameManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        VertexMetadataNameManager {
+            cf: "vertex_metadata_names:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, name: &str, vertex_id: Uuid) -> Box<[u8]> {
+        build_key(vec![KeyComponent::SizedString(name), KeyComponent::Uuid(vertex_id)])
+    }
+
+    fn iterate<'a>(&self, iterator: BufferedIterator, prefix: Box<[u8]>) -> Result<Box<Iterator<Item = VertexMetadataNameItem> + 'a>, Error> {
+        let filtered = take_while_prefixed(iterator, prefix);
+
+        let mapped = filtered.map(|item| -> VertexMetadataNameItem {
+            let (k, _) = item;
+            let mut cursor = Cursor::new(k);
+            let name = read_sized_string(&mut cursor);
+            let vertex_id = read_uuid(&mut cursor);
+            Ok((name, vertex_id))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
+    /// Iterates over the vertices with metadata called `name`, in ID order,
+    /// starting at `vertex_id`.
+    pub fn iterate_for_name<'a>(&self, name: &str, vertex_id: Uuid) -> Result<Box<Iterator<Item = VertexMetadataNameItem> + 'a>, Error> {
+        let prefix = build_key(vec![KeyComponent::SizedString(name)]);
+        let iterator = self.buffer.iterator_cf(self.cf, &self.key(name, vertex_id))?;
+        self.iterate(iterator, prefix)
+    }
+
+    pub fn iterate_all<'a>(&self) -> Result<Box<Iterator<Item = VertexMetadataNameItem> + 'a>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, b"")?;
+        self.iterate(iterator, Box::new([]))
+    }
+
+    pub fn exists(&self, name: &str, vertex_id: Uuid) -> Result<bool, Error> {
+        exists(&self.buffer, self.cf, self.key(name, vertex_id))
+    }
+
+    pub fn set(&self, batch: &mut Batch, name: &str, vertex_id: Uuid) -> Result<(), Error> {
+        batch.put_cf(self.cf, self.key(name, vertex_id), Box::new([]));
+        Ok(())
+    }
+
+    pub fn delete(&self, batch: &mut Batch, name: &str, vertex_id: Uuid) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(name, vertex_id));
+        Ok(())
+    }
+}
+
+/// Encodes a metadata value for use in `vertex_metadata_index_values:v1` and
+/// `unique_values:v1` keys. Numbers are encoded by their value, so e.g. `1`
+/// and `1.0` share an entry, matching how `MetadataPredicate::Equal` compares
+/// them.
+pub fn encode_metadata_value(value: &JsonValue) -> Result<String, Error> {
+    if let JsonValue::Number(ref number) = *value {
+        if let Some(number) = number.as_f64() {
+            // Avoid encoding negative zero separately
+            return Ok(if number == 0.0 { "0".to_string() } else { number.to_string() });
+        }
+    }
+
+    Ok(serde_json::to_string(value)?)
+}
+
+pub struct VertexMetadataIndexManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl VertexMetadataIndexManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        VertexMetadataIndexManager {
+            cf: "vertex_metadata_indexes:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, t: &models::Type, name: &str, id: Uuid) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Type(t), KeyComponent::SizedString(name), KeyComponent::Uuid(id)])
+    }
+
+    fn iterate<'a>(&self, iterator: BufferedIterator, prefix: Box<[u8]>) -> Result<Box<Iterator<Item = VertexMetadataIndexItem> + 'a>, Error> {
+        let filtered = take_while_prefixed(iterator, prefix);
+
+        let mapped = filtered.map(|item| -> VertexMetadataIndexItem {
+            let (k, v) = item;
+            let mut cursor = Cursor::new(k);
+            read_type(&mut cursor);
+            read_sized_string(&mut cursor);
+            let id = read_uuid(&mut cursor);
+            let index: models::VertexMetadataIndex = bincode::deserialize(&v.to_owned()[..])?;
+            Ok((id, index))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
+    pub fn iterate_all<'a>(&self) -> Result<Box<Iterator<Item = VertexMetadataIndexItem> + 'a>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, b"")?;
+        self.iterate(iterator, Box::new([]))
+    }
+
+    pub fn find(&self, index: &models::VertexMetadataIndex) -> Result<Option<Uuid>, Error> {
+        for (id, existing_index) in self.get_for_metadata(&index.t, &index.name[..])? {
+            if existing_index == *index {
+                return Ok(Some(id));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Gets the indexes that cover the metadata called `name` on vertices of
+    /// type `t`.
+    pub fn get_for_metadata(&self, t: &models::Type, name: &str) -> Result<Vec<(Uuid, models::VertexMetadataIndex)>, Error> {
+        let prefix = build_key(vec![KeyComponent::Type(t), KeyComponent::SizedString(name)]);
+        let iterator = self.buffer.iterator_cf(self.cf, &prefix)?;
+        self.iterate(iterator, prefix)?.collect()
+    }
+
+    pub fn create(&self, mut batch: &mut Batch, index: &models::VertexMetadataIndex) -> Result<Uuid, Error> {
+        let id = parent_uuid();
+        set_bincode(&mut batch, self.cf, self.key(&index.t, &index.name[..], id), index)?;
+        Ok(id)
+    }
+
+    pub fn delete(&self, mut batch: &mut Batch, index: &models::VertexMetadataIndex, id: Uuid) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(&index.t, &index.name[..], id));
         Ok(())
     }
 }
 
-pub struct VertexMetadataManager {
-    pub db: Arc<DB>,
-    pub cf: ColumnFamily,
+pub struct VertexMetadataIndexValueManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
 }
 
-impl VertexMetadataManager {
-    pub fn new(db: Arc<DB>) -> Self {
-        VertexMetadataManager {
-            cf: db.cf_handle("vertex_metadata:v1").unwrap(),
-            db: db,
+impl VertexMetadataIndexValueManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        VertexMetadataIndexValueManager {
+            cf: "vertex_metadata_index_values:v1",
+            buffer: buffer,
         }
     }
 
-    fn key(&self, vertex_id: Uuid, name: &str) -> Box<[u8]> {
-        build_key(vec![KeyComponent::Uuid(vertex_id), KeyComponent::UnsizedString(name)])
+    fn key(&self, index_id: Uuid, value: &str, vertex_id: Uuid) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Uuid(index_id),
+                       KeyComponent::NullTerminatedString(value),
+                       KeyComponent::Uuid(vertex_id)])
     }
 
-    pub fn iterate_for_owner
-        (&self,
-         vertex_id: Uuid)
-         -> Result<Box<Iterator<Item = OwnedMetadataItem>>, Error> {
-        iterate_metadata_for_owner(&self.db, self.cf, vertex_id)
+    fn iterate<'a>(&self, iterator: BufferedIterator, prefix: Box<[u8]>) -> Result<Box<Iterator<Item = VertexMetadataIndexValueItem> + 'a>, Error> {
+        let filtered = take_while_prefixed(iterator, prefix);
+
+        let mapped = filtered.map(|item| -> VertexMetadataIndexValueItem {
+            let (k, _) = item;
+            let mut cursor = Cursor::new(k);
+            let index_id = read_uuid(&mut cursor);
+            let value = read_null_terminated_string(&mut cursor);
+            let vertex_id = read_uuid(&mut cursor);
+            Ok((index_id, value, vertex_id))
+        });
+
+        Ok(Box::new(mapped))
     }
 
-    pub fn get(&self, vertex_id: Uuid, name: &str) -> Result<Option<JsonValue>, Error> {
-        get_json(&self.db, self.cf, self.key(vertex_id, name))
+    /// Iterates over the entries for an encoded value in an index, starting
+    /// at `vertex_id`.
+    pub fn iterate_for_value<'a>(&self, index_id: Uuid, value: &str, vertex_id: Uuid) -> Result<Box<Iterator<Item = VertexMetadataIndexValueItem> + 'a>, Error> {
+        let prefix = build_key(vec![KeyComponent::Uuid(index_id), KeyComponent::NullTerminatedString(value)]);
+        let iterator = self.buffer.iterator_cf(self.cf, &self.key(index_id, value, vertex_id))?;
+        self.iterate(iterator, prefix)
     }
 
-    pub fn set(&self, mut batch: &mut WriteBatch, vertex_id: Uuid, name: &str, value: &JsonValue) -> Result<(), Error> {
-        let key = self.key(vertex_id, name);
-        let value_json = json_serialize_value(value)?;
-        batch.put_cf(self.cf, &key, &value_json)?;
+    pub fn iterate_for_index<'a>(&self, index_id: Uuid) -> Result<Box<Iterator<Item = VertexMetadataIndexValueItem> + 'a>, Error> {
+        let prefix = build_key(vec![KeyComponent::Uuid(index_id)]);
+        let iterator = self.buffer.iterator_cf(self.cf, &prefix)?;
+        self.iterate(iterator, prefix)
+    }
+
+    pub fn iterate_all<'a>(&self) -> Result<Box<Iterator<Item = VertexMetadataIndexValueItem> + 'a>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, b"")?;
+        self.iterate(iterator, Box::new([]))
+    }
+
+    pub fn exists(&self, index_id: Uuid, value: &str, vertex_id: Uuid) -> Result<bool, Error> {
+        exists(&self.buffer, self.cf, self.key(index_id, value, vertex_id))
+    }
+
+    pub fn set(&self, mut batch: &mut Batch, index_id: Uuid, value: &str, vertex_id: Uuid) -> Result<(), Error> {
+        batch.put_cf(self.cf, self.key(index_id, value, vertex_id), Box::new([]));
         Ok(())
     }
 
-    pub fn delete(&self,
-                  mut batch: &mut WriteBatch,
-                  vertex_id: Uuid,
-                  name: &str)
-                  -> Result<(), Error> {
-        batch.delete_cf(self.cf, &self.key(vertex_id, name))?;
+    pub fn delete(&self, mut batch: &mut Batch, index_id: Uuid, value: &str, vertex_id: Uuid) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(index_id, value, vertex_id));
         Ok(())
     }
 }
 
 pub struct EdgeMetadataManager {
-    pub db: Arc<DB>,
-    pub cf: ColumnFamily,
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
 }
 
 impl EdgeMetadataManager {
-    pub fn new(db: Arc<DB>) -> Self {
+    pub fn new(buffer: WriteBuffer) -> Self {
         EdgeMetadataManager {
-            cf: db.cf_handle("edge_metadata:v1").unwrap(),
-            db: db,
+            cf: "edge_metadata:v1",
+            buffer: buffer,
         }
     }
 
@@ -642,30 +1943,17 @@ impl EdgeMetadataManager {
                        KeyComponent::UnsizedString(name)])
     }
 
-    pub fn iterate_for_owner<'a>(&self, outbound_id: Uuid, t: &'a models::Type, inbound_id: Uuid) -> Result<Box<Iterator<Item=EdgeMetadataItem> + 'a>, Error> {
-        let prefix = build_key(vec![KeyComponent::Uuid(outbound_id),
-                                    KeyComponent::Type(t),
-                                    KeyComponent::Uuid(inbound_id)]);
-
-        let iterator = self.db
-            .iterator_cf(self.cf, IteratorMode::From(&prefix, Direction::Forward))?;
+    fn iterate<'a>(&self, prefix: Box<[u8]>) -> Result<Box<Iterator<Item=EdgeMetadataItem> + 'a>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, &prefix)?;
         let filtered = take_while_prefixed(iterator, prefix);
 
         let mapped = filtered.map(move |item| -> EdgeMetadataItem {
             let (k, v) = item;
             let mut cursor = Cursor::new(k);
-
             let edge_metadata_outbound_id = read_uuid(&mut cursor);
-            debug_assert_eq!(edge_metadata_outbound_id, outbound_id);
-            
             let edge_metadata_t = read_type(&mut cursor);
-            debug_assert_eq!(&edge_metadata_t, t);
-
             let edge_metadata_inbound_id = read_uuid(&mut cursor);
-            debug_assert_eq!(edge_metadata_inbound_id, inbound_id);
-
             let edge_metadata_name = read_unsized_string(&mut cursor);
-
             let value = json_deserialize_value(&v.to_owned()[..])?;
             Ok(((edge_metadata_outbound_id, edge_metadata_t, edge_metadata_inbound_id, edge_metadata_name), value))
         });
@@ -673,39 +1961,490 @@ impl EdgeMetadataManager {
         Ok(Box::new(mapped))
     }
 
+    pub fn iterate_for_owner<'a>(&self, outbound_id: Uuid, t: &'a models::Type, inbound_id: Uuid) -> Result<Box<Iterator<Item=EdgeMetadataItem> + 'a>, Error> {
+        let prefix = build_key(vec![KeyComponent::Uuid(outbound_id),
+                                    KeyComponent::Type(t),
+                                    KeyComponent::Uuid(inbound_id)]);
+        self.iterate(prefix)
+    }
+
+    pub fn iterate_all<'a>(&self) -> Result<Box<Iterator<Item=EdgeMetadataItem> + 'a>, Error> {
+        self.iterate(Box::new([]))
+    }
+
     pub fn get(&self,
                outbound_id: Uuid,
                t: &models::Type,
                inbound_id: Uuid,
                name: &str)
                -> Result<Option<JsonValue>, Error> {
-        get_json(&self.db,
+        get_json(&self.buffer,
                  self.cf,
                  self.key(outbound_id, t, inbound_id, name))
     }
 
+    /// Gets the version number of an edge's metadata, or `None` if it isn't
+    /// set.
+    pub fn get_version_number(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid, name: &str) -> Result<Option<u64>, Error> {
+        if self.get(outbound_id, t, inbound_id, name)?.is_none() {
+            return Ok(None);
+        }
+
+        let version = EdgeMetadataVersionNumberManager::new(self.buffer.clone()).get(outbound_id, t, inbound_id, name)?;
+        Ok(Some(version.unwrap_or(1)))
+    }
+
+    /// Watches a metadata value, so that committing fails with
+    /// `Error::Conflict` if another transaction writes to it first.
+    pub fn watch(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid, name: &str) -> Result<(), Error> {
+        let version_number_manager = EdgeMetadataVersionNumberManager::new(self.buffer.clone());
+        self.buffer.watch_cf(self.cf, &self.key(outbound_id, t, inbound_id, name))?;
+        self.buffer.watch_cf(version_number_manager.cf, &version_number_manager.key(outbound_id, t, inbound_id, name))
+    }
+
     pub fn set(&self,
-               mut batch: &mut WriteBatch, 
+               mut batch: &mut Batch, 
                outbound_id: Uuid,
                t: &models::Type,
                inbound_id: Uuid,
                name: &str,
                value: &JsonValue)
-               -> Result<(), Error> {
+               -> Result<u64, Error> {
+        let version = match self.get_version_number(outbound_id, t, inbound_id, name)? {
+            Some(version) => version + 1,
+            None => 1
+        };
+
         let key = self.key(outbound_id, t, inbound_id, name);
         let value_json = json_serialize_value(value)?;
-        batch.put_cf(self.cf, &key, &value_json)?;
-        Ok(())
+        batch.put_cf(self.cf, key, value_json);
+        EdgeMetadataVersionNumberManager::new(self.buffer.clone()).set(&mut batch, outbound_id, t, inbound_id, name, version)?;
+        EdgeMetadataNameManager::new(self.buffer.clone()).set(&mut batch, name, outbound_id, t, inbound_id)?;
+        ExpiryManager::new(self.buffer.clone()).delete(&mut batch, &ExpiringItem::EdgeMetadata(outbound_id, t.clone(), inbound_id, name.to_string()))?;
+        Ok(version)
     }
 
     pub fn delete(&self,
-                  mut batch: &mut WriteBatch,
+                  mut batch: &mut Batch,
                   outbound_id: Uuid,
                   t: &models::Type,
                   inbound_id: Uuid,
                   name: &str)
                   -> Result<(), Error> {
-        batch.delete_cf(self.cf, &self.key(outbound_id, t, inbound_id, name))?;
+        batch.delete_cf(self.cf, self.key(outbound_id, t, inbound_id, name));
+        EdgeMetadataVersionNumberManager::new(self.buffer.clone()).delete(&mut batch, outbound_id, t, inbound_id, name)?;
+        EdgeMetadataNameManager::new(self.buffer.clone()).delete(&mut batch, name, outbound_id, t, inbound_id)?;
+        ExpiryManager::new(self.buffer.clone()).delete(&mut batch, &ExpiringItem::EdgeMetadata(outbound_id, t.clone(), inbound_id, name.to_string()))
+    }
+}
+
+/// Tracks the version number of each piece of edge metadata. Metadata that
+/// was last written before version numbers were tracked has no entry, and is
+/// at version 1.
+pub struct EdgeMetadataVersionNumberManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl EdgeMetadataVersionNumberManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        EdgeMetadataVersionNumberManager {
+            cf: "edge_metadata_version_numbers:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid, name: &str) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Uuid(outbound_id),
+                       KeyComponent::Type(t),
+                       KeyComponent::Uuid(inbound_id),
+                       KeyComponent::UnsizedString(name)])
+    }
+
+    pub fn get(&self, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid, name: &str) -> Result<Option<u64>, Error> {
+        match self.buffer.get_cf(self.cf, &self.key(outbound_id, t, inbound_id, name))? {
+            Some(value_bytes) => Ok(Some(bincode::deserialize(&value_bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set(&self,
+               mut batch: &mut Batch,
+               outbound_id: Uuid,
+               t: &models::Type,
+               inbound_id: Uuid,
+               name: &str,
+               version: u64)
+               -> Result<(), Error> {
+        set_bincode(&mut batch, self.cf, self.key(outbound_id, t, inbound_id, name), &version)
+    }
+
+    pub fn delete(&self, batch: &mut Batch, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid, name: &str) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(outbound_id, t, inbound_id, name));
+        Ok(())
+    }
+}
+
+/// Indexes edge metadata by name, so that the edges with metadata of a given
+/// name can be found without scanning every edge.
+pub struct EdgeMetadataNameManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl EdgeMetadataNameManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        EdgeMetadataNameManager {
+            cf: "edge_metadata_names:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, name: &str, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Box<[u8]> {
+        // Types are null-terminated rather than size-prefixed so that edges
+        // are sorted the same way as `EdgeKey`s
+        build_key(vec![KeyComponent::SizedString(name),
+                       KeyComponent::Uuid(outbound_id),
+                       KeyComponent::NullTerminatedString(&t.0),
+                       KeyComponent::Uuid(inbound_id)])
+    }
+
+    fn iterate<'a, I>(&self, iterator: I, prefix: Box<[u8]>) -> Result<Box<Iterator<Item = EdgeMetadataNameItem> + 'a>, Error>
+        where I: Iterator<Item = DBIteratorItem> + 'a
+    {
+        let filtered = take_while_prefixed(iterator, prefix);
+
+        let mapped = filtered.map(|item| -> EdgeMetadataNameItem {
+            let (k, _) = item;
+            let mut cursor = Cursor::new(k);
+            let name = read_sized_string(&mut cursor);
+            let outbound_id = read_uuid(&mut cursor);
+            let t = models::Type::new(read_null_terminated_string(&mut cursor)).unwrap();
+            let inbound_id = read_uuid(&mut cursor);
+            Ok((name, outbound_id, t, inbound_id))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
+    /// Iterates over the edges with metadata called `name`, sorted by key,
+    /// optionally starting after a given edge.
+    pub fn iterate_for_name<'a>(&self, name: &str, after: Option<&models::EdgeKey>) -> Result<Box<Iterator<Item = EdgeMetadataNameItem> + 'a>, Error> {
+        let prefix = build_key(vec![KeyComponent::SizedString(name)]);
+
+        match after {
+            Some(after) => {
+                let after_key = self.key(name, after.outbound_id, &after.t, after.inbound_id);
+                let iterator = self.buffer.iterator_cf(self.cf, &after_key)?;
+                let filtered = iterator.skip_while(move |&(ref k, _)| *k <= after_key);
+                self.iterate(filtered, prefix)
+            },
+            None => {
+                let iterator = self.buffer.iterator_cf(self.cf, &prefix)?;
+                self.iterate(iterator, prefix)
+            }
+        }
+    }
+
+    pub fn iterate_all<'a>(&self) -> Result<Box<Iterator<Item = EdgeMetadataNameItem> + 'a>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, b"")?;
+        self.iterate(iterator, Box::new([]))
+    }
+
+    pub fn exists(&self, name: &str, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Result<bool, Error> {
+        exists(&self.buffer, self.cf, self.key(name, outbound_id, t, inbound_id))
+    }
+
+    pub fn set(&self, batch: &mut Batch, name: &str, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Result<(), Error> {
+        batch.put_cf(self.cf, self.key(name, outbound_id, t, inbound_id), Box::new([]));
+        Ok(())
+    }
+
+    pub fn delete(&self, batch: &mut Batch, name: &str, outbound_id: Uuid, t: &models::Type, inbound_id: Uuid) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(name, outbound_id, t, inbound_id));
+        Ok(())
+    }
+}
+
+pub struct UniqueConstraintManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl UniqueConstraintManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        UniqueConstraintManager {
+            cf: "unique_constraints:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, t: &models::Type, name: &str) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Type(t), KeyComponent::SizedString(name)])
+    }
+
+    pub fn iterate_all<'a>(&self) -> Result<Box<Iterator<Item = UniqueConstraintItem> + 'a>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, b"")?;
+
+        let mapped = iterator.map(|item| -> UniqueConstraintItem {
+            let (k, v) = item;
+            let mut cursor = Cursor::new(k);
+            let t = read_type(&mut cursor);
+            let name = read_sized_string(&mut cursor);
+            let id: Uuid = bincode::deserialize(&v.to_owned()[..])?;
+            Ok((id, models::UniqueConstraint::new(t, name)))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
+    pub fn find(&self, constraint: &models::UniqueConstraint) -> Result<Option<Uuid>, Error> {
+        match self.buffer.get_cf(self.cf, &self.key(&constraint.t, &constraint.name[..]))? {
+            Some(value_bytes) => Ok(Some(bincode::deserialize(&value_bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn create(&self, mut batch: &mut Batch, constraint: &models::UniqueConstraint) -> Result<Uuid, Error> {
+        let id = parent_uuid();
+        set_bincode(&mut batch, self.cf, self.key(&constraint.t, &constraint.name[..]), &id)?;
+        Ok(id)
+    }
+
+    pub fn delete(&self, mut batch: &mut Batch, constraint: &models::UniqueConstraint) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(&constraint.t, &constraint.name[..]));
+        Ok(())
+    }
+}
+
+pub struct UniqueValueManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl UniqueValueManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        UniqueValueManager {
+            cf: "unique_values:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, constraint_id: Uuid, value: &str) -> Box<[u8]> {
+        build_key(vec![KeyComponent::Uuid(constraint_id), KeyComponent::UnsizedString(value)])
+    }
+
+    fn iterate<'a>(&self, prefix: Box<[u8]>) -> Result<Box<Iterator<Item = UniqueValueItem> + 'a>, Error> {
+        let iterator = self.buffer.iterator_cf(self.cf, &prefix)?;
+        let filtered = take_while_prefixed(iterator, prefix);
+
+        let mapped = filtered.map(|item| -> UniqueValueItem {
+            let (k, v) = item;
+            let mut cursor = Cursor::new(k);
+            let constraint_id = read_uuid(&mut cursor);
+            let value = read_unsized_string(&mut cursor);
+            Ok((constraint_id, value, parse_uuid_key(v)))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
+    pub fn iterate_for_constraint<'a>(&self, constraint_id: Uuid) -> Result<Box<Iterator<Item = UniqueValueItem> + 'a>, Error> {
+        self.iterate(build_key(vec![KeyComponent::Uuid(constraint_id)]))
+    }
+
+    pub fn iterate_all<'a>(&self) -> Result<Box<Iterator<Item = UniqueValueItem> + 'a>, Error> {
+        self.iterate(Box::new([]))
+    }
+
+    /// Gets the ID of the vertex that has claimed an encoded value.
+    pub fn get(&self, constraint_id: Uuid, value: &str) -> Result<Option<Uuid>, Error> {
+        match self.buffer.get_cf(self.cf, &self.key(constraint_id, value))? {
+            Some(vertex_id_bytes) => Ok(Some(parse_uuid_key(vertex_id_bytes))),
+            None => Ok(None)
+        }
+    }
+
+    /// Watches an encoded value, so that committing fails with
+    /// `Error::Conflict` if another transaction claims or releases it first.
+    pub fn watch(&self, constraint_id: Uuid, value: &str) -> Result<(), Error> {
+        self.buffer.watch_cf(self.cf, &self.key(constraint_id, value))
+    }
+
+    /// Like `get`, but also sees the writes staged in `batch`.
+    pub fn get_staged(&self, batch: &Batch, constraint_id: Uuid, value: &str) -> Result<Option<Uuid>, Error> {
+        match batch.get_cf(self.cf, &self.key(constraint_id, value)) {
+            Some(Some(vertex_id_bytes)) => Ok(Some(parse_uuid_key(vertex_id_bytes.to_vec().into_boxed_slice()))),
+            Some(None) => Ok(None),
+            None => self.get(constraint_id, value)
+        }
+    }
+
+    pub fn set(&self, mut batch: &mut Batch, constraint_id: Uuid, value: &str, vertex_id: Uuid) -> Result<(), Error> {
+        let vertex_id_bytes = build_key(vec![KeyComponent::Uuid(vertex_id)]);
+        batch.put_cf(self.cf, self.key(constraint_id, value), vertex_id_bytes);
+        Ok(())
+    }
+
+    pub fn delete(&self, mut batch: &mut Batch, constraint_id: Uuid, value: &str) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(constraint_id, value));
+        Ok(())
+    }
+}
+
+fn expiring_item_key_components(item: &ExpiringItem) -> Vec<KeyComponent> {
+    match *item {
+        ExpiringItem::Vertex(id) => vec![KeyComponent::NullTerminatedString("vertex"), KeyComponent::Uuid(id)],
+        ExpiringItem::Edge(outbound_id, ref t, inbound_id) => {
+            vec![KeyComponent::NullTerminatedString("edge"),
+                 KeyComponent::Uuid(outbound_id),
+                 KeyComponent::Type(t),
+                 KeyComponent::Uuid(inbound_id)]
+        },
+        ExpiringItem::VertexMetadata(id, ref name) => {
+            vec![KeyComponent::NullTerminatedString("vertex_metadata"),
+                 KeyComponent::Uuid(id),
+                 KeyComponent::UnsizedString(name)]
+        },
+        ExpiringItem::EdgeMetadata(outbound_id, ref t, inbound_id, ref name) => {
+            vec![KeyComponent::NullTerminatedString("edge_metadata"),
+                 KeyComponent::Uuid(outbound_id),
+                 KeyComponent::Type(t),
+                 KeyComponent::Uuid(inbound_id),
+                 KeyComponent::UnsizedString(name)]
+        }
+    }
+}
+
+fn read_expiring_item(mut cursor: &mut Cursor<Box<[u8]>>) -> ExpiringItem {
+    match &read_null_terminated_string(&mut cursor)[..] {
+        "vertex" => ExpiringItem::Vertex(read_uuid(&mut cursor)),
+        "edge" => {
+            let outbound_id = read_uuid(&mut cursor);
+            let t = read_type(&mut cursor);
+            let inbound_id = read_uuid(&mut cursor);
+            ExpiringItem::Edge(outbound_id, t, inbound_id)
+        },
+        "vertex_metadata" => {
+            let id = read_uuid(&mut cursor);
+            let name = read_unsized_string(&mut cursor);
+            ExpiringItem::VertexMetadata(id, name)
+        },
+        "edge_metadata" => {
+            let outbound_id = read_uuid(&mut cursor);
+            let t = read_type(&mut cursor);
+            let inbound_id = read_uuid(&mut cursor);
+            let name = read_unsized_string(&mut cursor);
+            ExpiringItem::EdgeMetadata(outbound_id, t, inbound_id, name)
+        },
+        kind => panic!("Unknown kind of expiring item `{}`", kind)
+    }
+}
+
+/// Tracks when each vertex, edge or piece of metadata with a time-to-live
+/// expires. Items without an entry never expire.
+pub struct ExpiryManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl ExpiryManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        ExpiryManager {
+            cf: "expiries:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, item: &ExpiringItem) -> Box<[u8]> {
+        build_key(expiring_item_key_components(item))
+    }
+
+    pub fn get(&self, item: &ExpiringItem) -> Result<Option<DateTime<UTC>>, Error> {
+        match self.buffer.get_cf(self.cf, &self.key(item))? {
+            Some(value_bytes) => Ok(Some(bincode::deserialize(&value_bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns whether an item has expired as of `now`.
+    pub fn has_expired(&self, item: &ExpiringItem, now: DateTime<UTC>) -> Result<bool, Error> {
+        match self.get(item)? {
+            Some(expiry) => Ok(expiry <= now),
+            None => Ok(false)
+        }
+    }
+
+    pub fn set(&self, mut batch: &mut Batch, item: &ExpiringItem, expiry: DateTime<UTC>) -> Result<(), Error> {
+        let expiry_range_manager = ExpiryRangeManager::new(self.buffer.clone());
+
+        if let Some(old_expiry) = self.get(item)? {
+            expiry_range_manager.delete(&mut batch, old_expiry, item)?;
+        }
+
+        set_bincode(&mut batch, self.cf, self.key(item), &expiry)?;
+        expiry_range_manager.set(&mut batch, expiry, item)
+    }
+
+    /// Stops an item from expiring. Nothing is written for items that
+    /// weren't going to expire, which is most of them.
+    pub fn delete(&self, mut batch: &mut Batch, item: &ExpiringItem) -> Result<(), Error> {
+        if let Some(expiry) = self.get(item)? {
+            batch.delete_cf(self.cf, self.key(item));
+            ExpiryRangeManager::new(self.buffer.clone()).delete(&mut batch, expiry, item)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Orders expiring items by when they expire, so that expired items can be
+/// found without scanning every expiry.
+pub struct ExpiryRangeManager {
+    pub buffer: WriteBuffer,
+    pub cf: &'static str,
+}
+
+impl ExpiryRangeManager {
+    pub fn new(buffer: WriteBuffer) -> Self {
+        ExpiryRangeManager {
+            cf: "expiry_ranges:v1",
+            buffer: buffer,
+        }
+    }
+
+    fn key(&self, expiry: DateTime<UTC>, item: &ExpiringItem) -> Box<[u8]> {
+        let mut components = vec![KeyComponent::DateTime(expiry)];
+        components.extend(expiring_item_key_components(item));
+        build_key(components)
+    }
+
+    /// Iterates over the items that have expired as of `now`, most recently
+    /// expired first.
+    pub fn iterate_for_expired<'a>(&self, now: DateTime<UTC>) -> Result<Box<Iterator<Item = ExpiryRangeItem> + 'a>, Error> {
+        // Datetimes sort in descending order, so everything from `now` on
+        // has already expired
+        let low_key = build_key(vec![KeyComponent::DateTime(now)]);
+        let iterator = self.buffer.iterator_cf(self.cf, &low_key)?;
+
+        let mapped = iterator.map(|item| -> ExpiryRangeItem {
+            let (k, _) = item;
+            let mut cursor = Cursor::new(k);
+            let expiry = read_datetime(&mut cursor);
+            let expiring_item = read_expiring_item(&mut cursor);
+            Ok((expiry, expiring_item))
+        });
+
+        Ok(Box::new(mapped))
+    }
+
+    pub fn set(&self, batch: &mut Batch, expiry: DateTime<UTC>, item: &ExpiringItem) -> Result<(), Error> {
+        batch.put_cf(self.cf, self.key(expiry, item), Box::new([]));
+        Ok(())
+    }
+
+    pub fn delete(&self, batch: &mut Batch, expiry: DateTime<UTC>, item: &ExpiringItem) -> Result<(), Error> {
+        batch.delete_cf(self.cf, self.key(expiry, item));
         Ok(())
     }
 }
@@ -1,3 +1,4 @@
+mod buffer;
 mod converters;
 mod keys;
 mod managers;
@@ -5,4 +6,4 @@ mod datastore;
 mod models;
 mod tests;
 
-pub use self::datastore::{RocksdbDatastore, RocksdbTransaction};
+pub use self::datastore::{ConsistencyReport, RocksdbDatastore, RocksdbTransaction};
@@ -46,3 +46,12 @@ impl EdgeValue {
         }
     }
 }
+
+/// Something that can expire after a time-to-live.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExpiringItem {
+    Vertex(Uuid),
+    Edge(Uuid, models::Type, Uuid),
+    VertexMetadata(Uuid, String),
+    EdgeMetadata(Uuid, models::Type, Uuid, String),
+}
@@ -4,9 +4,21 @@ pub use super::datastore::RocksdbDatastore;
 pub use super::super::tests;
 pub use super::super::util::generate_random_secret;
 pub use std::env;
+use super::buffer::CRASH_AFTER_WRITES;
+use super::datastore::{CF_NAMES, PENDING_BACKFILL_KEY};
+use super::keys::{build_key, KeyComponent};
+use super::super::{Datastore, Transaction, EdgeQuery, EdgeOrder, VertexQuery, EdgeKey, MetadataOperation, Type, UniqueConstraint, VertexMetadataIndex, Weight};
+use errors::Error;
+use bincode;
+use chrono::{Duration, UTC};
+use rocksdb::{DB, IteratorMode, Options};
+use serde_json::Value as JsonValue;
 use std::path::Path;
+use std::thread;
+use std::time;
+use uuid::Uuid;
 
-fn datastore() -> RocksdbDatastore {
+fn datastore_path() -> String {
     // RocksDB can only have one connection open to a database at a time.
     // Because Rust may run the tests in parallel, we need to add a random
     // secret to the test database directory to ensure we can have multiple
@@ -14,13 +26,501 @@ fn datastore() -> RocksdbDatastore {
     let test_rdb_directory = env::var("TEST_RDB_DIRECTORY").unwrap_or("/tmp/test-rdb".to_string());
     let unique = generate_random_secret();
     let path = Path::new(&test_rdb_directory[..]).join(unique);
+    path.to_str().unwrap().to_string()
+}
 
+fn open_datastore(path: &str) -> RocksdbDatastore {
     let max_open_files_str = env::var("ROCKSDB_MAX_OPEN_FILES").unwrap_or("512".to_string());
     let max_open_files = max_open_files_str.parse::<i32>().unwrap();
+    RocksdbDatastore::new(path, Some(max_open_files), false).unwrap()
+}
 
-    RocksdbDatastore::new(path.to_str().unwrap(), Some(max_open_files), false).unwrap()
+fn datastore() -> RocksdbDatastore {
+    open_datastore(&datastore_path())
 }
 
 test_account_impl!(datastore());
 test_transaction_impl!(datastore());
 test_metadata_impl!(datastore());
+test_rollback_impl!(datastore());
+
+#[test]
+fn should_keep_column_families_in_step_after_a_crash() {
+    let path = datastore_path();
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+
+    // Updating an edge writes to a dozen or so column families when it's
+    // committed. Crash partway through committing after each write in turn,
+    // reopen the database, and make sure the column families still agree.
+    for crash_after_writes in 0.. {
+        let (account_id, outbound_id, inbound_id, crashed) = {
+            let datastore = open_datastore(&path);
+            let (account_id, _) = datastore.create_account().unwrap();
+            let trans = datastore.transaction(account_id).unwrap();
+            let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+            let inbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+            let key = EdgeKey::new(outbound_id, edge_t.clone(), inbound_id);
+            trans.create_edge(key.clone(), Weight::new(0.5).unwrap()).unwrap();
+            trans.commit().unwrap();
+
+            let trans = datastore.transaction(account_id).unwrap();
+            trans.create_edge(key, Weight::new(1.0).unwrap()).unwrap();
+            CRASH_AFTER_WRITES.with(|c| c.set(Some(crash_after_writes)));
+            let result = trans.commit();
+            CRASH_AFTER_WRITES.with(|c| c.set(None));
+
+            if let Err(ref err) = result {
+                assert_eq!(*err, Error::Unexpected("Simulated crash".to_string()));
+            }
+
+            (account_id, outbound_id, inbound_id, result.is_err())
+        };
+
+        let datastore = open_datastore(&path);
+        assert!(datastore.verify().unwrap().is_consistent());
+
+        let expected_weight = if crashed { 0.5 } else { 1.0 };
+        let trans = datastore.transaction(account_id).unwrap();
+        let key = EdgeKey::new(outbound_id, edge_t.clone(), inbound_id);
+        let edges = trans.get_edges(EdgeQuery::Edge(key)).unwrap();
+        let outbound_edges = trans.get_edges(VertexQuery::Vertex(outbound_id).outbound_edges(None, None, None, 10)).unwrap();
+        let inbound_edges = trans.get_edges(VertexQuery::Vertex(inbound_id).inbound_edges(None, None, None, 10)).unwrap();
+        let sorted_outbound_edges = trans.get_edges(VertexQuery::Vertex(outbound_id).sorted_outbound_edges(None, None, None, EdgeOrder::StrongestFirst, 10)).unwrap();
+        let sorted_inbound_edges = trans.get_edges(VertexQuery::Vertex(inbound_id).sorted_inbound_edges(None, None, None, EdgeOrder::StrongestFirst, 10)).unwrap();
+        let created_outbound_edges = trans.get_edges(VertexQuery::Vertex(outbound_id).created_outbound_edges(None, None, None, 10)).unwrap();
+        let created_inbound_edges = trans.get_edges(VertexQuery::Vertex(inbound_id).created_inbound_edges(None, None, None, 10)).unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].weight.value(), expected_weight);
+        assert_eq!(edges[0].version, if crashed { 1 } else { 2 });
+
+        for range in &[outbound_edges, inbound_edges, sorted_outbound_edges, sorted_inbound_edges, created_outbound_edges, created_inbound_edges] {
+            assert_eq!(range.len(), 1);
+            assert_eq!(range[0].weight.value(), expected_weight);
+            assert_eq!(range[0].created_datetime, edges[0].created_datetime);
+            assert_eq!(range[0].updated_datetime, edges[0].updated_datetime);
+            assert_eq!(range[0].version, edges[0].version);
+        }
+
+        if !crashed {
+            assert!(crash_after_writes > 0);
+            break;
+        }
+    }
+}
+
+#[test]
+fn should_not_commit_versioned_writes_that_were_overtaken() {
+    let mut sandbox = tests::DatastoreTestSandbox::new(datastore());
+    sandbox.setup("should_not_commit_versioned_writes_that_were_overtaken");
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+    let trans = sandbox.transaction();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_id = trans.create_vertex(vertex_t).unwrap();
+    let key = EdgeKey::new(outbound_id, edge_t, inbound_id);
+    trans.create_edge(key.clone(), Weight::new(0.5).unwrap()).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(outbound_id), "foo".to_string(), JsonValue::Bool(false)).unwrap();
+    trans.commit().unwrap();
+
+    // Both transactions pass their version checks, since neither has
+    // committed yet, but only the first to commit wins
+    let first_trans = sandbox.transaction();
+    let second_trans = sandbox.transaction();
+    first_trans.create_edge_if_version(key.clone(), Weight::new(0.6).unwrap(), Some(1)).unwrap();
+    first_trans.set_vertex_metadata_if_version(outbound_id, "foo".to_string(), JsonValue::Bool(true), Some(1)).unwrap();
+    second_trans.create_edge_if_version(key.clone(), Weight::new(0.7).unwrap(), Some(1)).unwrap();
+    second_trans.commit().unwrap();
+    assert_eq!(first_trans.commit().unwrap_err(), Error::Conflict);
+
+    // None of the first transaction's writes made it through
+    let trans = sandbox.transaction();
+    let edges = trans.get_edges(EdgeQuery::Edge(key)).unwrap();
+    assert_eq!(edges[0].weight, Weight::new(0.7).unwrap());
+    assert_eq!(edges[0].version, 2);
+    let metadata = trans.get_versioned_vertex_metadata(VertexQuery::Vertex(outbound_id), "foo".to_string()).unwrap();
+    assert_eq!(metadata[&outbound_id].value, JsonValue::Bool(false));
+    assert_eq!(metadata[&outbound_id].version, 1);
+    sandbox.teardown();
+}
+
+#[test]
+fn should_not_commit_metadata_updates_that_were_overtaken() {
+    let mut sandbox = tests::DatastoreTestSandbox::new(datastore());
+    sandbox.setup("should_not_commit_metadata_updates_that_were_overtaken");
+    let name = sandbox.generate_unique_string("counter");
+    let increment = MetadataOperation::Increment(vec![], 1.0);
+
+    // Both transactions increment from the same value, so the second to
+    // commit would lose the first's increment if it went through
+    let first_trans = sandbox.transaction();
+    let second_trans = sandbox.transaction();
+    first_trans.update_global_metadata(name.clone(), increment.clone()).unwrap();
+    second_trans.update_global_metadata(name.clone(), increment.clone()).unwrap();
+    first_trans.commit().unwrap();
+    assert_eq!(second_trans.commit().unwrap_err(), Error::Conflict);
+
+    // Retrying the transaction picks up the first increment
+    let trans = sandbox.transaction();
+    assert_eq!(trans.update_global_metadata(name.clone(), increment).unwrap(), JsonValue::from(2));
+    trans.commit().unwrap();
+
+    let trans = sandbox.transaction();
+    trans.delete_global_metadata(name).unwrap();
+    trans.commit().unwrap();
+    sandbox.teardown();
+}
+
+#[test]
+fn should_not_commit_vertices_that_were_created_concurrently() {
+    let mut sandbox = tests::DatastoreTestSandbox::new(datastore());
+    sandbox.setup("should_not_commit_vertices_that_were_created_concurrently");
+    let t = Type::new("test_vertex_type".to_string()).unwrap();
+    let id = Uuid::new_v4();
+
+    // Neither transaction sees the other's vertex, so both creates pass
+    let first_trans = sandbox.transaction();
+    let second_trans = sandbox.transaction();
+    first_trans.create_vertex_with_id(id, t.clone()).unwrap();
+    second_trans.create_vertex_with_id(id, t).unwrap();
+    first_trans.commit().unwrap();
+    assert_eq!(second_trans.commit().unwrap_err(), Error::Conflict);
+
+    let trans = sandbox.transaction();
+    assert_eq!(trans.get_vertices(VertexQuery::Vertex(id)).unwrap().len(), 1);
+    trans.delete_vertices(VertexQuery::Vertex(id)).unwrap();
+    trans.commit().unwrap();
+    sandbox.teardown();
+}
+
+#[test]
+fn should_not_commit_unique_values_that_were_claimed_concurrently() {
+    let mut sandbox = tests::DatastoreTestSandbox::new(datastore());
+    sandbox.setup("should_not_commit_unique_values_that_were_claimed_concurrently");
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    sandbox.datastore.create_unique_constraint(UniqueConstraint::new(vertex_t.clone(), "external_id".to_string())).unwrap();
+    let trans = sandbox.transaction();
+    let first_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let second_id = trans.create_vertex(vertex_t).unwrap();
+    trans.commit().unwrap();
+
+    // Neither transaction can see the other's claim on the value, so both
+    // pass the constraint check, but only the first to commit wins
+    let first_trans = sandbox.transaction();
+    let second_trans = sandbox.transaction();
+    first_trans.set_vertex_metadata(VertexQuery::Vertex(first_id), "external_id".to_string(), JsonValue::Bool(true)).unwrap();
+    second_trans.set_vertex_metadata(VertexQuery::Vertex(second_id), "external_id".to_string(), JsonValue::Bool(true)).unwrap();
+    first_trans.commit().unwrap();
+    assert_eq!(second_trans.commit().unwrap_err(), Error::Conflict);
+
+    let trans = sandbox.transaction();
+    let metadata = trans.get_vertex_metadata(VertexQuery::Vertices(vec![first_id, second_id]), "external_id".to_string()).unwrap();
+    assert_eq!(metadata.keys().cloned().collect::<Vec<_>>(), vec![first_id]);
+    assert!(sandbox.datastore.verify().unwrap().is_consistent());
+    sandbox.teardown();
+}
+
+#[test]
+fn should_verify_and_repair_indexes() {
+    let path = datastore_path();
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+    let metadata_name = "test-metadata".to_string();
+
+    let (account_id, outbound_id, inbound_id) = {
+        let datastore = open_datastore(&path);
+        let (account_id, _) = datastore.create_account().unwrap();
+        let trans = datastore.transaction(account_id).unwrap();
+        let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+        let inbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+        trans.create_edge(EdgeKey::new(outbound_id, edge_t.clone(), inbound_id), Weight::new(0.5).unwrap()).unwrap();
+        trans.set_vertex_metadata(VertexQuery::Vertex(outbound_id), metadata_name.clone(), JsonValue::Bool(false)).unwrap();
+        trans.set_vertex_metadata(VertexQuery::Vertex(inbound_id), metadata_name.clone(), JsonValue::Bool(true)).unwrap();
+        trans.commit().unwrap();
+        datastore.create_vertex_metadata_index(VertexMetadataIndex::new(vertex_t.clone(), metadata_name.clone(), vec![])).unwrap();
+        assert!(datastore.verify().unwrap().is_consistent());
+        (account_id, outbound_id, inbound_id)
+    };
+
+    let key = EdgeKey::new(outbound_id, edge_t.clone(), inbound_id);
+    let stale_datetime = UTC::now() - Duration::days(1);
+
+    // Break the invariants directly in the database, the way a crash in
+    // the middle of a non-atomic write could have
+    {
+        let db = DB::open_cf(&Options::default(), &path, &CF_NAMES).unwrap();
+        let vertices_cf = db.cf_handle("vertices:v1").unwrap();
+        let edge_ranges_cf = db.cf_handle("edge_ranges:v1").unwrap();
+        let reversed_edge_ranges_cf = db.cf_handle("reversed_edge_ranges:v1").unwrap();
+        let edge_weight_ranges_cf = db.cf_handle("edge_weight_ranges:v1").unwrap();
+        let reversed_edge_weight_ranges_cf = db.cf_handle("reversed_edge_weight_ranges:v1").unwrap();
+        let vertex_metadata_index_values_cf = db.cf_handle("vertex_metadata_index_values:v1").unwrap();
+        let edge_created_datetimes_cf = db.cf_handle("edge_created_datetimes:v1").unwrap();
+        let edge_created_ranges_cf = db.cf_handle("edge_created_ranges:v1").unwrap();
+        let vertex_metadata_names_cf = db.cf_handle("vertex_metadata_names:v1").unwrap();
+        let edge_metadata_names_cf = db.cf_handle("edge_metadata_names:v1").unwrap();
+
+        db.delete_cf(vertices_cf, &build_key(vec![KeyComponent::Uuid(inbound_id)])).unwrap();
+
+        for (k, _) in db.iterator_cf(edge_ranges_cf, IteratorMode::Start).unwrap() {
+            db.delete_cf(edge_ranges_cf, &k).unwrap();
+        }
+
+        let stale_key = build_key(vec![KeyComponent::Uuid(inbound_id),
+                                       KeyComponent::Type(&edge_t),
+                                       KeyComponent::DateTime(stale_datetime),
+                                       KeyComponent::Uuid(outbound_id)]);
+        let stale_value = bincode::serialize(&Weight::new(0.5).unwrap(), bincode::Infinite).unwrap();
+        db.put_cf(reversed_edge_ranges_cf, &stale_key, &stale_value).unwrap();
+
+        for (k, _) in db.iterator_cf(edge_weight_ranges_cf, IteratorMode::Start).unwrap() {
+            db.delete_cf(edge_weight_ranges_cf, &k).unwrap();
+        }
+
+        let stale_weight_key = build_key(vec![KeyComponent::Uuid(inbound_id),
+                                              KeyComponent::Type(&edge_t),
+                                              KeyComponent::Weight(Weight::new(0.25).unwrap()),
+                                              KeyComponent::Uuid(outbound_id)]);
+        let stale_weight_value = bincode::serialize(&stale_datetime, bincode::Infinite).unwrap();
+        db.put_cf(reversed_edge_weight_ranges_cf, &stale_weight_key, &stale_weight_value).unwrap();
+
+        for (k, _) in db.iterator_cf(vertex_metadata_index_values_cf, IteratorMode::Start).unwrap() {
+            if k.ends_with(outbound_id.as_bytes()) {
+                db.delete_cf(vertex_metadata_index_values_cf, &k).unwrap();
+            }
+        }
+
+        for cf in &[edge_created_datetimes_cf, edge_created_ranges_cf] {
+            for (k, _) in db.iterator_cf(*cf, IteratorMode::Start).unwrap() {
+                db.delete_cf(*cf, &k).unwrap();
+            }
+        }
+
+        db.delete_cf(vertex_metadata_names_cf, &build_key(vec![KeyComponent::SizedString(&metadata_name[..]), KeyComponent::Uuid(outbound_id)])).unwrap();
+
+        let stale_name_key = build_key(vec![KeyComponent::SizedString("stale-metadata"),
+                                            KeyComponent::Uuid(outbound_id),
+                                            KeyComponent::NullTerminatedString(&edge_t.0),
+                                            KeyComponent::Uuid(inbound_id)]);
+        db.put_cf(edge_metadata_names_cf, &stale_name_key, &[]).unwrap();
+    }
+
+    let datastore = open_datastore(&path);
+    let report = datastore.verify().unwrap();
+    assert!(!report.is_consistent());
+    assert_eq!(report.orphaned_edges, vec![key.clone()]);
+    assert_eq!(report.missing_edge_ranges, vec![key.clone()]);
+    assert_eq!(report.missing_reversed_edge_ranges, vec![]);
+    assert_eq!(report.orphaned_edge_ranges, vec![]);
+    assert_eq!(report.orphaned_reversed_edge_ranges.len(), 1);
+    assert_eq!(report.orphaned_reversed_edge_ranges[0].0, key);
+    assert_eq!(report.missing_edge_weight_ranges, vec![key.clone()]);
+    assert_eq!(report.missing_reversed_edge_weight_ranges, vec![]);
+    assert_eq!(report.orphaned_edge_weight_ranges, vec![]);
+    assert_eq!(report.orphaned_reversed_edge_weight_ranges, vec![(key.clone(), Weight::new(0.25).unwrap())]);
+    assert_eq!(report.missing_edge_created_datetimes, vec![key.clone()]);
+    assert_eq!(report.orphaned_edge_created_datetimes, vec![]);
+    assert_eq!(report.missing_edge_created_ranges, vec![key.clone()]);
+    assert_eq!(report.missing_reversed_edge_created_ranges, vec![]);
+    assert_eq!(report.orphaned_edge_created_ranges, vec![]);
+    assert_eq!(report.orphaned_reversed_edge_created_ranges, vec![]);
+    assert_eq!(report.orphaned_vertex_metadata, vec![(inbound_id, metadata_name.clone())]);
+    assert_eq!(report.orphaned_vertex_owners, vec![(account_id, inbound_id)]);
+    assert_eq!(report.missing_vertex_owners, vec![]);
+    assert_eq!(report.orphaned_vertex_types, vec![(vertex_t.clone(), inbound_id)]);
+    assert_eq!(report.missing_vertex_types, vec![]);
+    assert_eq!(report.orphaned_account_metadata, vec![]);
+    assert_eq!(report.orphaned_edge_metadata, vec![]);
+    assert_eq!(report.missing_vertex_metadata_index_values.len(), 1);
+    assert_eq!(report.missing_vertex_metadata_index_values[0].1, outbound_id);
+    assert_eq!(report.orphaned_vertex_metadata_index_values.len(), 1);
+    assert_eq!(report.orphaned_vertex_metadata_index_values[0].2, inbound_id);
+    assert_eq!(report.missing_vertex_metadata_names, vec![(outbound_id, metadata_name.clone())]);
+    assert_eq!(report.orphaned_vertex_metadata_names, vec![]);
+    assert_eq!(report.missing_edge_metadata_names, vec![]);
+    assert_eq!(report.orphaned_edge_metadata_names, vec![(key.clone(), "stale-metadata".to_string())]);
+
+    assert_eq!(datastore.repair_indexes().unwrap(), report);
+
+    // Only the orphaned primary data should be left
+    let report = datastore.verify().unwrap();
+    assert_eq!(report.orphaned_edges, vec![key.clone()]);
+    assert_eq!(report.orphaned_vertex_metadata, vec![(inbound_id, metadata_name)]);
+    assert_eq!(report.missing_edge_ranges, vec![]);
+    assert_eq!(report.orphaned_reversed_edge_ranges, vec![]);
+    assert_eq!(report.missing_edge_weight_ranges, vec![]);
+    assert_eq!(report.orphaned_reversed_edge_weight_ranges, vec![]);
+    assert_eq!(report.missing_edge_created_datetimes, vec![]);
+    assert_eq!(report.missing_edge_created_ranges, vec![]);
+    assert_eq!(report.orphaned_vertex_owners, vec![]);
+    assert_eq!(report.orphaned_vertex_types, vec![]);
+    assert_eq!(report.missing_vertex_metadata_index_values, vec![]);
+    assert_eq!(report.orphaned_vertex_metadata_index_values, vec![]);
+    assert_eq!(report.missing_vertex_metadata_names, vec![]);
+    assert_eq!(report.orphaned_edge_metadata_names, vec![]);
+}
+
+#[test]
+fn should_backfill_new_column_families() {
+    let path = datastore_path();
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+
+    let (account_id, vertex_ids) = {
+        let datastore = open_datastore(&path);
+        let (account_id, _) = datastore.create_account().unwrap();
+        let trans = datastore.transaction(account_id).unwrap();
+        let mut vertex_ids = vec![
+            trans.create_vertex(vertex_t.clone()).unwrap(),
+            trans.create_vertex(vertex_t.clone()).unwrap()
+        ];
+        vertex_ids.sort();
+        let key = EdgeKey::new(vertex_ids[0], edge_t.clone(), vertex_ids[1]);
+        trans.create_edge(key.clone(), Weight::new(0.5).unwrap()).unwrap();
+        trans.set_vertex_metadata(VertexQuery::Vertex(vertex_ids[1]), "test-metadata".to_string(), JsonValue::Bool(true)).unwrap();
+        trans.set_edge_metadata(EdgeQuery::Edge(key), "test-metadata".to_string(), JsonValue::Bool(true)).unwrap();
+        trans.commit().unwrap();
+        (account_id, vertex_ids)
+    };
+
+    // Drop the indexes, so the database looks like one created before they
+    // were added
+    {
+        let mut db = DB::open_cf(&Options::default(), &path, &CF_NAMES).unwrap();
+        db.drop_cf("vertex_owners:v1").unwrap();
+        db.drop_cf("vertex_types:v1").unwrap();
+        db.drop_cf("edge_weight_ranges:v1").unwrap();
+        db.drop_cf("reversed_edge_weight_ranges:v1").unwrap();
+        db.drop_cf("vertex_metadata_indexes:v1").unwrap();
+        db.drop_cf("vertex_metadata_index_values:v1").unwrap();
+        db.drop_cf("unique_constraints:v1").unwrap();
+        db.drop_cf("unique_values:v1").unwrap();
+        db.drop_cf("edge_created_datetimes:v1").unwrap();
+        db.drop_cf("edge_created_ranges:v1").unwrap();
+        db.drop_cf("reversed_edge_created_ranges:v1").unwrap();
+        db.drop_cf("edge_history_types:v1").unwrap();
+        db.drop_cf("edge_history:v1").unwrap();
+        db.drop_cf("reversed_edge_history:v1").unwrap();
+        db.drop_cf("edge_version_numbers:v1").unwrap();
+        db.drop_cf("vertex_metadata_version_numbers:v1").unwrap();
+        db.drop_cf("vertex_metadata_names:v1").unwrap();
+        db.drop_cf("edge_metadata_names:v1").unwrap();
+        db.drop_cf("expiries:v1").unwrap();
+        db.drop_cf("expiry_ranges:v1").unwrap();
+        db.drop_cf("global_metadata_version_numbers:v1").unwrap();
+        db.drop_cf("account_metadata_version_numbers:v1").unwrap();
+        db.drop_cf("edge_metadata_version_numbers:v1").unwrap();
+    }
+
+    let datastore = open_datastore(&path);
+    assert!(datastore.verify().unwrap().is_consistent());
+
+    let (other_account_id, _) = datastore.create_account().unwrap();
+    let trans = datastore.transaction(other_account_id).unwrap();
+    let vertices = trans.get_vertices(VertexQuery::AllOfType(vertex_t.clone(), None, 10)).unwrap();
+    assert_eq!(vertices.iter().map(|vertex| vertex.id).collect::<Vec<_>>(), vertex_ids);
+    let q = VertexQuery::Vertex(vertex_ids[1]).sorted_inbound_edges(Some(edge_t.clone()), None, None, EdgeOrder::StrongestFirst, 10);
+    assert_eq!(trans.get_edges(q).unwrap().len(), 1);
+
+    // Edges from before created datetimes were tracked are treated as
+    // created when they were last updated
+    let q = VertexQuery::Vertex(vertex_ids[1]).created_inbound_edges(Some(edge_t), None, None, 10);
+    let edges = trans.get_edges(q).unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].created_datetime, edges[0].updated_datetime);
+
+    // Edges and metadata from before version numbers were tracked are at
+    // version 1
+    assert_eq!(edges[0].version, 1);
+    let metadata = trans.get_versioned_edge_metadata(EdgeQuery::Edge(edges[0].key.clone()), "test-metadata".to_string()).unwrap();
+    assert_eq!(metadata[&edges[0].key].version, 1);
+
+    let vertices = trans.get_vertices(VertexQuery::HasMetadata("test-metadata".to_string(), None, 10)).unwrap();
+    assert_eq!(vertices.iter().map(|vertex| vertex.id).collect::<Vec<_>>(), vec![vertex_ids[1]]);
+    assert_eq!(trans.get_edges(EdgeQuery::HasMetadata("test-metadata".to_string(), None, 10)).unwrap().len(), 1);
+
+    // Account deletion relies on the owner index to find the account's
+    // vertices
+    datastore.delete_account(account_id).unwrap();
+    let trans = datastore.transaction(other_account_id).unwrap();
+    assert_eq!(trans.get_vertices(VertexQuery::Vertices(vertex_ids)).unwrap().len(), 0);
+}
+
+#[test]
+fn should_resume_an_interrupted_backfill() {
+    let path = datastore_path();
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+
+    let vertex_ids = {
+        let datastore = open_datastore(&path);
+        let (account_id, _) = datastore.create_account().unwrap();
+        let trans = datastore.transaction(account_id).unwrap();
+        let mut vertex_ids = vec![
+            trans.create_vertex(vertex_t.clone()).unwrap(),
+            trans.create_vertex(vertex_t.clone()).unwrap()
+        ];
+        vertex_ids.sort();
+        trans.create_edge(EdgeKey::new(vertex_ids[0], edge_t.clone(), vertex_ids[1]), Weight::new(0.5).unwrap()).unwrap();
+        trans.commit().unwrap();
+        vertex_ids
+    };
+
+    // Empty out the indexes and leave the marker behind, so the database
+    // looks like one whose backfill was interrupted after the column
+    // families were created
+    {
+        let mut db = DB::open_cf(&Options::default(), &path, &CF_NAMES).unwrap();
+        db.put(PENDING_BACKFILL_KEY, b"vertex_owners:v1").unwrap();
+
+        for cf_name in &["vertex_owners:v1", "vertex_types:v1", "edge_weight_ranges:v1", "reversed_edge_weight_ranges:v1"] {
+            db.drop_cf(cf_name).unwrap();
+            db.create_cf(cf_name, &Options::default()).unwrap();
+        }
+    }
+
+    {
+        let datastore = open_datastore(&path);
+        assert!(datastore.verify().unwrap().is_consistent());
+        let (account_id, _) = datastore.create_account().unwrap();
+        let trans = datastore.transaction(account_id).unwrap();
+        let vertices = trans.get_vertices(VertexQuery::AllOfType(vertex_t, None, 10)).unwrap();
+        assert_eq!(vertices.iter().map(|vertex| vertex.id).collect::<Vec<_>>(), vertex_ids);
+        let q = VertexQuery::Vertex(vertex_ids[1]).sorted_inbound_edges(Some(edge_t), None, None, EdgeOrder::StrongestFirst, 10);
+        assert_eq!(trans.get_edges(q).unwrap().len(), 1);
+    }
+
+    // The marker is cleared once the backfill is done
+    let db = DB::open_cf(&Options::default(), &path, &CF_NAMES).unwrap();
+    assert!(db.get(PENDING_BACKFILL_KEY).unwrap().is_none());
+}
+
+#[test]
+fn should_stay_consistent_after_deleting_expired_items() {
+    let datastore = datastore();
+    let (account_id, _) = datastore.create_account().unwrap();
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+    datastore.enable_edge_history(edge_t.clone()).unwrap();
+    let trans = datastore.transaction(account_id).unwrap();
+    let ttl = Duration::milliseconds(100);
+    let expiring_id = trans.create_vertex_with_ttl(vertex_t.clone(), ttl).unwrap();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_id = trans.create_vertex(vertex_t).unwrap();
+    let key = EdgeKey::new(outbound_id, edge_t.clone(), inbound_id);
+    trans.create_edge_with_ttl(key.clone(), Weight::new(0.5).unwrap(), ttl).unwrap();
+    trans.create_edge(EdgeKey::new(expiring_id, edge_t, inbound_id), Weight::new(0.5).unwrap()).unwrap();
+    trans.set_vertex_metadata_with_ttl(VertexQuery::Vertex(outbound_id), "test-metadata".to_string(), JsonValue::Bool(true), ttl).unwrap();
+    trans.commit().unwrap();
+
+    thread::sleep(time::Duration::from_millis(200));
+    datastore.delete_expired().unwrap();
+    assert!(datastore.verify().unwrap().is_consistent());
+
+    // The expired edge is recorded as deleted in its history
+    let trans = datastore.transaction(account_id).unwrap();
+    let history = trans.get_edge_history(key).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1].weight, None);
+    assert_eq!(trans.get_vertices(VertexQuery::Vertices(vec![expiring_id, outbound_id, inbound_id])).unwrap().len(), 2);
+}
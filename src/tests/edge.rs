@@ -1,13 +1,16 @@
-use super::super::{Datastore, Transaction, EdgeQuery, VertexQuery, EdgeKey};
+use super::super::{Datastore, Transaction, EdgeQuery, EdgeOrder, VertexQuery, EdgeKey};
 use super::sandbox::DatastoreTestSandbox;
 use errors::Error;
 use models;
 use uuid::Uuid;
-use chrono::UTC;
+use chrono::{Duration, TimeZone, UTC};
 use chrono::Timelike;
+use serde_json::Value as JsonValue;
 use super::util::{create_edges, create_time_range_queryable_edges};
 use std::collections::HashSet;
 use std::f32;
+use std::thread;
+use std::time;
 use std::u32;
 
 pub fn should_get_a_valid_edge<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
@@ -35,7 +38,7 @@ pub fn should_get_a_valid_edge<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
     assert_eq!(e[0].key.outbound_id, outbound_id);
     assert_eq!(e[0].key.t, edge_t);
     assert_eq!(e[0].key.inbound_id, inbound_id);
-    assert!(e[0].weight.0 > 0.0);
+    assert!(e[0].weight.value() > 0.0);
     assert!(e[0].created_datetime >= start_time);
     assert!(e[0].created_datetime <= end_time);
 }
@@ -74,7 +77,7 @@ pub fn should_create_a_valid_edge<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>
     let e = trans.get_edges(EdgeQuery::Edge(key.clone())).unwrap();
     assert_eq!(e.len(), 1);
     assert_eq!(key, e[0].key);
-    assert!(e[0].weight.0 > 0.0);
+    assert!(e[0].weight.value() > 0.0);
 
     // `create_edge` should support the ability of updating an existing edge
     // - test for that
@@ -85,14 +88,175 @@ pub fn should_create_a_valid_edge<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>
     let e = trans.get_edges(EdgeQuery::Edge(key.clone())).unwrap();
     assert_eq!(e.len(), 1);
     assert_eq!(key, e[0].key);
-    assert!(e[0].weight.0 < 0.0);
+    assert!(e[0].weight.value() < 0.0);
 
     // REGRESSION: Second check that getting an edge range will only fetch a
     // single edge
     let e = trans.get_edges(VertexQuery::Vertex(outbound_id).outbound_edges(None, None, None, 10)).unwrap();
     assert_eq!(e.len(), 1);
     assert_eq!(key, e[0].key);
-    assert!(e[0].weight.0 < 0.0);
+    assert!(e[0].weight.value() < 0.0);
+}
+
+pub fn should_create_edges_at_a_given_datetime<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new(sandbox.generate_unique_string("test_edge_type")).unwrap();
+    let trans = sandbox.transaction();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone(), vertex_t.clone()]).unwrap();
+    let keys: Vec<EdgeKey> = inbound_ids.iter().map(|&id| EdgeKey::new(outbound_id, edge_t.clone(), id)).collect();
+    let weight = models::Weight::new(0.5).unwrap();
+
+    // Create the edges out of order
+    let june = UTC.ymd(2016, 6, 1).and_hms(0, 0, 0);
+    let january = UTC.ymd(2016, 1, 1).and_hms(0, 0, 0);
+    let march = UTC.ymd(2016, 3, 1).and_hms(0, 0, 0);
+    trans.create_edge_at(keys[0].clone(), weight, june).unwrap();
+    trans.create_edge_at(keys[1].clone(), weight, january).unwrap();
+    trans.create_edge_at(keys[2].clone(), weight, march).unwrap();
+    trans.commit().unwrap();
+
+    let get_keys = |q: EdgeQuery| -> Vec<EdgeKey> {
+        let trans = sandbox.transaction();
+        trans.get_edges(q).unwrap().into_iter().map(|edge| edge.key).collect()
+    };
+
+    let e = get_keys(EdgeQuery::Edge(keys[0].clone()));
+    assert_eq!(e, vec![keys[0].clone()]);
+    let all = VertexQuery::Vertex(outbound_id).outbound_edges(Some(edge_t.clone()), None, None, 10);
+    assert_eq!(get_keys(all.clone()), vec![keys[0].clone(), keys[2].clone(), keys[1].clone()]);
+    let april = UTC.ymd(2016, 4, 1).and_hms(0, 0, 0);
+    let february = UTC.ymd(2016, 2, 1).and_hms(0, 0, 0);
+    let q = VertexQuery::Vertex(outbound_id).outbound_edges(Some(edge_t.clone()), Some(april), Some(february), 10);
+    assert_eq!(get_keys(q.clone()), vec![keys[2].clone()]);
+    let q = VertexQuery::Vertex(inbound_ids[1]).inbound_edges(Some(edge_t.clone()), Some(february), None, 10);
+    assert_eq!(get_keys(q), vec![keys[1].clone()]);
+    let q = VertexQuery::Vertex(outbound_id).sorted_outbound_edges(Some(edge_t.clone()), None, None, EdgeOrder::OldestFirst, 10);
+    assert_eq!(get_keys(q), vec![keys[1].clone(), keys[2].clone(), keys[0].clone()]);
+
+    // Move an edge back in time, and make sure it's no longer found at its
+    // old datetime
+    let trans = sandbox.transaction();
+    let february_15th = UTC.ymd(2016, 2, 15).and_hms(0, 0, 0);
+    trans.create_edge_at(keys[0].clone(), weight, february_15th).unwrap();
+    let e = trans.get_edges(EdgeQuery::Edge(keys[0].clone())).unwrap();
+    assert_eq!(e[0].created_datetime, june);
+    assert_eq!(e[0].updated_datetime, february_15th);
+    trans.commit().unwrap();
+
+    let q = VertexQuery::Vertex(outbound_id).outbound_edges(Some(edge_t.clone()), Some(april), Some(february), 10);
+    assert_eq!(get_keys(q), vec![keys[2].clone(), keys[0].clone()]);
+    let q = VertexQuery::Vertex(outbound_id).outbound_edges(Some(edge_t.clone()), None, Some(april), 10);
+    assert_eq!(get_keys(q), vec![]);
+    let q = VertexQuery::Vertex(inbound_ids[0]).inbound_edges(Some(edge_t.clone()), Some(february_15th), Some(february_15th), 10);
+    assert_eq!(get_keys(q), vec![keys[0].clone()]);
+    assert_eq!(get_keys(all), vec![keys[2].clone(), keys[0].clone(), keys[1].clone()]);
+}
+
+pub fn should_not_create_edges_at_unrepresentable_datetimes<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new("test_edge_type".to_string()).unwrap();
+    let trans = sandbox.transaction();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_id = trans.create_vertex(vertex_t).unwrap();
+    let key = EdgeKey::new(outbound_id, edge_t, inbound_id);
+    let weight = models::Weight::new(0.5).unwrap();
+
+    for &datetime in &[UTC.ymd(1969, 12, 31).and_hms(23, 59, 59), UTC.ymd(2040, 1, 1).and_hms(0, 0, 0)] {
+        let result = trans.create_edge_at(key.clone(), weight, datetime);
+        assert_eq!(result.unwrap_err(), Error::OutOfRange("update_datetime".to_string()));
+    }
+
+    assert_eq!(trans.get_edges(EdgeQuery::Edge(key.clone())).unwrap().len(), 0);
+    trans.create_edge_at(key.clone(), weight, UTC.ymd(1970, 1, 1).and_hms(0, 0, 0)).unwrap();
+    assert_eq!(trans.get_edges(EdgeQuery::Edge(key)).unwrap().len(), 1);
+}
+
+pub fn should_keep_created_datetimes_when_updating_edges<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new(sandbox.generate_unique_string("test_edge_type")).unwrap();
+    let trans = sandbox.transaction();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone()]).unwrap();
+    let keys: Vec<EdgeKey> = inbound_ids.iter().map(|&id| EdgeKey::new(outbound_id, edge_t.clone(), id)).collect();
+    let weight = models::Weight::new(0.5).unwrap();
+
+    let january = UTC.ymd(2016, 1, 1).and_hms(0, 0, 0);
+    let february = UTC.ymd(2016, 2, 1).and_hms(0, 0, 0);
+    let march = UTC.ymd(2016, 3, 1).and_hms(0, 0, 0);
+    let april = UTC.ymd(2016, 4, 1).and_hms(0, 0, 0);
+    trans.create_edge_at(keys[0].clone(), weight, january).unwrap();
+    trans.create_edge_at(keys[1].clone(), weight, february).unwrap();
+
+    // New edges are created and updated at the same time
+    let e = trans.get_edges(EdgeQuery::Edge(keys[0].clone())).unwrap();
+    assert_eq!(e[0].created_datetime, january);
+    assert_eq!(e[0].updated_datetime, january);
+
+    // Re-creating an edge only changes when it was updated
+    trans.create_edge_at(keys[0].clone(), models::Weight::new(1.0).unwrap(), march).unwrap();
+    let e = trans.get_edges(EdgeQuery::Edge(keys[0].clone())).unwrap();
+    assert_eq!(e[0].weight.value(), 1.0);
+    assert_eq!(e[0].created_datetime, january);
+    assert_eq!(e[0].updated_datetime, march);
+    trans.create_edge(keys[1].clone(), weight).unwrap();
+    let e = trans.get_edges(EdgeQuery::Edge(keys[1].clone())).unwrap();
+    assert_eq!(e[0].created_datetime, february);
+    assert!(e[0].updated_datetime > april);
+    trans.commit().unwrap();
+
+    let get_keys = |q: EdgeQuery| -> Vec<EdgeKey> {
+        let trans = sandbox.transaction();
+        trans.get_edges(q).unwrap().into_iter().map(|edge| edge.key).collect()
+    };
+
+    // Time range queries can filter on either datetime
+    let q = VertexQuery::Vertex(outbound_id).outbound_edges(Some(edge_t.clone()), None, None, 10);
+    assert_eq!(get_keys(q), vec![keys[1].clone(), keys[0].clone()]);
+    let q = VertexQuery::Vertex(outbound_id).created_outbound_edges(Some(edge_t.clone()), None, None, 10);
+    assert_eq!(get_keys(q), vec![keys[1].clone(), keys[0].clone()]);
+    let q = VertexQuery::Vertex(outbound_id).outbound_edges(Some(edge_t.clone()), Some(april), None, 10);
+    assert_eq!(get_keys(q), vec![keys[0].clone()]);
+    let q = VertexQuery::Vertex(outbound_id).created_outbound_edges(Some(edge_t.clone()), Some(april), None, 10);
+    assert_eq!(get_keys(q), vec![keys[1].clone(), keys[0].clone()]);
+    let q = VertexQuery::Vertex(outbound_id).created_outbound_edges(Some(edge_t.clone()), Some(january), Some(january), 10);
+    assert_eq!(get_keys(q), vec![keys[0].clone()]);
+    let q = VertexQuery::Vertex(outbound_id).created_outbound_edges(None, Some(march), Some(february), 10);
+    assert_eq!(get_keys(q), vec![keys[1].clone()]);
+    let q = VertexQuery::Vertex(outbound_id).created_outbound_edges(Some(edge_t.clone()), None, None, 1);
+    assert_eq!(get_keys(q), vec![keys[1].clone()]);
+    let q = VertexQuery::Vertex(inbound_ids[0]).created_inbound_edges(Some(edge_t.clone()), Some(january), None, 10);
+    assert_eq!(get_keys(q), vec![keys[0].clone()]);
+    let q = VertexQuery::Vertex(inbound_ids[0]).inbound_edges(Some(edge_t.clone()), Some(january), None, 10);
+    assert_eq!(get_keys(q), vec![]);
+
+    // Created datetimes are returned by every kind of query
+    let trans = sandbox.transaction();
+    let q = VertexQuery::Vertex(outbound_id).outbound_edges(Some(edge_t.clone()), Some(april), None, 10);
+    let e = trans.get_edges(q).unwrap();
+    assert_eq!(e[0].created_datetime, january);
+    assert_eq!(e[0].updated_datetime, march);
+    let q = VertexQuery::Vertex(outbound_id).created_outbound_edges(Some(edge_t.clone()), Some(january), None, 10);
+    let e = trans.get_edges(q).unwrap();
+    assert_eq!(e[0].created_datetime, january);
+    assert_eq!(e[0].updated_datetime, march);
+
+    // A deleted and re-created edge gets a new created datetime
+    trans.delete_edges(EdgeQuery::Edge(keys[0].clone())).unwrap();
+    trans.create_edge_at(keys[0].clone(), weight, april).unwrap();
+    let e = trans.get_edges(EdgeQuery::Edge(keys[0].clone())).unwrap();
+    assert_eq!(e[0].created_datetime, april);
+    let q = VertexQuery::Vertex(outbound_id).created_outbound_edges(Some(edge_t), Some(january), None, 10);
+    assert_eq!(trans.get_edges(q).unwrap().len(), 0);
 }
 
 pub fn should_not_create_an_invalid_edge<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
@@ -128,6 +292,54 @@ pub fn should_not_create_an_edge_with_bad_permissions<D, T>(mut sandbox: &mut Da
     assert_eq!(result.unwrap_err(), Error::Unauthorized);
 }
 
+pub fn should_create_edges_in_bulk<D, T>(mut sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let trans = sandbox.transaction();
+    let unowned_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    trans.commit().unwrap();
+
+    let (id, _) = sandbox.register_account();
+    let trans = sandbox.datastore.transaction(id).unwrap();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone()]).unwrap();
+    let edge_t = models::Type::new("test_edge_type".to_string()).unwrap();
+
+    let results = trans.create_edges(vec![
+        (EdgeKey::new(outbound_id, edge_t.clone(), inbound_ids[0]), models::Weight::new(0.5).unwrap()),
+        (EdgeKey::new(outbound_id, edge_t.clone(), Uuid::default()), models::Weight::new(0.5).unwrap()),
+        (EdgeKey::new(unowned_id, edge_t.clone(), inbound_ids[0]), models::Weight::new(0.5).unwrap()),
+        (EdgeKey::new(outbound_id, edge_t.clone(), inbound_ids[1]), models::Weight::new(0.5).unwrap()),
+        (EdgeKey::new(outbound_id, edge_t.clone(), inbound_ids[0]), models::Weight::new(-0.5).unwrap()),
+    ]).unwrap();
+
+    assert_eq!(results, vec![
+        Ok(()),
+        Err(Error::VertexNotFound),
+        Err(Error::Unauthorized),
+        Ok(()),
+        Ok(())
+    ]);
+
+    // Failures shouldn't prevent the other edges from being created, and
+    // the last weight for a repeated edge should win
+    let range = trans.get_edges(VertexQuery::Vertex(outbound_id).outbound_edges(Some(edge_t.clone()), None, None, 10)).unwrap();
+    assert_eq!(range.len(), 2);
+
+    for edge in &range {
+        if edge.key.inbound_id == inbound_ids[0] {
+            assert!((edge.weight.value() + 0.5).abs() < f32::EPSILON);
+        } else {
+            assert_eq!(edge.key.inbound_id, inbound_ids[1]);
+            assert!((edge.weight.value() - 0.5).abs() < f32::EPSILON);
+        }
+    }
+
+    assert_eq!(trans.get_edge_count(VertexQuery::Vertex(unowned_id).outbound_edges(Some(edge_t), None, None, 10)).unwrap(), 0);
+}
+
 pub fn should_delete_a_valid_edge<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
     where D: Datastore<T>,
           T: Transaction
@@ -296,6 +508,55 @@ pub fn should_get_no_edges_for_reversed_time<D, T>(mut sandbox: &mut DatastoreTe
     check_edge_range(range, outbound_id, 0);
 }
 
+pub fn should_limit_edges_with_a_low_datetime_across_vertices<D, T>(mut sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let (first_outbound_id, start_time, _, _) = create_time_range_queryable_edges(&mut sandbox);
+    let (second_outbound_id, _, _, _) = create_time_range_queryable_edges(&mut sandbox);
+    let (third_outbound_id, _, _, _) = create_time_range_queryable_edges(&mut sandbox);
+    let trans = sandbox.transaction();
+    let t = models::Type::new("test_edge_type".to_string()).unwrap();
+    let ids = vec![first_outbound_id, second_outbound_id, third_outbound_id];
+    let q = VertexQuery::Vertices(ids).outbound_edges(Some(t), None, Some(start_time), 15);
+    assert_eq!(trans.get_edges(q.clone()).unwrap().len(), 15);
+    assert_eq!(trans.stream_edges(q).unwrap().count(), 15);
+}
+
+pub fn should_get_inbound_edges_with_their_keys<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new("test_edge_type".to_string()).unwrap();
+    let inbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let outbound_ids = vec![trans.create_vertex(vertex_t.clone()).unwrap(), trans.create_vertex(vertex_t).unwrap()];
+    let keys: HashSet<EdgeKey> = outbound_ids.iter().map(|id| EdgeKey::new(*id, edge_t.clone(), inbound_id)).collect();
+
+    for key in &keys {
+        trans.create_edge(key.clone(), models::Weight::new(0.5).unwrap()).unwrap();
+    }
+
+    // Inbound edges are found through an index keyed by the inbound vertex,
+    // but their keys should still be the right way around
+    let q = VertexQuery::Vertex(inbound_id).inbound_edges(Some(edge_t), None, None, 10);
+    let edges = trans.get_edges(q.clone()).unwrap();
+    assert_eq!(edges.iter().map(|edge| edge.key.clone()).collect::<HashSet<EdgeKey>>(), keys);
+
+    // Which also means that writes through the query find the edges
+    trans.set_edge_metadata(q.clone(), "foo".to_string(), JsonValue::Bool(true)).unwrap();
+
+    for key in &keys {
+        let metadata = trans.get_edge_metadata(EdgeQuery::Edge(key.clone()), "foo".to_string()).unwrap();
+        assert_eq!(metadata[key], JsonValue::Bool(true));
+    }
+
+    trans.delete_edges(q.clone()).unwrap();
+    assert_eq!(trans.get_edges(q).unwrap().len(), 0);
+    assert_eq!(trans.get_edges(EdgeQuery::Edges(keys.into_iter().collect())).unwrap().len(), 0);
+}
+
 pub fn should_get_edges<D, T>(mut sandbox: &mut DatastoreTestSandbox<D, T>)
     where D: Datastore<T>,
           T: Transaction
@@ -314,6 +575,231 @@ pub fn should_get_edges<D, T>(mut sandbox: &mut DatastoreTestSandbox<D, T>)
     check_edge_range(range, outbound_id, 5);
 }
 
+pub fn should_stream_edges<D, T>(mut sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let (outbound_id, _) = create_edges(&mut sandbox);
+    let trans = sandbox.transaction();
+    let t = models::Type::new("test_edge_type".to_string()).unwrap();
+    let q = VertexQuery::Vertex(outbound_id).outbound_edges(Some(t), None, None, 10);
+    let streamed: Vec<models::Edge> = trans.stream_edges(q.clone()).unwrap().map(|item| item.unwrap()).collect();
+    let range = trans.get_edges(q).unwrap();
+    let streamed_keys: Vec<EdgeKey> = streamed.iter().map(|edge| edge.key.clone()).collect();
+    let range_keys: Vec<EdgeKey> = range.iter().map(|edge| edge.key.clone()).collect();
+    assert_eq!(streamed_keys, range_keys);
+    check_edge_range(streamed, outbound_id, 5);
+}
+
+/// Creates outbound edges from a new vertex with weights of -0.5, 0.25, 0.75
+/// and 1.0, in that order, along with an edge of another type with a weight
+/// of 0.5. Returns the edge type, the outbound ID and the inbound IDs in the
+/// same order.
+fn create_weighted_edges<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>) -> (models::Type, Uuid, Vec<Uuid>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new(sandbox.generate_unique_string("test_weighted_edge_type")).unwrap();
+    let other_edge_t = models::Type::new(sandbox.generate_unique_string("test_other_weighted_edge_type")).unwrap();
+    let trans = sandbox.transaction();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone(), vertex_t.clone(), vertex_t.clone(), vertex_t]).unwrap();
+
+    for (inbound_id, weight) in inbound_ids.iter().zip(vec![-0.5, 0.25, 0.75, 1.0]) {
+        trans.create_edge(EdgeKey::new(outbound_id, edge_t.clone(), *inbound_id), models::Weight::new(weight).unwrap()).unwrap();
+    }
+
+    trans.create_edge(EdgeKey::new(outbound_id, other_edge_t, inbound_ids[4]), models::Weight::new(0.5).unwrap()).unwrap();
+    trans.commit().unwrap();
+    (edge_t, outbound_id, inbound_ids)
+}
+
+fn inbound_ids(range: Vec<models::Edge>) -> Vec<Uuid> {
+    range.into_iter().map(|edge| edge.key.inbound_id).collect()
+}
+
+pub fn should_get_edges_sorted_by_weight<D, T>(mut sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let (t, outbound_id, ids) = create_weighted_edges(&mut sandbox);
+    let trans = sandbox.transaction();
+
+    let q = VertexQuery::Vertex(outbound_id).sorted_outbound_edges(Some(t.clone()), None, None, EdgeOrder::StrongestFirst, 3);
+    assert_eq!(inbound_ids(trans.get_edges(q).unwrap()), vec![ids[3], ids[2], ids[1]]);
+
+    let q = VertexQuery::Vertex(outbound_id).sorted_outbound_edges(Some(t.clone()), None, None, EdgeOrder::WeakestFirst, 10);
+    assert_eq!(inbound_ids(trans.get_edges(q).unwrap()), vec![ids[0], ids[1], ids[2], ids[3]]);
+
+    // Edges of every type are included when no type is given
+    let q = VertexQuery::Vertex(outbound_id).sorted_outbound_edges(None, None, None, EdgeOrder::StrongestFirst, 3);
+    assert_eq!(inbound_ids(trans.get_edges(q).unwrap()), vec![ids[3], ids[2], ids[4]]);
+
+    // Inbound edges are sorted across all of the query's vertices
+    let q = VertexQuery::Vertices(ids[0..4].to_vec()).sorted_inbound_edges(Some(t.clone()), None, None, EdgeOrder::StrongestFirst, 2);
+    let range = trans.get_edges(q).unwrap();
+    assert!(range.iter().all(|edge| edge.key.outbound_id == outbound_id));
+    assert_eq!(inbound_ids(range), vec![ids[3], ids[2]]);
+
+    // Changing an edge's weight should move it
+    trans.create_edge(EdgeKey::new(outbound_id, t.clone(), ids[3]), models::Weight::new(0.0).unwrap()).unwrap();
+    let q = VertexQuery::Vertex(outbound_id).sorted_outbound_edges(Some(t), None, None, EdgeOrder::StrongestFirst, 10);
+    assert_eq!(inbound_ids(trans.get_edges(q).unwrap()), vec![ids[2], ids[1], ids[3], ids[0]]);
+}
+
+pub fn should_get_edges_in_weight_range<D, T>(mut sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let (t, outbound_id, ids) = create_weighted_edges(&mut sandbox);
+    let trans = sandbox.transaction();
+    let high = Some(models::Weight::new(1.0).unwrap());
+    let low = Some(models::Weight::new(0.5).unwrap());
+
+    let q = VertexQuery::Vertex(outbound_id).sorted_outbound_edges(Some(t.clone()), high, low, EdgeOrder::StrongestFirst, 10);
+    assert_eq!(inbound_ids(trans.get_edges(q).unwrap()), vec![ids[3], ids[2]]);
+
+    let q = VertexQuery::Vertex(outbound_id).sorted_outbound_edges(Some(t.clone()), high, low, EdgeOrder::WeakestFirst, 10);
+    assert_eq!(inbound_ids(trans.get_edges(q).unwrap()), vec![ids[2], ids[3]]);
+
+    let q = VertexQuery::Vertex(outbound_id).sorted_outbound_edges(None, high, low, EdgeOrder::WeakestFirst, 10);
+    assert_eq!(inbound_ids(trans.get_edges(q).unwrap()), vec![ids[4], ids[2], ids[3]]);
+
+    // Both ends of the range are inclusive
+    let exact = Some(models::Weight::new(0.75).unwrap());
+    let q = VertexQuery::Vertex(outbound_id).sorted_outbound_edges(Some(t.clone()), exact, exact, EdgeOrder::WeakestFirst, 10);
+    assert_eq!(inbound_ids(trans.get_edges(q).unwrap()), vec![ids[2]]);
+
+    let q = VertexQuery::Vertex(outbound_id).sorted_outbound_edges(Some(t.clone()), low, high, EdgeOrder::StrongestFirst, 10);
+    assert_eq!(trans.get_edges(q).unwrap().len(), 0);
+
+    // Time orders can be filtered by weight too
+    let low = Some(models::Weight::new(0.0).unwrap());
+    let q = VertexQuery::Vertex(outbound_id).sorted_outbound_edges(Some(t.clone()), None, low, EdgeOrder::NewestFirst, 10);
+    assert_eq!(inbound_ids(trans.get_edges(q).unwrap()), vec![ids[3], ids[2], ids[1]]);
+
+    let q = VertexQuery::Vertex(outbound_id).sorted_outbound_edges(Some(t), None, low, EdgeOrder::OldestFirst, 2);
+    assert_eq!(inbound_ids(trans.get_edges(q).unwrap()), vec![ids[1], ids[2]]);
+}
+
+pub fn should_combine_edge_queries<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let follows_t = models::Type::new(sandbox.generate_unique_string("test_follows_type")).unwrap();
+    let blocks_t = models::Type::new(sandbox.generate_unique_string("test_blocks_type")).unwrap();
+    let trans = sandbox.transaction();
+    let ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone(), vertex_t]).unwrap();
+    let (a, b, c) = (ids[0], ids[1], ids[2]);
+    let weight = models::Weight::new(1.0).unwrap();
+    let follows_b = EdgeKey::new(a, follows_t.clone(), b);
+    let follows_c = EdgeKey::new(a, follows_t.clone(), c);
+    let blocks_c = EdgeKey::new(a, blocks_t.clone(), c);
+    let results = trans.create_edges(vec![(follows_b.clone(), weight), (follows_c.clone(), weight), (blocks_c.clone(), weight)]).unwrap();
+    assert!(results.iter().all(|result| result.is_ok()));
+
+    let get_keys = |q: EdgeQuery| -> Vec<EdgeKey> {
+        trans.get_edges(q).unwrap().into_iter().map(|edge| edge.key).collect()
+    };
+
+    let sorted = |mut keys: Vec<EdgeKey>| -> Vec<EdgeKey> {
+        keys.sort();
+        keys
+    };
+
+    let all = VertexQuery::Vertex(a).outbound_edges(None, None, None, u32::MAX);
+    let follows = VertexQuery::Vertex(a).outbound_edges(Some(follows_t.clone()), None, None, u32::MAX);
+    let blocks = VertexQuery::Vertex(a).outbound_edges(Some(blocks_t), None, None, u32::MAX);
+    let to_c = VertexQuery::Vertex(c).inbound_edges(None, None, None, u32::MAX);
+
+    assert_eq!(sorted(get_keys(follows.clone().union(blocks.clone()))), sorted(vec![follows_b.clone(), follows_c.clone(), blocks_c.clone()]));
+    assert_eq!(get_keys(follows.clone().union(all.clone())).len(), 3);
+    assert_eq!(sorted(get_keys(all.clone().intersection(to_c.clone()))), sorted(vec![follows_c.clone(), blocks_c.clone()]));
+    assert_eq!(get_keys(follows.clone().difference(to_c.clone())), vec![follows_b.clone()]);
+    assert_eq!(get_keys(blocks.clone().difference(all.clone())), vec![]);
+
+    // Set operations can be piped from, and used to delete edges
+    let q = follows.intersection(to_c);
+    assert_eq!(trans.get_vertices(q.clone().inbound_vertices(u32::MAX)).unwrap().into_iter().map(|vertex| vertex.id).collect::<Vec<Uuid>>(), vec![c]);
+    trans.delete_edges(q).unwrap();
+    assert_eq!(sorted(get_keys(all)), sorted(vec![follows_b, blocks_c]));
+}
+
+/// Gets every page of a paged edge query, checking that each page but the
+/// last has a cursor.
+fn get_all_edge_pages<T: Transaction>(trans: &T, q: &Fn(Option<models::EdgeCursor>) -> EdgeQuery, limit: usize) -> Vec<Vec<models::Edge>> {
+    let mut pages = Vec::new();
+    let mut cursor = None;
+
+    loop {
+        let page = trans.get_edge_page(q(cursor)).unwrap();
+        assert!(page.edges.len() <= limit);
+        assert_eq!(page.cursor.is_some(), page.edges.len() == limit);
+        cursor = page.cursor;
+        pages.push(page.edges);
+
+        if cursor.is_none() {
+            return pages;
+        }
+    }
+}
+
+pub fn should_page_through_edges<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new(sandbox.generate_unique_string("test_paged_edge_type")).unwrap();
+    let other_edge_t = models::Type::new(sandbox.generate_unique_string("test_other_paged_edge_type")).unwrap();
+    let trans = sandbox.transaction();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_ids = trans.create_vertices(vec![vertex_t.clone(); 7]).unwrap();
+    let weight = models::Weight::new(1.0).unwrap();
+
+    // Creating the edges in bulk gives many of them the same update
+    // datetime, which pages have to be able to split between
+    let mut items: Vec<(EdgeKey, models::Weight)> = inbound_ids.iter().map(|id| (EdgeKey::new(outbound_id, edge_t.clone(), *id), weight)).collect();
+    items.push((EdgeKey::new(outbound_id, other_edge_t.clone(), inbound_ids[0]), weight));
+    items.push((EdgeKey::new(outbound_id, other_edge_t.clone(), inbound_ids[1]), weight));
+    assert!(trans.create_edges(items).unwrap().iter().all(|result| result.is_ok()));
+    trans.commit().unwrap();
+
+    let trans = sandbox.transaction();
+    let pages = get_all_edge_pages(&trans, &|cursor| VertexQuery::Vertex(outbound_id).outbound_edge_page(Some(edge_t.clone()), cursor, 3), 3);
+    assert_eq!(pages.iter().map(|page| page.len()).collect::<Vec<usize>>(), vec![3, 3, 1]);
+    let edges: Vec<models::Edge> = pages.into_iter().flat_map(|page| page).collect();
+
+    let mut expected_ids = inbound_ids.clone();
+    expected_ids.sort();
+    let mut ids: Vec<Uuid> = edges.iter().map(|edge| edge.key.inbound_id).collect();
+    ids.sort();
+    assert_eq!(ids, expected_ids);
+
+    // Newest first, with ties broken by the inbound ID
+    for window in edges.windows(2) {
+        assert!(window[0].updated_datetime >= window[1].updated_datetime);
+
+        if window[0].updated_datetime == window[1].updated_datetime {
+            assert!(window[0].key.inbound_id < window[1].key.inbound_id);
+        }
+    }
+
+    // Without a type, the last page is full, so there's an extra empty page
+    let pages = get_all_edge_pages(&trans, &|cursor| VertexQuery::Vertex(outbound_id).outbound_edge_page(None, cursor, 3), 3);
+    assert_eq!(pages.iter().map(|page| page.len()).collect::<Vec<usize>>(), vec![3, 3, 3, 0]);
+    let keys: HashSet<EdgeKey> = pages.into_iter().flat_map(|page| page).map(|edge| edge.key).collect();
+    assert_eq!(keys.len(), 9);
+
+    // Inbound pages can span several vertices
+    let pages = get_all_edge_pages(&trans, &|cursor| VertexQuery::Vertices(inbound_ids.clone()).inbound_edge_page(None, cursor, 2), 2);
+    assert_eq!(pages.iter().map(|page| page.len()).collect::<Vec<usize>>(), vec![2, 2, 2, 2, 1]);
+    let keys: HashSet<EdgeKey> = pages.into_iter().flat_map(|page| page).map(|edge| edge.key).collect();
+    assert_eq!(keys.len(), 9);
+    assert!(keys.iter().all(|key| key.outbound_id == outbound_id));
+}
+
 fn check_edge_range(range: Vec<models::Edge>, expected_outbound_id: Uuid, expected_length: usize) {
     assert_eq!(range.len(), expected_length);
     let mut covered_ids: HashSet<Uuid> = HashSet::new();
@@ -322,8 +808,211 @@ fn check_edge_range(range: Vec<models::Edge>, expected_outbound_id: Uuid, expect
     for edge in &range {
         assert_eq!(edge.key.outbound_id, expected_outbound_id);
         assert_eq!(edge.key.t, t);
-        assert!(edge.weight.0 <= 1.0 + f32::EPSILON && edge.weight.0 >= 1.0 - f32::EPSILON);
+        assert!(edge.weight.value() <= 1.0 + f32::EPSILON && edge.weight.value() >= 1.0 - f32::EPSILON);
         assert!(!covered_ids.contains(&edge.key.inbound_id));
         covered_ids.insert(edge.key.inbound_id);
     }
 }
+
+pub fn should_keep_edge_history<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new(sandbox.generate_unique_string("test_edge_history_type")).unwrap();
+    let trans = sandbox.transaction();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone()]).unwrap();
+    let key = EdgeKey::new(outbound_id, edge_t.clone(), inbound_ids[0]);
+    let other_key = EdgeKey::new(outbound_id, edge_t.clone(), inbound_ids[1]);
+    let january = UTC.ymd(2016, 1, 1).and_hms(0, 0, 0);
+    let february = UTC.ymd(2016, 2, 1).and_hms(0, 0, 0);
+    let march = UTC.ymd(2016, 3, 1).and_hms(0, 0, 0);
+
+    // Edges that exist when history is enabled are kept as their first
+    // version, at whatever version number they're at
+    trans.create_edge_at(key.clone(), models::Weight::new(0.5).unwrap(), january).unwrap();
+    trans.create_edge_at(key.clone(), models::Weight::new(0.1).unwrap(), january).unwrap();
+    trans.commit().unwrap();
+    sandbox.datastore.enable_edge_history(edge_t.clone()).unwrap();
+    sandbox.datastore.enable_edge_history(edge_t.clone()).unwrap();
+    assert!(sandbox.datastore.get_edge_history_types().unwrap().contains(&edge_t));
+
+    let trans = sandbox.transaction();
+    trans.create_edge_at(key.clone(), models::Weight::new(0.2).unwrap(), march).unwrap();
+    trans.create_edge_at(key.clone(), models::Weight::new(0.3).unwrap(), february).unwrap();
+    trans.create_edge(other_key.clone(), models::Weight::new(0.5).unwrap()).unwrap();
+    trans.delete_edges(EdgeQuery::Edge(key.clone())).unwrap();
+    trans.commit().unwrap();
+
+    let trans = sandbox.transaction();
+    let history = trans.get_edge_history(key.clone()).unwrap();
+    assert_eq!(history.len(), 4);
+    assert_eq!(history[0], models::EdgeVersion::new(Some(models::Weight::new(0.1).unwrap()), Some(2), january));
+    assert_eq!(history[1], models::EdgeVersion::new(Some(models::Weight::new(0.3).unwrap()), Some(4), february));
+    assert_eq!(history[2], models::EdgeVersion::new(Some(models::Weight::new(0.2).unwrap()), Some(3), march));
+    assert_eq!(history[3].weight, None);
+    assert_eq!(history[3].version, None);
+    assert!(history[3].datetime > march);
+    assert_eq!(trans.get_edge_history(other_key.clone()).unwrap().len(), 1);
+    trans.commit().unwrap();
+
+    // Disabling history deletes it, and stops new versions from being kept
+    sandbox.datastore.disable_edge_history(edge_t.clone()).unwrap();
+    assert!(!sandbox.datastore.get_edge_history_types().unwrap().contains(&edge_t));
+    let trans = sandbox.transaction();
+    assert!(trans.get_edge_history(key.clone()).unwrap().is_empty());
+    trans.create_edge(other_key.clone(), models::Weight::new(0.6).unwrap()).unwrap();
+    trans.commit().unwrap();
+    let trans = sandbox.transaction();
+    assert!(trans.get_edge_history(other_key).unwrap().is_empty());
+}
+
+pub fn should_get_edges_as_of_a_given_datetime<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new(sandbox.generate_unique_string("test_edge_as_of_type")).unwrap();
+    sandbox.datastore.enable_edge_history(edge_t.clone()).unwrap();
+
+    let trans = sandbox.transaction();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone(), vertex_t.clone()]).unwrap();
+    let keys: Vec<EdgeKey> = inbound_ids.iter().map(|&id| EdgeKey::new(outbound_id, edge_t.clone(), id)).collect();
+    let january = UTC.ymd(2016, 1, 1).and_hms(0, 0, 0);
+    let february = UTC.ymd(2016, 2, 1).and_hms(0, 0, 0);
+    let march = UTC.ymd(2016, 3, 1).and_hms(0, 0, 0);
+    let april = UTC.ymd(2016, 4, 1).and_hms(0, 0, 0);
+    trans.create_edge_at(keys[0].clone(), models::Weight::new(0.1).unwrap(), january).unwrap();
+    trans.create_edge_at(keys[1].clone(), models::Weight::new(0.1).unwrap(), february).unwrap();
+    trans.create_edge_at(keys[0].clone(), models::Weight::new(0.2).unwrap(), march).unwrap();
+    trans.create_edge_at(keys[2].clone(), models::Weight::new(0.1).unwrap(), april).unwrap();
+    trans.delete_edges(EdgeQuery::Edge(keys[1].clone())).unwrap();
+    trans.commit().unwrap();
+
+    let get_edges_as_of = |q: VertexQuery, converter: models::QueryTypeConverter, datetime, limit| -> Vec<models::Edge> {
+        let trans = sandbox.transaction();
+        trans.get_edges_as_of(q, converter, edge_t.clone(), datetime, limit).unwrap()
+    };
+
+    let outbound = || VertexQuery::Vertex(outbound_id);
+    let edges = get_edges_as_of(outbound(), models::QueryTypeConverter::Outbound, UTC.ymd(2015, 1, 1).and_hms(0, 0, 0), 10);
+    assert!(edges.is_empty());
+
+    let edges = get_edges_as_of(outbound(), models::QueryTypeConverter::Outbound, UTC.ymd(2016, 2, 15).and_hms(0, 0, 0), 10);
+    let edge_keys: Vec<EdgeKey> = edges.iter().map(|edge| edge.key.clone()).collect();
+    assert_eq!(edge_keys, vec![keys[1].clone(), keys[0].clone()]);
+    assert_eq!(edges[1].weight, models::Weight::new(0.1).unwrap());
+    assert_eq!(edges[1].version, 1);
+
+    let edges = get_edges_as_of(outbound(), models::QueryTypeConverter::Outbound, UTC.ymd(2016, 5, 1).and_hms(0, 0, 0), 10);
+    let edge_keys: Vec<EdgeKey> = edges.iter().map(|edge| edge.key.clone()).collect();
+    assert_eq!(edge_keys, vec![keys[2].clone(), keys[0].clone(), keys[1].clone()]);
+    assert_eq!(edges[1].weight, models::Weight::new(0.2).unwrap());
+    assert_eq!(edges[1].created_datetime, january);
+    assert_eq!(edges[1].updated_datetime, march);
+    assert_eq!(edges[1].version, 2);
+
+    let edges = get_edges_as_of(outbound(), models::QueryTypeConverter::Outbound, UTC.ymd(2016, 5, 1).and_hms(0, 0, 0), 1);
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].key, keys[2]);
+
+    // The deleted edge is gone as of now
+    let edges = get_edges_as_of(outbound(), models::QueryTypeConverter::Outbound, UTC::now(), 10);
+    let edge_keys: Vec<EdgeKey> = edges.iter().map(|edge| edge.key.clone()).collect();
+    assert_eq!(edge_keys, vec![keys[2].clone(), keys[0].clone()]);
+
+    let edges = get_edges_as_of(VertexQuery::Vertex(inbound_ids[0]), models::QueryTypeConverter::Inbound, february, 10);
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].key, keys[0]);
+    assert_eq!(edges[0].updated_datetime, january);
+
+    // History is deleted along with the vertices
+    let trans = sandbox.transaction();
+    trans.delete_vertices(VertexQuery::Vertex(inbound_ids[0])).unwrap();
+    trans.commit().unwrap();
+    let trans = sandbox.transaction();
+    assert!(trans.get_edge_history(keys[0].clone()).unwrap().is_empty());
+    assert_eq!(trans.get_edge_history(keys[2].clone()).unwrap().len(), 1);
+}
+
+pub fn should_create_edges_with_expected_versions<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new("test_edge_type".to_string()).unwrap();
+    let trans = sandbox.transaction();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_id = trans.create_vertex(vertex_t).unwrap();
+    let key = EdgeKey::new(outbound_id, edge_t.clone(), inbound_id);
+    let weight = models::Weight::new(0.5).unwrap();
+
+    // The edge doesn't exist yet, so it isn't at any version
+    let result = trans.create_edge_if_version(key.clone(), weight, Some(1));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+    assert_eq!(trans.create_edge_if_version(key.clone(), weight, None).unwrap(), 1);
+    let result = trans.create_edge_if_version(key.clone(), weight, None);
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+
+    // Unconditional updates bump the version too
+    trans.create_edge(key.clone(), weight).unwrap();
+    let edges = trans.get_edges(EdgeQuery::Edge(key.clone())).unwrap();
+    assert_eq!(edges[0].version, 2);
+    let result = trans.create_edge_if_version(key.clone(), models::Weight::new(0.7).unwrap(), Some(1));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+    assert_eq!(trans.create_edge_if_version(key.clone(), models::Weight::new(0.7).unwrap(), Some(2)).unwrap(), 3);
+    let edges = trans.get_edges(EdgeQuery::Edge(key.clone())).unwrap();
+    assert_eq!(edges[0].version, 3);
+    assert_eq!(edges[0].weight, models::Weight::new(0.7).unwrap());
+    trans.commit().unwrap();
+
+    // Writes based on a version that another transaction has since moved on
+    // from are rejected
+    let first_trans = sandbox.transaction();
+    let second_trans = sandbox.transaction();
+    assert_eq!(first_trans.create_edge_if_version(key.clone(), models::Weight::new(0.8).unwrap(), Some(3)).unwrap(), 4);
+    first_trans.commit().unwrap();
+    let result = second_trans.create_edge_if_version(key.clone(), models::Weight::new(0.9).unwrap(), Some(3));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+
+    // Deleting the edge resets its version
+    let trans = sandbox.transaction();
+    trans.delete_edges(EdgeQuery::Edge(key.clone())).unwrap();
+    assert_eq!(trans.create_edge_if_version(key, weight, None).unwrap(), 1);
+
+    let result = trans.create_edge_if_version(EdgeKey::new(outbound_id, edge_t, Uuid::default()), weight, None);
+    assert_eq!(result.unwrap_err(), Error::VertexNotFound);
+}
+
+pub fn should_expire_edges<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new("test_edge_type".to_string()).unwrap();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_ids: Vec<Uuid> = (0..3).map(|_| trans.create_vertex(vertex_t.clone()).unwrap()).collect();
+    let keys: Vec<EdgeKey> = inbound_ids.iter().map(|id| EdgeKey::new(outbound_id, edge_t.clone(), *id)).collect();
+    let weight = models::Weight::new(0.5).unwrap();
+    trans.create_edge_with_ttl(keys[0].clone(), weight, Duration::milliseconds(100)).unwrap();
+    trans.create_edge_with_ttl(keys[1].clone(), weight, Duration::days(1)).unwrap();
+
+    // Setting an edge without a TTL clears any expiry it had
+    trans.create_edge_with_ttl(keys[2].clone(), weight, Duration::milliseconds(100)).unwrap();
+    trans.create_edge(keys[2].clone(), weight).unwrap();
+    trans.commit().unwrap();
+
+    thread::sleep(time::Duration::from_millis(200));
+    let trans = sandbox.transaction();
+    assert_eq!(trans.get_edges(EdgeQuery::Edge(keys[0].clone())).unwrap().len(), 0);
+    let edges = trans.get_edges(VertexQuery::Vertex(outbound_id).outbound_edges(Some(edge_t), None, None, 10)).unwrap();
+    let inbound_edge_ids: HashSet<Uuid> = edges.iter().map(|edge| edge.key.inbound_id).collect();
+    assert_eq!(inbound_edge_ids, inbound_ids[1..].iter().cloned().collect());
+
+    // The expired edge can be recreated
+    trans.create_edge(keys[0].clone(), weight).unwrap();
+    assert_eq!(trans.get_edges(EdgeQuery::Edge(keys[0].clone())).unwrap().len(), 1);
+}
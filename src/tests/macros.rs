@@ -34,22 +34,40 @@ macro_rules! test_transaction_impl {
 		define_test!(should_get_all_vertices, $code);
 		define_test!(should_get_all_vertices_with_zero_limit, $code);
 		define_test!(should_get_all_vertices_out_of_range, $code);
+		define_test!(should_get_all_vertices_of_type, $code);
+		define_test!(should_page_vertices_of_type, $code);
+		define_test!(should_stream_vertices, $code);
+		define_test!(should_create_vertices_in_bulk, $code);
+		define_test!(should_create_vertex_with_id, $code);
+		define_test!(should_not_create_vertex_with_existing_id, $code);
 		define_test!(should_get_single_vertices, $code);
 		define_test!(should_get_single_vertices_nonexisting, $code);
 		define_test!(should_get_vertices, $code);
 		define_test!(should_get_vertices_piped, $code);
+		define_test!(should_traverse_vertices, $code);
+		define_test!(should_traverse_cycles_without_a_depth_limit, $code);
+		define_test!(should_combine_vertex_queries, $code);
 
 		// Vertex updates
 		define_test!(should_delete_a_valid_vertex, $code);
 		define_test!(should_not_delete_an_invalid_vertex, $code);
 		define_test!(should_not_delete_an_unowned_vertex, $code);
+		define_test!(should_expire_vertices, $code);
+		define_test!(should_hide_items_that_expire_during_a_transaction, $code);
+		define_test!(should_not_accept_non_positive_ttls, $code);
+		define_test!(should_not_accept_ttls_past_unrepresentable_datetimes, $code);
+		define_test!(should_delete_expired_items, $code);
 
 		// Edges
 		define_test!(should_get_a_valid_edge, $code);
 		define_test!(should_not_get_an_invalid_edge, $code);
 		define_test!(should_create_a_valid_edge, $code);
+		define_test!(should_create_edges_at_a_given_datetime, $code);
+		define_test!(should_not_create_edges_at_unrepresentable_datetimes, $code);
+		define_test!(should_keep_created_datetimes_when_updating_edges, $code);
 		define_test!(should_not_create_an_invalid_edge, $code);
 		define_test!(should_not_create_an_edge_with_bad_permissions, $code);
+		define_test!(should_create_edges_in_bulk, $code);
 		define_test!(should_delete_a_valid_edge, $code);
 		define_test!(should_not_delete_an_invalid_edge, $code);
 		define_test!(should_not_delete_an_edge_with_bad_permissions, $code);
@@ -63,7 +81,22 @@ macro_rules! test_transaction_impl {
 		define_test!(should_get_edges_with_no_low, $code);
 		define_test!(should_get_edges_with_no_time, $code);
 		define_test!(should_get_no_edges_for_reversed_time, $code);
+		define_test!(should_limit_edges_with_a_low_datetime_across_vertices, $code);
+		define_test!(should_get_inbound_edges_with_their_keys, $code);
 		define_test!(should_get_edges, $code);
+		define_test!(should_stream_edges, $code);
+		define_test!(should_get_edges_sorted_by_weight, $code);
+		define_test!(should_get_edges_in_weight_range, $code);
+		define_test!(should_page_through_edges, $code);
+		define_test!(should_combine_edge_queries, $code);
+		define_test!(should_keep_edge_history, $code);
+		define_test!(should_get_edges_as_of_a_given_datetime, $code);
+		define_test!(should_create_edges_with_expected_versions, $code);
+		define_test!(should_expire_edges, $code);
+
+		// Paths
+		define_test!(should_get_the_shortest_path, $code);
+		define_test!(should_get_the_weighted_path, $code);
 	)
 }
 
@@ -76,11 +109,42 @@ macro_rules! test_metadata_impl {
 		define_test!(should_handle_account_metadata, $code);
 		define_test!(should_not_set_invalid_account_metadata, $code);
 		define_test!(should_not_delete_invalid_account_metadata, $code);
+		define_test!(should_update_global_metadata_atomically, $code);
+		define_test!(should_update_account_metadata_atomically, $code);
+		define_test!(should_list_global_metadata, $code);
+		define_test!(should_list_account_metadata, $code);
 		define_test!(should_handle_vertex_metadata, $code);
+		define_test!(should_stream_vertex_metadata, $code);
+		define_test!(should_filter_vertices_by_metadata, $code);
+		define_test!(should_filter_edges_by_metadata, $code);
+		define_test!(should_lookup_vertices_by_metadata, $code);
+		define_test!(should_find_vertices_with_metadata, $code);
+		define_test!(should_find_edges_with_metadata, $code);
+		define_test!(should_enforce_unique_constraints, $code);
+		define_test!(should_set_vertex_metadata_with_expected_versions, $code);
+		define_test!(should_set_global_metadata_with_expected_versions, $code);
+		define_test!(should_set_account_metadata_with_expected_versions, $code);
+		define_test!(should_set_edge_metadata_with_expected_versions, $code);
+		define_test!(should_update_vertex_metadata_atomically, $code);
+		define_test!(should_list_vertex_metadata, $code);
 		define_test!(should_not_set_invalid_vertex_metadata, $code);
 		define_test!(should_not_delete_invalid_vertex_metadata, $code);
 		define_test!(should_handle_edge_metadata, $code);
+		define_test!(should_update_edge_metadata_atomically, $code);
+		define_test!(should_list_edge_metadata, $code);
 		define_test!(should_not_set_invalid_edge_metadata, $code);
 		define_test!(should_not_delete_invalid_edge_metadata, $code);
+		define_test!(should_expire_vertex_metadata, $code);
+		define_test!(should_expire_edge_metadata, $code);
+	)
+}
+
+/// Use this macro to enable the standard test suite for transaction
+/// rollbacks.
+#[macro_export]
+macro_rules! test_rollback_impl {
+	($code:expr) => (
+		define_test!(should_rollback_created_items, $code);
+		define_test!(should_rollback_deleted_items, $code);
 	)
 }
@@ -1,8 +1,13 @@
-use super::super::{Datastore, Transaction, EdgeQuery, VertexQuery, EdgeKey, Type, Weight};
+use super::super::{Datastore, Transaction, EdgeQuery, VertexQuery, EdgeKey, MetadataOperation, MetadataPredicate, Type, UniqueConstraint, VersionedMetadata, VertexMetadataIndex, Weight};
 use super::sandbox::DatastoreTestSandbox;
 use errors::Error;
 use uuid::Uuid;
 use serde_json::Value as JsonValue;
+use serde_json;
+use chrono::Duration;
+use std::collections::HashMap;
+use std::thread;
+use std::time;
 
 pub fn should_handle_global_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
     where D: Datastore<T>,
@@ -84,6 +89,119 @@ pub fn should_not_delete_invalid_account_metadata<D, T>(sandbox: &mut DatastoreT
     assert_eq!(result.unwrap_err(), Error::MetadataNotFound);
 }
 
+pub fn should_update_global_metadata_atomically<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let name = sandbox.generate_unique_string("atomic-global-metadata");
+    let trans = sandbox.transaction();
+
+    // Compare-and-swap only sets the value if it matches what's expected
+    let result = trans.update_global_metadata(name.clone(), MetadataOperation::CompareAndSwap(Some(json("1")), json("2")));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+    assert_eq!(trans.get_global_metadata(name.clone()).unwrap_err(), Error::MetadataNotFound);
+    let result = trans.update_global_metadata(name.clone(), MetadataOperation::CompareAndSwap(None, json("1")));
+    assert_eq!(result.unwrap(), json("1"));
+    let result = trans.update_global_metadata(name.clone(), MetadataOperation::CompareAndSwap(None, json("2")));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+    let result = trans.update_global_metadata(name.clone(), MetadataOperation::CompareAndSwap(Some(json("1.0")), json(r#"{"count": 1}"#)));
+    assert_eq!(result.unwrap(), json(r#"{"count": 1}"#));
+
+    // Increments add to the number at a path, treating missing keys as 0
+    let result = trans.update_global_metadata(name.clone(), MetadataOperation::Increment(vec!["count".to_string()], 2.0));
+    assert_eq!(result.unwrap(), json(r#"{"count": 3}"#));
+    let result = trans.update_global_metadata(name.clone(), MetadataOperation::Increment(vec!["total".to_string()], 0.5));
+    assert_eq!(result.unwrap(), json(r#"{"count": 3, "total": 0.5}"#));
+    let result = trans.update_global_metadata(name.clone(), MetadataOperation::Increment(vec!["missing".to_string(), "count".to_string()], 1.0));
+    assert_eq!(result.unwrap_err(), Error::InvalidMetadataOperation);
+    let result = trans.update_global_metadata(name.clone(), MetadataOperation::Increment(vec![], 1.0));
+    assert_eq!(result.unwrap_err(), Error::InvalidMetadataOperation);
+
+    // Merge patches merge objects recursively and remove keys set to null
+    let patch = json(r#"{"total": null, "nested": {"a": 1, "b": [1, 2]}}"#);
+    let result = trans.update_global_metadata(name.clone(), MetadataOperation::MergePatch(patch));
+    assert_eq!(result.unwrap(), json(r#"{"count": 3, "nested": {"a": 1, "b": [1, 2]}}"#));
+    let patch = json(r#"{"nested": {"a": null, "b": [3]}}"#);
+    let result = trans.update_global_metadata(name.clone(), MetadataOperation::MergePatch(patch));
+    assert_eq!(result.unwrap(), json(r#"{"count": 3, "nested": {"b": [3]}}"#));
+    assert_eq!(trans.get_global_metadata(name.clone()).unwrap(), json(r#"{"count": 3, "nested": {"b": [3]}}"#));
+
+    let result = trans.update_global_metadata(name.clone(), MetadataOperation::MergePatch(json("[1]")));
+    assert_eq!(result.unwrap(), json("[1]"));
+    let result = trans.update_global_metadata(name.clone(), MetadataOperation::Increment(vec!["0".to_string()], -1.0));
+    assert_eq!(result.unwrap(), json("[0]"));
+}
+
+pub fn should_update_account_metadata_atomically<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let name = sandbox.generate_unique_string("atomic-account-metadata");
+    let trans = sandbox.transaction();
+
+    let result = trans.update_account_metadata(sandbox.owner_id, name.clone(), MetadataOperation::Increment(vec![], 1.0));
+    assert_eq!(result.unwrap(), json("1"));
+    let result = trans.update_account_metadata(sandbox.owner_id, name.clone(), MetadataOperation::Increment(vec![], 1.0));
+    assert_eq!(result.unwrap(), json("2"));
+    let result = trans.update_account_metadata(sandbox.owner_id, name.clone(), MetadataOperation::CompareAndSwap(Some(json("1")), json("3")));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+    assert_eq!(trans.get_account_metadata(sandbox.owner_id, name.clone()).unwrap(), json("2"));
+
+    let result = trans.update_account_metadata(Uuid::default(), name, MetadataOperation::Increment(vec![], 1.0));
+    assert_eq!(result.unwrap_err(), Error::AccountNotFound);
+}
+
+pub fn should_list_global_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let prefix = sandbox.generate_unique_string("listed-global-metadata");
+    let trans = sandbox.transaction();
+
+    for name in &["c", "a", "b"] {
+        trans.set_global_metadata(format!("{}/{}", prefix, name), json(r#""value""#)).unwrap();
+    }
+
+    // Names that only share part of the prefix aren't listed
+    trans.set_global_metadata(prefix[..prefix.len() - 1].to_string(), json("true")).unwrap();
+
+    let a = format!("{}/a", prefix);
+    let b = format!("{}/b", prefix);
+    let c = format!("{}/c", prefix);
+
+    // Pages are sorted by name
+    let names = trans.get_global_metadata_names(prefix.clone(), None, 2).unwrap();
+    assert_eq!(names, vec![a.clone(), b.clone()]);
+    let names = trans.get_global_metadata_names(prefix.clone(), Some(b.clone()), 2).unwrap();
+    assert_eq!(names, vec![c.clone()]);
+    let names = trans.get_global_metadata_names(prefix.clone(), Some(c.clone()), 2).unwrap();
+    assert_eq!(names.len(), 0);
+    let names = trans.get_global_metadata_names(prefix.clone(), Some("".to_string()), 10).unwrap();
+    assert_eq!(names, vec![a.clone(), b.clone(), c.clone()]);
+
+    let metadata = trans.get_all_global_metadata(prefix.clone(), Some(a.clone()), 10).unwrap();
+    assert_eq!(metadata, vec![(b, json(r#""value""#)), (c, json(r#""value""#))]);
+}
+
+pub fn should_list_account_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    assert_eq!(trans.get_account_metadata_names(sandbox.owner_id).unwrap().len(), 0);
+
+    trans.set_account_metadata(sandbox.owner_id, "b".to_string(), json("2")).unwrap();
+    trans.set_account_metadata(sandbox.owner_id, "a".to_string(), json("1")).unwrap();
+
+    let names = trans.get_account_metadata_names(sandbox.owner_id).unwrap();
+    assert_eq!(names, vec!["a".to_string(), "b".to_string()]);
+    let metadata = trans.get_all_account_metadata(sandbox.owner_id).unwrap();
+    assert_eq!(metadata, vec![("a".to_string(), json("1")), ("b".to_string(), json("2"))]);
+
+    let result = trans.get_account_metadata_names(Uuid::default());
+    assert_eq!(result.unwrap_err(), Error::AccountNotFound);
+}
+
 pub fn should_handle_vertex_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
     where D: Datastore<T>,
           T: Transaction
@@ -114,6 +232,589 @@ pub fn should_handle_vertex_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D,
     assert_eq!(result.len(), 0);
 }
 
+pub fn should_stream_vertex_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let t = Type::new(sandbox.generate_unique_string("test_vertex_type")).unwrap();
+    let first_id = trans.create_vertex(t.clone()).unwrap();
+    let second_id = trans.create_vertex(t.clone()).unwrap();
+    let third_id = trans.create_vertex(t.clone()).unwrap();
+    let name = sandbox.generate_unique_string("vertex-metadata");
+
+    trans.set_vertex_metadata(VertexQuery::Vertex(first_id), name.clone(), JsonValue::Bool(true)).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(third_id), name.clone(), JsonValue::Bool(false)).unwrap();
+
+    // Vertices without the metadata should be skipped
+    let q = VertexQuery::Vertices(vec![first_id, second_id, third_id]);
+    let streamed: HashMap<Uuid, JsonValue> = trans.stream_vertex_metadata(q.clone(), name.clone()).unwrap().map(|item| item.unwrap()).collect();
+    assert_eq!(streamed.len(), 2);
+    assert_eq!(streamed.get(&first_id), Some(&JsonValue::Bool(true)));
+    assert_eq!(streamed.get(&third_id), Some(&JsonValue::Bool(false)));
+    assert_eq!(streamed, trans.get_vertex_metadata(q, name).unwrap());
+}
+
+fn json(s: &str) -> JsonValue {
+    serde_json::from_str(s).unwrap()
+}
+
+pub fn should_filter_vertices_by_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let t = Type::new("test_vertex_type".to_string()).unwrap();
+    let ids = trans.create_vertices(vec![t.clone(), t.clone(), t.clone(), t]).unwrap();
+    let name = sandbox.generate_unique_string("vertex-metadata");
+
+    // The last vertex has no metadata
+    let values = vec![
+        json(r#"{"age": 25, "status": "active", "tags": ["a", "b"]}"#),
+        json(r#"{"age": 35.5, "status": "inactive", "tags": []}"#),
+        json(r#"{"age": "40", "status": "active"}"#),
+    ];
+
+    for (id, value) in ids.iter().zip(values) {
+        trans.set_vertex_metadata(VertexQuery::Vertex(*id), name.clone(), value).unwrap();
+    }
+
+    let get_ids = |predicate: MetadataPredicate| -> Vec<Uuid> {
+        let q = VertexQuery::Vertices(ids.clone()).filter(name.clone(), predicate);
+        let mut ids: Vec<Uuid> = trans.get_vertices(q).unwrap().into_iter().map(|vertex| vertex.id).collect();
+        ids.sort();
+        ids
+    };
+
+    let path = |path: &str, predicate: MetadataPredicate| -> MetadataPredicate {
+        MetadataPredicate::Path(path.split('.').map(|s| s.to_string()).collect(), Box::new(predicate))
+    };
+
+    let sorted = |mut ids: Vec<Uuid>| -> Vec<Uuid> {
+        ids.sort();
+        ids
+    };
+
+    assert_eq!(get_ids(MetadataPredicate::Exists), sorted(vec![ids[0], ids[1], ids[2]]));
+    assert_eq!(get_ids(path("status", MetadataPredicate::Equal(json(r#""active""#)))), sorted(vec![ids[0], ids[2]]));
+    assert_eq!(get_ids(path("status", MetadataPredicate::NotEqual(json(r#""active""#)))), vec![ids[1]]);
+
+    // Ordering comparisons only match values of the same type, so the
+    // string "40" is never included
+    assert_eq!(get_ids(path("age", MetadataPredicate::GreaterThan(json("30")))), vec![ids[1]]);
+    assert_eq!(get_ids(path("age", MetadataPredicate::GreaterThanOrEqual(json("25")))), sorted(vec![ids[0], ids[1]]));
+    assert_eq!(get_ids(path("age", MetadataPredicate::LessThan(json("25")))), vec![]);
+    assert_eq!(get_ids(path("age", MetadataPredicate::LessThanOrEqual(json("25.0")))), vec![ids[0]]);
+    assert_eq!(get_ids(path("age", MetadataPredicate::Equal(json("25.0")))), vec![ids[0]]);
+    assert_eq!(get_ids(path("age", MetadataPredicate::GreaterThan(json("\"30\"")))), vec![ids[2]]);
+
+    // Paths can go into arrays, and don't match if there's nothing there
+    assert_eq!(get_ids(path("tags", MetadataPredicate::Exists)), sorted(vec![ids[0], ids[1]]));
+    assert_eq!(get_ids(path("tags.1", MetadataPredicate::Equal(json(r#""b""#)))), vec![ids[0]]);
+    assert_eq!(get_ids(path("tags.0", MetadataPredicate::Exists)), vec![ids[0]]);
+    assert_eq!(get_ids(path("missing", MetadataPredicate::NotEqual(json("1")))), vec![]);
+
+    // Filtered queries can be piped from
+    let q = VertexQuery::Vertices(ids.clone()).filter(name.clone(), path("age", MetadataPredicate::GreaterThan(json("30"))));
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+    trans.create_edge(EdgeKey::new(ids[1], edge_t.clone(), ids[3]), Weight::new(1.0).unwrap()).unwrap();
+    let range = trans.get_edges(q.outbound_edges(Some(edge_t), None, None, 10)).unwrap();
+    assert_eq!(range.len(), 1);
+    assert_eq!(range[0].key.inbound_id, ids[3]);
+}
+
+pub fn should_filter_edges_by_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = Type::new(sandbox.generate_unique_string("test_edge_type")).unwrap();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone(), vertex_t]).unwrap();
+    let name = sandbox.generate_unique_string("edge-metadata");
+    let weight = Weight::new(0.5).unwrap();
+    let keys: Vec<EdgeKey> = inbound_ids.iter().map(|id| EdgeKey::new(outbound_id, edge_t.clone(), *id)).collect();
+
+    for key in &keys {
+        trans.create_edge(key.clone(), weight).unwrap();
+    }
+
+    trans.set_edge_metadata(EdgeQuery::Edge(keys[0].clone()), name.clone(), json(r#"{"since": 2010}"#)).unwrap();
+    trans.set_edge_metadata(EdgeQuery::Edge(keys[1].clone()), name.clone(), json(r#"{"since": 2015}"#)).unwrap();
+
+    let all = VertexQuery::Vertex(outbound_id).outbound_edges(Some(edge_t), None, None, 10);
+    let since = |predicate: MetadataPredicate| -> MetadataPredicate {
+        MetadataPredicate::Path(vec!["since".to_string()], Box::new(predicate))
+    };
+
+    let get_keys = |q: EdgeQuery| -> Vec<EdgeKey> {
+        let mut keys: Vec<EdgeKey> = trans.get_edges(q).unwrap().into_iter().map(|edge| edge.key).collect();
+        keys.sort();
+        keys
+    };
+
+    let mut expected_keys = vec![keys[0].clone(), keys[1].clone()];
+    expected_keys.sort();
+    assert_eq!(get_keys(all.clone().filter(name.clone(), MetadataPredicate::Exists)), expected_keys);
+    assert_eq!(get_keys(all.clone().filter(name.clone(), since(MetadataPredicate::LessThan(json("2012"))))), vec![keys[0].clone()]);
+
+    let q = all.filter(name.clone(), since(MetadataPredicate::GreaterThanOrEqual(json("2012"))));
+    let vertices = trans.get_vertices(q.inbound_vertices(10)).unwrap();
+    assert_eq!(vertices.len(), 1);
+    assert_eq!(vertices[0].id, inbound_ids[1]);
+}
+
+pub fn should_lookup_vertices_by_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let t = Type::new(sandbox.generate_unique_string("lookup")).unwrap();
+    let other_t = Type::new(sandbox.generate_unique_string("other-lookup")).unwrap();
+    let name = sandbox.generate_unique_string("lookup-metadata");
+    let index = VertexMetadataIndex::new(t.clone(), name.clone(), vec!["email".to_string()]);
+
+    let trans = sandbox.transaction();
+    let ids = trans.create_vertices(vec![t.clone(), t.clone(), t.clone(), other_t.clone()]).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(ids[0]), name.clone(), json(r#"{"email": "a@example.com", "age": 30}"#)).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(ids[1]), name.clone(), json(r#"{"email": "b@example.com", "age": 30.0}"#)).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(ids[2]), name.clone(), json(r#"{"email": "a@example.com"}"#)).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(ids[3]), name.clone(), json(r#"{"email": "a@example.com"}"#)).unwrap();
+    trans.commit().unwrap();
+
+    let lookup = |path: &str, value: &str, start_id: Option<Uuid>, limit: u32| -> Vec<Uuid> {
+        let predicate = MetadataPredicate::Path(vec![path.to_string()], Box::new(MetadataPredicate::Equal(json(value))));
+        let q = VertexQuery::Lookup(t.clone(), name.clone(), predicate, start_id, limit);
+        sandbox.transaction().get_vertices(q).unwrap().into_iter().map(|vertex| vertex.id).collect()
+    };
+
+    let mut expected_ids = vec![ids[0], ids[2]];
+    expected_ids.sort();
+
+    // Lookups work before there's an index
+    assert_eq!(lookup("email", r#""a@example.com""#, None, 10), expected_ids);
+    assert_eq!(lookup("age", "30", None, 10), {
+        let mut ids = vec![ids[0], ids[1]];
+        ids.sort();
+        ids
+    });
+
+    // And give the same results with one, including for existing vertices
+    sandbox.datastore.create_vertex_metadata_index(index.clone()).unwrap();
+    sandbox.datastore.create_vertex_metadata_index(index.clone()).unwrap();
+    assert!(sandbox.datastore.get_vertex_metadata_indexes().unwrap().contains(&index));
+    assert_eq!(lookup("email", r#""a@example.com""#, None, 10), expected_ids);
+    assert_eq!(lookup("email", r#""a@example.com""#, None, 1), vec![expected_ids[0]]);
+    assert_eq!(lookup("email", r#""a@example.com""#, Some(expected_ids[0]), 10), vec![expected_ids[1]]);
+    assert_eq!(lookup("email", r#""c@example.com""#, None, 10), vec![]);
+
+    // The index follows changes to the metadata and vertices
+    let trans = sandbox.transaction();
+    trans.set_vertex_metadata(VertexQuery::Vertex(ids[0]), name.clone(), json(r#"{"email": "c@example.com"}"#)).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(ids[1]), name.clone(), json(r#"{"email": "a@example.com"}"#)).unwrap();
+    trans.delete_vertices(VertexQuery::Vertex(ids[2])).unwrap();
+    trans.commit().unwrap();
+    assert_eq!(lookup("email", r#""a@example.com""#, None, 10), vec![ids[1]]);
+    assert_eq!(lookup("email", r#""c@example.com""#, None, 10), vec![ids[0]]);
+
+    let trans = sandbox.transaction();
+    trans.delete_vertex_metadata(VertexQuery::Vertex(ids[1]), name.clone()).unwrap();
+    trans.commit().unwrap();
+    assert_eq!(lookup("email", r#""a@example.com""#, None, 10), vec![]);
+
+    sandbox.datastore.delete_vertex_metadata_index(index.clone()).unwrap();
+    sandbox.datastore.delete_vertex_metadata_index(index.clone()).unwrap();
+    assert!(!sandbox.datastore.get_vertex_metadata_indexes().unwrap().contains(&index));
+    assert_eq!(lookup("email", r#""c@example.com""#, None, 10), vec![ids[0]]);
+}
+
+pub fn should_find_vertices_with_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let name = sandbox.generate_unique_string("has-vertex-metadata");
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let trans = sandbox.transaction();
+    let mut ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone(), vertex_t.clone()]).unwrap();
+    ids.sort();
+    trans.set_vertex_metadata(VertexQuery::Vertex(ids[0]), name.clone(), json("1")).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(ids[2]), name.clone(), json("2")).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(ids[1]), "other".to_string(), json("3")).unwrap();
+
+    let vertices = trans.get_vertices(VertexQuery::HasMetadata(name.clone(), None, 10)).unwrap();
+    assert_eq!(vertices.iter().map(|vertex| vertex.id).collect::<Vec<Uuid>>(), vec![ids[0], ids[2]]);
+
+    // Results are paged by ID
+    let vertices = trans.get_vertices(VertexQuery::HasMetadata(name.clone(), None, 1)).unwrap();
+    assert_eq!(vertices.iter().map(|vertex| vertex.id).collect::<Vec<Uuid>>(), vec![ids[0]]);
+    let vertices = trans.get_vertices(VertexQuery::HasMetadata(name.clone(), Some(ids[0]), 1)).unwrap();
+    assert_eq!(vertices.iter().map(|vertex| vertex.id).collect::<Vec<Uuid>>(), vec![ids[2]]);
+
+    // The results can be piped into other queries
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+    let key = EdgeKey::new(ids[2], edge_t, ids[1]);
+    trans.create_edge(key.clone(), Weight::new(0.5).unwrap()).unwrap();
+    let edges = trans.get_edges(VertexQuery::HasMetadata(name.clone(), None, 10).outbound_edges(None, None, None, 10)).unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].key, key);
+
+    // Vertices stop showing up once their metadata or they are deleted
+    trans.delete_vertex_metadata(VertexQuery::Vertex(ids[0]), name.clone()).unwrap();
+    trans.delete_vertices(VertexQuery::Vertex(ids[2])).unwrap();
+    assert_eq!(trans.get_vertices(VertexQuery::HasMetadata(name, None, 10)).unwrap().len(), 0);
+}
+
+pub fn should_find_edges_with_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let name = sandbox.generate_unique_string("has-edge-metadata");
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+    let trans = sandbox.transaction();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone(), vertex_t.clone()]).unwrap();
+    let mut keys: Vec<EdgeKey> = inbound_ids.iter().map(|&id| EdgeKey::new(outbound_id, edge_t.clone(), id)).collect();
+    keys.sort();
+
+    for key in &keys {
+        trans.create_edge(key.clone(), Weight::new(0.5).unwrap()).unwrap();
+    }
+
+    trans.set_edge_metadata(EdgeQuery::Edge(keys[0].clone()), name.clone(), json("1")).unwrap();
+    trans.set_edge_metadata(EdgeQuery::Edge(keys[2].clone()), name.clone(), json("2")).unwrap();
+
+    let edges = trans.get_edges(EdgeQuery::HasMetadata(name.clone(), None, 10)).unwrap();
+    assert_eq!(edges.into_iter().map(|edge| edge.key).collect::<Vec<EdgeKey>>(), vec![keys[0].clone(), keys[2].clone()]);
+
+    // Results are paged by key
+    let edges = trans.get_edges(EdgeQuery::HasMetadata(name.clone(), Some(keys[0].clone()), 1)).unwrap();
+    assert_eq!(edges.into_iter().map(|edge| edge.key).collect::<Vec<EdgeKey>>(), vec![keys[2].clone()]);
+    let edges = trans.get_edges(EdgeQuery::HasMetadata(name.clone(), Some(keys[2].clone()), 1)).unwrap();
+    assert_eq!(edges.len(), 0);
+
+    // The results can be piped into other queries
+    let vertices = trans.get_vertices(EdgeQuery::HasMetadata(name.clone(), None, 10).inbound_vertices(10)).unwrap();
+    let mut vertex_ids: Vec<Uuid> = vertices.into_iter().map(|vertex| vertex.id).collect();
+    vertex_ids.sort();
+    assert_eq!(vertex_ids, vec![keys[0].inbound_id, keys[2].inbound_id]);
+
+    // Edges stop showing up once their metadata or they are deleted
+    trans.delete_edge_metadata(EdgeQuery::Edge(keys[0].clone()), name.clone()).unwrap();
+    trans.delete_edges(EdgeQuery::Edge(keys[2].clone())).unwrap();
+    assert_eq!(trans.get_edges(EdgeQuery::HasMetadata(name, None, 10)).unwrap().len(), 0);
+}
+
+pub fn should_enforce_unique_constraints<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let t = Type::new(sandbox.generate_unique_string("unique")).unwrap();
+    let other_t = Type::new(sandbox.generate_unique_string("other-unique")).unwrap();
+    let name = sandbox.generate_unique_string("unique-metadata");
+    let duplicated_name = sandbox.generate_unique_string("duplicated-metadata");
+    let constraint = UniqueConstraint::new(t.clone(), name.clone());
+    let duplicated_constraint = UniqueConstraint::new(t.clone(), duplicated_name.clone());
+
+    // Constraints can't be created over values that are already duplicated
+    let trans = sandbox.transaction();
+    let ids = trans.create_vertices(vec![t.clone(), t.clone()]).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertices(ids.clone()), duplicated_name.clone(), json("1")).unwrap();
+    trans.commit().unwrap();
+    let result = sandbox.datastore.create_unique_constraint(duplicated_constraint.clone());
+    assert_eq!(result.unwrap_err(), Error::UniqueConstraintViolated);
+    assert!(!sandbox.datastore.get_unique_constraints().unwrap().contains(&duplicated_constraint));
+
+    sandbox.datastore.create_unique_constraint(constraint.clone()).unwrap();
+    sandbox.datastore.create_unique_constraint(constraint.clone()).unwrap();
+    assert!(sandbox.datastore.get_unique_constraints().unwrap().contains(&constraint));
+
+    // The same vertex is returned for the same value
+    let trans = sandbox.transaction();
+    let first_id = trans.get_or_create_vertex(t.clone(), name.clone(), json(r#""a""#)).unwrap();
+    assert_eq!(trans.get_or_create_vertex(t.clone(), name.clone(), json(r#""a""#)).unwrap(), first_id);
+    let second_id = trans.get_or_create_vertex(t.clone(), name.clone(), json("1")).unwrap();
+    assert!(second_id != first_id);
+    assert_eq!(trans.get_or_create_vertex(t.clone(), name.clone(), json("1.0")).unwrap(), second_id);
+    trans.commit().unwrap();
+
+    let trans = sandbox.transaction();
+    let metadata = trans.get_vertex_metadata(VertexQuery::Vertex(first_id), name.clone()).unwrap();
+    assert_eq!(metadata[&first_id], json(r#""a""#));
+
+    // Values can't be reused by another vertex of the type
+    let result = trans.set_vertex_metadata(VertexQuery::Vertex(second_id), name.clone(), json(r#""a""#));
+    assert_eq!(result.unwrap_err(), Error::UniqueConstraintViolated);
+    let result = trans.set_vertex_metadata(VertexQuery::Vertices(vec![first_id, second_id]), name.clone(), json(r#""b""#));
+    assert_eq!(result.unwrap_err(), Error::UniqueConstraintViolated);
+    let metadata = trans.get_vertex_metadata(VertexQuery::Vertices(vec![first_id, second_id]), name.clone()).unwrap();
+    assert_eq!(metadata[&first_id], json(r#""a""#));
+    assert_eq!(metadata[&second_id], json("1"));
+
+    // But can be set again on the same vertex, or on a vertex of another type
+    trans.set_vertex_metadata(VertexQuery::Vertex(first_id), name.clone(), json(r#""a""#)).unwrap();
+    let other_id = trans.create_vertex(other_t).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(other_id), name.clone(), json(r#""a""#)).unwrap();
+
+    // Values are released when they're changed or deleted
+    trans.set_vertex_metadata(VertexQuery::Vertex(first_id), name.clone(), json(r#""b""#)).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(second_id), name.clone(), json(r#""a""#)).unwrap();
+    trans.delete_vertex_metadata(VertexQuery::Vertex(first_id), name.clone()).unwrap();
+    trans.delete_vertices(VertexQuery::Vertex(second_id)).unwrap();
+    let third_id = trans.get_or_create_vertex(t.clone(), name.clone(), json(r#""a""#)).unwrap();
+    assert!(third_id != first_id && third_id != second_id);
+    trans.set_vertex_metadata(VertexQuery::Vertex(first_id), name.clone(), json(r#""b""#)).unwrap();
+    trans.commit().unwrap();
+
+    sandbox.datastore.delete_unique_constraint(constraint.clone()).unwrap();
+    sandbox.datastore.delete_unique_constraint(constraint.clone()).unwrap();
+    assert!(!sandbox.datastore.get_unique_constraints().unwrap().contains(&constraint));
+    let trans = sandbox.transaction();
+    trans.set_vertex_metadata(VertexQuery::Vertex(first_id), name.clone(), json(r#""a""#)).unwrap();
+}
+
+pub fn should_set_vertex_metadata_with_expected_versions<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let t = Type::new("test_vertex_type".to_string()).unwrap();
+    let name = sandbox.generate_unique_string("versioned-metadata");
+    let trans = sandbox.transaction();
+    let id = trans.create_vertex(t).unwrap();
+    let q = VertexQuery::Vertex(id);
+    assert!(trans.get_versioned_vertex_metadata(q.clone(), name.clone()).unwrap().is_empty());
+
+    // The metadata isn't set yet, so it isn't at any version
+    let result = trans.set_vertex_metadata_if_version(id, name.clone(), json("1"), Some(1));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+    assert_eq!(trans.set_vertex_metadata_if_version(id, name.clone(), json("1"), None).unwrap(), 1);
+    let result = trans.set_vertex_metadata_if_version(id, name.clone(), json("1"), None);
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+
+    // Unconditional updates bump the version too
+    trans.set_vertex_metadata(q.clone(), name.clone(), json("2")).unwrap();
+    let metadata = trans.get_versioned_vertex_metadata(q.clone(), name.clone()).unwrap();
+    assert_eq!(metadata[&id], VersionedMetadata::new(json("2"), 2));
+    let result = trans.set_vertex_metadata_if_version(id, name.clone(), json("3"), Some(1));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+    assert_eq!(trans.set_vertex_metadata_if_version(id, name.clone(), json("3"), Some(2)).unwrap(), 3);
+    let metadata = trans.get_versioned_vertex_metadata(q.clone(), name.clone()).unwrap();
+    assert_eq!(metadata[&id], VersionedMetadata::new(json("3"), 3));
+    trans.commit().unwrap();
+
+    // Writes based on a version that another transaction has since moved on
+    // from are rejected
+    let first_trans = sandbox.transaction();
+    let second_trans = sandbox.transaction();
+    assert_eq!(first_trans.set_vertex_metadata_if_version(id, name.clone(), json("4"), Some(3)).unwrap(), 4);
+    first_trans.commit().unwrap();
+    let result = second_trans.set_vertex_metadata_if_version(id, name.clone(), json("5"), Some(3));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+
+    // Deleting the metadata resets its version
+    let trans = sandbox.transaction();
+    trans.delete_vertex_metadata(q.clone(), name.clone()).unwrap();
+    assert_eq!(trans.set_vertex_metadata_if_version(id, name.clone(), json("1"), None).unwrap(), 1);
+
+    let result = trans.set_vertex_metadata_if_version(Uuid::default(), name, json("1"), None);
+    assert_eq!(result.unwrap_err(), Error::VertexNotFound);
+}
+
+pub fn should_set_global_metadata_with_expected_versions<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let name = sandbox.generate_unique_string("versioned-global-metadata");
+    let trans = sandbox.transaction();
+    assert_eq!(trans.get_versioned_global_metadata(name.clone()).unwrap_err(), Error::MetadataNotFound);
+
+    // The metadata isn't set yet, so it isn't at any version
+    let result = trans.set_global_metadata_if_version(name.clone(), json("1"), Some(1));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+    assert_eq!(trans.set_global_metadata_if_version(name.clone(), json("1"), None).unwrap(), 1);
+    let result = trans.set_global_metadata_if_version(name.clone(), json("1"), None);
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+
+    // Unconditional writes bump the version too
+    trans.set_global_metadata(name.clone(), json("2")).unwrap();
+    trans.update_global_metadata(name.clone(), MetadataOperation::Increment(vec![], 1.0)).unwrap();
+    assert_eq!(trans.get_versioned_global_metadata(name.clone()).unwrap(), VersionedMetadata::new(json("3"), 3));
+    let result = trans.set_global_metadata_if_version(name.clone(), json("4"), Some(2));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+    assert_eq!(trans.set_global_metadata_if_version(name.clone(), json("4"), Some(3)).unwrap(), 4);
+    trans.commit().unwrap();
+
+    // Writes based on a version that another transaction has since moved on
+    // from are rejected
+    let first_trans = sandbox.transaction();
+    let second_trans = sandbox.transaction();
+    assert_eq!(first_trans.set_global_metadata_if_version(name.clone(), json("5"), Some(4)).unwrap(), 5);
+    first_trans.commit().unwrap();
+    let result = second_trans.set_global_metadata_if_version(name.clone(), json("6"), Some(4));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+
+    // Deleting the metadata resets its version
+    let trans = sandbox.transaction();
+    trans.delete_global_metadata(name.clone()).unwrap();
+    assert_eq!(trans.set_global_metadata_if_version(name.clone(), json("1"), None).unwrap(), 1);
+    trans.delete_global_metadata(name).unwrap();
+    trans.commit().unwrap();
+}
+
+pub fn should_set_account_metadata_with_expected_versions<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let name = sandbox.generate_unique_string("versioned-account-metadata");
+    let owner_id = sandbox.owner_id;
+    let trans = sandbox.transaction();
+    assert_eq!(trans.get_versioned_account_metadata(owner_id, name.clone()).unwrap_err(), Error::MetadataNotFound);
+
+    // The metadata isn't set yet, so it isn't at any version
+    let result = trans.set_account_metadata_if_version(owner_id, name.clone(), json("1"), Some(1));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+    assert_eq!(trans.set_account_metadata_if_version(owner_id, name.clone(), json("1"), None).unwrap(), 1);
+    let result = trans.set_account_metadata_if_version(owner_id, name.clone(), json("1"), None);
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+
+    // Unconditional writes bump the version too
+    trans.set_account_metadata(owner_id, name.clone(), json("2")).unwrap();
+    trans.update_account_metadata(owner_id, name.clone(), MetadataOperation::Increment(vec![], 1.0)).unwrap();
+    assert_eq!(trans.get_versioned_account_metadata(owner_id, name.clone()).unwrap(), VersionedMetadata::new(json("3"), 3));
+    let result = trans.set_account_metadata_if_version(owner_id, name.clone(), json("4"), Some(2));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+    assert_eq!(trans.set_account_metadata_if_version(owner_id, name.clone(), json("4"), Some(3)).unwrap(), 4);
+    trans.commit().unwrap();
+
+    // Writes based on a version that another transaction has since moved on
+    // from are rejected
+    let first_trans = sandbox.transaction();
+    let second_trans = sandbox.transaction();
+    assert_eq!(first_trans.set_account_metadata_if_version(owner_id, name.clone(), json("5"), Some(4)).unwrap(), 5);
+    first_trans.commit().unwrap();
+    let result = second_trans.set_account_metadata_if_version(owner_id, name.clone(), json("6"), Some(4));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+
+    // Deleting the metadata resets its version
+    let trans = sandbox.transaction();
+    trans.delete_account_metadata(owner_id, name.clone()).unwrap();
+    assert_eq!(trans.set_account_metadata_if_version(owner_id, name.clone(), json("1"), None).unwrap(), 1);
+
+    let result = trans.get_versioned_account_metadata(Uuid::default(), name.clone());
+    assert_eq!(result.unwrap_err(), Error::AccountNotFound);
+    let result = trans.set_account_metadata_if_version(Uuid::default(), name, json("1"), None);
+    assert_eq!(result.unwrap_err(), Error::AccountNotFound);
+}
+
+pub fn should_set_edge_metadata_with_expected_versions<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+    let name = sandbox.generate_unique_string("versioned-metadata");
+    let trans = sandbox.transaction();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_id = trans.create_vertex(vertex_t).unwrap();
+    let key = EdgeKey::new(outbound_id, edge_t, inbound_id);
+    trans.create_edge(key.clone(), Weight::new(0.5).unwrap()).unwrap();
+    let q = EdgeQuery::Edge(key.clone());
+    assert!(trans.get_versioned_edge_metadata(q.clone(), name.clone()).unwrap().is_empty());
+
+    // The metadata isn't set yet, so it isn't at any version
+    let result = trans.set_edge_metadata_if_version(key.clone(), name.clone(), json("1"), Some(1));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+    assert_eq!(trans.set_edge_metadata_if_version(key.clone(), name.clone(), json("1"), None).unwrap(), 1);
+    let result = trans.set_edge_metadata_if_version(key.clone(), name.clone(), json("1"), None);
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+
+    // Unconditional writes bump the version too
+    trans.set_edge_metadata(q.clone(), name.clone(), json("2")).unwrap();
+    trans.update_edge_metadata(q.clone(), name.clone(), MetadataOperation::Increment(vec![], 1.0)).unwrap();
+    let metadata = trans.get_versioned_edge_metadata(q.clone(), name.clone()).unwrap();
+    assert_eq!(metadata[&key], VersionedMetadata::new(json("3"), 3));
+    let result = trans.set_edge_metadata_if_version(key.clone(), name.clone(), json("4"), Some(2));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+    assert_eq!(trans.set_edge_metadata_if_version(key.clone(), name.clone(), json("4"), Some(3)).unwrap(), 4);
+    trans.commit().unwrap();
+
+    // Writes based on a version that another transaction has since moved on
+    // from are rejected
+    let first_trans = sandbox.transaction();
+    let second_trans = sandbox.transaction();
+    assert_eq!(first_trans.set_edge_metadata_if_version(key.clone(), name.clone(), json("5"), Some(4)).unwrap(), 5);
+    first_trans.commit().unwrap();
+    let result = second_trans.set_edge_metadata_if_version(key.clone(), name.clone(), json("6"), Some(4));
+    assert_eq!(result.unwrap_err(), Error::Conflict);
+
+    // Deleting the metadata resets its version
+    let trans = sandbox.transaction();
+    trans.delete_edge_metadata(q, name.clone()).unwrap();
+    assert_eq!(trans.set_edge_metadata_if_version(key.clone(), name.clone(), json("1"), None).unwrap(), 1);
+
+    let missing_key = EdgeKey::new(outbound_id, Type::new("test_edge_type".to_string()).unwrap(), outbound_id);
+    let result = trans.set_edge_metadata_if_version(missing_key, name, json("1"), None);
+    assert_eq!(result.unwrap_err(), Error::EdgeNotFound);
+}
+
+pub fn should_update_vertex_metadata_atomically<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let t = Type::new(sandbox.generate_unique_string("atomic")).unwrap();
+    let name = sandbox.generate_unique_string("atomic-vertex-metadata");
+    let trans = sandbox.transaction();
+    let ids = trans.create_vertices(vec![t.clone(), t.clone()]).unwrap();
+    let q = VertexQuery::Vertices(ids.clone());
+    trans.set_vertex_metadata(VertexQuery::Vertex(ids[0]), name.clone(), json(r#"{"count": 1}"#)).unwrap();
+
+    // The operation is applied to each vertex's value
+    let metadata = trans.update_vertex_metadata(q.clone(), name.clone(), MetadataOperation::Increment(vec!["count".to_string()], 1.0)).unwrap();
+    assert_eq!(metadata.len(), 2);
+    assert_eq!(metadata[&ids[0]], json(r#"{"count": 2}"#));
+    assert_eq!(metadata[&ids[1]], json(r#"{"count": 1}"#));
+    assert_eq!(trans.get_vertex_metadata(q.clone(), name.clone()).unwrap(), metadata);
+
+    // Nothing is updated unless the operation succeeds for every vertex
+    let operation = MetadataOperation::CompareAndSwap(Some(json(r#"{"count": 2}"#)), json("0"));
+    assert_eq!(trans.update_vertex_metadata(q.clone(), name.clone(), operation).unwrap_err(), Error::Conflict);
+    assert_eq!(trans.get_vertex_metadata(q.clone(), name.clone()).unwrap(), metadata);
+
+    // Updates have to respect unique constraints
+    sandbox.datastore.create_unique_constraint(UniqueConstraint::new(t, name.clone())).unwrap();
+    let operation = MetadataOperation::MergePatch(json(r#"{"count": 3}"#));
+    let result = trans.update_vertex_metadata(q.clone(), name.clone(), operation);
+    assert_eq!(result.unwrap_err(), Error::UniqueConstraintViolated);
+    assert_eq!(trans.get_vertex_metadata(q.clone(), name.clone()).unwrap(), metadata);
+    let operation = MetadataOperation::MergePatch(json(r#"{"count": 3}"#));
+    trans.update_vertex_metadata(VertexQuery::Vertex(ids[1]), name.clone(), operation).unwrap();
+    let operation = MetadataOperation::Increment(vec!["count".to_string()], 1.0);
+    let result = trans.update_vertex_metadata(VertexQuery::Vertex(ids[0]), name.clone(), operation);
+    assert_eq!(result.unwrap_err(), Error::UniqueConstraintViolated);
+}
+
+pub fn should_list_vertex_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let t = Type::new("test_vertex_type".to_string()).unwrap();
+    let ids = trans.create_vertices(vec![t.clone(), t]).unwrap();
+    assert_eq!(trans.get_vertex_metadata_names(ids[0]).unwrap().len(), 0);
+
+    trans.set_vertex_metadata(VertexQuery::Vertex(ids[0]), "b".to_string(), json("2")).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(ids[0]), "a".to_string(), json("1")).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(ids[1]), "c".to_string(), json("3")).unwrap();
+
+    let names = trans.get_vertex_metadata_names(ids[0]).unwrap();
+    assert_eq!(names, vec!["a".to_string(), "b".to_string()]);
+    let metadata = trans.get_all_vertex_metadata(ids[1]).unwrap();
+    assert_eq!(metadata, vec![("c".to_string(), json("3"))]);
+
+    let result = trans.get_vertex_metadata_names(Uuid::default());
+    assert_eq!(result.unwrap_err(), Error::VertexNotFound);
+}
+
 pub fn should_not_set_invalid_vertex_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
     where D: Datastore<T>,
           T: Transaction
@@ -174,6 +875,69 @@ pub fn should_handle_edge_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T
     assert_eq!(result.len(), 0);
 }
 
+pub fn should_update_edge_metadata_atomically<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t]).unwrap();
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+    let weight = Weight::new(0.5).unwrap();
+    let keys: Vec<EdgeKey> = inbound_ids.iter().map(|&id| EdgeKey::new(outbound_id, edge_t.clone(), id)).collect();
+    let q = EdgeQuery::Edges(keys.clone());
+    let name = sandbox.generate_unique_string("atomic-edge-metadata");
+    trans.create_edge(keys[0].clone(), weight).unwrap();
+    trans.create_edge(keys[1].clone(), weight).unwrap();
+    trans.set_edge_metadata(EdgeQuery::Edge(keys[0].clone()), name.clone(), json(r#"{"a": 1}"#)).unwrap();
+
+    let metadata = trans.update_edge_metadata(q.clone(), name.clone(), MetadataOperation::MergePatch(json(r#"{"b": 2}"#))).unwrap();
+    assert_eq!(metadata.len(), 2);
+    assert_eq!(metadata[&keys[0]], json(r#"{"a": 1, "b": 2}"#));
+    assert_eq!(metadata[&keys[1]], json(r#"{"b": 2}"#));
+    assert_eq!(trans.get_edge_metadata(q.clone(), name.clone()).unwrap(), metadata);
+
+    // Nothing is updated unless the operation succeeds for every edge
+    let operation = MetadataOperation::Increment(vec!["a".to_string()], 1.0);
+    assert_eq!(trans.update_edge_metadata(q.clone(), name.clone(), operation).unwrap(), metadata_with_a(&keys));
+    let operation = MetadataOperation::CompareAndSwap(Some(json(r#"{"a": 2, "b": 2}"#)), json("0"));
+    assert_eq!(trans.update_edge_metadata(q.clone(), name.clone(), operation).unwrap_err(), Error::Conflict);
+    assert_eq!(trans.get_edge_metadata(q, name).unwrap(), metadata_with_a(&keys));
+}
+
+fn metadata_with_a(keys: &[EdgeKey]) -> HashMap<EdgeKey, JsonValue> {
+    let mut metadata = HashMap::new();
+    metadata.insert(keys[0].clone(), json(r#"{"a": 2, "b": 2}"#));
+    metadata.insert(keys[1].clone(), json(r#"{"a": 1, "b": 2}"#));
+    metadata
+}
+
+pub fn should_list_edge_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_id = trans.create_vertex(vertex_t).unwrap();
+    let key = EdgeKey::new(outbound_id, Type::new("test_edge_type".to_string()).unwrap(), inbound_id);
+    let reversed_key = EdgeKey::new(inbound_id, Type::new("test_edge_type".to_string()).unwrap(), outbound_id);
+    trans.create_edge(key.clone(), Weight::new(0.5).unwrap()).unwrap();
+    assert_eq!(trans.get_edge_metadata_names(key.clone()).unwrap().len(), 0);
+
+    trans.set_edge_metadata(EdgeQuery::Edge(key.clone()), "b".to_string(), json("2")).unwrap();
+    trans.set_edge_metadata(EdgeQuery::Edge(key.clone()), "a".to_string(), json("1")).unwrap();
+
+    let names = trans.get_edge_metadata_names(key.clone()).unwrap();
+    assert_eq!(names, vec!["a".to_string(), "b".to_string()]);
+    let metadata = trans.get_all_edge_metadata(key).unwrap();
+    assert_eq!(metadata, vec![("a".to_string(), json("1")), ("b".to_string(), json("2"))]);
+
+    let result = trans.get_edge_metadata_names(reversed_key);
+    assert_eq!(result.unwrap_err(), Error::EdgeNotFound);
+}
+
 pub fn should_not_set_invalid_edge_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
     where D: Datastore<T>,
           T: Transaction
@@ -200,3 +964,55 @@ pub fn should_not_delete_invalid_edge_metadata<D, T>(sandbox: &mut DatastoreTest
     trans.create_edge(key.clone(), weight).unwrap();
     trans.delete_edge_metadata(EdgeQuery::Edge(key), "bleh".to_string()).unwrap();
 }
+
+pub fn should_expire_vertex_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let id = trans.create_vertex(vertex_t).unwrap();
+    let q = VertexQuery::Vertex(id);
+    trans.set_vertex_metadata_with_ttl(q.clone(), "expiring".to_string(), JsonValue::Bool(true), Duration::milliseconds(100)).unwrap();
+    trans.set_vertex_metadata_with_ttl(q.clone(), "lasting".to_string(), JsonValue::Bool(true), Duration::days(1)).unwrap();
+
+    // Setting metadata without a TTL clears any expiry it had
+    trans.set_vertex_metadata_with_ttl(q.clone(), "reset".to_string(), JsonValue::Bool(true), Duration::milliseconds(100)).unwrap();
+    trans.set_vertex_metadata(q.clone(), "reset".to_string(), JsonValue::Bool(false)).unwrap();
+    trans.commit().unwrap();
+
+    thread::sleep(time::Duration::from_millis(200));
+    let trans = sandbox.transaction();
+    assert_eq!(trans.get_vertex_metadata(q.clone(), "expiring".to_string()).unwrap().len(), 0);
+    assert_eq!(trans.get_vertex_metadata(q.clone(), "lasting".to_string()).unwrap()[&id], JsonValue::Bool(true));
+    assert_eq!(trans.get_vertex_metadata(q.clone(), "reset".to_string()).unwrap()[&id], JsonValue::Bool(false));
+    assert_eq!(trans.get_vertices(q).unwrap().len(), 1);
+}
+
+pub fn should_expire_edge_metadata<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_id = trans.create_vertex(vertex_t).unwrap();
+    let key = EdgeKey::new(outbound_id, edge_t, inbound_id);
+    trans.create_edge(key.clone(), Weight::new(0.5).unwrap()).unwrap();
+    let q = EdgeQuery::Edge(key.clone());
+    trans.set_edge_metadata_with_ttl(q.clone(), "expiring".to_string(), JsonValue::Bool(true), Duration::milliseconds(100)).unwrap();
+    trans.set_edge_metadata_with_ttl(q.clone(), "lasting".to_string(), JsonValue::Bool(true), Duration::days(1)).unwrap();
+
+    // Setting metadata without a TTL clears any expiry it had
+    trans.set_edge_metadata_with_ttl(q.clone(), "reset".to_string(), JsonValue::Bool(true), Duration::milliseconds(100)).unwrap();
+    trans.set_edge_metadata(q.clone(), "reset".to_string(), JsonValue::Bool(false)).unwrap();
+    trans.commit().unwrap();
+
+    thread::sleep(time::Duration::from_millis(200));
+    let trans = sandbox.transaction();
+    assert_eq!(trans.get_edge_metadata(q.clone(), "expiring".to_string()).unwrap().len(), 0);
+    assert_eq!(trans.get_edge_metadata(q.clone(), "lasting".to_string()).unwrap()[&key], JsonValue::Bool(true));
+    assert_eq!(trans.get_edge_metadata(q.clone(), "reset".to_string()).unwrap()[&key], JsonValue::Bool(false));
+    assert_eq!(trans.get_edges(q).unwrap().len(), 1);
+}
@@ -2,17 +2,19 @@
 //! 
 //! These are exported so that datastore implementations outside of the
 //! `braid` crate can reuse them. Generally you can use the convenience macros
-//! `test_account_impl`, `test_metadata_impl`, and `test_transaction_impl`
-//! rather than referencing these functions directly - but they may be useful
-//! to reference directly if you want to implement a datastore that doesn't
-//! support full functionality.
+//! `test_account_impl`, `test_metadata_impl`, `test_transaction_impl`, and
+//! `test_rollback_impl` rather than referencing these functions directly -
+//! but they may be useful to reference directly if you want to implement a
+//! datastore that doesn't support full functionality.
 
 mod account;
 mod edge;
 #[macro_use]
 mod macros;
 mod metadata;
+mod path;
 mod sandbox;
+mod transaction;
 mod util;
 mod vertex;
 
@@ -20,6 +22,8 @@ pub use self::account::*;
 pub use self::edge::*;
 pub use self::macros::*;
 pub use self::metadata::*;
+pub use self::path::*;
 pub use self::sandbox::*;
+pub use self::transaction::*;
 pub use self::util::*;
 pub use self::vertex::*;
@@ -0,0 +1,103 @@
+use super::super::{Datastore, Transaction, EdgeKey, PathQuery, QueryTypeConverter, Type, Weight};
+use super::sandbox::DatastoreTestSandbox;
+use models;
+use uuid::Uuid;
+
+/// Creates a -> b -> c -> d with strong edges, a weak shortcut from a -> d,
+/// and an edge of another type from d -> e. Returns the edge type and the
+/// vertex IDs.
+fn create_path_graph<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>) -> (Type, [Uuid; 5])
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = Type::new(sandbox.generate_unique_string("test_edge_type")).unwrap();
+    let other_edge_t = Type::new(sandbox.generate_unique_string("test_other_edge_type")).unwrap();
+    let trans = sandbox.transaction();
+    let ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone(), vertex_t.clone(), vertex_t.clone(), vertex_t]).unwrap();
+    let ids = [ids[0], ids[1], ids[2], ids[3], ids[4]];
+    let strong = Weight::new(1.0).unwrap();
+    let weak = Weight::new(-0.5).unwrap();
+
+    let results = trans.create_edges(vec![
+        (EdgeKey::new(ids[0], edge_t.clone(), ids[1]), strong),
+        (EdgeKey::new(ids[1], edge_t.clone(), ids[2]), strong),
+        (EdgeKey::new(ids[2], edge_t.clone(), ids[3]), strong),
+        (EdgeKey::new(ids[0], edge_t.clone(), ids[3]), weak),
+        (EdgeKey::new(ids[3], other_edge_t, ids[4]), strong),
+    ]).unwrap();
+
+    assert!(results.iter().all(|result| result.is_ok()));
+    trans.commit().unwrap();
+    (edge_t, ids)
+}
+
+fn path_keys(path: Option<Vec<models::Edge>>) -> Option<Vec<EdgeKey>> {
+    path.map(|path| path.into_iter().map(|edge| edge.key).collect())
+}
+
+pub fn should_get_the_shortest_path<D, T>(mut sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let (edge_t, ids) = create_path_graph(&mut sandbox);
+    let (a, b, c, d, e) = (ids[0], ids[1], ids[2], ids[3], ids[4]);
+    let trans = sandbox.transaction();
+
+    let path = trans.get_path(PathQuery::Shortest(a, d, QueryTypeConverter::Outbound, Some(edge_t.clone()), 10)).unwrap();
+    assert_eq!(path_keys(path), Some(vec![EdgeKey::new(a, edge_t.clone(), d)]));
+
+    let path = trans.get_path(PathQuery::Shortest(a, c, QueryTypeConverter::Outbound, Some(edge_t.clone()), 10)).unwrap();
+    assert_eq!(path_keys(path), Some(vec![EdgeKey::new(a, edge_t.clone(), b), EdgeKey::new(b, edge_t.clone(), c)]));
+
+    // Inbound paths follow edges backwards, but still return the edges as
+    // they are stored
+    let path = trans.get_path(PathQuery::Shortest(c, a, QueryTypeConverter::Inbound, Some(edge_t.clone()), 10)).unwrap();
+    assert_eq!(path_keys(path), Some(vec![EdgeKey::new(b, edge_t.clone(), c), EdgeKey::new(a, edge_t.clone(), b)]));
+
+    let path = trans.get_path(PathQuery::Shortest(a, c, QueryTypeConverter::Outbound, Some(edge_t.clone()), 1)).unwrap();
+    assert!(path.is_none());
+
+    let path = trans.get_path(PathQuery::Shortest(c, a, QueryTypeConverter::Outbound, Some(edge_t.clone()), 10)).unwrap();
+    assert!(path.is_none());
+
+    let path = trans.get_path(PathQuery::Shortest(a, e, QueryTypeConverter::Outbound, Some(edge_t.clone()), 10)).unwrap();
+    assert!(path.is_none());
+
+    let path = trans.get_path(PathQuery::Shortest(a, e, QueryTypeConverter::Outbound, None, 10)).unwrap();
+    assert_eq!(path.unwrap().len(), 2);
+
+    let path = trans.get_path(PathQuery::Shortest(a, a, QueryTypeConverter::Outbound, Some(edge_t), 0)).unwrap();
+    assert_eq!(path_keys(path), Some(vec![]));
+}
+
+pub fn should_get_the_weighted_path<D, T>(mut sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let (edge_t, ids) = create_path_graph(&mut sandbox);
+    let (a, b, c, d) = (ids[0], ids[1], ids[2], ids[3]);
+    let trans = sandbox.transaction();
+
+    // The longer path is made up of stronger edges
+    let path = trans.get_path(PathQuery::Weighted(a, d, QueryTypeConverter::Outbound, Some(edge_t.clone()), 10)).unwrap();
+    assert_eq!(path_keys(path), Some(vec![
+        EdgeKey::new(a, edge_t.clone(), b),
+        EdgeKey::new(b, edge_t.clone(), c),
+        EdgeKey::new(c, edge_t.clone(), d)
+    ]));
+
+    let path = trans.get_path(PathQuery::Weighted(d, a, QueryTypeConverter::Inbound, Some(edge_t.clone()), 10)).unwrap();
+    assert_eq!(path_keys(path), Some(vec![
+        EdgeKey::new(c, edge_t.clone(), d),
+        EdgeKey::new(b, edge_t.clone(), c),
+        EdgeKey::new(a, edge_t.clone(), b)
+    ]));
+
+    // But it's too long when the depth is limited
+    let path = trans.get_path(PathQuery::Weighted(a, d, QueryTypeConverter::Outbound, Some(edge_t.clone()), 2)).unwrap();
+    assert_eq!(path_keys(path), Some(vec![EdgeKey::new(a, edge_t.clone(), d)]));
+
+    let path = trans.get_path(PathQuery::Weighted(d, a, QueryTypeConverter::Outbound, Some(edge_t), 10)).unwrap();
+    assert!(path.is_none());
+}
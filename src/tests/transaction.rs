@@ -0,0 +1,64 @@
+use super::super::{Datastore, Transaction, EdgeQuery, VertexQuery, EdgeKey, Type, Weight};
+use super::sandbox::DatastoreTestSandbox;
+use errors::Error;
+use serde_json::Value as JsonValue;
+
+pub fn should_rollback_created_items<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let name = sandbox.generate_unique_string("rollback-metadata");
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+
+    let trans = sandbox.transaction();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    trans.commit().unwrap();
+
+    let trans = sandbox.transaction();
+    let inbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let key = EdgeKey::new(outbound_id, edge_t.clone(), inbound_id);
+    trans.create_edge(key.clone(), Weight::new(0.5).unwrap()).unwrap();
+    trans.set_global_metadata(name.clone(), JsonValue::Bool(true)).unwrap();
+
+    // The transaction should be able to read its own writes
+    assert_eq!(trans.get_vertices(VertexQuery::Vertex(inbound_id)).unwrap().len(), 1);
+    assert_eq!(trans.get_edges(EdgeQuery::Edge(key.clone())).unwrap().len(), 1);
+    assert_eq!(trans.get_global_metadata(name.clone()).unwrap(), JsonValue::Bool(true));
+    trans.rollback().unwrap();
+
+    let trans = sandbox.transaction();
+    assert_eq!(trans.get_vertices(VertexQuery::Vertex(outbound_id)).unwrap().len(), 1);
+    assert_eq!(trans.get_vertices(VertexQuery::Vertex(inbound_id)).unwrap().len(), 0);
+    assert_eq!(trans.get_edges(EdgeQuery::Edge(key)).unwrap().len(), 0);
+    assert_eq!(trans.get_global_metadata(name).unwrap_err(), Error::MetadataNotFound);
+}
+
+pub fn should_rollback_deleted_items<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = Type::new("test_edge_type".to_string()).unwrap();
+
+    let trans = sandbox.transaction();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let key = EdgeKey::new(outbound_id, edge_t.clone(), inbound_id);
+    trans.create_edge(key.clone(), Weight::new(0.5).unwrap()).unwrap();
+    trans.commit().unwrap();
+
+    // Deleting the vertex should also delete the edge, at least from the
+    // point of view of the transaction
+    let trans = sandbox.transaction();
+    trans.delete_vertices(VertexQuery::Vertex(inbound_id)).unwrap();
+    assert_eq!(trans.get_vertices(VertexQuery::Vertex(inbound_id)).unwrap().len(), 0);
+    assert_eq!(trans.get_edges(EdgeQuery::Edge(key.clone())).unwrap().len(), 0);
+    assert_eq!(trans.get_edge_count(EdgeQuery::Edge(key.clone())).unwrap(), 0);
+    trans.rollback().unwrap();
+
+    let trans = sandbox.transaction();
+    assert_eq!(trans.get_vertices(VertexQuery::Vertex(inbound_id)).unwrap().len(), 1);
+    assert_eq!(trans.get_edges(EdgeQuery::Edge(key.clone())).unwrap().len(), 1);
+    assert_eq!(trans.get_edge_count(EdgeQuery::Edge(key)).unwrap(), 1);
+}
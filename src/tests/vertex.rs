@@ -1,10 +1,15 @@
-use super::super::{Datastore, Transaction, VertexQuery};
+use super::super::{Datastore, Transaction, EdgeQuery, VertexQuery};
 use super::sandbox::DatastoreTestSandbox;
 use super::util::{create_edge_from, create_edges};
+use errors::Error;
 use uuid::Uuid;
 use models;
+use chrono::Duration;
+use serde_json::Value as JsonValue;
 use std::u32;
 use std::collections::HashSet;
+use std::thread;
+use std::time;
 
 pub fn should_get_all_vertices<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
     where D: Datastore<T>,
@@ -82,6 +87,161 @@ pub fn should_get_all_vertices_out_of_range<D, T>(sandbox: &mut DatastoreTestSan
     assert_eq!(range.len(), 0);
 }
 
+pub fn should_get_all_vertices_of_type<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new(sandbox.generate_unique_string("test_vertex_type")).unwrap();
+    let other_vertex_t = models::Type::new("test_other_vertex_type".to_string()).unwrap();
+    let trans = sandbox.transaction();
+
+    let mut inserted_ids = vec![
+        trans.create_vertex(vertex_t.clone()).unwrap(),
+        trans.create_vertex(vertex_t.clone()).unwrap(),
+        trans.create_vertex(vertex_t.clone()).unwrap()
+    ];
+
+    trans.create_vertex(other_vertex_t.clone()).unwrap();
+    trans.create_vertex(other_vertex_t.clone()).unwrap();
+
+    inserted_ids.sort();
+    let range = trans.get_vertices(VertexQuery::AllOfType(vertex_t.clone(), None, u32::MAX)).unwrap();
+    trans.commit().unwrap();
+
+    assert_eq!(range.len(), 3);
+
+    for (vertex, inserted_id) in range.iter().zip(inserted_ids.iter()) {
+        assert_eq!(vertex.id, *inserted_id);
+        assert_eq!(vertex.t, vertex_t);
+    }
+}
+
+pub fn should_page_vertices_of_type<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new(sandbox.generate_unique_string("test_vertex_type")).unwrap();
+    let trans = sandbox.transaction();
+
+    let mut inserted_ids = vec![
+        trans.create_vertex(vertex_t.clone()).unwrap(),
+        trans.create_vertex(vertex_t.clone()).unwrap(),
+        trans.create_vertex(vertex_t.clone()).unwrap(),
+        trans.create_vertex(vertex_t.clone()).unwrap(),
+        trans.create_vertex(vertex_t.clone()).unwrap()
+    ];
+
+    inserted_ids.sort();
+    let first_page = trans.get_vertices(VertexQuery::AllOfType(vertex_t.clone(), None, 2)).unwrap();
+    let second_page = trans.get_vertices(VertexQuery::AllOfType(vertex_t.clone(), Some(first_page[1].id), 2)).unwrap();
+    let third_page = trans.get_vertices(VertexQuery::AllOfType(vertex_t.clone(), Some(second_page[1].id), 2)).unwrap();
+    let out_of_range = trans.get_vertices(VertexQuery::AllOfType(vertex_t.clone(), Some(Uuid::parse_str("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF").unwrap()), u32::MAX)).unwrap();
+    trans.commit().unwrap();
+
+    let paged_ids: Vec<Uuid> = first_page.iter().chain(second_page.iter()).chain(third_page.iter()).map(|vertex| vertex.id).collect();
+    assert_eq!(paged_ids, inserted_ids);
+    assert_eq!(third_page.len(), 1);
+    assert_eq!(out_of_range.len(), 0);
+}
+
+pub fn should_stream_vertices<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new(sandbox.generate_unique_string("test_vertex_type")).unwrap();
+    let trans = sandbox.transaction();
+
+    let mut inserted_ids = vec![
+        trans.create_vertex(vertex_t.clone()).unwrap(),
+        trans.create_vertex(vertex_t.clone()).unwrap(),
+        trans.create_vertex(vertex_t.clone()).unwrap(),
+        trans.create_vertex(vertex_t.clone()).unwrap(),
+        trans.create_vertex(vertex_t.clone()).unwrap()
+    ];
+
+    inserted_ids.sort();
+    let q = VertexQuery::AllOfType(vertex_t.clone(), None, u32::MAX);
+
+    // Partially consuming a stream and dropping it should be fine
+    let partial: Vec<models::Vertex> = trans.stream_vertices(q.clone()).unwrap().take(2).map(|item| item.unwrap()).collect();
+    assert_eq!(partial.len(), 2);
+
+    let streamed: Vec<models::Vertex> = trans.stream_vertices(q.clone()).unwrap().map(|item| item.unwrap()).collect();
+    let range = trans.get_vertices(q).unwrap();
+    trans.commit().unwrap();
+
+    assert_eq!(streamed, range);
+    let streamed_ids: Vec<Uuid> = streamed.iter().map(|vertex| vertex.id).collect();
+    assert_eq!(streamed_ids, inserted_ids);
+}
+
+pub fn should_create_vertices_in_bulk<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let first_t = models::Type::new(sandbox.generate_unique_string("test_vertex_type")).unwrap();
+    let second_t = models::Type::new(sandbox.generate_unique_string("test_other_vertex_type")).unwrap();
+    let trans = sandbox.transaction();
+    let ids = trans.create_vertices(vec![first_t.clone(), second_t.clone(), first_t.clone()]).unwrap();
+    assert_eq!(ids.len(), 3);
+
+    let range = trans.get_vertices(VertexQuery::Vertices(ids.clone())).unwrap();
+    trans.commit().unwrap();
+    assert_eq!(range.len(), 3);
+
+    for vertex in &range {
+        if vertex.id == ids[1] {
+            assert_eq!(vertex.t, second_t);
+        } else {
+            assert!(vertex.id == ids[0] || vertex.id == ids[2]);
+            assert_eq!(vertex.t, first_t);
+        }
+    }
+}
+
+pub fn should_create_vertex_with_id<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let t = models::Type::new(sandbox.generate_unique_string("test_vertex_type")).unwrap();
+    let id = Uuid::new_v4();
+    let trans = sandbox.transaction();
+    trans.create_vertex_with_id(id, t.clone()).unwrap();
+    trans.commit().unwrap();
+
+    let trans = sandbox.transaction();
+    let range = trans.get_vertices(VertexQuery::Vertex(id)).unwrap();
+    assert_eq!(range.len(), 1);
+    assert_eq!(range[0].id, id);
+    assert_eq!(range[0].t, t);
+
+    let range = trans.get_vertices(VertexQuery::AllOfType(t, None, u32::MAX)).unwrap();
+    assert_eq!(range.len(), 1);
+    assert_eq!(range[0].id, id);
+}
+
+pub fn should_not_create_vertex_with_existing_id<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let first_t = models::Type::new(sandbox.generate_unique_string("test_vertex_type")).unwrap();
+    let second_t = models::Type::new(sandbox.generate_unique_string("test_other_vertex_type")).unwrap();
+    let trans = sandbox.transaction();
+    let id = trans.create_vertex(first_t.clone()).unwrap();
+    let result = trans.create_vertex_with_id(id, second_t.clone());
+    assert_eq!(result.unwrap_err(), Error::VertexAlreadyExists);
+    trans.commit().unwrap();
+
+    // Replaying the same write in a later transaction fails the same way,
+    // and leaves the original vertex untouched
+    let trans = sandbox.transaction();
+    let result = trans.create_vertex_with_id(id, second_t);
+    assert_eq!(result.unwrap_err(), Error::VertexAlreadyExists);
+    let range = trans.get_vertices(VertexQuery::Vertex(id)).unwrap();
+    assert_eq!(range.len(), 1);
+    assert_eq!(range[0].t, first_t);
+}
+
 pub fn should_get_single_vertices<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
     where D: Datastore<T>,
           T: Transaction
@@ -164,6 +324,142 @@ pub fn should_get_vertices_piped<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
     assert_eq!(range[0].id, inserted_id_5);
 }
 
+pub fn should_traverse_vertices<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new(sandbox.generate_unique_string("test_edge_type")).unwrap();
+    let other_edge_t = models::Type::new(sandbox.generate_unique_string("test_other_edge_type")).unwrap();
+    let trans = sandbox.transaction();
+    let ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone(), vertex_t.clone(), vertex_t.clone(), vertex_t]).unwrap();
+    let (a, b, c, d, e) = (ids[0], ids[1], ids[2], ids[3], ids[4]);
+
+    // a -> b -> c -> d -> a, with a shortcut from a -> c, and an edge of
+    // another type from a -> e
+    let weight = models::Weight::new(1.0).unwrap();
+    let results = trans.create_edges(vec![
+        (models::EdgeKey::new(a, edge_t.clone(), b), weight),
+        (models::EdgeKey::new(b, edge_t.clone(), c), weight),
+        (models::EdgeKey::new(c, edge_t.clone(), d), weight),
+        (models::EdgeKey::new(d, edge_t.clone(), a), weight),
+        (models::EdgeKey::new(a, edge_t.clone(), c), weight),
+        (models::EdgeKey::new(a, other_edge_t, e), weight),
+    ]).unwrap();
+    assert!(results.iter().all(|result| result.is_ok()));
+
+    let get_ids = |q: VertexQuery| -> Vec<Uuid> {
+        trans.get_vertices(q).unwrap().into_iter().map(|vertex| vertex.id).collect()
+    };
+
+    let sorted = |mut ids: Vec<Uuid>| -> Vec<Uuid> {
+        ids.sort();
+        ids
+    };
+
+    let start = VertexQuery::Vertex(a);
+    assert_eq!(get_ids(start.clone().outbound_traversal(Some(edge_t.clone()), 0, 0, u32::MAX)), vec![a]);
+    assert_eq!(get_ids(start.clone().outbound_traversal(Some(edge_t.clone()), 1, 2, u32::MAX)), sorted(vec![b, c, d]));
+    assert_eq!(get_ids(start.clone().outbound_traversal(Some(edge_t.clone()), 1, 2, 2)), sorted(vec![b, c, d])[..2].to_vec());
+
+    // Vertices are only returned at their shortest depth, so `c` is excluded
+    // here even though it can also be reached in two hops, and `a` is
+    // excluded even though it can be reached by going around the cycle
+    assert_eq!(get_ids(start.clone().outbound_traversal(Some(edge_t.clone()), 2, 2, u32::MAX)), vec![d]);
+    assert_eq!(get_ids(start.clone().outbound_traversal(Some(edge_t.clone()), 1, 10, u32::MAX)), sorted(vec![b, c, d]));
+
+    assert_eq!(get_ids(start.clone().outbound_traversal(None, 1, 1, u32::MAX)), sorted(vec![b, c, e]));
+    assert_eq!(get_ids(start.clone().outbound_traversal(Some(edge_t.clone()), 2, 1, u32::MAX)), vec![]);
+    assert_eq!(get_ids(VertexQuery::Vertex(d).inbound_traversal(Some(edge_t.clone()), 1, 1, u32::MAX)), vec![c]);
+    assert_eq!(get_ids(VertexQuery::Vertex(d).inbound_traversal(Some(edge_t), 1, 3, u32::MAX)), sorted(vec![a, b, c]));
+}
+
+pub fn should_traverse_cycles_without_a_depth_limit<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new(sandbox.generate_unique_string("test_edge_type")).unwrap();
+    let trans = sandbox.transaction();
+    let ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone(), vertex_t]).unwrap();
+    let (a, b, c) = (ids[0], ids[1], ids[2]);
+
+    // a <-> b -> c, with a loop on b
+    let weight = models::Weight::new(1.0).unwrap();
+    let results = trans.create_edges(vec![
+        (models::EdgeKey::new(a, edge_t.clone(), b), weight),
+        (models::EdgeKey::new(b, edge_t.clone(), a), weight),
+        (models::EdgeKey::new(b, edge_t.clone(), b), weight),
+        (models::EdgeKey::new(b, edge_t.clone(), c), weight),
+    ]).unwrap();
+    assert!(results.iter().all(|result| result.is_ok()));
+
+    let get_ids = |q: VertexQuery| -> Vec<Uuid> {
+        trans.get_vertices(q).unwrap().into_iter().map(|vertex| vertex.id).collect()
+    };
+
+    let sorted = |mut ids: Vec<Uuid>| -> Vec<Uuid> {
+        ids.sort();
+        ids
+    };
+
+    // Going around the cycles never reaches anything new, so these have to
+    // finish long before the depth cap
+    let start = VertexQuery::Vertex(a);
+    assert_eq!(get_ids(start.clone().outbound_traversal(Some(edge_t.clone()), 1, u32::MAX, u32::MAX)), sorted(vec![b, c]));
+    assert_eq!(get_ids(start.outbound_traversal(Some(edge_t.clone()), 2, u32::MAX, u32::MAX)), vec![c]);
+    assert_eq!(get_ids(VertexQuery::Vertex(c).inbound_traversal(Some(edge_t), 0, u32::MAX, u32::MAX)), sorted(vec![a, b, c]));
+}
+
+pub fn should_combine_vertex_queries<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let follows_t = models::Type::new(sandbox.generate_unique_string("test_follows_type")).unwrap();
+    let blocks_t = models::Type::new(sandbox.generate_unique_string("test_blocks_type")).unwrap();
+    let trans = sandbox.transaction();
+    let ids = trans.create_vertices(vec![vertex_t.clone(), vertex_t.clone(), vertex_t.clone(), vertex_t.clone(), vertex_t.clone(), vertex_t]).unwrap();
+    let (a, b, c, d, e, f) = (ids[0], ids[1], ids[2], ids[3], ids[4], ids[5]);
+
+    // `a` follows c, d and e and blocks d; `b` follows d, e and f
+    let weight = models::Weight::new(1.0).unwrap();
+    let results = trans.create_edges(vec![
+        (models::EdgeKey::new(a, follows_t.clone(), c), weight),
+        (models::EdgeKey::new(a, follows_t.clone(), d), weight),
+        (models::EdgeKey::new(a, follows_t.clone(), e), weight),
+        (models::EdgeKey::new(a, blocks_t.clone(), d), weight),
+        (models::EdgeKey::new(b, follows_t.clone(), d), weight),
+        (models::EdgeKey::new(b, follows_t.clone(), e), weight),
+        (models::EdgeKey::new(b, follows_t.clone(), f), weight),
+        (models::EdgeKey::new(e, follows_t.clone(), a), weight),
+    ]).unwrap();
+    assert!(results.iter().all(|result| result.is_ok()));
+
+    let get_ids = |q: VertexQuery| -> Vec<Uuid> {
+        trans.get_vertices(q).unwrap().into_iter().map(|vertex| vertex.id).collect()
+    };
+
+    let sorted = |mut ids: Vec<Uuid>| -> Vec<Uuid> {
+        ids.sort();
+        ids
+    };
+
+    let followed_by = |id: Uuid| VertexQuery::Vertex(id).outbound_edges(Some(follows_t.clone()), None, None, u32::MAX).inbound_vertices(u32::MAX);
+    let blocked_by_a = VertexQuery::Vertex(a).outbound_edges(Some(blocks_t.clone()), None, None, u32::MAX).inbound_vertices(u32::MAX);
+
+    assert_eq!(get_ids(followed_by(a).union(followed_by(b))), sorted(vec![c, d, e, f]));
+    assert_eq!(get_ids(followed_by(a).intersection(followed_by(b))), sorted(vec![d, e]));
+    assert_eq!(get_ids(followed_by(a).difference(blocked_by_a.clone())), sorted(vec![c, e]));
+    assert_eq!(get_ids(blocked_by_a.clone().difference(followed_by(a))), vec![]);
+
+    // Set operations can be nested, and piped from
+    let q = followed_by(a).intersection(followed_by(b)).difference(blocked_by_a);
+    assert_eq!(get_ids(q.clone()), vec![e]);
+    let q = q.outbound_edges(Some(follows_t.clone()), None, None, u32::MAX).inbound_vertices(u32::MAX);
+    assert_eq!(get_ids(q), vec![a]);
+}
+
 pub fn should_delete_a_valid_vertex<D, T>(mut sandbox: &mut DatastoreTestSandbox<D, T>)
     where D: Datastore<T>,
           T: Transaction
@@ -203,3 +499,136 @@ pub fn should_not_delete_an_unowned_vertex<D, T>(mut sandbox: &mut DatastoreTest
     let result = trans.get_vertices(q).unwrap();
     assert_eq!(result.len(), 1);
 }
+
+pub fn should_expire_vertices<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new("test_edge_type".to_string()).unwrap();
+    let expiring_id = trans.create_vertex_with_ttl(vertex_t.clone(), Duration::milliseconds(100)).unwrap();
+    let lasting_id = trans.create_vertex_with_ttl(vertex_t.clone(), Duration::days(1)).unwrap();
+    let key = models::EdgeKey::new(lasting_id, edge_t, expiring_id);
+    trans.create_edge(key.clone(), models::Weight::new(0.5).unwrap()).unwrap();
+    trans.commit().unwrap();
+
+    // Expired vertices are gone, along with their edges
+    thread::sleep(time::Duration::from_millis(200));
+    let trans = sandbox.transaction();
+    let vertices = trans.get_vertices(VertexQuery::Vertices(vec![expiring_id, lasting_id])).unwrap();
+    assert_eq!(vertices.iter().map(|vertex| vertex.id).collect::<Vec<_>>(), vec![lasting_id]);
+    assert_eq!(trans.get_edges(EdgeQuery::Edge(key)).unwrap().len(), 0);
+}
+
+pub fn should_hide_items_that_expire_during_a_transaction<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new("test_edge_type".to_string()).unwrap();
+    let ttl = Duration::milliseconds(100);
+    let expiring_id = trans.create_vertex_with_ttl(vertex_t.clone(), ttl).unwrap();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let key = models::EdgeKey::new(outbound_id, edge_t.clone(), inbound_id);
+    let weight = models::Weight::new(0.5).unwrap();
+    trans.create_edge_with_ttl(key.clone(), weight, ttl).unwrap();
+    trans.create_edge(models::EdgeKey::new(outbound_id, edge_t.clone(), expiring_id), weight).unwrap();
+    trans.set_vertex_metadata(VertexQuery::Vertex(expiring_id), "foo".to_string(), JsonValue::Bool(true)).unwrap();
+    trans.set_vertex_metadata_with_ttl(VertexQuery::Vertex(inbound_id), "foo".to_string(), JsonValue::Bool(true), ttl).unwrap();
+
+    // Items are hidden once they've expired, even from the transaction that
+    // created them
+    thread::sleep(time::Duration::from_millis(200));
+    let vertices = trans.get_vertices(VertexQuery::Vertices(vec![expiring_id, outbound_id, inbound_id])).unwrap();
+    assert_eq!(vertices.len(), 2);
+    assert_eq!(trans.get_all_vertex_metadata(expiring_id).unwrap_err(), Error::VertexNotFound);
+    assert_eq!(trans.get_all_vertex_metadata(inbound_id).unwrap(), vec![]);
+    assert_eq!(trans.get_vertex_metadata(VertexQuery::Vertices(vec![expiring_id, inbound_id]), "foo".to_string()).unwrap().len(), 0);
+    assert_eq!(trans.get_edges(EdgeQuery::Edge(key)).unwrap().len(), 0);
+    let edges = trans.get_edges(VertexQuery::Vertex(outbound_id).outbound_edges(Some(edge_t), None, None, 10)).unwrap();
+    assert_eq!(edges.len(), 0);
+
+    // An expired vertex's ID can be reused, without any of its metadata
+    trans.create_vertex_with_id(expiring_id, vertex_t).unwrap();
+    assert_eq!(trans.get_all_vertex_metadata(expiring_id).unwrap(), vec![]);
+}
+
+pub fn should_not_accept_non_positive_ttls<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new("test_edge_type".to_string()).unwrap();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let key = models::EdgeKey::new(outbound_id, edge_t, inbound_id);
+    let weight = models::Weight::new(0.5).unwrap();
+    let expected_err = Error::OutOfRange("ttl".to_string());
+
+    for ttl in vec![Duration::zero(), Duration::seconds(-1)] {
+        assert_eq!(trans.create_vertex_with_ttl(vertex_t.clone(), ttl).unwrap_err(), expected_err);
+        assert_eq!(trans.create_edge_with_ttl(key.clone(), weight, ttl).unwrap_err(), expected_err);
+        let result = trans.set_vertex_metadata_with_ttl(VertexQuery::Vertex(outbound_id), "foo".to_string(), JsonValue::Bool(true), ttl);
+        assert_eq!(result.unwrap_err(), expected_err);
+        let result = trans.set_edge_metadata_with_ttl(EdgeQuery::Edge(key.clone()), "foo".to_string(), JsonValue::Bool(true), ttl);
+        assert_eq!(result.unwrap_err(), expected_err);
+    }
+
+    assert_eq!(trans.get_edges(EdgeQuery::Edge(key)).unwrap().len(), 0);
+    assert_eq!(trans.get_vertex_metadata(VertexQuery::Vertex(outbound_id), "foo".to_string()).unwrap().len(), 0);
+}
+
+pub fn should_not_accept_ttls_past_unrepresentable_datetimes<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new("test_edge_type".to_string()).unwrap();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let key = models::EdgeKey::new(outbound_id, edge_t, inbound_id);
+    let ttl = Duration::days(365 * 100);
+    let expected_err = Error::OutOfRange("ttl".to_string());
+
+    assert_eq!(trans.create_vertex_with_ttl(vertex_t, ttl).unwrap_err(), expected_err);
+    assert_eq!(trans.create_edge_with_ttl(key.clone(), models::Weight::new(0.5).unwrap(), ttl).unwrap_err(), expected_err);
+    let result = trans.set_vertex_metadata_with_ttl(VertexQuery::Vertex(outbound_id), "foo".to_string(), JsonValue::Bool(true), ttl);
+    assert_eq!(result.unwrap_err(), expected_err);
+    let result = trans.set_edge_metadata_with_ttl(EdgeQuery::Edge(key.clone()), "foo".to_string(), JsonValue::Bool(true), ttl);
+    assert_eq!(result.unwrap_err(), expected_err);
+    assert_eq!(trans.get_edges(EdgeQuery::Edge(key)).unwrap().len(), 0);
+}
+
+pub fn should_delete_expired_items<D, T>(sandbox: &mut DatastoreTestSandbox<D, T>)
+    where D: Datastore<T>,
+          T: Transaction
+{
+    let trans = sandbox.transaction();
+    let vertex_t = models::Type::new("test_vertex_type".to_string()).unwrap();
+    let edge_t = models::Type::new("test_edge_type".to_string()).unwrap();
+    let ttl = Duration::milliseconds(100);
+    let expiring_id = trans.create_vertex_with_ttl(vertex_t.clone(), ttl).unwrap();
+    let outbound_id = trans.create_vertex(vertex_t.clone()).unwrap();
+    let inbound_id = trans.create_vertex(vertex_t).unwrap();
+    let key = models::EdgeKey::new(outbound_id, edge_t, inbound_id);
+    trans.create_edge_with_ttl(key.clone(), models::Weight::new(0.5).unwrap(), ttl).unwrap();
+    trans.set_vertex_metadata_with_ttl(VertexQuery::Vertex(inbound_id), "foo".to_string(), JsonValue::Bool(true), ttl).unwrap();
+    trans.commit().unwrap();
+
+    thread::sleep(time::Duration::from_millis(200));
+    sandbox.datastore.delete_expired().unwrap();
+
+    // Sweeping again is harmless
+    sandbox.datastore.delete_expired().unwrap();
+
+    let trans = sandbox.transaction();
+    let vertices = trans.get_vertices(VertexQuery::Vertices(vec![expiring_id, outbound_id, inbound_id])).unwrap();
+    assert_eq!(vertices.len(), 2);
+    assert_eq!(trans.get_edges(EdgeQuery::Edge(key)).unwrap().len(), 0);
+    assert_eq!(trans.get_vertex_metadata(VertexQuery::Vertex(inbound_id), "foo".to_string()).unwrap().len(), 0);
+}
@@ -2,9 +2,21 @@ use errors::Error;
 use std::vec::Vec;
 use serde_json::value::Value as JsonValue;
 use models;
+use paths;
 use uuid::Uuid;
+use chrono::{DateTime, Duration, UTC};
 use std::collections::HashMap;
 
+/// A lazily evaluated stream of vertices.
+pub type VertexStream<'a> = Box<Iterator<Item = Result<models::Vertex, Error>> + 'a>;
+
+/// A lazily evaluated stream of edges.
+pub type EdgeStream<'a> = Box<Iterator<Item = Result<models::Edge, Error>> + 'a>;
+
+/// A lazily evaluated stream of vertex metadata, as tuples of the vertex ID
+/// and the metadata value.
+pub type VertexMetadataStream<'a> = Box<Iterator<Item = Result<(Uuid, JsonValue), Error>> + 'a>;
+
 /// Specifies a datastore implementation.
 ///
 /// Datastores are responsible for managing accounts, and constructing new
@@ -45,6 +57,72 @@ pub trait Datastore<T: Transaction> {
     /// * `account_id` - The ID of the account that's triggering the
     /// transaction.
     fn transaction(&self, account_id: Uuid) -> Result<T, Error>;
+
+    /// Creates an index on vertex metadata, and fills it in for existing
+    /// vertices. Does nothing if the index already exists.
+    ///
+    /// # Arguments
+    /// * `index` - The index to create.
+    fn create_vertex_metadata_index(&self, index: models::VertexMetadataIndex) -> Result<(), Error>;
+
+    /// Deletes an index on vertex metadata. Does nothing if the index does
+    /// not exist.
+    ///
+    /// # Arguments
+    /// * `index` - The index to delete.
+    fn delete_vertex_metadata_index(&self, index: models::VertexMetadataIndex) -> Result<(), Error>;
+
+    /// Gets all of the indexes on vertex metadata, sorted by type, name and
+    /// then path.
+    fn get_vertex_metadata_indexes(&self) -> Result<Vec<models::VertexMetadataIndex>, Error>;
+
+    /// Creates a unique constraint on vertex metadata. Does nothing if the
+    /// constraint already exists.
+    ///
+    /// # Arguments
+    /// * `constraint` - The constraint to create.
+    ///
+    /// # Errors
+    /// Returns `Error::UniqueConstraintViolated` if existing vertices
+    /// already violate the constraint.
+    fn create_unique_constraint(&self, constraint: models::UniqueConstraint) -> Result<(), Error>;
+
+    /// Deletes a unique constraint on vertex metadata. Does nothing if the
+    /// constraint does not exist.
+    ///
+    /// # Arguments
+    /// * `constraint` - The constraint to delete.
+    fn delete_unique_constraint(&self, constraint: models::UniqueConstraint) -> Result<(), Error>;
+
+    /// Gets all of the unique constraints on vertex metadata, sorted by type
+    /// and then name.
+    fn get_unique_constraints(&self) -> Result<Vec<models::UniqueConstraint>, Error>;
+
+    /// Starts keeping the history of edges of a type, so that earlier
+    /// versions can be queried via `Transaction::get_edge_history` and
+    /// `Transaction::get_edges_as_of`. The current version of each existing
+    /// edge of the type is kept as its first version. Does nothing if
+    /// history is already enabled for the type.
+    ///
+    /// # Arguments
+    /// * `t` - The edge type.
+    fn enable_edge_history(&self, t: models::Type) -> Result<(), Error>;
+
+    /// Stops keeping the history of edges of a type, and deletes the history
+    /// kept so far. Does nothing if history is not enabled for the type.
+    ///
+    /// # Arguments
+    /// * `t` - The edge type.
+    fn disable_edge_history(&self, t: models::Type) -> Result<(), Error>;
+
+    /// Gets all of the edge types that history is enabled for, sorted.
+    fn get_edge_history_types(&self) -> Result<Vec<models::Type>, Error>;
+
+    /// Physically removes the vertices, edges and metadata whose
+    /// time-to-live has run out. Expired items are already invisible to
+    /// reads as soon as they expire, so this only reclaims space, and is
+    /// meant to be called periodically, e.g. from a background thread.
+    fn delete_expired(&self) -> Result<(), Error>;
 }
 
 /// Specifies a transaction implementation, which are returned by datastores.
@@ -67,12 +145,59 @@ pub trait Transaction {
     /// * `t` - The type of the vertex.
     fn create_vertex(&self, t: models::Type) -> Result<Uuid, Error>;
 
+    /// Creates a new vertex with a caller-supplied ID, e.g. when re-importing
+    /// a dump or replaying writes.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the vertex.
+    /// * `t` - The type of the vertex.
+    ///
+    /// # Errors
+    /// Returns `Error::VertexAlreadyExists` if a vertex with the ID already
+    /// exists.
+    fn create_vertex_with_id(&self, id: Uuid, t: models::Type) -> Result<(), Error>;
+
+    /// Creates many vertices at once, returning their IDs in the same order
+    /// as the types they were created from. The default implementation calls
+    /// `create_vertex` for each vertex.
+    ///
+    /// # Arguments
+    /// * `types` - The type of each vertex to create.
+    fn create_vertices(&self, types: Vec<models::Type>) -> Result<Vec<Uuid>, Error> {
+        types.into_iter().map(|t| self.create_vertex(t)).collect()
+    }
+
+    /// Creates a new vertex that expires after a time-to-live. Once it
+    /// expires, it is invisible along with its metadata and edges, even to
+    /// transactions that are already open, and its ID can be reused. It's
+    /// physically removed by `Datastore::delete_expired`.
+    ///
+    /// # Arguments
+    /// * `t` - The type of the vertex.
+    /// * `ttl` - How long the vertex should live for.
+    ///
+    /// # Errors
+    /// Returns `Error::OutOfRange` if the time-to-live isn't positive, or if
+    /// the vertex would expire past `util::MAX_DATETIME`.
+    fn create_vertex_with_ttl(&self, t: models::Type, ttl: Duration) -> Result<Uuid, Error>;
+
     /// Gets a range of vertices specified by a query.
     ///
     /// # Arguments
     /// * `q` - The query to run.
     fn get_vertices(&self, q: models::VertexQuery) -> Result<Vec<models::Vertex>, Error>;
 
+    /// Streams a range of vertices specified by a query. Unlike
+    /// `get_vertices`, results may be loaded lazily as the stream is
+    /// consumed, so this is suitable for large scans. The default
+    /// implementation loads all of the results up front via `get_vertices`.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    fn stream_vertices<'a>(&'a self, q: models::VertexQuery) -> Result<VertexStream<'a>, Error> {
+        Ok(Box::new(self.get_vertices(q)?.into_iter().map(Ok)))
+    }
+
     /// Deletes existing vertices specified by a query.
     ///
     /// # Arguments
@@ -85,7 +210,7 @@ pub trait Transaction {
     /// but does not need to own the inbounding vertex.
     ///
     /// # Arguments
-    /// `key`: The edge to create.
+    /// * `key` - The edge to create.
     /// * `weight` - The edge weight.
     ///
     /// # Errors
@@ -93,6 +218,84 @@ pub trait Transaction {
     /// not exist. Returns `Error::Unauthorized` if the account tied to the
     /// current transaction does not own the source vertex.
     fn create_edge(&self, key: models::EdgeKey, weight: models::Weight) -> Result<(), Error>;
+
+    /// Creates a new edge with a caller-specified update datetime, e.g. when
+    /// backfilling historical edges. This otherwise follows the same rules
+    /// as `create_edge`. Datetimes do not need to arrive in order.
+    ///
+    /// # Arguments
+    /// * `key` - The edge to create.
+    /// * `weight` - The edge weight.
+    /// * `update_datetime` - The update datetime to stamp the edge with.
+    ///
+    /// # Errors
+    /// Return `Error::VertexNotFound` if either of the connected vertices do
+    /// not exist. Returns `Error::Unauthorized` if the account tied to the
+    /// current transaction does not own the source vertex. Returns
+    /// `Error::OutOfRange` if the datetime is before the unix epoch or past
+    /// `util::MAX_DATETIME`, since not every datastore can store those.
+    fn create_edge_at(&self, key: models::EdgeKey, weight: models::Weight, update_datetime: DateTime<UTC>) -> Result<(), Error>;
+
+    /// Creates or updates an edge like `create_edge`, but makes it expire
+    /// after a time-to-live. Once it expires, it is invisible along with its
+    /// metadata, like an expired vertex. Creating or updating the edge again
+    /// without a time-to-live stops it from expiring.
+    ///
+    /// # Arguments
+    /// * `key` - The edge to create.
+    /// * `weight` - The edge weight.
+    /// * `ttl` - How long the edge should live for.
+    ///
+    /// # Errors
+    /// Returns `Error::OutOfRange` under the same conditions as
+    /// `create_vertex_with_ttl`. Otherwise, returns the same errors as
+    /// `create_edge`.
+    fn create_edge_with_ttl(&self, key: models::EdgeKey, weight: models::Weight, ttl: Duration) -> Result<(), Error>;
+
+    /// Creates or updates an edge like `create_edge`, but only if the edge
+    /// is at an expected version, returning its new version. This stops
+    /// concurrent writers from silently overwriting each other's updates.
+    ///
+    /// # Arguments
+    /// * `key` - The edge to create or update.
+    /// * `weight` - The edge weight.
+    /// * `expected_version` - The version the edge should be at, or `None`
+    ///   if it shouldn't exist yet.
+    ///
+    /// # Errors
+    /// Returns `Error::Conflict` if the edge isn't at the expected version.
+    /// Otherwise, returns the same errors as `create_edge`.
+    fn create_edge_if_version(&self, key: models::EdgeKey, weight: models::Weight, expected_version: Option<u64>) -> Result<u64, Error>;
+
+    /// Creates or updates many edges at once. This follows the same rules as
+    /// `create_edge`, but an edge that cannot be created does not prevent the
+    /// others from being created. If the same edge is specified more than
+    /// once, the last weight wins. The default implementation calls
+    /// `create_edge` for each edge.
+    ///
+    /// # Arguments
+    /// * `items` - The edges to create, with their weights.
+    ///
+    /// # Errors
+    /// Returns a result for each edge, in the same order as `items`. An
+    /// edge's result is `Error::VertexNotFound` if either of its vertices do
+    /// not exist, or `Error::Unauthorized` if the account tied to the current
+    /// transaction does not own its source vertex. Any other error fails the
+    /// whole call.
+    fn create_edges(&self, items: Vec<(models::EdgeKey, models::Weight)>) -> Result<Vec<Result<(), Error>>, Error> {
+        let mut results = Vec::with_capacity(items.len());
+
+        for (key, weight) in items {
+            match self.create_edge(key, weight) {
+                Ok(()) => results.push(Ok(())),
+                Err(Error::VertexNotFound) => results.push(Err(Error::VertexNotFound)),
+                Err(Error::Unauthorized) => results.push(Err(Error::Unauthorized)),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(results)
+    }
     
     /// Gets a range of edges specified by a query.
     ///
@@ -100,6 +303,74 @@ pub trait Transaction {
     /// * `q` - The query to run.
     fn get_edges(&self, q: models::EdgeQuery) -> Result<Vec<models::Edge>, Error>;
 
+    /// Streams a range of edges specified by a query. Unlike `get_edges`,
+    /// results may be loaded lazily as the stream is consumed. The default
+    /// implementation loads all of the results up front via `get_edges`.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    fn stream_edges<'a>(&'a self, q: models::EdgeQuery) -> Result<EdgeStream<'a>, Error> {
+        Ok(Box::new(self.get_edges(q)?.into_iter().map(Ok)))
+    }
+
+    /// Gets a page of edges, along with a cursor for the next page if the
+    /// query is a paged pipe that filled its limit. Passing the cursor back
+    /// in the same query gets the edges that come after this page. For any
+    /// other query, all of the results are returned in a single page.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    fn get_edge_page(&self, q: models::EdgeQuery) -> Result<models::EdgePage, Error> {
+        let limit = match q {
+            models::EdgeQuery::PagedPipe(_, _, _, _, limit) => Some(limit as usize),
+            _ => None
+        };
+
+        let edges = self.get_edges(q)?;
+
+        let cursor = match (limit, edges.last()) {
+            (Some(limit), Some(edge)) if edges.len() == limit => {
+                Some(models::EdgeCursor::new(edge.key.clone(), edge.updated_datetime))
+            },
+            _ => None
+        };
+
+        Ok(models::EdgePage {
+            edges: edges,
+            cursor: cursor
+        })
+    }
+
+    /// Gets the history of an edge, oldest version first. This is empty
+    /// unless history is enabled for the edge's type. The history of an edge
+    /// is deleted along with either of its vertices.
+    ///
+    /// # Arguments
+    /// * `key` - The edge to get the history of.
+    fn get_edge_history(&self, key: models::EdgeKey) -> Result<Vec<models::EdgeVersion>, Error>;
+
+    /// Gets the edges of a type as they were at a past datetime, using the
+    /// history kept for the type. This is like an `EdgeQuery::Pipe`, except
+    /// that each edge's weight and update datetime come from its latest
+    /// version at or before `datetime`, and edges that were deleted by then
+    /// are left out. An edge's created datetime is its earliest version
+    /// since it was last deleted, and its version is 0, since it can't be
+    /// updated from the past. Edges are returned latest first.
+    ///
+    /// # Arguments
+    /// * `q` - The vertices to get the edges of.
+    /// * `converter` - Whether to get the outbound or inbound edges.
+    /// * `t` - The edge type, which should have history enabled.
+    /// * `datetime` - The datetime to get the edges as of.
+    /// * `limit` - The maximum number of edges to return.
+    fn get_edges_as_of(&self,
+                       q: models::VertexQuery,
+                       converter: models::QueryTypeConverter,
+                       t: models::Type,
+                       datetime: DateTime<UTC>,
+                       limit: u32)
+                       -> Result<Vec<models::Edge>, Error>;
+
     /// Deletes a set of edges specified by a query.
     ///
     /// # Arguments
@@ -113,6 +384,17 @@ pub trait Transaction {
     /// * `t` - The edge type.
     fn get_edge_count(&self, q: models::EdgeQuery) -> Result<u64, Error>;
 
+    /// Finds a path between two vertices, returning its edges in the order
+    /// they're followed, or `None` if there is no path within the query's
+    /// maximum depth. The path from a vertex to itself is empty. The default
+    /// implementation searches the graph with `get_edges`.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    fn get_path(&self, q: models::PathQuery) -> Result<Option<Vec<models::Edge>>, Error> {
+        paths::get_path(self, q)
+    }
+
     /// Gets a global metadata value.
     ///
     /// # Arguments
@@ -122,6 +404,28 @@ pub trait Transaction {
     /// Returns `Error::MetadataNotFound` if the metadata does not exist.
     fn get_global_metadata(&self, name: String) -> Result<JsonValue, Error>;
 
+    /// Lists global metadata names and values, sorted by name.
+    ///
+    /// # Arguments
+    /// * `prefix` - Only names starting with this prefix are listed.
+    /// * `start_name` - If set, only names after this one are listed, e.g. the
+    /// last name of a previous page.
+    /// * `limit` - The maximum number of names to list.
+    fn get_all_global_metadata(&self, prefix: String, start_name: Option<String>, limit: u32) -> Result<Vec<(String, JsonValue)>, Error>;
+
+    /// Lists global metadata names, sorted. The default implementation calls
+    /// `get_all_global_metadata` and discards the values.
+    ///
+    /// # Arguments
+    /// * `prefix` - Only names starting with this prefix are listed.
+    /// * `start_name` - If set, only names after this one are listed, e.g. the
+    /// last name of a previous page.
+    /// * `limit` - The maximum number of names to list.
+    fn get_global_metadata_names(&self, prefix: String, start_name: Option<String>, limit: u32) -> Result<Vec<String>, Error> {
+        let metadata = self.get_all_global_metadata(prefix, start_name, limit)?;
+        Ok(metadata.into_iter().map(|(name, _)| name).collect())
+    }
+
     /// Sets a global metadata value.
     ///
     /// # Arguments
@@ -129,6 +433,22 @@ pub trait Transaction {
     /// * `value` - The metadata value.
     fn set_global_metadata(&self, name: String, value: JsonValue) -> Result<(), Error>;
 
+    /// Atomically updates a global metadata value, returning the new value.
+    /// Concurrent updates of the same value never lose writes, but some
+    /// datastores (e.g. rocksdb) detect them optimistically, so `commit`
+    /// can then fail with `Error::Conflict`. The whole transaction should be
+    /// retried in that case. The other `update_*` methods work the same way.
+    ///
+    /// # Arguments
+    /// * `name` - The metadata name.
+    /// * `operation` - The operation to apply.
+    ///
+    /// # Errors
+    /// Returns `Error::Conflict` if a compare-and-swap doesn't find the
+    /// expected value, and `Error::InvalidMetadataOperation` if an increment
+    /// can't be applied.
+    fn update_global_metadata(&self, name: String, operation: models::MetadataOperation) -> Result<JsonValue, Error>;
+
     /// Deletes a global metadata value.
     ///
     /// # Arguments
@@ -138,6 +458,30 @@ pub trait Transaction {
     /// Returns `Error::MetadataNotFound` if the metadata does not exist.
     fn delete_global_metadata(&self, name: String) -> Result<(), Error>;
 
+    /// Gets a global metadata value along with its version, for use with
+    /// `set_global_metadata_if_version`.
+    ///
+    /// # Arguments
+    /// * `name` - The metadata name.
+    ///
+    /// # Errors
+    /// Returns `Error::MetadataNotFound` if the metadata does not exist.
+    fn get_versioned_global_metadata(&self, name: String) -> Result<models::VersionedMetadata, Error>;
+
+    /// Sets a global metadata value like `set_global_metadata`, but only if
+    /// the metadata is at an expected version, returning its new version.
+    ///
+    /// # Arguments
+    /// * `name` - The metadata name.
+    /// * `value` - The metadata value.
+    /// * `expected_version` - The version the metadata should be at, or
+    ///   `None` if it shouldn't be set yet.
+    ///
+    /// # Errors
+    /// Returns `Error::Conflict` if the metadata isn't at the expected
+    /// version.
+    fn set_global_metadata_if_version(&self, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error>;
+
     /// Gets an account metadata value.
     ///
     /// # Arguments
@@ -148,6 +492,29 @@ pub trait Transaction {
     /// Returns `Error::MetadataNotFound` if the metadata does not exist.
     fn get_account_metadata(&self, account_id: Uuid, name: String) -> Result<JsonValue, Error>;
 
+    /// Lists the metadata names and values of an account, sorted by name.
+    ///
+    /// # Arguments
+    /// * `account_id` - The ID of the account.
+    ///
+    /// # Errors
+    /// Returns `Error::AccountNotFound` if the account does not exist.
+    fn get_all_account_metadata(&self, account_id: Uuid) -> Result<Vec<(String, JsonValue)>, Error>;
+
+    /// Lists the metadata names of an account, sorted. The default
+    /// implementation calls `get_all_account_metadata` and discards the
+    /// values.
+    ///
+    /// # Arguments
+    /// * `account_id` - The ID of the account.
+    ///
+    /// # Errors
+    /// Returns `Error::AccountNotFound` if the account does not exist.
+    fn get_account_metadata_names(&self, account_id: Uuid) -> Result<Vec<String>, Error> {
+        let metadata = self.get_all_account_metadata(account_id)?;
+        Ok(metadata.into_iter().map(|(name, _)| name).collect())
+    }
+
     /// Sets an account metadata value.
     ///
     /// # Arguments
@@ -160,6 +527,19 @@ pub trait Transaction {
     /// exist.
     fn set_account_metadata(&self, account_id: Uuid, name: String, value: JsonValue) -> Result<(), Error>;
 
+    /// Atomically updates an account metadata value, returning the new
+    /// value.
+    ///
+    /// # Arguments
+    /// * `account_id`: The ID of the account that the metadata is tied to.
+    /// * `name` - The metadata name.
+    /// * `operation` - The operation to apply.
+    ///
+    /// # Errors
+    /// Returns `Error::AccountNotFound` if the specified account ID does not
+    /// exist. Otherwise returns the same errors as `update_global_metadata`.
+    fn update_account_metadata(&self, account_id: Uuid, name: String, operation: models::MetadataOperation) -> Result<JsonValue, Error>;
+
     /// Deletes an account metadata value.
     ///
     /// # Arguments
@@ -170,6 +550,33 @@ pub trait Transaction {
     /// Returns `Error::MetadataNotFound` if the metadata does not exist.
     fn delete_account_metadata(&self, account_id: Uuid, name: String) -> Result<(), Error>;
 
+    /// Gets an account metadata value along with its version, for use with
+    /// `set_account_metadata_if_version`.
+    ///
+    /// # Arguments
+    /// * `account_id` - The ID of the account that the metadata is tied to.
+    /// * `name` - The metadata name.
+    ///
+    /// # Errors
+    /// Returns `Error::AccountNotFound` if the account does not exist, and
+    /// `Error::MetadataNotFound` if the metadata does not exist.
+    fn get_versioned_account_metadata(&self, account_id: Uuid, name: String) -> Result<models::VersionedMetadata, Error>;
+
+    /// Sets an account metadata value like `set_account_metadata`, but only
+    /// if the metadata is at an expected version, returning its new version.
+    ///
+    /// # Arguments
+    /// * `account_id` - The ID of the account that the metadata is tied to.
+    /// * `name` - The metadata name.
+    /// * `value` - The metadata value.
+    /// * `expected_version` - The version the metadata should be at, or
+    ///   `None` if it shouldn't be set yet.
+    ///
+    /// # Errors
+    /// Returns `Error::AccountNotFound` if the account does not exist, and
+    /// `Error::Conflict` if the metadata isn't at the expected version.
+    fn set_account_metadata_if_version(&self, account_id: Uuid, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error>;
+
     /// Gets a vertex metadata value.
     ///
     /// # Arguments
@@ -177,14 +584,86 @@ pub trait Transaction {
     /// * `name` - The metadata name.
     fn get_vertex_metadata(&self, q: models::VertexQuery, name: String) -> Result<HashMap<Uuid, JsonValue>, Error>;
 
+    /// Streams a vertex metadata value for each vertex matching a query.
+    /// Vertices without the metadata are skipped. The default implementation
+    /// loads all of the results up front via `get_vertex_metadata`.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    /// * `name` - The metadata name.
+    fn stream_vertex_metadata<'a>(&'a self, q: models::VertexQuery, name: String) -> Result<VertexMetadataStream<'a>, Error> {
+        Ok(Box::new(self.get_vertex_metadata(q, name)?.into_iter().map(Ok)))
+    }
+
+    /// Lists the metadata names and values of a vertex, sorted by name.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the vertex.
+    ///
+    /// # Errors
+    /// Returns `Error::VertexNotFound` if the vertex does not exist.
+    fn get_all_vertex_metadata(&self, id: Uuid) -> Result<Vec<(String, JsonValue)>, Error>;
+
+    /// Lists the metadata names of a vertex, sorted. The default
+    /// implementation calls `get_all_vertex_metadata` and discards the
+    /// values.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the vertex.
+    ///
+    /// # Errors
+    /// Returns `Error::VertexNotFound` if the vertex does not exist.
+    fn get_vertex_metadata_names(&self, id: Uuid) -> Result<Vec<String>, Error> {
+        let metadata = self.get_all_vertex_metadata(id)?;
+        Ok(metadata.into_iter().map(|(name, _)| name).collect())
+    }
+
     /// Sets a vertex metadata value.
     ///
     /// # Arguments
     /// * `q` - The query to run.
     /// * `name` - The metadata name.
     /// * `value` - The metadata value.
+    ///
+    /// # Errors
+    /// Returns `Error::UniqueConstraintViolated` if this would give more
+    /// than one vertex the same value for a unique constraint. No metadata
+    /// is set in that case.
     fn set_vertex_metadata(&self, q: models::VertexQuery, name: String, value: JsonValue) -> Result<(), Error>;
 
+    /// Sets a vertex metadata value like `set_vertex_metadata`, but makes it
+    /// expire after a time-to-live, after which it's invisible, like an
+    /// expired vertex. Setting or updating the metadata again without a
+    /// time-to-live stops it from expiring.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    /// * `name` - The metadata name.
+    /// * `value` - The metadata value.
+    /// * `ttl` - How long the metadata should live for.
+    ///
+    /// # Errors
+    /// Returns `Error::OutOfRange` under the same conditions as
+    /// `create_vertex_with_ttl`. Otherwise, returns the same errors as
+    /// `set_vertex_metadata`.
+    fn set_vertex_metadata_with_ttl(&self, q: models::VertexQuery, name: String, value: JsonValue, ttl: Duration) -> Result<(), Error>;
+
+    /// Atomically updates a vertex metadata value for each vertex matching a
+    /// query, returning the new values. The operation is applied to each
+    /// vertex's value separately.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    /// * `name` - The metadata name.
+    /// * `operation` - The operation to apply.
+    ///
+    /// # Errors
+    /// Returns the same errors as `update_global_metadata` if the operation
+    /// fails for any of the vertices, and `Error::UniqueConstraintViolated`
+    /// under the same conditions as `set_vertex_metadata`. No metadata is
+    /// updated in either case.
+    fn update_vertex_metadata(&self, q: models::VertexQuery, name: String, operation: models::MetadataOperation) -> Result<HashMap<Uuid, JsonValue>, Error>;
+
     /// Deletes a vertex metadata value.
     ///
     /// # Arguments
@@ -192,6 +671,68 @@ pub trait Transaction {
     /// * `name` - The metadata name.
     fn delete_vertex_metadata(&self, q: models::VertexQuery, name: String) -> Result<(), Error>;
 
+    /// Gets a vertex metadata value along with its version, for use with
+    /// `set_vertex_metadata_if_version`.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    /// * `name` - The metadata name.
+    fn get_versioned_vertex_metadata(&self, q: models::VertexQuery, name: String) -> Result<HashMap<Uuid, models::VersionedMetadata>, Error>;
+
+    /// Sets a vertex metadata value like `set_vertex_metadata`, but only if
+    /// the metadata is at an expected version, returning its new version.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the vertex.
+    /// * `name` - The metadata name.
+    /// * `value` - The metadata value.
+    /// * `expected_version` - The version the metadata should be at, or
+    ///   `None` if it shouldn't be set yet.
+    ///
+    /// # Errors
+    /// Returns `Error::VertexNotFound` if the vertex does not exist, and
+    /// `Error::Conflict` if the metadata isn't at the expected version.
+    /// Returns `Error::UniqueConstraintViolated` under the same conditions
+    /// as `set_vertex_metadata`.
+    fn set_vertex_metadata_if_version(&self, id: Uuid, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error>;
+
+    /// Gets the ID of the vertex of type `t` whose metadata called `name` is
+    /// equal to `value`, creating the vertex and setting its metadata if
+    /// there isn't one. This is meant to be used with a unique constraint on
+    /// `t` and `name`, which makes sure there's at most one such vertex. The
+    /// default implementation uses a `VertexQuery::Lookup` query, then
+    /// `create_vertex` and `set_vertex_metadata`, deleting the vertex again
+    /// if its metadata can't be set.
+    ///
+    /// # Arguments
+    /// * `t` - The type of the vertex.
+    /// * `name` - The metadata name.
+    /// * `value` - The metadata value.
+    ///
+    /// # Errors
+    /// Returns `Error::UniqueConstraintViolated` if another transaction
+    /// created the vertex at the same time. The transaction should then be
+    /// rolled back and retried.
+    fn get_or_create_vertex(&self, t: models::Type, name: String, value: JsonValue) -> Result<Uuid, Error> {
+        let predicate = models::MetadataPredicate::Equal(value.clone());
+        let q = models::VertexQuery::Lookup(t.clone(), name.clone(), predicate, None, 1);
+
+        if let Some(vertex) = self.get_vertices(q)?.into_iter().next() {
+            return Ok(vertex.id);
+        }
+
+        let id = self.create_vertex(t)?;
+
+        // Not every datastore can roll back a transaction, so the vertex
+        // has to be cleaned up here rather than left without its metadata
+        if let Err(err) = self.set_vertex_metadata(models::VertexQuery::Vertex(id), name, value) {
+            self.delete_vertices(models::VertexQuery::Vertex(id))?;
+            return Err(err);
+        }
+
+        Ok(id)
+    }
+
     /// Gets an edge metadata value.
     ///
     /// # Arguments
@@ -199,6 +740,28 @@ pub trait Transaction {
     /// * `name` - The metadata name.
     fn get_edge_metadata(&self, q: models::EdgeQuery, name: String) -> Result<HashMap<models::EdgeKey, JsonValue>, Error>;
 
+    /// Lists the metadata names and values of an edge, sorted by name.
+    ///
+    /// # Arguments
+    /// * `key` - The edge.
+    ///
+    /// # Errors
+    /// Returns `Error::EdgeNotFound` if the edge does not exist.
+    fn get_all_edge_metadata(&self, key: models::EdgeKey) -> Result<Vec<(String, JsonValue)>, Error>;
+
+    /// Lists the metadata names of an edge, sorted. The default
+    /// implementation calls `get_all_edge_metadata` and discards the values.
+    ///
+    /// # Arguments
+    /// * `key` - The edge.
+    ///
+    /// # Errors
+    /// Returns `Error::EdgeNotFound` if the edge does not exist.
+    fn get_edge_metadata_names(&self, key: models::EdgeKey) -> Result<Vec<String>, Error> {
+        let metadata = self.get_all_edge_metadata(key)?;
+        Ok(metadata.into_iter().map(|(name, _)| name).collect())
+    }
+
     /// Sets an edge metadata value.
     ///
     /// # Arguments
@@ -207,6 +770,36 @@ pub trait Transaction {
     /// * `value` - The metadata value.
     fn set_edge_metadata(&self, q: models::EdgeQuery, name: String, value: JsonValue) -> Result<(), Error>;
 
+    /// Sets an edge metadata value like `set_edge_metadata`, but makes it
+    /// expire after a time-to-live, after which it's invisible, like an
+    /// expired vertex. Setting or updating the metadata again without a
+    /// time-to-live stops it from expiring.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    /// * `name` - The metadata name.
+    /// * `value` - The metadata value.
+    /// * `ttl` - How long the metadata should live for.
+    ///
+    /// # Errors
+    /// Returns `Error::OutOfRange` under the same conditions as
+    /// `create_vertex_with_ttl`.
+    fn set_edge_metadata_with_ttl(&self, q: models::EdgeQuery, name: String, value: JsonValue, ttl: Duration) -> Result<(), Error>;
+
+    /// Atomically updates an edge metadata value for each edge matching a
+    /// query, returning the new values. The operation is applied to each
+    /// edge's value separately.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    /// * `name` - The metadata name.
+    /// * `operation` - The operation to apply.
+    ///
+    /// # Errors
+    /// Returns the same errors as `update_global_metadata` if the operation
+    /// fails for any of the edges. No metadata is updated in that case.
+    fn update_edge_metadata(&self, q: models::EdgeQuery, name: String, operation: models::MetadataOperation) -> Result<HashMap<models::EdgeKey, JsonValue>, Error>;
+
     /// Deletes an edge metadata value.
     ///
     /// # Arguments
@@ -214,7 +807,37 @@ pub trait Transaction {
     /// * `name` - The metadata name.
     fn delete_edge_metadata(&self, q: models::EdgeQuery, name: String) -> Result<(), Error>;
 
+    /// Gets an edge metadata value along with its version, for use with
+    /// `set_edge_metadata_if_version`.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    /// * `name` - The metadata name.
+    fn get_versioned_edge_metadata(&self, q: models::EdgeQuery, name: String) -> Result<HashMap<models::EdgeKey, models::VersionedMetadata>, Error>;
+
+    /// Sets an edge metadata value like `set_edge_metadata`, but only if the
+    /// metadata is at an expected version, returning its new version.
+    ///
+    /// # Arguments
+    /// * `key` - The edge.
+    /// * `name` - The metadata name.
+    /// * `value` - The metadata value.
+    /// * `expected_version` - The version the metadata should be at, or
+    ///   `None` if it shouldn't be set yet.
+    ///
+    /// # Errors
+    /// Returns `Error::EdgeNotFound` if the edge does not exist, and
+    /// `Error::Conflict` if the metadata isn't at the expected version.
+    fn set_edge_metadata_if_version(&self, key: models::EdgeKey, name: String, value: JsonValue, expected_version: Option<u64>) -> Result<u64, Error>;
+
     /// Commits the transaction.
+    ///
+    /// # Errors
+    /// Returns `Error::Conflict` if another transaction committed a change to
+    /// something that this transaction wrote with an expected version, or
+    /// updated with one of the `update_*` methods, after this transaction
+    /// read it. Nothing is committed in that case, and the transaction should
+    /// be retried from the start.
     fn commit(self) -> Result<(), Error>;
 
     /// Rolls the transaction back.
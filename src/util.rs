@@ -3,13 +3,22 @@
 use rand::{Rng, OsRng};
 use crypto::sha2::Sha256;
 use crypto::digest::Digest;
-use errors::ValidationError;
+use errors::{Error, ValidationError};
 use uuid::Uuid;
-use chrono::{DateTime, UTC};
+use chrono::{DateTime, Duration, NaiveDateTime, Timelike, UTC};
 use byteorder::BigEndian;
 use std::io::Cursor;
 use std::io::Write;
 use byteorder::WriteBytesExt;
+use models;
+use std::i32;
+
+lazy_static! {
+    /// The latest datetime that can be stored. Datastores don't all share
+    /// the same limits, so they all use the narrowest one, which is
+    /// rocksdb's nanoseconds since the epoch.
+    pub static ref MAX_DATETIME: DateTime<UTC> = DateTime::from_utc(NaiveDateTime::from_timestamp(i32::MAX as i64, 0), UTC).with_nanosecond(1999999999u32).unwrap();
+}
 
 /// Generates a securely random string consisting of letters (uppercase and
 /// lowercase) and digits.
@@ -81,6 +90,70 @@ pub fn nanos_since_epoch(datetime: &DateTime<UTC>) -> u64 {
     timestamp * 1000000000 + nanoseconds
 }
 
+/// Gets the datetime that something created now with a given time-to-live
+/// expires at.
+///
+/// # Arguments
+/// * `ttl` - The time-to-live.
+///
+/// # Errors
+/// Returns `Error::OutOfRange` if the time-to-live isn't positive, or is so
+/// long that the expiry datetime is past `MAX_DATETIME`.
+pub fn expiry_datetime(ttl: Duration) -> Result<DateTime<UTC>, Error> {
+    if ttl <= Duration::zero() {
+        return Err(Error::OutOfRange("ttl".to_string()));
+    }
+
+    match UTC::now().checked_add_signed(ttl) {
+        Some(expiry) if expiry <= *MAX_DATETIME => Ok(expiry),
+        _ => Err(Error::OutOfRange("ttl".to_string()))
+    }
+}
+
+/// Checks that a caller-specified datetime can be stored, i.e. that it's
+/// between the unix epoch and `MAX_DATETIME`.
+///
+/// # Arguments
+/// * `datetime` - The datetime to check.
+/// * `name` - The name of the argument, used in the error.
+///
+/// # Errors
+/// Returns `Error::OutOfRange` if the datetime can't be stored.
+pub fn check_datetime(datetime: DateTime<UTC>, name: &str) -> Result<DateTime<UTC>, Error> {
+    if datetime.timestamp() < 0 || datetime > *MAX_DATETIME {
+        Err(Error::OutOfRange(name.to_string()))
+    } else {
+        Ok(datetime)
+    }
+}
+
+/// Gets an edge as it was at a given datetime from its history, or `None` if
+/// it didn't exist then.
+///
+/// # Arguments
+/// * `key` - The key to the edge.
+/// * `versions` - The versions of the edge, oldest first.
+/// * `datetime` - The datetime to get the edge as of.
+pub fn edge_as_of(key: models::EdgeKey, versions: &[models::EdgeVersion], datetime: DateTime<UTC>) -> Option<models::Edge> {
+    // Tracks the created datetime, weight, update datetime and version
+    // number of the edge as of each version
+    let mut current: Option<(DateTime<UTC>, models::Weight, DateTime<UTC>, u64)> = None;
+
+    for edge_version in versions.iter().take_while(|edge_version| edge_version.datetime <= datetime) {
+        current = match (edge_version.weight, edge_version.version) {
+            (Some(weight), Some(version)) => {
+                let created_datetime = current.map_or(edge_version.datetime, |(created_datetime, _, _, _)| created_datetime);
+                Some((created_datetime, weight, edge_version.datetime, version))
+            },
+            _ => None
+        };
+    }
+
+    current.map(|(created_datetime, weight, updated_datetime, version)| {
+        models::Edge::new_with_version(key, weight, created_datetime, updated_datetime, version)
+    })
+}
+
 /// Returns a new UUID.
 pub fn parent_uuid() -> Uuid {
     loop {
@@ -107,11 +180,12 @@ pub fn child_uuid(parent: Uuid) -> Uuid {
 
 #[cfg(test)]
 mod tests {
-    use super::{generate_random_secret, get_salted_hash, next_uuid, nanos_since_epoch, parent_uuid, child_uuid};
+    use super::{generate_random_secret, get_salted_hash, next_uuid, nanos_since_epoch, parent_uuid, child_uuid, edge_as_of};
+    use models::{EdgeKey, EdgeVersion, Type, Weight};
     use regex::Regex;
     use uuid::Uuid;
     use core::str::FromStr;
-    use chrono::{DateTime, NaiveDateTime, UTC};
+    use chrono::{DateTime, NaiveDateTime, TimeZone, UTC};
 
     #[test]
     fn should_generate_random_secret() {
@@ -161,4 +235,32 @@ mod tests {
         assert!(cid1 != cid2);
         assert!(cid2 > cid1);
     }
+
+    #[test]
+    fn should_get_edge_as_of() {
+        let key = EdgeKey::new(parent_uuid(), Type::new("test_edge_type".to_string()).unwrap(), parent_uuid());
+        let datetime = |day| UTC.ymd(2017, 1, day).and_hms(0, 0, 0);
+        let versions = vec![
+            EdgeVersion::new(Some(Weight::new(0.1).unwrap()), Some(1), datetime(1)),
+            EdgeVersion::new(Some(Weight::new(0.2).unwrap()), Some(2), datetime(3)),
+            EdgeVersion::new(None, None, datetime(5)),
+            EdgeVersion::new(Some(Weight::new(0.3).unwrap()), Some(1), datetime(7))
+        ];
+
+        assert!(edge_as_of(key.clone(), &versions, UTC.ymd(2016, 12, 31).and_hms(0, 0, 0)).is_none());
+
+        let edge = edge_as_of(key.clone(), &versions, datetime(4)).unwrap();
+        assert_eq!(edge.weight, Weight::new(0.2).unwrap());
+        assert_eq!(edge.created_datetime, datetime(1));
+        assert_eq!(edge.updated_datetime, datetime(3));
+        assert_eq!(edge.version, 2);
+
+        assert!(edge_as_of(key.clone(), &versions, datetime(6)).is_none());
+
+        let edge = edge_as_of(key, &versions, datetime(8)).unwrap();
+        assert_eq!(edge.weight, Weight::new(0.3).unwrap());
+        assert_eq!(edge.created_datetime, datetime(7));
+        assert_eq!(edge.updated_datetime, datetime(7));
+        assert_eq!(edge.version, 1);
+    }
 }